@@ -5,14 +5,79 @@
   const _buffer = [];
   const _capturedUrls = new Set(); // dedup perf entries
 
+  // --- Capture settings (pushed in from config.json "capture") ---
+  const _capture = window.__harharharCapture || {};
+  const MAX_BODY = _capture.max_body_bytes || 500000;
+  const _domainCapture = _capture.domains || {};
+  // Most specific last, so a subdomain's override beats its parent's
+  const _captureDomains = Object.keys(_domainCapture).sort(function (a, b) { return a.length - b.length; });
+
+  // Effective settings for a request URL: the global ones, then any per-domain overrides
+  function settingsFor(url) {
+    var out = {
+      enabled: true,
+      request_bodies: _capture.request_bodies !== false,
+      response_bodies: _capture.response_bodies !== false,
+      block_service_workers: _capture.block_service_workers === true
+    };
+    var host = url ? hostOf(url) : location.hostname;
+    _captureDomains.forEach(function (d) {
+      if (host !== d && !host.endsWith('.' + d)) return;
+      var o = _domainCapture[d];
+      ['enabled', 'request_bodies', 'response_bodies', 'block_service_workers'].forEach(function (k) {
+        if (typeof o[k] === 'boolean') out[k] = o[k];
+      });
+    });
+    return out;
+  }
+
+  // Tauri's invoke, held onto the first time it's seen so a page that later
+  // replaces window.__TAURI_INTERNALS__ can't break (or read) capture traffic
+  var _invoke = null;
+  function ipc() {
+    if (!_invoke && window.__TAURI_INTERNALS__) {
+      var internals = window.__TAURI_INTERNALS__;
+      _invoke = internals.invoke.bind(internals);
+    }
+    return _invoke;
+  }
+
+  // Negotiated protocol ("h2", "h3", "http/1.1") from Resource Timing; empty for
+  // cross-origin responses without Timing-Allow-Origin
+  function protocolOf(entry) {
+    try {
+      var timing = entry.type === 'navigation'
+        ? performance.getEntriesByType('navigation')
+        : performance.getEntriesByName(new URL(entry.url, location.href).href, 'resource');
+      var last = timing[timing.length - 1];
+      return (last && last.nextHopProtocol) || undefined;
+    } catch (_) {
+      return undefined;
+    }
+  }
+
   function send(entry) {
+    var settings = settingsFor(entry.url);
+    if (!settings.enabled) return;
+    if (entry.status > 0 && entry.protocol === undefined) entry.protocol = protocolOf(entry);
+    if (!settings.request_bodies) {
+      entry.requestBody = null;
+      delete entry.requestParts;
+    }
+    if (!settings.response_bodies) {
+      entry.responseBody = null;
+      delete entry.responseCharset;
+      delete entry.responseEncoding;
+      delete entry.responseSize;
+    }
     try {
-      if (window.__TAURI_INTERNALS__) {
+      var invoke = ipc();
+      if (invoke) {
         // Flush buffered entries first
         while (_buffer.length > 0) {
-          window.__TAURI_INTERNALS__.invoke('save_capture_data', { data: _buffer.shift() });
+          invoke('save_capture_data', { data: _buffer.shift() });
         }
-        window.__TAURI_INTERNALS__.invoke('save_capture_data', { data: entry });
+        invoke('save_capture_data', { data: entry });
       } else {
         _buffer.push(entry);
       }
@@ -23,9 +88,10 @@
 
   // Retry flushing buffer until IPC is ready (check every 50ms for up to 30s)
   const _flushTimer = setInterval(function () {
-    if (window.__TAURI_INTERNALS__ && _buffer.length > 0) {
+    var invoke = ipc();
+    if (invoke && _buffer.length > 0) {
       while (_buffer.length > 0) {
-        window.__TAURI_INTERNALS__.invoke('save_capture_data', { data: _buffer.shift() });
+        invoke('save_capture_data', { data: _buffer.shift() });
       }
     }
   }, 50);
@@ -46,11 +112,148 @@
     timestamp: new Date().toISOString()
   });
 
+  // --- Interception rules (pushed in from apps/<name>/config.json "rules") ---
+  // A rule group applies when either the page or the request is on one of the app's domains.
+  const _ruleGroups = window.__harharharRules || [];
+
+  function hostOf(u) {
+    try { return new URL(u, location.href).hostname; } catch (_) { return ''; }
+  }
+
+  function applyRules(url) {
+    var out = { url: url, block: false, headers: {} };
+    var reqHost = hostOf(url);
+    _ruleGroups.forEach(function (g) {
+      if (g.domains.indexOf(location.hostname) < 0 && g.domains.indexOf(reqHost) < 0) return;
+      g.rules.forEach(function (r) {
+        if (out.url.indexOf(r.match) < 0) return;
+        if (r.block) out.block = true;
+        if (r.rewrite) out.url = out.url.split(r.rewrite.from).join(r.rewrite.to);
+        Object.keys(r.set_headers || {}).forEach(function (k) {
+          out.headers[k.toLowerCase()] = r.set_headers[k];
+        });
+      });
+    });
+    return out;
+  }
+
+  // --- Body decoding ---
+  // "base64:..." of up to maxBytes bytes
+  function toBase64(bytes, maxBytes) {
+    var s = '';
+    for (var i = 0; i < Math.min(bytes.length, maxBytes); i++) s += String.fromCharCode(bytes[i]);
+    return 'base64:' + btoa(s);
+  }
+
+  // Decode body bytes using the charset from Content-Type (fetch's text() always
+  // assumes UTF-8). Bytes that aren't valid text in that charset — binary or
+  // compressed payloads — are kept as base64 for the Rust side to unpack, with
+  // their full size, as only the first MAX_BODY bytes are kept.
+  function decodeBody(bytes, contentType) {
+    var m = /charset=["']?([^;"'\s]+)/i.exec(contentType || '');
+    var charset = m ? m[1].toLowerCase() : 'utf-8';
+    try {
+      var text = new TextDecoder(charset, { fatal: true }).decode(bytes);
+      return { text: text.substring(0, MAX_BODY), charset: charset === 'utf-8' || charset === 'utf8' ? null : charset };
+    } catch (_) {
+      return { text: toBase64(bytes, MAX_BODY * 3 / 4), encoding: 'base64', size: bytes.length };
+    }
+  }
+
+  // --- Response body reader ---
+  // Reads a (cloned) response chunk by chunk. Streamed bodies — NDJSON, SSE,
+  // token-by-token output — keep per-chunk timing so they show up as streams.
+  const STREAM_TYPES = /event-stream|ndjson|jsonl|stream\+json|x-stream/i;
+
+  // A stream still open this long (SSE feeds, long-poll NDJSON) is captured with what
+  // arrived so far; so is one that fills MAX_BODY — either may never end
+  const STREAM_FLUSH_MS = 60000;
+
+  function readBody(res) {
+    const out = { text: null, chunks: [], streamed: false };
+    const contentType = res.headers.get('content-type') || '';
+    if (!res.body || !res.body.getReader) {
+      return res.arrayBuffer().then(function (buf) {
+        return Object.assign(out, decodeBody(new Uint8Array(buf), contentType));
+      }, function () { return out; });
+    }
+    return new Promise(function (resolve) {
+      const reader = res.body.getReader();
+      const start = performance.now();
+      const parts = [];
+      let kept = 0;
+      let total = 0;
+      let finished = false;
+      function finish(open) {
+        if (finished) return;
+        finished = true;
+        clearTimeout(timer);
+        const bytes = new Uint8Array(kept);
+        let offset = 0;
+        parts.forEach(function (p) { bytes.set(p, offset); offset += p.byteLength; });
+        Object.assign(out, decodeBody(bytes, contentType));
+        if (out.size != null) out.size = total;
+        out.chunks = out.chunks.slice();
+        const spread = out.chunks.length > 1 ? out.chunks[out.chunks.length - 1].t - out.chunks[0].t : 0;
+        out.streamed = open || STREAM_TYPES.test(contentType) || spread > 500;
+        if (!out.streamed) out.chunks = [];
+        if (open) out.open = true;
+        resolve(out);
+      }
+      const timer = setTimeout(function () { finish(true); }, STREAM_FLUSH_MS);
+      (async function () {
+        try {
+          for (;;) {
+            const { done, value } = await reader.read();
+            if (done) break;
+            total += value.byteLength;
+            if (out.chunks.length < 500) out.chunks.push({ t: Math.round(performance.now() - start), bytes: value.byteLength });
+            // Keep draining past the limit so the tee'd stream isn't held in memory
+            if (kept < MAX_BODY) { parts.push(value); kept += value.byteLength; }
+            else if (STREAM_TYPES.test(contentType)) finish(true);
+          }
+        } catch (_) {}
+        finish(false);
+      })();
+    });
+  }
+
+  // --- multipart/form-data bodies ---
+  // Part names, filenames and content types; small text values kept, files hashed
+  // (not stored) so upload endpoints get a real shape instead of a boundary blob.
+  async function describeFormData(fd) {
+    const parts = [];
+    for (const [name, value] of fd.entries()) {
+      if (typeof value === 'string') {
+        parts.push({ name: name, type: 'text', size: value.length, value: value.substring(0, 2000) });
+        continue;
+      }
+      const part = { name: name, type: 'file', filename: value.name || '', contentType: value.type || '', size: value.size };
+      if (value.size <= 50 * 1024 * 1024) {
+        try {
+          const digest = await crypto.subtle.digest('SHA-256', await value.arrayBuffer());
+          part.sha256 = Array.from(new Uint8Array(digest)).map(function (b) { return b.toString(16).padStart(2, '0'); }).join('');
+        } catch (_) {}
+      }
+      parts.push(part);
+    }
+    return parts;
+  }
+
   // --- fetch wrapper ---
   const _fetch = window.fetch.bind(window);
 
   window.fetch = async function (...args) {
-    const req = new Request(...args);
+    let req = new Request(...args);
+    const ruled = applyRules(req.url);
+    if (ruled.block) {
+      console.log('[harharhar] blocked by rule: ' + req.url);
+      throw new TypeError('Blocked by harharhar rule');
+    }
+    if (ruled.url !== req.url) req = new Request(ruled.url, req);
+    Object.keys(ruled.headers).forEach(function (k) {
+      try { req.headers.set(k, ruled.headers[k]); } catch (_) {}
+    });
     const reqForFetch = req.clone();
 
     const method = req.method;
@@ -58,27 +261,46 @@
     const requestHeaders = {};
     req.headers.forEach(function (v, k) { requestHeaders[k] = v; });
     let requestBody = null;
-    try { requestBody = await req.text(); } catch (_) {}
+    let requestParts = null;
+    if (/multipart\/form-data/i.test(req.headers.get('content-type') || '')) {
+      try { requestParts = await describeFormData(await req.clone().formData()); } catch (_) {}
+    }
+    try { requestBody = requestParts ? '[multipart: ' + requestParts.length + ' parts]' : await req.text(); } catch (_) {}
 
     _capturedUrls.add(url);
 
     const t0 = performance.now();
     try {
       const res = await _fetch(reqForFetch);
-      const clone = res.clone();
-      let responseBody = null;
-      try { responseBody = (await clone.text()).substring(0, 500000); } catch (_) {}
       const responseHeaders = {};
       res.headers.forEach(function (v, k) { responseHeaders[k] = v; });
+      const duration = Math.round(performance.now() - t0);
 
-      send({ type: 'fetch', method: method, url: url, requestHeaders: requestHeaders,
-        requestBody: requestBody, status: res.status, statusText: res.statusText,
-        responseHeaders: responseHeaders, responseBody: responseBody,
-        duration: Math.round(performance.now() - t0), timestamp: new Date().toISOString() });
+      // Read the body from a clone in the background — the page gets the response
+      // right away, so streamed output still reaches it as it arrives
+      const bodyRead = settingsFor(url).response_bodies
+        ? readBody(res.clone())
+        : Promise.resolve({ text: null, chunks: [], streamed: false });
+      bodyRead.then(function (body) {
+        const entry = { type: 'fetch', method: method, url: url, requestHeaders: requestHeaders,
+          requestBody: requestBody, status: res.status, statusText: res.statusText,
+          responseHeaders: responseHeaders, responseBody: body.text,
+          duration: duration, timestamp: new Date().toISOString() };
+        if (requestParts) entry.requestParts = requestParts;
+        if (body.charset) entry.responseCharset = body.charset;
+        if (body.encoding) entry.responseEncoding = body.encoding;
+        if (body.size != null) entry.responseSize = body.size;
+        if (body.streamed) {
+          entry.streamed = true;
+          entry.chunks = body.chunks;
+        }
+        if (body.open) entry.streamOpen = true;
+        send(entry);
+      });
       return res;
     } catch (err) {
       send({ type: 'fetch', method: method, url: url, requestHeaders: requestHeaders,
-        requestBody: requestBody, status: 0, statusText: err.message,
+        requestBody: requestBody, requestParts: requestParts || undefined, status: 0, statusText: err.message,
         responseHeaders: {}, responseBody: null,
         duration: Math.round(performance.now() - t0), timestamp: new Date().toISOString() });
       throw err;
@@ -92,12 +314,17 @@
 
   XMLHttpRequest.prototype.open = function (method, url) {
     this.__m = method;
-    this.__u = new URL(url, location.href).href;
+    this.__r = applyRules(new URL(url, location.href).href);
+    this.__u = this.__r.url;
     this.__h = {};
-    return _open.apply(this, arguments);
+    var args = Array.prototype.slice.call(arguments);
+    args[1] = this.__u;
+    return _open.apply(this, args);
   };
 
   XMLHttpRequest.prototype.setRequestHeader = function (k, v) {
+    // Rule-set headers win over the page's own
+    if (this.__r && this.__r.headers[k.toLowerCase()] !== undefined) return;
     if (this.__h) this.__h[k] = v;
     return _setH.call(this, k, v);
   };
@@ -105,12 +332,35 @@
   XMLHttpRequest.prototype.send = function (body) {
     var t0 = performance.now();
     var xhr = this;
+
+    if (xhr.__r && xhr.__r.block) {
+      console.log('[harharhar] blocked by rule: ' + xhr.__u);
+      setTimeout(function () {
+        xhr.dispatchEvent(new ProgressEvent('error'));
+        xhr.dispatchEvent(new ProgressEvent('loadend'));
+      });
+      return;
+    }
+    if (xhr.__r) {
+      Object.keys(xhr.__r.headers).forEach(function (k) {
+        if (xhr.__h) xhr.__h[k] = xhr.__r.headers[k];
+        _setH.call(xhr, k, xhr.__r.headers[k]);
+      });
+    }
     _capturedUrls.add(xhr.__u);
 
+    // FormData bodies are described part by part (the browser builds the multipart text itself)
+    var isForm = typeof FormData !== 'undefined' && body instanceof FormData;
+    var partsPromise = isForm ? describeFormData(body) : null;
+    var requestBody = isForm ? '[multipart]' : (body ? String(body).substring(0, MAX_BODY) : null);
+    if (isForm && xhr.__h && !Object.keys(xhr.__h).some(function (k) { return k.toLowerCase() === 'content-type'; })) {
+      xhr.__h['content-type'] = 'multipart/form-data';
+    }
+
     // Send request-start immediately so we don't lose it on navigation
     send({ type: 'xhr-start', method: xhr.__m, url: xhr.__u,
       requestHeaders: xhr.__h || {},
-      requestBody: body ? String(body).substring(0, 500000) : null,
+      requestBody: requestBody,
       status: 0, statusText: 'pending', responseHeaders: {},
       responseBody: null, duration: 0, timestamp: new Date().toISOString() });
 
@@ -120,16 +370,85 @@
         var i = l.indexOf(': ');
         if (i > 0) rh[l.slice(0, i)] = l.slice(i + 2);
       });
-      send({ type: 'xhr', method: xhr.__m, url: xhr.__u,
+      var entry = { type: 'xhr', method: xhr.__m, url: xhr.__u,
         requestHeaders: xhr.__h || {},
-        requestBody: body ? String(body).substring(0, 500000) : null,
+        requestBody: requestBody,
         status: xhr.status, statusText: xhr.statusText, responseHeaders: rh,
-        responseBody: (xhr.responseText || '').substring(0, 500000),
-        duration: Math.round(performance.now() - t0), timestamp: new Date().toISOString() });
+        responseBody: null,
+        duration: Math.round(performance.now() - t0), timestamp: new Date().toISOString() };
+
+      // responseText only exists for text responses; binary ones go through decodeBody
+      var ct = xhr.getResponseHeader('content-type') || '';
+      var bodyPromise;
+      try {
+        if (xhr.responseType === '' || xhr.responseType === 'text') {
+          entry.responseBody = (xhr.responseText || '').substring(0, MAX_BODY);
+        } else if (xhr.responseType === 'json') {
+          entry.responseBody = JSON.stringify(xhr.response).substring(0, MAX_BODY);
+        } else if (xhr.responseType === 'document') {
+          entry.responseBody = xhr.response ? xhr.response.documentElement.outerHTML.substring(0, MAX_BODY) : null;
+        } else if (xhr.response instanceof ArrayBuffer) {
+          bodyPromise = Promise.resolve(decodeBody(new Uint8Array(xhr.response), ct));
+        } else if (xhr.response instanceof Blob) {
+          bodyPromise = xhr.response.arrayBuffer().then(function (b) { return decodeBody(new Uint8Array(b), ct); });
+        }
+      } catch (_) {}
+
+      Promise.all([partsPromise, bodyPromise]).then(function (results) {
+        var parts = results[0], body = results[1];
+        if (body) {
+          entry.responseBody = body.text;
+          if (body.charset) entry.responseCharset = body.charset;
+          if (body.encoding) entry.responseEncoding = body.encoding;
+          if (body.size != null) entry.responseSize = body.size;
+        }
+        if (parts) {
+          entry.requestParts = parts;
+          entry.requestBody = '[multipart: ' + parts.length + ' parts]';
+        }
+        send(entry);
+      });
     });
     return _send.call(this, body);
   };
 
+  // --- navigator.sendBeacon ---
+  // Usually telemetry, so the Rust side drops these unless the app opts in via
+  // "capture_noise" — but some apps send their real event API this way.
+  if (navigator.sendBeacon) {
+    const _sendBeacon = navigator.sendBeacon.bind(navigator);
+    navigator.sendBeacon = function (url, data) {
+      const queued = _sendBeacon(url, data);
+      const entry = { type: 'beacon', method: 'POST', url: new URL(url, location.href).href,
+        requestHeaders: { cookie: document.cookie || '' }, requestBody: null,
+        status: queued ? 0 : -1, statusText: queued ? 'queued' : 'rejected',
+        responseHeaders: {}, responseBody: null, duration: 0, timestamp: new Date().toISOString() };
+      let bodyPromise = null;
+      try {
+        if (typeof data === 'string') {
+          entry.requestHeaders['content-type'] = 'text/plain;charset=UTF-8';
+          entry.requestBody = data.substring(0, MAX_BODY);
+        } else if (data instanceof URLSearchParams) {
+          entry.requestHeaders['content-type'] = 'application/x-www-form-urlencoded;charset=UTF-8';
+          entry.requestBody = data.toString().substring(0, MAX_BODY);
+        } else if (data instanceof FormData) {
+          entry.requestHeaders['content-type'] = 'multipart/form-data';
+          bodyPromise = describeFormData(data).then(function (parts) {
+            entry.requestParts = parts;
+            entry.requestBody = '[multipart: ' + parts.length + ' parts]';
+          });
+        } else if (data instanceof Blob) {
+          if (data.type) entry.requestHeaders['content-type'] = data.type;
+          bodyPromise = data.text().then(function (t) { entry.requestBody = t.substring(0, MAX_BODY); });
+        } else if (data) {
+          entry.requestBody = new TextDecoder().decode(ArrayBuffer.isView(data) ? data : new Uint8Array(data)).substring(0, MAX_BODY);
+        }
+      } catch (_) {}
+      Promise.resolve(bodyPromise).catch(function () {}).then(function () { send(entry); });
+      return queued;
+    };
+  }
+
   // --- Capture document.cookie once on load ---
   function captureCookies() {
     var cookies = document.cookie;
@@ -193,29 +512,61 @@
   // --- WebSocket wrapper ---
   const _WS = window.WebSocket;
   const _wsSockets = [];
-  window.__hh_ws = _wsSockets;
+  const _wsSubUntil = new WeakMap(); // socket -> ms timestamp, set by ws_subscribe
+
+  // Binary frames are recorded as "base64:..." (ArrayBuffer/typed arrays directly, Blobs once read)
+  function wsPayload(data, done) {
+    if (typeof data === 'string') return done(data.substring(0, 100000));
+    function toB64(buf) {
+      return toBase64(new Uint8Array(buf), 75000);
+    }
+    if (data instanceof ArrayBuffer) return done(toB64(data));
+    if (ArrayBuffer.isView(data)) return done(toB64(data.buffer.slice(data.byteOffset, data.byteOffset + data.byteLength)));
+    if (data instanceof Blob) {
+      data.arrayBuffer().then(function (b) { done(toB64(b)); }, function () { done('[binary]'); });
+      return;
+    }
+    done('[binary]');
+  }
+
+  // While a ws_subscribe is active, frames are tagged ws-sub-* so
+  // they're kept in captures even though they carry no auth headers.
+  function wsType(ws, dir) {
+    var until = _wsSubUntil.get(ws);
+    return (until && Date.now() < until ? 'ws-sub-' : 'ws-msg-') + dir;
+  }
 
   window.WebSocket = function (url, protocols) {
     var ws = protocols ? new _WS(url, protocols) : new _WS(url);
     _wsSockets.push(ws);
 
-    send({ type: 'ws-open', method: 'WS', url: url, requestHeaders: {}, requestBody: null,
+    // The handshake carries the page's cookies; recorded so auth.json can tell cookie-authed sockets
+    send({ type: 'ws-open', method: 'WS', url: url, requestHeaders: { cookie: document.cookie || '' }, requestBody: null,
       status: 0, statusText: '', responseHeaders: {}, responseBody: null,
       duration: 0, timestamp: new Date().toISOString() });
 
     ws.addEventListener('message', function (e) {
-      var data = typeof e.data === 'string' ? e.data.substring(0, 100000) : '[binary]';
-      send({ type: 'ws-msg-in', method: 'WS', url: url, requestHeaders: {}, requestBody: null,
-        status: 0, statusText: 'incoming', responseHeaders: {}, responseBody: data,
-        duration: 0, timestamp: new Date().toISOString() });
+      var type = wsType(ws, 'in');
+      var timestamp = new Date().toISOString();
+      wsPayload(e.data, function (data) {
+        send({ type: type, method: 'WS', url: url, requestHeaders: {}, requestBody: null,
+          status: 0, statusText: 'incoming', responseHeaders: {}, responseBody: data,
+          duration: 0, timestamp: timestamp });
+      });
     });
 
     var _wsSend = ws.send.bind(ws);
+    var sentFirst = false;
     ws.send = function (data) {
-      var body = typeof data === 'string' ? data.substring(0, 100000) : '[binary]';
-      send({ type: 'ws-msg-out', method: 'WS', url: url, requestHeaders: {}, requestBody: body,
-        status: 0, statusText: 'outgoing', responseHeaders: {}, responseBody: null,
-        duration: 0, timestamp: new Date().toISOString() });
+      // The first frame is always kept — it's where sockets usually authenticate
+      var type = sentFirst ? wsType(ws, 'out') : 'ws-first-out';
+      sentFirst = true;
+      var timestamp = new Date().toISOString();
+      wsPayload(data, function (body) {
+        send({ type: type, method: 'WS', url: url, requestHeaders: {}, requestBody: body,
+          status: 0, statusText: 'outgoing', responseHeaders: {}, responseBody: null,
+          duration: 0, timestamp: timestamp });
+      });
       return _wsSend(data);
     };
 
@@ -227,5 +578,48 @@
   window.WebSocket.CLOSING = _WS.CLOSING;
   window.WebSocket.CLOSED = _WS.CLOSED;
 
+  // --- Service workers ---
+  // Page fetches are wrapped above even when a worker answers them, but requests the
+  // worker makes itself never pass through the page. Where that matters, registration
+  // fails as if unsupported and installed workers are removed, so the app falls back
+  // to fetching from the page (fully after one reload).
+  if (navigator.serviceWorker && settingsFor(location.href).block_service_workers) {
+    try {
+      navigator.serviceWorker.register = function () {
+        return Promise.reject(new DOMException('Service workers are blocked by harharhar', 'SecurityError'));
+      };
+      navigator.serviceWorker.getRegistrations().then(function (regs) {
+        regs.forEach(function (r) { r.unregister(); });
+      }, function () {});
+    } catch (_) {}
+  }
+
+  // --- Bridge for evals from Rust (window.__hh) ---
+  // The hooks above have to live in the page's own JS world to wrap its fetch/XHR/WebSocket,
+  // and webviews don't offer a content world that can do that. So instead of loose globals,
+  // everything harharhar keeps in the page sits behind one frozen, non-writable,
+  // non-configurable property defined before any page script runs, holding natives
+  // captured up front — pages can't clobber element refs, sockets or the eval reply path.
+  const _stringify = JSON.stringify;
+  var _refs = [];
+  Object.defineProperty(window, '__hh', {
+    value: Object.freeze({
+      // read_ui element refs, for click_ref/type_ref/select_ref
+      setRefs: function (refs) { _refs = refs; },
+      ref: function (i) { return _refs[i]; },
+      sockets: function () { return _wsSockets.slice(); },
+      subscribe: function (ws, until) { _wsSubUntil.set(ws, until); },
+      // Result of an eval back to eval_js_with_timeout
+      reply: function (id, value) {
+        var s = typeof value === 'string' ? value : _stringify(value);
+        var invoke = ipc();
+        if (invoke) invoke('eval_callback', { id: id, result: s || 'null' });
+      }
+    }),
+    writable: false,
+    configurable: false,
+    enumerable: false
+  });
+
   console.log('[harharhar] intercept active — buffered capture enabled');
 })();
@@ -0,0 +1,143 @@
+use crate::config;
+use crate::endpoints::EndpointCatalog;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// Instructions an analyzer may execute per run before it's stopped
+const FUEL: u64 = 2_000_000_000;
+
+/// Linear memory an analyzer may grow to
+const MAX_MEMORY_BYTES: usize = 256 * 1024 * 1024;
+
+/// One engine for every analyzer, so compiled modules can be reused across runs
+static ENGINE: LazyLock<Result<wasmtime::Engine, String>> = LazyLock::new(|| {
+    let mut wasm_config = wasmtime::Config::new();
+    wasm_config.consume_fuel(true);
+    wasmtime::Engine::new(&wasm_config).map_err(|e| e.to_string())
+});
+
+/// Compiled modules by path, with the mtime they were compiled at: endpoints are
+/// regenerated often, and compiling a module costs far more than running it
+static MODULES: LazyLock<Mutex<HashMap<PathBuf, (SystemTime, wasmtime::Module)>>> =
+    LazyLock::new(Default::default);
+
+/// Run every WASM analyzer for `app_name` over its freshly built catalog. Each one's
+/// JSON output becomes a section of endpoints.json's "extensions", keyed by file name.
+///
+/// Analyzers are `.wasm` files in ~/.harharhar/analyzers/ (every app) and
+/// apps/<app>/analyzers/ (that app; overrides a shared one of the same name). They
+/// get no imports at all — no files, network, clock or env — and run with fuel and
+/// memory limits, so a shared analyzer can't do anything but read the catalog.
+///
+/// A module exports `memory`, `alloc(len: i32) -> i32` and
+/// `analyze(ptr: i32, len: i32) -> i64`. The input JSON ({"app", "endpoints"}) is
+/// written at `alloc(len)`; `analyze` returns its output JSON's `ptr << 32 | len`.
+pub fn run(app_name: &str, catalog: &EndpointCatalog) -> BTreeMap<String, serde_json::Value> {
+    let mut modules: BTreeMap<String, PathBuf> = BTreeMap::new();
+    let root = config::data_dir();
+    for dir in [
+        root.join("analyzers"),
+        root.join("apps").join(app_name).join("analyzers"),
+    ] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().is_some_and(|e| e == "wasm") {
+                if let Some(stem) = path.file_stem() {
+                    modules.insert(stem.to_string_lossy().to_string(), path);
+                }
+            }
+        }
+    }
+    if modules.is_empty() {
+        return BTreeMap::new();
+    }
+
+    let input = serde_json::json!({"app": app_name, "endpoints": catalog.endpoints});
+    let Ok(input) = serde_json::to_vec(&input) else {
+        return BTreeMap::new();
+    };
+    let mut sections = BTreeMap::new();
+    for (name, path) in modules {
+        match analyze(&path, &input) {
+            Ok(section) => {
+                sections.insert(name, section);
+            }
+            Err(e) => eprintln!("[harharhar] analyzer {name}: {e}"),
+        }
+    }
+    sections
+}
+
+/// Instantiate one module in a fresh sandbox and run it over `input`
+fn analyze(path: &Path, input: &[u8]) -> Result<serde_json::Value, String> {
+    let engine = ENGINE.as_ref().map_err(|e| e.clone())?;
+    let module = compiled(engine, path)?;
+
+    let limits = wasmtime::StoreLimitsBuilder::new()
+        .memory_size(MAX_MEMORY_BYTES)
+        .instances(1)
+        .build();
+    let mut store = wasmtime::Store::new(engine, limits);
+    store.limiter(|limits| limits);
+    store.set_fuel(FUEL).map_err(|e| e.to_string())?;
+
+    // No imports are provided: a module that needs any fails to instantiate
+    let instance = wasmtime::Instance::new(&mut store, &module, &[]).map_err(|e| e.to_string())?;
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or("no exported memory")?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&mut store, "alloc")
+        .map_err(|e| e.to_string())?;
+    let analyze = instance
+        .get_typed_func::<(i32, i32), i64>(&mut store, "analyze")
+        .map_err(|e| e.to_string())?;
+
+    let len = i32::try_from(input.len()).map_err(|_| "catalog too large")?;
+    let ptr = alloc.call(&mut store, len).map_err(|e| e.to_string())?;
+    memory
+        .write(&mut store, ptr as u32 as usize, input)
+        .map_err(|e| e.to_string())?;
+    let packed = analyze
+        .call(&mut store, (ptr, len))
+        .map_err(|e| e.to_string())?;
+
+    let (out_ptr, out_len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+    // The module picks ptr and len: check them against its memory before allocating
+    let in_bounds = out_ptr
+        .checked_add(out_len)
+        .is_some_and(|end| end <= memory.data_size(&store));
+    if !in_bounds {
+        return Err("output is outside the module's memory".to_string());
+    }
+    let mut output = vec![0u8; out_len];
+    memory
+        .read(&store, out_ptr, &mut output)
+        .map_err(|e| e.to_string())?;
+    match serde_json::from_slice(&output) {
+        Ok(value @ serde_json::Value::Object(_)) => Ok(value),
+        Ok(_) => Err("output is not a JSON object".to_string()),
+        Err(e) => Err(format!("output is not JSON: {e}")),
+    }
+}
+
+/// The compiled module at `path`, from MODULES unless the file changed since
+fn compiled(engine: &wasmtime::Engine, path: &Path) -> Result<wasmtime::Module, String> {
+    let mtime = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| e.to_string())?;
+    let mut modules = MODULES.lock().unwrap();
+    if let Some((compiled_at, module)) = modules.get(path) {
+        if *compiled_at == mtime {
+            return Ok(module.clone());
+        }
+    }
+    let module = wasmtime::Module::from_file(engine, path).map_err(|e| e.to_string())?;
+    modules.insert(path.to_path_buf(), (mtime, module.clone()));
+    Ok(module)
+}
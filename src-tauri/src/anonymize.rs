@@ -0,0 +1,343 @@
+use crate::config;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// Headers whose values are credentials — replaced wholesale (cookie values one by one)
+const SECRET_HEADERS: &[&str] = &[
+    "cookie",
+    "set-cookie",
+    "authorization",
+    "x-csrf-token",
+    "x-xsrf-token",
+    "proxy-authorization",
+];
+
+/// Fields whose numbers are measurements, not identifiers
+const KEEP_NUMBERS: &[&str] = &[
+    "status",
+    "duration",
+    "t",
+    "bytes",
+    "size",
+    "times_seen",
+    "visits",
+    "times",
+    "chunks",
+];
+
+/// Tries at a fake that's new before settling for one already handed out: a short
+/// token has few of them
+const MAX_ATTEMPTS: u64 = 64;
+
+/// App files copied into the output, besides captures/. sessions/ is never exported.
+const APP_FILES: &[&str] = &[
+    "config.json",
+    "endpoints.json",
+    "endpoint-notes.json",
+    "auth.json",
+    "sitemap.json",
+    "history.jsonl",
+    "digest.md",
+    "examples.sh",
+    "examples-inpage.md",
+];
+
+/// Write an anonymized copy of an app to `out_dir`: cookies, tokens, emails and
+/// numeric IDs get consistent fake values (the same original always maps to the
+/// same fake, across every file), so requests still line up with the responses
+/// their IDs came from. Returns the number of files written.
+pub fn run(app_name: &str, out_dir: &Path) -> Result<usize, String> {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    if !app_dir.is_dir() {
+        return Err(format!("no app named \"{app_name}\""));
+    }
+    fs::create_dir_all(out_dir.join("captures")).map_err(|e| e.to_string())?;
+
+    let mut anon = Anonymizer::new();
+    let mut written = 0;
+
+    let mut captures: Vec<std::path::PathBuf> = fs::read_dir(app_dir.join("captures"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    captures.sort();
+    for path in captures {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        // Archived apps keep gzipped captures; the copy is plain JSONL
+        let contents = if let Some(stem) = name.strip_suffix(".gz") {
+            let mut text = String::new();
+            let bytes = fs::read(&path).map_err(|e| e.to_string())?;
+            flate2::read::GzDecoder::new(&bytes[..])
+                .read_to_string(&mut text)
+                .map_err(|e| format!("{name}: {e}"))?;
+            (stem.to_string(), text)
+        } else if name.ends_with(".jsonl") {
+            (name, fs::read_to_string(&path).map_err(|e| e.to_string())?)
+        } else {
+            continue;
+        };
+        let out = anon.jsonl(&contents.1);
+        fs::write(out_dir.join("captures").join(&contents.0), out).map_err(|e| e.to_string())?;
+        written += 1;
+    }
+
+    for file in APP_FILES {
+        let Ok(contents) = fs::read_to_string(app_dir.join(file)) else {
+            continue;
+        };
+        let out = if file.ends_with(".jsonl") {
+            anon.jsonl(&contents)
+        } else if file.ends_with(".json") {
+            match serde_json::from_str::<serde_json::Value>(&contents) {
+                Ok(mut value) => {
+                    anon.value(&mut value, "");
+                    serde_json::to_string_pretty(&value).unwrap_or_default()
+                }
+                Err(_) => anon.text(&contents),
+            }
+        } else {
+            anon.text(&contents)
+        };
+        fs::write(out_dir.join(file), out).map_err(|e| e.to_string())?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+struct Anonymizer {
+    /// Per-run secret so fakes can't be reversed by hashing guesses
+    salt: u64,
+    fakes: HashMap<String, String>,
+    used: HashSet<String>,
+}
+
+impl Anonymizer {
+    fn new() -> Self {
+        let salt = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Anonymizer {
+            salt,
+            fakes: HashMap::new(),
+            used: HashSet::new(),
+        }
+    }
+
+    fn jsonl(&mut self, contents: &str) -> String {
+        let mut out = String::with_capacity(contents.len());
+        for line in contents.lines() {
+            match serde_json::from_str::<serde_json::Value>(line) {
+                Ok(mut value) => {
+                    self.value(&mut value, "");
+                    out.push_str(&value.to_string());
+                }
+                Err(_) => out.push_str(&self.text(line)),
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Anonymize a JSON value in place; `key` is the field it sits under
+    fn value(&mut self, value: &mut serde_json::Value, key: &str) {
+        let lower = key.to_lowercase();
+        match value {
+            serde_json::Value::String(s) if SECRET_HEADERS.contains(&lower.as_str()) => {
+                *s = self.header(&lower, s);
+            }
+            serde_json::Value::String(s) => *s = self.text(s),
+            serde_json::Value::Number(n) => {
+                let digits = n.to_string();
+                if !KEEP_NUMBERS.contains(&key) && n.is_u64() && digits.len() >= 5 {
+                    let fake = self.fake(&digits, Kind::Digits);
+                    if let Ok(fake) = fake.parse::<u64>() {
+                        *value = serde_json::Value::from(fake);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.value(item, key);
+                }
+            }
+            serde_json::Value::Object(map) => {
+                for (k, v) in map.iter_mut() {
+                    self.value(v, k);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Credential header: cookie names stay, values are replaced; other headers keep
+    /// their scheme word ("Bearer ...")
+    fn header(&mut self, name: &str, value: &str) -> String {
+        if name == "set-cookie" {
+            // One cookie per line; after its name=value come attributes (Path=/, Expires=...)
+            return value
+                .split('\n')
+                .map(|line| {
+                    let (pair, attributes) = line.split_at(line.find(';').unwrap_or(line.len()));
+                    match pair.split_once('=') {
+                        Some((k, v)) if !v.trim().is_empty() => {
+                            format!("{k}={}{attributes}", self.fake(v.trim(), Kind::Token))
+                        }
+                        _ => line.to_string(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        if name == "cookie" {
+            return value
+                .split(';')
+                .map(|part| match part.split_once('=') {
+                    Some((k, v)) if !v.trim().is_empty() => {
+                        format!("{k}={}", self.fake(v.trim(), Kind::Token))
+                    }
+                    _ => part.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(";");
+        }
+        match value.split_once(' ') {
+            Some((scheme, secret)) => format!("{scheme} {}", self.fake(secret, Kind::Token)),
+            None => self.fake(value, Kind::Token),
+        }
+    }
+
+    /// Replace emails, token-looking strings and 5+ digit runs inside free text
+    /// (URLs, bodies, markdown) — everything else is left as is.
+    fn text(&mut self, text: &str) -> String {
+        let is_word = |c: char| c.is_ascii_alphanumeric() || "-_.@+".contains(c);
+        let mut out = String::with_capacity(text.len());
+        let mut word = String::new();
+        for c in text.chars().chain(std::iter::once('\0')) {
+            if is_word(c) {
+                word.push(c);
+                continue;
+            }
+            if !word.is_empty() {
+                let replaced = self.word(&word);
+                out.push_str(&replaced);
+                word.clear();
+            }
+            if c != '\0' {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    fn word(&mut self, word: &str) -> String {
+        let trimmed = word.trim_end_matches('.');
+        let suffix = &word[trimmed.len()..];
+        if let Some((local, domain)) = trimmed.split_once('@') {
+            if !local.is_empty() && domain.contains('.') && !domain.starts_with('.') {
+                return format!("{}{suffix}", self.fake(trimmed, Kind::Email));
+            }
+        }
+        let has_letter = trimmed.bytes().any(|b| b.is_ascii_alphabetic());
+        let digit_count = trimmed.bytes().filter(|b| b.is_ascii_digit()).count();
+        // Long mixed strings are tokens/hashes; "fetch", "2026-02-21" and "v2" aren't
+        if trimmed.len() >= 16 && has_letter && digit_count >= 2 && !trimmed.contains("..") {
+            return format!("{}{suffix}", self.fake(trimmed, Kind::Token));
+        }
+        // Otherwise only long digit runs (IDs) change: /users/123456/posts -> /users/804112/posts
+        let mut out = String::with_capacity(word.len());
+        let mut digits = String::new();
+        for c in word.chars().chain(std::iter::once('\0')) {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+            if digits.len() >= 5 {
+                let fake = self.fake(&digits, Kind::Digits);
+                out.push_str(&fake);
+            } else {
+                out.push_str(&digits);
+            }
+            digits.clear();
+            if c != '\0' {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    /// The fake for `original` — the same every time it's asked for, and (short tokens
+    /// aside) never the same fake for two different originals. Strings with nothing
+    /// alphanumeric in them are returned as they are.
+    fn fake(&mut self, original: &str, kind: Kind) -> String {
+        if let Some(fake) = self.fakes.get(original) {
+            return fake.clone();
+        }
+        if !original.chars().any(|c| c.is_ascii_alphanumeric()) {
+            return original.to_string();
+        }
+        let mut attempt = 0u64;
+        let fake = loop {
+            let mut hash = self.salt ^ 0xcbf29ce484222325 ^ attempt;
+            let mut next = || {
+                for b in original.bytes() {
+                    hash ^= b as u64;
+                    hash = hash.wrapping_mul(0x100000001b3);
+                }
+                hash = hash.wrapping_mul(0x100000001b3) ^ (hash >> 29);
+                hash
+            };
+            let candidate = match kind {
+                // Same length, no leading zero, so it still parses as the same kind of number;
+                // 20 digits start "1" then 0-7 so they stay under u64::MAX (18446...)
+                Kind::Digits => (0..original.len())
+                    .map(|i| {
+                        let d = (next() % 10) as u8;
+                        let d = match (i, original.len() == 20) {
+                            (0, true) => 1,
+                            (0, false) => 1 + d % 9,
+                            (1, true) => d % 8,
+                            _ => d,
+                        };
+                        (b'0' + d) as char
+                    })
+                    .collect(),
+                // Same length and character classes, so formats/validators still accept it
+                Kind::Token => original
+                    .chars()
+                    .map(|c| match c {
+                        'a'..='z' => (b'a' + (next() % 26) as u8) as char,
+                        'A'..='Z' => (b'A' + (next() % 26) as u8) as char,
+                        '0'..='9' => (b'0' + (next() % 10) as u8) as char,
+                        other => other,
+                    })
+                    .collect(),
+                Kind::Email => format!("user-{:06x}@example.com", next() & 0xffffff),
+            };
+            let fresh = !self.used.contains(&candidate);
+            if candidate != original && (fresh || attempt >= MAX_ATTEMPTS) {
+                break candidate;
+            }
+            attempt += 1;
+        };
+        self.used.insert(fake.clone());
+        self.fakes.insert(original.to_string(), fake.clone());
+        fake
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Digits,
+    Token,
+    Email,
+}
@@ -0,0 +1,218 @@
+use crate::capture_index;
+use crate::config;
+use crate::endpoints;
+use crate::errors::CommandError;
+use std::time::{Duration, Instant};
+
+/// How often a waiting assertion checks again
+const POLL: Duration = Duration::from_millis(250);
+
+/// A waiting assertion reports its latest failed check this often
+const PROGRESS_EVERY: Duration = Duration::from_secs(1);
+
+/// Run `check` until it passes or `wait_ms` (default 0: check once) runs out, sending
+/// the failing result as a "waiting" progress event about once a second meanwhile.
+/// Errors end the wait right away.
+fn poll(
+    cmd: &serde_json::Value,
+    progress: &dyn Fn(serde_json::Value),
+    mut check: impl FnMut() -> Result<serde_json::Value, CommandError>,
+) -> serde_json::Value {
+    let wait = Duration::from_millis(cmd.get("wait_ms").and_then(|v| v.as_u64()).unwrap_or(0));
+    let started = Instant::now();
+    let deadline = started + wait;
+    let mut reported = started;
+    loop {
+        let result = match check() {
+            Ok(result) => result,
+            Err(e) => return e.reply(),
+        };
+        let pass = result
+            .get("pass")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if pass || Instant::now() >= deadline {
+            return result;
+        }
+        if reported.elapsed() >= PROGRESS_EVERY {
+            reported = Instant::now();
+            progress(serde_json::json!({
+                "event": "waiting",
+                "elapsed_ms": started.elapsed().as_millis() as u64,
+                "result": result,
+            }));
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+/// {"text": "Welcome", "selector": "main", "absent": false, "ignore_case": false, "wait_ms": 0}:
+/// does the page (or the first element matching `selector`) show `text`?
+pub fn assert_text(
+    app: &tauri::AppHandle,
+    window: &str,
+    cmd: &serde_json::Value,
+    timeout: Duration,
+    progress: &dyn Fn(serde_json::Value),
+) -> serde_json::Value {
+    let text = cmd.get("text").and_then(|v| v.as_str()).unwrap_or("");
+    if text.is_empty() {
+        return CommandError::InvalidParams("missing text".to_string()).reply();
+    }
+    let selector = cmd.get("selector").and_then(|v| v.as_str());
+    let absent = cmd.get("absent").and_then(|v| v.as_bool()).unwrap_or(false);
+    let ignore_case = cmd
+        .get("ignore_case")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let js = format!(
+        "(() => {{ const el = {sel} ? document.querySelector({sel}) : document.body; \
+         if (!el) return JSON.stringify({{element: false}}); \
+         let hay = el.innerText || el.textContent || ''; let needle = {text}; \
+         if ({ic}) {{ hay = hay.toLowerCase(); needle = needle.toLowerCase(); }} \
+         return JSON.stringify({{element: true, count: hay.split(needle).length - 1}}); }})()",
+        sel = serde_json::to_string(&selector).unwrap_or_default(),
+        text = serde_json::to_string(text).unwrap_or_default(),
+        ic = ignore_case,
+    );
+    poll(cmd, progress, || {
+        let page: serde_json::Value = crate::eval_js_with_timeout(app, window, &js, timeout)
+            .map(|r| serde_json::from_str(&r).unwrap_or_default())?;
+        let element = page
+            .get("element")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let count = page.get("count").and_then(|v| v.as_u64()).unwrap_or(0);
+        let mut result = serde_json::json!({
+            "ok": true,
+            "pass": element && (count > 0) != absent,
+            "assert": "text",
+            "text": text,
+            "absent": absent,
+            "count": count,
+        });
+        if let Some(sel) = selector {
+            result["selector"] = serde_json::Value::from(sel);
+            if !element {
+                result["reason"] = serde_json::Value::from("selector matched nothing");
+            }
+        }
+        Ok(result)
+    })
+}
+
+/// {"equals": url} | {"contains": "..."} | {"path": "/items/123"}, plus "wait_ms":
+/// is the window on that URL? `path` compares normalized paths, so "/items/{id}"
+/// matches any item page.
+pub fn assert_url(
+    app: &tauri::AppHandle,
+    window: &str,
+    cmd: &serde_json::Value,
+    timeout: Duration,
+    progress: &dyn Fn(serde_json::Value),
+) -> serde_json::Value {
+    let field = |k: &str| cmd.get(k).and_then(|v| v.as_str());
+    let (kind, expected) = match (field("equals"), field("contains"), field("path")) {
+        (Some(url), _, _) => ("equals", url),
+        (_, Some(part), _) => ("contains", part),
+        (_, _, Some(path)) => ("path", path),
+        _ => {
+            return CommandError::InvalidParams("missing equals, contains or path".to_string())
+                .reply()
+        }
+    };
+    poll(cmd, progress, || {
+        let actual = crate::eval_js_with_timeout(app, window, "location.href", timeout)?;
+        let pass = match kind {
+            "equals" => actual == expected,
+            "contains" => actual.contains(expected),
+            _ => url::Url::parse(&actual).is_ok_and(|u| {
+                endpoints::normalize_path(u.path()) == endpoints::normalize_path(expected)
+            }),
+        };
+        let mut result = serde_json::json!({
+            "ok": true,
+            "pass": pass,
+            "assert": "url",
+            "url": actual,
+        });
+        result[kind] = serde_json::Value::from(expected);
+        Ok(result)
+    })
+}
+
+/// {"pattern": "POST /api/items", "status": 201, "since": "<RFC 3339 time>" or
+/// "mark": "<mark name>", "app": "x", "wait_ms": 0}: has a capture of that endpoint been
+/// recorded since then? `pattern` is
+/// an endpoint pattern (IDs in it are normalized) or a piece of one. Apps default to
+/// `current_app`, or every app.
+pub fn assert_request_seen(
+    current_app: Option<String>,
+    cmd: &serde_json::Value,
+    progress: &dyn Fn(serde_json::Value),
+) -> serde_json::Value {
+    let pattern = cmd.get("pattern").and_then(|v| v.as_str()).unwrap_or("");
+    if pattern.is_empty() {
+        return CommandError::InvalidParams("missing pattern".to_string()).reply();
+    }
+    let matcher = crate::query::PatternMatch::new(pattern);
+    let status = cmd.get("status").and_then(|v| v.as_u64());
+    let since = match (
+        cmd.get("since").and_then(|v| v.as_str()),
+        cmd.get("mark").and_then(|v| v.as_str()),
+    ) {
+        (Some(ts), _) => match chrono::DateTime::parse_from_rfc3339(ts) {
+            Ok(t) => Some(t.with_timezone(&chrono::Utc)),
+            Err(e) => return CommandError::InvalidParams(format!("bad since: {e}")).reply(),
+        },
+        (None, Some(name)) => match crate::marks::get(name) {
+            Some(t) => Some(t),
+            None => return CommandError::NotFound(format!("no mark named {name:?}")).reply(),
+        },
+        (None, None) => None,
+    };
+    let apps = crate::query::target_apps(current_app, cmd);
+
+    poll(cmd, progress, || {
+        crate::query::flush_pending();
+        let mut matches = 0;
+        let mut latest: Option<(String, capture_index::Location)> = None;
+        for app_name in &apps {
+            let app_dir = config::data_dir().join("apps").join(app_name);
+            for loc in capture_index::locations(&app_dir) {
+                if !matcher.matches(&loc.pattern) || status.is_some_and(|s| loc.status != Some(s)) {
+                    continue;
+                }
+                let at = chrono::DateTime::parse_from_rfc3339(&loc.timestamp)
+                    .ok()
+                    .map(|t| t.with_timezone(&chrono::Utc));
+                if since.is_some_and(|since| at.is_none_or(|at| at < since)) {
+                    continue;
+                }
+                matches += 1;
+                if latest
+                    .as_ref()
+                    .is_none_or(|(_, l)| loc.timestamp >= l.timestamp)
+                {
+                    latest = Some((app_name.clone(), loc));
+                }
+            }
+        }
+        let mut result = serde_json::json!({
+            "ok": true,
+            "pass": matches > 0,
+            "assert": "request_seen",
+            "pattern": pattern,
+            "matches": matches,
+        });
+        if let Some((app_name, loc)) = latest {
+            result["latest"] = serde_json::json!({
+                "app": app_name,
+                "pattern": loc.pattern,
+                "status": loc.status,
+                "timestamp": loc.timestamp,
+            });
+        }
+        Ok(result)
+    })
+}
@@ -0,0 +1,117 @@
+use crate::config;
+use std::fs;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Parameter strings longer than this (scripts, page text to type) are cut in the log
+const MAX_PARAM_CHARS: usize = 500;
+
+/// audit.jsonl rolls over to audit.jsonl.1 past this size
+const MAX_LOG_BYTES: u64 = 5_000_000;
+
+/// Actions whose parameters carry what was typed or stored (passwords, cookies, tokens):
+/// the log keeps only their length
+const REDACTED: &[(&str, &[&str])] = &[
+    ("type", &["text", "value"]),
+    ("type_ref", &["text", "value"]),
+    ("set_cookie", &["value", "cookies"]),
+    ("set_storage", &["value", "items"]),
+    ("eval", &["js"]),
+    ("ws_send", &["message", "binary"]),
+    ("fetch_in_page", &["headers", "body"]),
+];
+
+/// Serializes appends across command threads
+static AUDIT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Append an executed command to `~/.harharhar/audit.jsonl`, so the user can review
+/// what an agent did in their sessions: `timestamp`, `channel` ("commands" or
+/// "cmd.json"), `action`, `params` (the command minus its token), `result` (`ok`,
+/// plus the error `code` or `status` when there is one, and its size) and `duration_ms`.
+/// Refused commands are recorded too. Typed text, cookie and storage values, eval'd JS,
+/// WebSocket messages and `fetch_in_page` headers and bodies are left out; the file is
+/// readable by its owner only.
+pub fn record(channel: &str, body: &str, result: &str, elapsed: std::time::Duration) {
+    let mut params = serde_json::from_str::<serde_json::Value>(body)
+        .unwrap_or_else(|_| serde_json::Value::from(cut(body)));
+    let action = params
+        .get("action")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+    if let Some(obj) = params.as_object_mut() {
+        obj.remove("token");
+        obj.remove("action");
+        let redacted = REDACTED
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map_or(&[][..], |(_, keys)| *keys);
+        for key in redacted {
+            if let Some(value) = obj.get_mut(key) {
+                *value = redact(value);
+            }
+        }
+        for value in obj.values_mut() {
+            if let Some(s) = value.as_str() {
+                *value = serde_json::Value::from(cut(s));
+            }
+        }
+    }
+
+    let parsed = serde_json::from_str::<serde_json::Value>(result).unwrap_or_default();
+    let field = |k: &str| parsed.get(k).and_then(|v| v.as_str()).map(String::from);
+    let mut summary = serde_json::json!({
+        "ok": parsed.get("error").is_none(),
+        "bytes": result.len(),
+    });
+    if let Some(code) = parsed.pointer("/error/code").and_then(|v| v.as_str()) {
+        summary["error"] = serde_json::Value::from(code);
+    }
+    if let Some(status) = field("status") {
+        summary["status"] = serde_json::Value::from(status);
+    }
+
+    let entry = serde_json::json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "channel": channel,
+        "action": action,
+        "params": params,
+        "result": summary,
+        "duration_ms": elapsed.as_millis() as u64,
+    });
+
+    let _lock = AUDIT_LOCK.lock().unwrap();
+    let path = config::data_dir().join("audit.jsonl");
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+        let _ = fs::rename(&path, config::data_dir().join("audit.jsonl.1"));
+    }
+    let mut options = fs::OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    if let Ok(mut f) = options.open(&path) {
+        let _ = writeln!(f, "{entry}");
+    }
+}
+
+/// A secret parameter as its size: "[redacted: 12 chars]", or for a map of them
+/// ({"cookies": {...}}) the same per key, so which names were set stays visible
+fn redact(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| (k.clone(), redact(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        serde_json::Value::Null => serde_json::Value::Null,
+        serde_json::Value::String(s) => format!("[redacted: {} chars]", s.chars().count()).into(),
+        other => format!("[redacted: {} chars]", other.to_string().len()).into(),
+    }
+}
+
+fn cut(s: &str) -> String {
+    match s.char_indices().nth(MAX_PARAM_CHARS) {
+        Some((i, _)) => format!("{}… [{} chars]", &s[..i], s.chars().count()),
+        None => s.to_string(),
+    }
+}
@@ -0,0 +1,99 @@
+use crate::config;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::fs;
+
+/// SHA-256 of `bytes`, in hex — the same digest intercept.js gives uploaded files
+pub fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Record what a binary response body is, as `"responseBinary": {"content_type",
+/// "bytes", "sha256", "complete"}`. `bytes` is the whole body's size when the capture
+/// says (`responseSize`: the page script and Chrome see all of it, but keep at most
+/// `max_body_bytes`); `sha256` is of the bytes kept, which is the whole body when
+/// `complete`.
+pub fn describe(data: &mut serde_json::Value, bytes: &[u8]) {
+    let content_type = crate::capture::header_value(data.get("responseHeaders"), "content-type")
+        .map(|ct| ct.split(';').next().unwrap_or(ct).trim().to_lowercase())
+        .unwrap_or_default();
+    let size = data
+        .get("responseSize")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(bytes.len() as u64)
+        .max(bytes.len() as u64);
+    data["responseBinary"] = serde_json::json!({
+        "content_type": content_type,
+        "bytes": size,
+        "sha256": sha256(bytes),
+        "complete": size == bytes.len() as u64,
+    });
+}
+
+/// With `capture.save_blobs` on, move a binary response body out of the capture line
+/// into `captures/blobs/{sha256}.{ext}` (written once per distinct body), leaving
+/// `responseBinary.blob` with the file's path under `captures/`. The capture as it
+/// should be written, or None to write it as it is.
+pub fn save(app_name: &str, data: &serde_json::Value) -> Option<serde_json::Value> {
+    let binary = data.get("responseBinary")?;
+    if !config::config().capture.save_blobs {
+        return None;
+    }
+    let sha = binary.get("sha256")?.as_str()?;
+    let bytes = data
+        .get("responseBody")?
+        .as_str()?
+        .strip_prefix("base64:")
+        .and_then(|b| base64::engine::general_purpose::STANDARD.decode(b).ok())?;
+    let content_type = binary
+        .get("content_type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let file_name = format!("{sha}.{}", extension(content_type));
+
+    let dir = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("captures")
+        .join("blobs");
+    let path = dir.join(&file_name);
+    if !path.exists() {
+        // Written aside and renamed, so a blob that's there is whole
+        let tmp = dir.join(format!("{file_name}.tmp"));
+        fs::create_dir_all(&dir).ok()?;
+        fs::write(&tmp, &bytes).ok()?;
+        fs::rename(&tmp, &path).ok()?;
+    }
+
+    let mut data = data.clone();
+    data["responseBody"] = serde_json::Value::Null;
+    data["responseBinary"]["blob"] = serde_json::Value::from(format!("blobs/{file_name}"));
+    Some(data)
+}
+
+/// File extension for a blob of `content_type`
+fn extension(content_type: &str) -> &'static str {
+    match content_type {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/avif" => "avif",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        "application/pdf" => "pdf",
+        "application/zip" | "application/x-zip-compressed" => "zip",
+        "application/gzip" | "application/x-gzip" => "gz",
+        "application/x-tar" => "tar",
+        "application/wasm" => "wasm",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => "xlsx",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => "docx",
+        "audio/mpeg" => "mp3",
+        "video/mp4" => "mp4",
+        "font/woff2" => "woff2",
+        "font/woff" => "woff",
+        _ => "bin",
+    }
+}
@@ -1,9 +1,11 @@
 use crate::config;
 use crate::endpoints;
+use crate::errors::CommandError;
+use crate::events;
 use crate::AppState;
+use base64::Engine;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::sync::atomic::{AtomicU32, Ordering};
 use tauri::{Emitter, Manager};
 
 const AUTH_HEADERS: &[&str] = &[
@@ -15,14 +17,58 @@ const AUTH_HEADERS: &[&str] = &[
 
 const COOKIE_HEADERS: &[&str] = &["cookie"];
 
-/// Counter to trigger periodic endpoint generation
-static CAPTURE_COUNT: AtomicU32 = AtomicU32::new(0);
+/// Cap on a decompressed response body (guards against compression bombs)
+const MAX_DECOMPRESSED: u64 = 5_000_000;
+
+/// Current session id per app (see `session_id`), so every capture can be tagged cheaply
+static SESSION_IDS: std::sync::LazyLock<
+    std::sync::Mutex<std::collections::HashMap<String, String>>,
+> = std::sync::LazyLock::new(Default::default);
+
+/// Apps whose session-expired event went out, until one of their calls succeeds again
+static SESSIONS_EXPIRED: std::sync::LazyLock<std::sync::Mutex<std::collections::HashSet<String>>> =
+    std::sync::LazyLock::new(Default::default);
+
+/// (domain, app) pairs a domain-conflict was raised for from captures this run
+static CONFLICTS_RAISED: std::sync::LazyLock<
+    std::sync::Mutex<std::collections::HashSet<(String, String)>>,
+> = std::sync::LazyLock::new(Default::default);
+
+/// Identical repeats of a request (polling) closer together than this are collapsed
+const REPEAT_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A collapsed run is written out at least this often, so long polls still show up
+const REPEAT_MAX_RUN: std::time::Duration = std::time::Duration::from_secs(300);
+
+/// A run of identical captures held back from the capture file
+struct RepeatRun {
+    hash: u64,
+    /// Repeats held back (the first occurrence was written normally)
+    count: u64,
+    /// `timestamp` of the first held-back repeat
+    since: String,
+    started: std::time::Instant,
+    last_at: std::time::Instant,
+    last: serde_json::Value,
+    session_ts: String,
+}
+
+/// Open repeat runs per (app, "METHOD url")
+static REPEATS: std::sync::LazyLock<
+    std::sync::Mutex<std::collections::HashMap<(String, String), RepeatRun>>,
+> = std::sync::LazyLock::new(Default::default);
+
+/// How often held repeat runs are checked for ones gone quiet or run long enough
+const REPEAT_CHECK_EVERY: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Started with the first held-back repeat
+static REPEAT_TIMER: std::sync::Once = std::sync::Once::new();
 
 /// JS that builds a lean accessibility-tree-like UI model.
-/// Stores element refs in window.__hh_refs for click_ref/type_ref.
+/// Stores element refs in intercept.js's window.__hh bridge for click_ref/type_ref.
 const READ_UI_JS: &str = r#"(() => {
   const refs = [];
-  window.__hh_refs = refs;
+  if (window.__hh) window.__hh.setRefs(refs);
   const lines = [];
 
   function isVis(el) {
@@ -133,6 +179,135 @@ const READ_UI_JS: &str = r#"(() => {
   return 'Page: ' + document.title + '\nURL: ' + location.href + '\n' + refs.length + ' elements\n---\n' + lines.join('\n');
 })()"#;
 
+/// JS that lists the page's forms as structured JSON: action URL, method, and
+/// each field's name, type, label and required-ness — a map from UI forms to the
+/// payloads they send. Fields outside any <form> are grouped under a null action.
+const READ_FORMS_JS: &str = r#"(() => {
+  function labelFor(el) {
+    if (el.labels && el.labels.length) return el.labels[0].innerText.trim();
+    const aria = el.getAttribute('aria-label');
+    if (aria) return aria;
+    const by = el.getAttribute('aria-labelledby');
+    if (by) { const l = document.getElementById(by); if (l) return l.innerText.trim(); }
+    return el.placeholder || el.title || '';
+  }
+
+  function describe(el) {
+    const type = el.tagName === 'INPUT' ? (el.type || 'text') : el.tagName.toLowerCase();
+    const field = { name: el.name || el.id || '', type: type, label: labelFor(el), required: el.required };
+    if (el.tagName === 'SELECT') {
+      field.options = Array.from(el.options).map(o => ({ value: o.value, text: o.text.trim() }));
+      field.multiple = el.multiple;
+    }
+    if (type === 'checkbox' || type === 'radio') field.value = el.value;
+    if (el.pattern) field.pattern = el.pattern;
+    if (el.maxLength > 0) field.maxLength = el.maxLength;
+    if (el.min) field.min = el.min;
+    if (el.max) field.max = el.max;
+    if (el.disabled) field.disabled = true;
+    return field;
+  }
+
+  const FIELDS = 'input:not([type="submit"]):not([type="button"]):not([type="reset"]):not([type="image"]), select, textarea';
+  const forms = Array.from(document.forms).map(f => {
+    const submit = f.querySelector('button[type="submit"], input[type="submit"], button:not([type])');
+    return {
+      id: f.id || f.getAttribute('name') || '',
+      action: f.action || location.href,
+      method: (f.getAttribute('method') || 'GET').toUpperCase(),
+      enctype: f.enctype,
+      submit: submit ? (submit.innerText || submit.value || '').trim() : '',
+      fields: Array.from(f.querySelectorAll(FIELDS)).map(describe),
+    };
+  });
+
+  const orphans = Array.from(document.querySelectorAll(FIELDS)).filter(el => !el.form && el.type !== 'hidden');
+  if (orphans.length) {
+    forms.push({ id: '', action: null, method: null, enctype: null, submit: '', fields: orphans.map(describe) });
+  }
+  return JSON.stringify({ url: location.href, forms: forms });
+})()"#;
+
+/// Visible tables and ARIA grids as rows keyed by column header. Called with the
+/// most rows to return per table.
+const READ_TABLES_JS: &str = r#"(maxRows) => {
+  function visible(el) {
+    return el.getClientRects().length > 0 && !el.closest('[aria-hidden="true"]');
+  }
+  function text(el) {
+    return (el.innerText || el.textContent || '').replace(/\s+/g, ' ').trim();
+  }
+  function captionOf(el) {
+    const cap = el.tagName === 'TABLE' && el.caption ? text(el.caption) : '';
+    if (cap) return cap;
+    const by = el.getAttribute('aria-labelledby');
+    if (by) { const l = document.getElementById(by); if (l) return text(l); }
+    return el.getAttribute('aria-label') || '';
+  }
+  // Cells of a row as texts, repeated over their colspan so columns line up
+  function expand(cells) {
+    const out = [];
+    for (const c of cells) {
+      const span = Math.min(parseInt(c.getAttribute('colspan') || c.getAttribute('aria-colspan') || '1', 10) || 1, 50);
+      for (let i = 0; i < span; i++) out.push(text(c));
+    }
+    return out;
+  }
+  // Unique keys: empty headers become "column N", repeats get " (2)", " (3)"...
+  function keys(headers, width) {
+    const seen = {};
+    const out = [];
+    for (let i = 0; i < width; i++) {
+      let k = headers[i] || ('column ' + (i + 1));
+      if (seen[k]) { seen[k]++; k = k + ' (' + seen[k] + ')'; } else { seen[k] = 1; }
+      out.push(k);
+    }
+    return out;
+  }
+  function build(el, kind, headerRow, bodyRows) {
+    const width = Math.max(headerRow.length, ...bodyRows.map(r => r.length), 0);
+    const cols = keys(headerRow, width);
+    const rows = bodyRows.slice(0, maxRows).map(r => {
+      const row = {};
+      cols.forEach((k, i) => { row[k] = r[i] === undefined ? '' : r[i]; });
+      return row;
+    });
+    return { kind: kind, caption: captionOf(el), id: el.id || '', headers: cols, rows: rows, total_rows: bodyRows.length };
+  }
+
+  const tables = [];
+  for (const t of document.querySelectorAll('table')) {
+    if (!visible(t)) continue;
+    // Rows of this table only, not of tables nested in its cells
+    const trs = Array.from(t.rows).filter(r => r.closest('table') === t && visible(r));
+    if (!trs.length) continue;
+    let headerRow = [];
+    let body = trs;
+    const thead = t.tHead && t.tHead.rows.length ? t.tHead.rows[t.tHead.rows.length - 1] : null;
+    if (thead) {
+      headerRow = expand(thead.cells);
+      body = trs.filter(r => r.parentElement !== t.tHead);
+    } else if (Array.from(trs[0].cells).every(c => c.tagName === 'TH')) {
+      headerRow = expand(trs[0].cells);
+      body = trs.slice(1);
+    }
+    tables.push(build(t, 'table', headerRow, body.map(r => expand(r.cells))));
+  }
+
+  for (const g of document.querySelectorAll('[role="grid"], [role="treegrid"], [role="table"]')) {
+    if (g.tagName === 'TABLE' || !visible(g)) continue;
+    const rows = Array.from(g.querySelectorAll('[role="row"]'))
+      .filter(r => r.closest('[role="grid"], [role="treegrid"], [role="table"]') === g && visible(r));
+    if (!rows.length) continue;
+    const cellsOf = r => Array.from(r.querySelectorAll('[role="columnheader"], [role="gridcell"], [role="cell"], [role="rowheader"]'));
+    const headerIdx = rows.findIndex(r => r.querySelector('[role="columnheader"]'));
+    const headerRow = headerIdx >= 0 ? expand(cellsOf(rows[headerIdx])) : [];
+    const body = rows.filter((_, i) => i !== headerIdx).map(r => expand(cellsOf(r)));
+    tables.push(build(g, g.getAttribute('role'), headerRow, body));
+  }
+  return JSON.stringify({ url: location.href, tables: tables });
+}"#;
+
 // --- Noise filtering ---
 
 /// Known analytics/tracking domains that have zero value for AI agents learning APIs.
@@ -162,12 +337,17 @@ const NOISE_PATH_PATTERNS: &[&str] = &[
 
 /// Returns true if the URL matches known noise patterns and should be skipped.
 pub fn should_skip_capture(url: &str) -> bool {
+    noise_reason(url).is_some()
+}
+
+/// Which noise filter a URL trips: "noise_domain", "noise_extension" or "noise_path".
+pub fn noise_reason(url: &str) -> Option<&'static str> {
     let url_lower = url.to_lowercase();
 
     // Check noise domains / domain-path prefixes
     for pattern in NOISE_DOMAINS {
         if url_lower.contains(pattern) {
-            return true;
+            return Some("noise_domain");
         }
     }
 
@@ -176,71 +356,577 @@ pub fn should_skip_capture(url: &str) -> bool {
     let path_part = url_lower.split('?').next().unwrap_or(&url_lower);
     for ext in NOISE_EXTENSIONS {
         if path_part.ends_with(ext) {
-            return true;
+            return Some("noise_extension");
         }
     }
 
     // Check tracking/telemetry path patterns
     for pattern in NOISE_PATH_PATTERNS {
         if url_lower.contains(pattern) {
-            return true;
+            return Some("noise_path");
         }
     }
 
-    false
+    None
+}
+
+/// True if one of an app's `capture_noise` fragments appears in the URL
+pub fn noise_opted_in(url: &str, capture_noise: &[String]) -> bool {
+    let url_lower = url.to_lowercase();
+    capture_noise
+        .iter()
+        .any(|p| p != "sendBeacon" && url_lower.contains(&p.to_lowercase()))
+}
+
+/// Noise check for a capture entry with the app's `capture_noise` opt-ins applied.
+/// sendBeacon entries are telemetry unless the app lists "sendBeacon" or a matching fragment.
+pub fn is_noise(entry_type: &str, url: &str, capture_noise: &[String]) -> bool {
+    if entry_type == "beacon" {
+        return !capture_noise.iter().any(|p| p == "sendBeacon")
+            && !noise_opted_in(url, capture_noise);
+    }
+    should_skip_capture(url) && !noise_opted_in(url, capture_noise)
 }
 
 // --- Process a single capture entry (called from Tauri IPC command) ---
 
+/// Case-insensitive header lookup on a capture entry's header map
+pub(crate) fn header_value<'a>(
+    headers: Option<&'a serde_json::Value>,
+    name: &str,
+) -> Option<&'a str> {
+    headers?
+        .as_object()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .and_then(|(_, v)| v.as_str())
+}
+
+/// Decode request/response bodies into more useful forms before a capture is saved.
+pub fn normalize_bodies(data: &mut serde_json::Value) {
+    // application/x-www-form-urlencoded → "requestForm": {key: value} (repeated keys → array)
+    let is_form = header_value(data.get("requestHeaders"), "content-type").is_some_and(|ct| {
+        ct.to_lowercase()
+            .starts_with("application/x-www-form-urlencoded")
+    });
+    if is_form {
+        if let Some(body) = data.get("requestBody").and_then(|v| v.as_str()) {
+            let mut form = serde_json::Map::new();
+            for (k, v) in url::form_urlencoded::parse(body.as_bytes()) {
+                let v = serde_json::Value::String(v.into_owned());
+                match form.get_mut(k.as_ref()) {
+                    Some(serde_json::Value::Array(values)) => values.push(v),
+                    Some(existing) => {
+                        *existing = serde_json::Value::Array(vec![existing.clone(), v])
+                    }
+                    None => {
+                        form.insert(k.into_owned(), v);
+                    }
+                }
+            }
+            data["requestForm"] = serde_json::Value::Object(form);
+        }
+    }
+
+    // "base64:..." response bodies are bytes the page couldn't decode as text —
+    // usually a payload the app compresses itself. Unpack it if it turns out to be text;
+    // otherwise it's a file (image, PDF, archive), recorded by size and hash.
+    let encoded = data
+        .get("responseBody")
+        .and_then(|v| v.as_str())
+        .and_then(|b| b.strip_prefix("base64:"))
+        .and_then(|b| base64::engine::general_purpose::STANDARD.decode(b).ok());
+    if let Some(bytes) = encoded {
+        let content_encoding = header_value(data.get("responseHeaders"), "content-encoding")
+            .unwrap_or("")
+            .to_lowercase();
+        if let Some((text, encoding)) = decompress(&bytes, &content_encoding) {
+            data["responseBody"] = serde_json::Value::String(text);
+            data["responseEncoding"] = serde_json::Value::String(encoding.to_string());
+        } else {
+            // Still bytes: say so, whether or not the page did
+            data["responseEncoding"] = serde_json::Value::from("base64");
+            crate::blobs::describe(data, &bytes);
+        }
+    }
+
+    let config = config::config();
+    let capture = &config.capture;
+    // Bodies bound for captures/blobs/ are kept whole until they're written there
+    if !(capture.save_blobs && data.get("responseBinary").is_some()) {
+        apply_response_policy(data, &capture.response_types);
+    }
+}
+
+/// Cut the response body to a preview, or to its size and hash, as the content-type
+/// policy says. `"responseBodyCut": {"policy", "bytes"}` records what was done; a cut
+/// page's CSRF-looking meta tags and hidden inputs go to `"responseTokens"`.
+fn apply_response_policy(data: &mut serde_json::Value, policy: &config::ResponseBodyPolicy) {
+    let Some(body) = data.get("responseBody").and_then(|v| v.as_str()) else {
+        return;
+    };
+    let content_type = header_value(data.get("responseHeaders"), "content-type")
+        .unwrap_or("")
+        .to_lowercase();
+    if content_type.is_empty() {
+        return;
+    }
+    let listed = |types: &[String]| {
+        types
+            .iter()
+            .any(|t| content_type.contains(&t.to_lowercase()))
+    };
+    // A binary body is still base64 here: its size and hash are of the decoded bytes
+    let binary = data.get("responseBinary");
+    let bytes = binary
+        .and_then(|b| b.get("bytes"))
+        .and_then(|v| v.as_u64())
+        .map_or(body.len(), |n| n as usize);
+    let mut tokens = Vec::new();
+    let (cut, kept) = if listed(&policy.full) {
+        return;
+    } else if listed(&policy.preview) {
+        let end = body
+            .char_indices()
+            .nth(policy.preview_chars)
+            .map_or(body.len(), |(i, _)| i);
+        if end == body.len() {
+            return;
+        }
+        // CSRF tracing reads tokens out of meta tags and hidden inputs anywhere in a
+        // page: keep those the preview would cut off
+        if body.contains("<meta") || body.contains("<input") {
+            tokens = crate::page_text::named_values(body)
+                .into_iter()
+                .filter(|(_, name, _)| crate::csrf::looks_like_token(name))
+                .map(|(tag, name, value)| {
+                    serde_json::json!({"tag": tag, "name": name, "value": value})
+                })
+                .collect();
+        }
+        ("preview", body[..end].to_string())
+    } else if listed(&policy.hash_only) {
+        let hash = match binary
+            .and_then(|b| b.get("sha256"))
+            .and_then(|v| v.as_str())
+        {
+            Some(sha) => sha.to_string(),
+            None => crate::blobs::sha256(body.as_bytes()),
+        };
+        (
+            "hash_only",
+            format!("[hash-only: {bytes} bytes; sha256: {hash}]"),
+        )
+    } else {
+        return;
+    };
+    data["responseBody"] = serde_json::Value::String(kept);
+    if cut == "hash_only" {
+        // The placeholder is text, whatever the body was
+        if let Some(obj) = data.as_object_mut() {
+            obj.remove("responseEncoding");
+        }
+    }
+    if !tokens.is_empty() {
+        data["responseTokens"] = serde_json::Value::Array(tokens);
+    }
+    data["responseBodyCut"] = serde_json::json!({"policy": cut, "bytes": bytes});
+}
+
+/// At most `max` bytes of a body, cut on a char boundary
+pub fn truncate_body(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Try gzip / zlib / raw deflate / brotli on a body, returning the UTF-8 text
+/// and which encoding it was in.
+fn decompress(bytes: &[u8], content_encoding: &str) -> Option<(String, &'static str)> {
+    use std::io::Read;
+    let mut attempts: Vec<(&'static str, Box<dyn Read + '_>)> = Vec::new();
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        attempts.push(("gzip", Box::new(flate2::read::GzDecoder::new(bytes))));
+    }
+    if bytes.first() == Some(&0x78) {
+        attempts.push(("deflate", Box::new(flate2::read::ZlibDecoder::new(bytes))));
+    }
+    if content_encoding.contains("deflate") {
+        attempts.push((
+            "deflate",
+            Box::new(flate2::read::DeflateDecoder::new(bytes)),
+        ));
+    }
+    // Brotli has no magic number, so only try it when the server said so
+    if content_encoding.contains("br") {
+        attempts.push((
+            "br",
+            Box::new(brotli_decompressor::Decompressor::new(bytes, 4096)),
+        ));
+    }
+    for (encoding, reader) in attempts {
+        let mut out = Vec::new();
+        if reader.take(MAX_DECOMPRESSED).read_to_end(&mut out).is_ok() {
+            if let Ok(text) = String::from_utf8(out) {
+                return Some((text, encoding));
+            }
+        }
+    }
+    None
+}
+
+/// A capture from a browser — a harharhar window's intercept.js or an attached Chrome
+/// tab: decode its bodies, look for secrets, show it in the explorer and save it
+pub fn accept(app: &tauri::AppHandle, mut data: serde_json::Value) {
+    normalize_bodies(&mut data);
+    crate::secrets::scan(&mut data);
+    let _ = app.emit("request-captured", &data);
+    let ts = app.state::<AppState>().session_ts.clone();
+    process_single(app, &data, &ts);
+}
+
 pub fn process_single(app: &tauri::AppHandle, data: &serde_json::Value, session_ts: &str) {
     save_capture(app, data, session_ts);
+}
 
-    let count = CAPTURE_COUNT.fetch_add(1, Ordering::Relaxed);
-    if count > 0 && count % 50 == 0 {
-        generate_all_endpoints(session_ts);
+// --- File-based command watcher ---
+
+/// This run's command secret: every command must carry it as `"token"`. Anything that
+/// can read ~/.harharhar/cmd-token (mode 0600) may drive the browser; other local
+/// processes that can only write into the commands folder can't.
+static CMD_TOKEN: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+/// Make a fresh secret for this run and write it to `cmd-token`, readable only by the user
+fn create_cmd_token() -> String {
+    use std::io::Read;
+    let mut bytes = [0u8; 32];
+    let from_os = fs::File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut bytes));
+    if from_os.is_err() {
+        // Each RandomState is seeded from the OS
+        use std::hash::{BuildHasher, Hasher};
+        for chunk in bytes.chunks_mut(8) {
+            let hasher = std::collections::hash_map::RandomState::new().build_hasher();
+            chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+        }
+    }
+    let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+
+    let path = config::data_dir().join("cmd-token");
+    let _ = fs::remove_file(&path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    match options.open(&path) {
+        Ok(mut file) => {
+            let _ = file.write_all(token.as_bytes());
+        }
+        Err(e) => eprintln!("[harharhar] can't write {}: {e}", path.display()),
     }
+    token
 }
 
-// --- File-based command watcher ---
+/// Held while a legacy `cmd.json` command runs
+static LEGACY_COMMAND: std::sync::Mutex<()> = std::sync::Mutex::new(());
 
 pub async fn start_command_watcher(app: tauri::AppHandle) {
     let cmd_path = config::data_dir().join("cmd.json");
     let result_path = config::data_dir().join("cmd-result.json");
+    let commands_dir = config::data_dir().join("commands");
+    let _ = fs::create_dir_all(&commands_dir);
+    CMD_TOKEN.get_or_init(create_cmd_token);
+
+    // Woken by filesystem events for new command files. If the platform watcher
+    // can't start, poll as before; otherwise poll only rarely, in case an event is lost.
+    let (wake_tx, mut wake_rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+    let watcher = watch_commands(&config::data_dir(), &commands_dir, wake_tx);
+    let poll = if watcher.is_some() {
+        std::time::Duration::from_secs(5)
+    } else {
+        std::time::Duration::from_millis(300)
+    };
 
     loop {
-        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let _ = tokio::time::timeout(poll, wake_rx.recv()).await;
+        // One pass below handles every command that has arrived by now
+        while wake_rx.try_recv().is_ok() {}
+        config::reload_if_changed();
+
+        // Legacy single-slot protocol: cmd.json -> cmd-result.json. Off this task like
+        // the multiplexed commands, so a slow eval doesn't hold them up, but one at a
+        // time, as there's only the one result file.
+        if let Ok(body) = fs::read_to_string(&cmd_path) {
+            // Delete command file immediately so we don't re-process
+            let _ = fs::remove_file(&cmd_path);
+
+            let app = app.clone();
+            let result_path = result_path.clone();
+            tauri::async_runtime::spawn_blocking(move || {
+                let _slot = LEGACY_COMMAND.lock().unwrap();
+                let result = handle_command(&app, &body, &Progress::none(), "cmd.json");
+                let _ = fs::write(&result_path, &result);
+            });
+        }
 
-        if !cmd_path.exists() {
-            continue;
+        // Multiplexed protocol: commands/{id}.json -> commands/{id}.result.json.
+        // Each command runs on its own blocking task so overlapping CLI calls
+        // don't wait on each other's evals.
+        for (id, body) in take_pending_commands(&commands_dir) {
+            let app = app.clone();
+            let result_path = commands_dir.join(format!("{id}.result.json"));
+            let progress = Progress::for_command(&commands_dir, &id, &body);
+            tauri::async_runtime::spawn_blocking(move || {
+                let result = handle_command(&app, &body, &progress, "commands");
+                write_result_file(&result_path, &result);
+            });
         }
+    }
+}
 
-        let body = match fs::read_to_string(&cmd_path) {
-            Ok(b) => b,
-            Err(_) => continue,
+/// Watch the data dir for `cmd.json` and `config.json` and `commands/` for `{id}.json`,
+/// sending on `wake` when one appears or changes. None if the platform watcher (FSEvents/inotify) couldn't start.
+fn watch_commands(
+    data_dir: &std::path::Path,
+    commands_dir: &std::path::Path,
+    wake: tokio::sync::mpsc::UnboundedSender<()>,
+) -> Option<notify::RecommendedWatcher> {
+    use notify::Watcher;
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else {
+            return;
         };
+        // config.json too, so an edit to it is loaded right away
+        let is_command = event.paths.iter().any(|p| {
+            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            let in_commands = p.parent().is_some_and(|d| d.ends_with("commands"));
+            name == "cmd.json"
+                || name == "config.json"
+                || (in_commands && name.ends_with(".json") && !name.ends_with(".result.json"))
+        });
+        if is_command {
+            let _ = wake.send(());
+        }
+    })
+    .ok()?;
+    watcher
+        .watch(data_dir, notify::RecursiveMode::NonRecursive)
+        .ok()?;
+    watcher
+        .watch(commands_dir, notify::RecursiveMode::NonRecursive)
+        .ok()?;
+    Some(watcher)
+}
+
+/// Read and remove all pending `{id}.json` command files.
+/// Partially written files (`*.tmp`) and results (`*.result.json`) are ignored.
+fn take_pending_commands(commands_dir: &std::path::Path) -> Vec<(String, String)> {
+    let entries = match fs::read_dir(commands_dir) {
+        Ok(e) => e,
+        Err(_) => return vec![],
+    };
 
-        // Delete command file immediately so we don't re-process
-        let _ = fs::remove_file(&cmd_path);
+    let mut pending = Vec::new();
+    for entry in entries.flatten() {
+        let name = match entry.file_name().to_str() {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+        if name.ends_with(".result.json") {
+            continue;
+        }
+        let id = match name.strip_suffix(".json") {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
+        let path = entry.path();
+        if let Ok(body) = fs::read_to_string(&path) {
+            // Delete command file immediately so we don't re-process
+            let _ = fs::remove_file(&path);
+            pending.push((id, body));
+        }
+    }
+    pending
+}
 
-        let result = handle_command(&app, &body);
-        let _ = fs::write(&result_path, &result);
+/// Write a result via temp file + rename so the CLI never reads a partial result.
+fn write_result_file(path: &std::path::Path, result: &str) {
+    let tmp_path = path.with_extension("json.tmp");
+    if fs::write(&tmp_path, result).is_ok() {
+        let _ = fs::rename(&tmp_path, path);
     }
 }
 
-/// Handle a command from the CLI (via file)
-fn handle_command(app: &tauri::AppHandle, body: &str) -> String {
+/// Incremental output for a long-running command.
+/// Commands sent with `"stream": true` over the multiplexed protocol get their
+/// progress events appended to `commands/{id}.progress.jsonl` as they happen;
+/// everything else discards them and only writes the final result.
+struct Progress {
+    path: Option<std::path::PathBuf>,
+}
+
+impl Progress {
+    fn none() -> Self {
+        Progress { path: None }
+    }
+
+    fn for_command(commands_dir: &std::path::Path, id: &str, body: &str) -> Self {
+        let stream = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|v| v.get("stream").and_then(|s| s.as_bool()))
+            .unwrap_or(false);
+        if !stream {
+            return Progress::none();
+        }
+        Progress {
+            path: Some(commands_dir.join(format!("{id}.progress.jsonl"))),
+        }
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.path.is_some()
+    }
+
+    fn emit(&self, event: serde_json::Value) {
+        let path = match self.path {
+            Some(ref p) => p,
+            None => return,
+        };
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+            let _ = writeln!(file, "{event}");
+        }
+    }
+}
+
+/// Bytes per chunk when streaming large results (e.g. read_page)
+const STREAM_CHUNK_CHARS: usize = 64 * 1024;
+
+/// Where a command's JS runs: which browser window, and how long to wait for it.
+struct EvalTarget {
+    window: String,
+    timeout: std::time::Duration,
+}
+
+/// Handle a command from the CLI (via file), recording it in the audit log
+fn handle_command(
+    app: &tauri::AppHandle,
+    body: &str,
+    progress: &Progress,
+    channel: &str,
+) -> String {
+    let started = std::time::Instant::now();
+    let result = run_command(app, body, progress);
+    crate::audit::record(channel, body, &result, started.elapsed());
+    result
+}
+
+fn run_command(app: &tauri::AppHandle, body: &str, progress: &Progress) -> String {
     let cmd: serde_json::Value = match serde_json::from_str(body) {
         Ok(v) => v,
-        Err(e) => return serde_json::json!({"error": e.to_string()}).to_string(),
+        Err(e) => return CommandError::InvalidJson(e.to_string()).reply().to_string(),
     };
 
+    let token = cmd.get("token").and_then(|v| v.as_str());
+    if CMD_TOKEN.get().is_none_or(|t| token != Some(t.as_str())) {
+        let mut reply = CommandError::Unauthorized.reply();
+        reply["hint"] = serde_json::Value::from(
+            "send commands with `harharhar cmd`, or put the contents of ~/.harharhar/cmd-token in \"token\"",
+        );
+        return reply.to_string();
+    }
+
     let action = cmd.get("action").and_then(|v| v.as_str()).unwrap_or("");
 
+    // Target window ("window": "2" or "browser-2", default browser-1) and
+    // per-command eval timeout, e.g. {"action":"read_page","timeout_ms":60000}
+    let target = EvalTarget {
+        window: crate::window_label(cmd.get("window").and_then(|v| v.as_str())),
+        timeout: std::time::Duration::from_millis(
+            cmd.get("timeout_ms")
+                .and_then(|v| v.as_u64())
+                .unwrap_or_else(crate::default_eval_timeout_ms),
+        ),
+    };
+    let window = target.window.as_str();
+
+    progress.emit(serde_json::json!({"event": "started", "action": action}));
+
+    // Don't let scripts flail against a CAPTCHA/2FA prompt: page-driving actions
+    // wait until a human has solved it and someone sends resume_after_human.
+    if PAGE_ACTIONS.contains(&action) {
+        if let Some(challenge) = crate::challenge::pending(app, window) {
+            return serde_json::json!({
+                "status": "human_needed",
+                "window": window,
+                "challenge": challenge,
+                "hint": "ask the user to solve it in the browser, then send resume_after_human",
+            })
+            .to_string();
+        }
+    }
+
+    // Destructive-looking commands wait for the user to allow them
+    if let Some(held) = crate::confirm::check(app, window, action, &cmd, target.timeout) {
+        return held.to_string();
+    }
+
+    if NAVIGATING_ACTIONS.contains(&action) {
+        crate::history::note_action(app, window, action);
+    }
+
+    let result = run_action(app, action, &cmd, &target, progress);
+
+    // A click or submit may land on a challenge page
+    if ACTIONS_THAT_MAY_CHALLENGE.contains(&action) {
+        crate::challenge::schedule_check(app, window);
+    }
+    result
+}
+
+/// Actions that drive the page and are held back while a challenge is pending
+const PAGE_ACTIONS: &[&str] = &[
+    "click",
+    "type",
+    "scroll",
+    "eval",
+    "fetch_in_page",
+    "read_ui",
+    "click_ref",
+    "type_ref",
+    "select_ref",
+    "set_storage",
+    "read_forms",
+    "read_tables",
+    "assert_text",
+    "assert_url",
+    "crawl",
+];
+
+/// Actions that may load a new page — noted so history.jsonl can record what led there
+const NAVIGATING_ACTIONS: &[&str] = &["navigate", "click", "click_ref", "select_ref", "eval"];
+
+/// Actions after which we look for a freshly shown challenge
+const ACTIONS_THAT_MAY_CHALLENGE: &[&str] =
+    &["click", "type", "click_ref", "type_ref", "select_ref"];
+
+fn run_action(
+    app: &tauri::AppHandle,
+    action: &str,
+    cmd: &serde_json::Value,
+    target: &EvalTarget,
+    progress: &Progress,
+) -> String {
+    let window = target.window.as_str();
+
     match action {
         "navigate" => {
             let url = cmd.get("url").and_then(|v| v.as_str()).unwrap_or("");
             if url.is_empty() {
-                return r#"{"error":"missing url"}"#.to_string();
+                return CommandError::InvalidParams("missing url".to_string()).reply().to_string();
             }
 
             // Require a label before navigating — forces intentional workflows.
@@ -248,7 +934,11 @@ fn handle_command(app: &tauri::AppHandle, body: &str) -> String {
             let label = cmd.get("label").and_then(|v| v.as_str()).unwrap_or("");
             let skip_label = cmd.get("skip_label").and_then(|v| v.as_bool()).unwrap_or(false);
             if label.is_empty() && !skip_label {
-                return r#"{"error":"label required — what are you about to do? Pass \"label\" or \"skip_label\": true"}"#.to_string();
+                return CommandError::InvalidParams(
+                    "label required — what are you about to do? Pass \"label\" or \"skip_label\": true".to_string(),
+                )
+                .reply()
+                .to_string();
             }
 
             let mut raw = url.to_string();
@@ -266,42 +956,67 @@ fn handle_command(app: &tauri::AppHandle, body: &str) -> String {
                             map.get(&domain).cloned()
                         });
                         if let Some(name) = resolved {
-                            let mut current = state.current_app.lock().unwrap();
-                            *current = Some(name);
+                            state.set_current_app(window, name);
                         }
                     }
 
+                    // Navigating away abandons any challenge on the current page
+                    app.state::<crate::AppState>().human_needed.lock().unwrap().remove(window);
+
                     // Close previous active label, then start new one
                     if !label.is_empty() {
-                        close_active_label(app);
+                        close_active_label(app, window);
                         let state = app.state::<crate::AppState>();
-                        let current_app = state.current_app.lock().unwrap().clone();
+                        let current_app = state.current_app(window);
                         let session_ts = state.session_ts.clone();
                         if let Some(ref app_name) = current_app {
                             let entry = serde_json::json!({
                                 "type": "annotation",
                                 "label": label,
                                 "url": raw,
+                                "window": window,
                                 "timestamp": chrono::Utc::now().to_rfc3339(),
                             });
                             append_capture(app_name, &entry, &session_ts);
                         }
                         // Track as active label
-                        *state.active_label.lock().unwrap() = Some(label.to_string());
+                        state
+                            .active_labels
+                            .lock()
+                            .unwrap()
+                            .insert(window.to_string(), label.to_string());
                     }
 
-                    match crate::open_browser(app, parsed) {
-                        Ok(_) => r#"{"ok":true}"#.to_string(),
-                        Err(e) => serde_json::json!({"error": e}).to_string(),
+                    match crate::open_browser(app, window, parsed) {
+                        Ok(_) => serde_json::json!({"ok": true, "window": window}).to_string(),
+                        Err(e) => e.reply().to_string(),
                     }
                 }
-                Err(e) => serde_json::json!({"error": e.to_string()}).to_string(),
+                Err(e) => CommandError::InvalidParams(format!("bad url: {e}")).reply().to_string(),
+            }
+        }
+
+        "open_incognito" => {
+            let url = cmd.get("url").and_then(|v| v.as_str()).unwrap_or("");
+            if url.is_empty() {
+                return CommandError::InvalidParams("missing url".to_string()).reply().to_string();
+            }
+            let mut raw = url.to_string();
+            if !raw.starts_with("http") {
+                raw = format!("https://{raw}");
+            }
+            match url::Url::parse(&raw) {
+                Ok(parsed) => match crate::open_incognito(app, parsed) {
+                    Ok(_) => r#"{"ok":true,"note":"incognito window — not captured"}"#.to_string(),
+                    Err(e) => CommandError::NavigationFailed(e).reply().to_string(),
+                },
+                Err(e) => CommandError::InvalidParams(format!("bad url: {e}")).reply().to_string(),
             }
         }
 
         "click" => {
             let selector = cmd.get("selector").and_then(|v| v.as_str()).unwrap_or("");
-            exec_js_with_result(app, &format!(
+            exec_js_with_result(app, target, &format!(
                 "(() => {{ const el = document.querySelector({}); if(el) {{ el.click(); return 'clicked'; }} else {{ return 'not found'; }} }})()",
                 serde_json::to_string(selector).unwrap()
             ))
@@ -310,7 +1025,7 @@ fn handle_command(app: &tauri::AppHandle, body: &str) -> String {
         "type" => {
             let selector = cmd.get("selector").and_then(|v| v.as_str()).unwrap_or("");
             let value = cmd.get("value").and_then(|v| v.as_str()).unwrap_or("");
-            exec_js_with_result(app, &format!(
+            exec_js_with_result(app, target, &format!(
                 "(() => {{ const el = document.querySelector({}); if(el) {{ el.focus(); el.value = {}; el.dispatchEvent(new Event('input', {{bubbles:true}})); return 'typed'; }} else {{ return 'not found'; }} }})()",
                 serde_json::to_string(selector).unwrap(),
                 serde_json::to_string(value).unwrap()
@@ -321,123 +1036,583 @@ fn handle_command(app: &tauri::AppHandle, body: &str) -> String {
             let amount = cmd.get("amount").and_then(|v| v.as_i64()).unwrap_or(500);
             let direction = cmd.get("direction").and_then(|v| v.as_str()).unwrap_or("down");
             let y = if direction == "up" { -amount } else { amount };
-            exec_js_with_result(app, &format!("window.scrollBy(0, {y}); 'scrolled'"))
+            exec_js_with_result(app, target, &format!("window.scrollBy(0, {y}); 'scrolled'"))
         }
 
         "eval" => {
             let js = cmd.get("js").and_then(|v| v.as_str()).unwrap_or("");
-            exec_js_with_result(app, js)
+            exec_js_with_result(app, target, js)
         }
 
+        "fetch_in_page" => fetch_in_page(app, target, cmd),
+
         "read_page" => {
-            exec_js_with_result(app, "document.documentElement.outerHTML.substring(0, 500000)")
+            // "html" (raw, default), or the main content as "text" / "markdown",
+            // extracted here from a larger slice of the page
+            let mode = cmd.get("mode").and_then(|v| v.as_str()).unwrap_or("html");
+            let max_chars = cmd.get("max_chars").and_then(|v| v.as_u64()).map(|n| n as usize);
+            let js = match mode {
+                "html" => "document.documentElement.outerHTML.substring(0, 500000)",
+                "text" | "markdown" => {
+                    "JSON.stringify({url: location.href, html: document.documentElement.outerHTML.substring(0, 5000000)})"
+                }
+                _ => {
+                    return CommandError::InvalidParams(format!("unknown mode {mode:?}: use html, text or markdown"))
+                        .reply()
+                        .to_string()
+                }
+            };
+            let page = match crate::eval_js_with_timeout(app, window, js, target.timeout) {
+                Ok(page) => page,
+                Err(e) => return e.reply().to_string(),
+            };
+            let content = if mode == "html" {
+                page
+            } else {
+                let page: serde_json::Value = serde_json::from_str(&page).unwrap_or_default();
+                let base = page
+                    .get("url")
+                    .and_then(|v| v.as_str())
+                    .and_then(|u| url::Url::parse(u).ok());
+                let html = page.get("html").and_then(|v| v.as_str()).unwrap_or("");
+                crate::page_text::extract(html, base.as_ref(), mode == "markdown")
+            };
+            let content = match max_chars {
+                Some(max) => crate::page_text::truncate(&content, max, mode),
+                None => content,
+            };
+            if !progress.is_streaming() {
+                return serde_json::json!({"ok": true, "result": content}).to_string();
+            }
+
+            // Streamed: send the page in chunks as progress events, then a summary result
+            let chars: Vec<char> = content.chars().collect();
+            let chunks: Vec<String> = chars
+                .chunks(STREAM_CHUNK_CHARS)
+                .map(|c| c.iter().collect())
+                .collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                progress.emit(serde_json::json!({
+                    "event": "chunk",
+                    "index": i,
+                    "total": chunks.len(),
+                    "data": chunk,
+                }));
+            }
+            serde_json::json!({"ok": true, "streamed": true, "chunks": chunks.len(), "chars": chars.len()}).to_string()
         }
 
         "read_ui" => {
-            exec_js_with_result(app, READ_UI_JS)
+            exec_js_with_result(app, target, READ_UI_JS)
+        }
+
+        "read_forms" => {
+            exec_js_with_result(app, target, READ_FORMS_JS)
+        }
+
+        "assert_text" => {
+            crate::assertions::assert_text(app, window, cmd, target.timeout, &|event| progress.emit(event))
+                .to_string()
+        }
+
+        "assert_url" => {
+            crate::assertions::assert_url(app, window, cmd, target.timeout, &|event| progress.emit(event))
+                .to_string()
+        }
+
+        "mark" => {
+            // {"name": "before-submit"} — default "last"
+            let name = cmd
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or(crate::marks::DEFAULT_MARK);
+            let at = crate::marks::set(name);
+            serde_json::json!({
+                "ok": true,
+                "mark": name,
+                "at": at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            })
+            .to_string()
+        }
+
+        "captures_since" => {
+            let current_app = app.state::<AppState>().current_app(window);
+            crate::marks::captures_since(current_app, cmd).to_string()
+        }
+
+        "query_captures" => {
+            let current_app = app.state::<AppState>().current_app(window);
+            crate::query::run(current_app, cmd).to_string()
+        }
+
+        "recent_captures" => {
+            let current_app = app.state::<AppState>().current_app(window);
+            crate::query::recent(current_app, cmd).to_string()
+        }
+
+        "capture_detail" => crate::query::detail(cmd).to_string(),
+
+        "list_notifications" => crate::notifications::list(cmd).to_string(),
+
+        "ack_notification" => crate::notifications::ack(cmd).to_string(),
+
+        "assert_request_seen" => {
+            let current_app = app.state::<AppState>().current_app(window);
+            crate::assertions::assert_request_seen(current_app, cmd, &|event| progress.emit(event))
+                .to_string()
+        }
+
+        "read_tables" => {
+            let max_rows = cmd.get("max_rows").and_then(|v| v.as_u64()).unwrap_or(200);
+            exec_js_with_result(app, target, &format!("({READ_TABLES_JS})({max_rows})"))
         }
 
         "click_ref" => {
             let ref_id = cmd.get("ref").and_then(|v| v.as_u64()).unwrap_or(0);
-            let result = exec_js_with_result(app, &format!(
-                "(() => {{ const refs = window.__hh_refs || []; const el = refs[{}]; if(!el) return JSON.stringify({{ok:false,err:'ref not found'}}); var role = el.getAttribute('role') || el.tagName.toLowerCase(); var label = (el.getAttribute('aria-label') || el.innerText || '').substring(0,80).trim(); el.scrollIntoView({{block:'center'}}); el.click(); return JSON.stringify({{ok:true,role:role,label:label,url:location.href}}); }})()",
+            let result = exec_js_with_result(app, target, &format!(
+                "(() => {{ const el = window.__hh && window.__hh.ref({}); if(!el) return JSON.stringify({{ok:false,err:'ref not found'}}); var role = el.getAttribute('role') || el.tagName.toLowerCase(); var label = (el.getAttribute('aria-label') || el.innerText || '').substring(0,80).trim(); el.scrollIntoView({{block:'center'}}); el.click(); return JSON.stringify({{ok:true,role:role,label:label,url:location.href}}); }})()",
                 ref_id
             ));
-            log_ui_action(app, "click_ref", ref_id, None, &result);
+            log_ui_action(app, window, "click_ref", ref_id, None, &result);
             result
         }
 
         "type_ref" => {
             let ref_id = cmd.get("ref").and_then(|v| v.as_u64()).unwrap_or(0);
             let value = cmd.get("value").and_then(|v| v.as_str()).unwrap_or("");
-            let result = exec_js_with_result(app, &format!(
-                "(() => {{ const refs = window.__hh_refs || []; const el = refs[{}]; if(!el) return JSON.stringify({{ok:false,err:'ref not found'}}); var role = el.getAttribute('role') || el.tagName.toLowerCase(); var label = (el.getAttribute('aria-label') || el.placeholder || '').substring(0,80).trim(); el.focus(); el.value = {}; el.dispatchEvent(new Event('input', {{bubbles:true}})); el.dispatchEvent(new Event('change', {{bubbles:true}})); return JSON.stringify({{ok:true,role:role,label:label,url:location.href}}); }})()",
+            let result = exec_js_with_result(app, target, &format!(
+                "(() => {{ const el = window.__hh && window.__hh.ref({}); if(!el) return JSON.stringify({{ok:false,err:'ref not found'}}); var role = el.getAttribute('role') || el.tagName.toLowerCase(); var label = (el.getAttribute('aria-label') || el.placeholder || '').substring(0,80).trim(); el.focus(); el.value = {}; el.dispatchEvent(new Event('input', {{bubbles:true}})); el.dispatchEvent(new Event('change', {{bubbles:true}})); return JSON.stringify({{ok:true,role:role,label:label,url:location.href}}); }})()",
                 ref_id,
                 serde_json::to_string(value).unwrap()
             ));
-            log_ui_action(app, "type_ref", ref_id, Some(value), &result);
+            log_ui_action(app, window, "type_ref", ref_id, Some(value), &result);
             result
         }
 
         "select_ref" => {
             let ref_id = cmd.get("ref").and_then(|v| v.as_u64()).unwrap_or(0);
             let value = cmd.get("value").and_then(|v| v.as_str()).unwrap_or("");
-            let result = exec_js_with_result(app, &format!(
-                "(() => {{ const refs = window.__hh_refs || []; const el = refs[{}]; if(!el) return JSON.stringify({{ok:false,err:'ref not found'}}); var role = el.getAttribute('role') || el.tagName.toLowerCase(); var label = (el.getAttribute('aria-label') || '').substring(0,80).trim(); el.value = {}; el.dispatchEvent(new Event('change', {{bubbles:true}})); return JSON.stringify({{ok:true,role:role,label:label,selected:el.value,url:location.href}}); }})()",
+            let result = exec_js_with_result(app, target, &format!(
+                "(() => {{ const el = window.__hh && window.__hh.ref({}); if(!el) return JSON.stringify({{ok:false,err:'ref not found'}}); var role = el.getAttribute('role') || el.tagName.toLowerCase(); var label = (el.getAttribute('aria-label') || '').substring(0,80).trim(); el.value = {}; el.dispatchEvent(new Event('change', {{bubbles:true}})); return JSON.stringify({{ok:true,role:role,label:label,selected:el.value,url:location.href}}); }})()",
                 ref_id,
                 serde_json::to_string(value).unwrap()
             ));
-            log_ui_action(app, "select_ref", ref_id, Some(value), &result);
+            log_ui_action(app, window, "select_ref", ref_id, Some(value), &result);
             result
         }
 
         "get_cookies" => {
             let url = cmd.get("url").and_then(|v| v.as_str()).unwrap_or("https://mail.google.com");
-            get_browser_cookies(app, url)
+            get_browser_cookies(app, url, target)
         }
 
+        "read_storage" => {
+            // {"storage": "local"|"session" (default both), "save": true → sessions/storage-{host}.json}
+            let which = cmd.get("storage").and_then(|v| v.as_str()).unwrap_or("");
+            let js = format!(
+                "(() => {{ const dump = s => {{ const o = {{}}; for (let i = 0; i < s.length; i++) {{ const k = s.key(i); o[k] = s.getItem(k); }} return o; }}; const w = {}; const r = {{ origin: location.origin, host: location.hostname }}; if (w !== 'session') r.localStorage = dump(localStorage); if (w !== 'local') r.sessionStorage = dump(sessionStorage); return JSON.stringify(r); }})()",
+                serde_json::to_string(which).unwrap()
+            );
+            if !cmd.get("save").and_then(|v| v.as_bool()).unwrap_or(false) {
+                return exec_js_with_result(app, target, &js);
+            }
+            // Saving needs the page's own JSON, not the {"ok", "result"} reply around it
+            let result = match crate::eval_js_with_timeout(app, &target.window, &js, target.timeout) {
+                Ok(result) if result.starts_with("error: ") => {
+                    return CommandError::EvalFailed(result["error: ".len()..].to_string())
+                        .reply()
+                        .to_string()
+                }
+                Ok(result) => result,
+                Err(e) => return e.reply().to_string(),
+            };
+            let mut snapshot = match serde_json::from_str::<serde_json::Value>(&result) {
+                Ok(snapshot) if snapshot.is_object() => snapshot,
+                _ => {
+                    return CommandError::Failed(format!("unexpected storage dump: {result}"))
+                        .reply()
+                        .to_string()
+                }
+            };
+            match app.state::<AppState>().current_app(window) {
+                Some(app_name) => {
+                    snapshot["captured_at"] = serde_json::json!(chrono::Utc::now().to_rfc3339());
+                    let host = snapshot.get("host").and_then(|v| v.as_str()).unwrap_or("unknown").to_string();
+                    let path = config::data_dir()
+                        .join("apps")
+                        .join(&app_name)
+                        .join("sessions")
+                        .join(format!("storage-{host}.json"));
+                    let _ = fs::write(&path, serde_json::to_string_pretty(&snapshot).unwrap_or_default());
+                    snapshot["saved_to"] = serde_json::json!(path.to_string_lossy());
+                    snapshot.to_string()
+                }
+                None => CommandError::UnknownApp("no app for this window — navigate with \"app\" first".to_string())
+                    .reply()
+                    .to_string(),
+            }
+        }
+
+        "set_storage" => {
+            // {"storage": "local"|"session", "key": "k", "value": "v"} or {"items": {"k": "v"}};
+            // a null value removes the key, "clear": true empties the storage first
+            let which = cmd.get("storage").and_then(|v| v.as_str()).unwrap_or("local");
+            if which != "local" && which != "session" {
+                return CommandError::InvalidParams("storage must be \"local\" or \"session\"".to_string())
+                    .reply()
+                    .to_string();
+            }
+            let mut items = cmd
+                .get("items")
+                .and_then(|v| v.as_object())
+                .cloned()
+                .unwrap_or_default();
+            if let Some(key) = cmd.get("key").and_then(|v| v.as_str()) {
+                items.insert(key.to_string(), cmd.get("value").cloned().unwrap_or(serde_json::Value::Null));
+            }
+            let clear = cmd.get("clear").and_then(|v| v.as_bool()).unwrap_or(false);
+            if items.is_empty() && !clear {
+                return CommandError::InvalidParams("missing key or items".to_string()).reply().to_string();
+            }
+            exec_js_with_result(app, target, &format!(
+                "(() => {{ const s = {which}Storage; if ({clear}) s.clear(); const items = {}; for (const [k, v] of Object.entries(items)) {{ if (v === null) s.removeItem(k); else s.setItem(k, typeof v === 'string' ? v : JSON.stringify(v)); }} return JSON.stringify({{ok: true, origin: location.origin, count: s.length}}); }})()",
+                serde_json::Value::Object(items)
+            ))
+        }
+
+        "crawl" => {
+            // Runs until the page budget is spent — stream progress to keep `harharhar cmd` waiting
+            let opts = crate::crawl::CrawlOptions::from_cmd(cmd);
+            crate::crawl::run(app, window, &opts, &|event| progress.emit(event)).to_string()
+        }
+
+        "history" => {
+            // {"app": "gmail", "limit": 50} — app defaults to the window's current app
+            let app_name = cmd
+                .get("app")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .or_else(|| app.state::<AppState>().current_app(window));
+            let limit = cmd.get("limit").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
+            match app_name {
+                Some(name) => crate::history::read(&name, limit).to_string(),
+                None => CommandError::InvalidParams("missing app".to_string()).reply().to_string(),
+            }
+        }
+
+        "name_endpoint" => {
+            // {"pattern": "POST /gp/api/x23", "name": "send_message", "tags": [...], "app": "x"}
+            // — app defaults to the window's current app
+            let app_name = cmd
+                .get("app")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .or_else(|| app.state::<AppState>().current_app(window));
+            match app_name {
+                Some(name) => crate::endpoints::name_endpoint(&name, cmd).to_string(),
+                None => CommandError::InvalidParams("missing app".to_string()).reply().to_string(),
+            }
+        }
+
+        "suggest" => {
+            // {"query": "send a message", "limit": 10, "app": "x"} — endpoints ranked for
+            // the query; app defaults to the window's current app
+            let app_name = cmd
+                .get("app")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .or_else(|| app.state::<AppState>().current_app(window));
+            match app_name {
+                Some(name) => crate::suggest::suggest(&name, cmd).to_string(),
+                None => CommandError::InvalidParams("missing app".to_string()).reply().to_string(),
+            }
+        }
+
+        "timeline" => {
+            // {"app": "gmail", "session": "2026-02-21T14-30"} — a session's timeline in trace
+            // event format, built now so the running session is up to date; app defaults to the
+            // window's app, session to the latest
+            let app_name = cmd
+                .get("app")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .or_else(|| app.state::<AppState>().current_app(window));
+            let Some(app_name) = app_name else {
+                return CommandError::InvalidParams("missing app".to_string()).reply().to_string();
+            };
+            let app_dir = config::data_dir().join("apps").join(&app_name);
+            let files = crate::capture_io::capture_files(&app_dir);
+            let file = match cmd.get("session").and_then(|v| v.as_str()) {
+                Some(session) => files
+                    .into_iter()
+                    .find(|f| f.file_stem().and_then(|s| s.to_str()) == Some(session)),
+                None => files.into_iter().last(),
+            };
+            match file {
+                Some(file) => crate::timeline::build(&app_name, &file).to_string(),
+                None => CommandError::NotFound(format!("no such session for {app_name}")).reply().to_string(),
+            }
+        }
+
+        "archive_app" => {
+            // {"app": "gmail", "archived": true} — false unarchives and saves any held traffic
+            let name = cmd.get("app").and_then(|v| v.as_str()).unwrap_or("");
+            let archived = cmd.get("archived").and_then(|v| v.as_bool()).unwrap_or(true);
+            match crate::archive_app(app, name, archived) {
+                Ok(()) => serde_json::json!({"ok": true, "app": name, "archived": archived}).to_string(),
+                Err(e) => CommandError::Failed(e).reply().to_string(),
+            }
+        }
+
+        "group_domains" => {
+            // {"app": "gmail", "group": "google.com"} — every *.google.com host belongs to gmail
+            let to = cmd.get("app").and_then(|v| v.as_str()).unwrap_or("");
+            let group = cmd.get("group").and_then(|v| v.as_str()).unwrap_or("");
+            if to.is_empty() || group.is_empty() {
+                return CommandError::InvalidParams("missing app or group".to_string()).reply().to_string();
+            }
+            match crate::domain_groups::confirm(app, to, group) {
+                Ok(added) => serde_json::json!({"ok": true, "app": to, "added": added}).to_string(),
+                Err(e) => CommandError::Failed(e).reply().to_string(),
+            }
+        }
+
+        "reassign_domain" => {
+            // {"domain": "api.example.com", "app": "newapp"} — settles a domain-conflict
+            let domain = cmd.get("domain").and_then(|v| v.as_str()).unwrap_or("");
+            let to = cmd.get("app").and_then(|v| v.as_str()).unwrap_or("");
+            if domain.is_empty() || to.is_empty() {
+                return CommandError::InvalidParams("missing domain or app".to_string()).reply().to_string();
+            }
+            match crate::move_domain(app, domain, to) {
+                Ok(previous) => serde_json::json!({"ok": true, "domain": domain, "app": to, "from": previous}).to_string(),
+                Err(e) => CommandError::UnknownApp(e).reply().to_string(),
+            }
+        }
+
+        "restore_domain" => {
+            // {"domain": "api.example.com", "app": "x"} — undo a domain cleanup removal;
+            // app defaults to the window's app
+            let domain = cmd.get("domain").and_then(|v| v.as_str()).unwrap_or("");
+            let to = cmd
+                .get("app")
+                .and_then(|v| v.as_str())
+                .map(String::from)
+                .or_else(|| app.state::<AppState>().current_app(window));
+            let Some(to) = to.filter(|_| !domain.is_empty()) else {
+                return CommandError::InvalidParams("missing domain or app".to_string()).reply().to_string();
+            };
+            match crate::cleanup::restore_domain(&to, domain) {
+                Ok(()) => {
+                    let ts = {
+                        let state = app.state::<AppState>();
+                        state.domain_map.lock().unwrap().insert(domain.to_string(), to.clone());
+                        state.session_ts.clone()
+                    };
+                    flush_unmapped(app, domain, &to, &ts);
+                    serde_json::json!({"ok": true, "domain": domain, "app": to}).to_string()
+                }
+                Err(e) => CommandError::UnknownDomain(e).reply().to_string(),
+            }
+        }
+
+        "open" => {
+            // {"url": "https://...", "app": "x"} — what a second launch hands over: bring the
+            // explorer forward and open url in the main browser, asking for its app (suggesting
+            // `app`) if the domain is new. With only `app`, it's where that app was left off.
+            if let Some(explorer) = app.get_webview_window("explorer") {
+                let _ = explorer.show();
+                let _ = explorer.set_focus();
+            }
+            let app_name = cmd.get("app").and_then(|v| v.as_str());
+            let url = match (cmd.get("url").and_then(|v| v.as_str()), app_name) {
+                (Some(url), _) => url.to_string(),
+                (None, Some(name)) => match crate::history::start_url(name) {
+                    Ok(url) => url,
+                    Err(e) => return CommandError::UnknownApp(e).reply().to_string(),
+                },
+                (None, None) => return serde_json::json!({"ok": true}).to_string(),
+            };
+            match crate::open_url(app, &url, app_name) {
+                Ok(()) => serde_json::json!({"ok": true, "url": url}).to_string(),
+                Err(e) => CommandError::NavigationFailed(e).reply().to_string(),
+            }
+        }
+
+        "why_dropped" => {
+            // {"url": "substring or full URL", "limit": 20}
+            let url = cmd.get("url").and_then(|v| v.as_str()).unwrap_or("");
+            let limit = cmd.get("limit").and_then(|v| v.as_u64()).unwrap_or(20) as usize;
+            crate::droplog::why_dropped(url, limit).to_string()
+        }
+
+        "capture_debug" => {
+            // {"on": true} — log dropped captures to debug.log
+            let mut cfg = config::read_config();
+            cfg.capture_debug = cmd.get("on").and_then(|v| v.as_bool()).unwrap_or(true);
+            config::write_config(&cfg);
+            serde_json::json!({"ok": true, "capture_debug": cfg.capture_debug}).to_string()
+        }
+
+        "set_cookie" => match set_browser_cookies(app, window, cmd) {
+            Ok(count) => serde_json::json!({"ok": true, "set": count}).to_string(),
+            Err(e) => e.reply().to_string(),
+        },
+
         "status" => {
-            let result = serde_json::json!({
-                "browser_open": app.get_webview_window("browser").is_some(),
-                "apps": config::list_apps(),
+            // Open browser windows and the app each one is on
+            let state = app.state::<AppState>();
+            let mut windows = serde_json::Map::new();
+            for label in app.webview_windows().keys() {
+                if label.starts_with("browser-") {
+                    windows.insert(
+                        label.clone(),
+                        serde_json::json!({
+                            "app": state.current_app(label),
+                            "human_needed": crate::challenge::pending(app, label),
+                        }),
+                    );
+                }
+            }
+            if crate::cdp::attached() {
+                windows.insert(
+                    crate::cdp::WINDOW.to_string(),
+                    serde_json::json!({
+                        "app": state.current_app(crate::cdp::WINDOW),
+                        "human_needed": crate::challenge::pending(app, crate::cdp::WINDOW),
+                    }),
+                );
+            }
+            let curl_impersonate = config::installed_curl_binaries();
+            let apps = config::list_apps();
+            let sessions: serde_json::Map<String, serde_json::Value> = apps
+                .iter()
+                .map(|name| (name.clone(), crate::stats::session_status(name)))
+                .collect();
+            let mut result = serde_json::json!({
+                "browser_open": !windows.is_empty(),
+                "windows": windows,
+                "apps": apps,
+                "sessions": sessions,
+                "curl_impersonate": curl_impersonate,
             });
+            if curl_impersonate.is_empty() {
+                result["warnings"] = serde_json::json!([
+                    "curl-impersonate is not installed: examples.sh uses plain curl, whose TLS fingerprint sites can tell from a browser's. Install it (brew tap shakacode/brew && brew install curl-impersonate) for faithful replays."
+                ]);
+            }
             result.to_string()
         }
 
+        // Waits up to timeout_ms for the challenge to disappear — pass a generous one
+        "resume_after_human" => crate::challenge::wait_for_human(app, window, target.timeout).to_string(),
+
+        "dismiss_consent" => match crate::consent::dismiss(app, window) {
+            Ok(result) => result.to_string(),
+            Err(e) => e.reply().to_string(),
+        },
+
+        // Stop waiting on evals still running in this window ("all": true for every window),
+        // e.g. a read_page stuck on a hung page
+        "cancel" => {
+            let all = cmd.get("all").and_then(|v| v.as_bool()).unwrap_or(false);
+            let cancelled = crate::cancel_evals(app, if all { None } else { Some(window) });
+            serde_json::json!({"ok": true, "cancelled": cancelled}).to_string()
+        }
+
+        "close_window" => {
+            match app.get_webview_window(window) {
+                Some(wv) => {
+                    close_active_label(app, window);
+                    app.state::<AppState>().current_apps.lock().unwrap().remove(window);
+                    match wv.close() {
+                        Ok(_) => serde_json::json!({"ok": true, "window": window}).to_string(),
+                        Err(e) => CommandError::Failed(e.to_string()).reply().to_string(),
+                    }
+                }
+                None => CommandError::BrowserNotOpen(window.to_string()).reply().to_string(),
+            }
+        }
+
         "ws_send" => {
-            let message = cmd.get("message").and_then(|v| v.as_str()).unwrap_or("");
+            // "message" is sent as text; "binary" is base64 and sent as an ArrayBuffer
             let index = cmd.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
-            exec_js_with_result(app, &format!(
-                "(() => {{ const sockets = window.__hh_ws || []; const ws = sockets.filter(s => s.readyState === 1)[{}]; if(ws) {{ ws.send({}); return 'sent'; }} else {{ return 'no open websocket'; }} }})()",
+            let payload = match cmd.get("binary").and_then(|v| v.as_str()) {
+                Some(b64) => format!(
+                    "Uint8Array.from(atob({}), c => c.charCodeAt(0)).buffer",
+                    serde_json::to_string(b64).unwrap()
+                ),
+                None => serde_json::to_string(cmd.get("message").and_then(|v| v.as_str()).unwrap_or("")).unwrap(),
+            };
+            exec_js_with_result(app, target, &format!(
+                "(() => {{ const sockets = window.__hh ? window.__hh.sockets() : []; const ws = sockets.filter(s => s.readyState === 1)[{}]; if(ws) {{ ws.send({}); return 'sent'; }} else {{ return 'no open websocket'; }} }})()",
                 index,
-                serde_json::to_string(message).unwrap()
+                payload
+            ))
+        }
+
+        "ws_subscribe" => {
+            // Record every frame on open socket #index into captures for duration_ms (default 30s)
+            let index = cmd.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+            let duration = cmd.get("duration_ms").and_then(|v| v.as_u64()).unwrap_or(30_000);
+            exec_js_with_result(app, target, &format!(
+                "(() => {{ const sockets = window.__hh ? window.__hh.sockets() : []; const ws = sockets.filter(s => s.readyState === 1)[{index}]; if(!ws) return 'no open websocket'; const until = Date.now() + {duration}; window.__hh.subscribe(ws, until); return JSON.stringify({{ok: true, url: ws.url, until: new Date(until).toISOString()}}); }})()"
             ))
         }
 
         "ws_list" => {
-            exec_js_with_result(app, "(() => { const sockets = window.__hh_ws || []; return JSON.stringify(sockets.map((s,i) => ({index:i, url:s.url, state:['CONNECTING','OPEN','CLOSING','CLOSED'][s.readyState]}))); })()")
+            exec_js_with_result(app, target, "(() => { const sockets = window.__hh ? window.__hh.sockets() : []; return JSON.stringify(sockets.map((s,i) => ({index:i, url:s.url, state:['CONNECTING','OPEN','CLOSING','CLOSED'][s.readyState]}))); })()")
         }
 
         "annotate" => {
             let label = cmd.get("label").and_then(|v| v.as_str()).unwrap_or("");
             if label.is_empty() {
-                return r#"{"error":"missing label"}"#.to_string();
+                return CommandError::InvalidParams("missing label".to_string()).reply().to_string();
             }
             let state = app.state::<crate::AppState>();
-            let current_app = state.current_app.lock().unwrap().clone();
+            let current_app = state.current_app(window);
             let session_ts = state.session_ts.clone();
 
             match current_app {
                 Some(ref app_name) => {
                     // Close previous label, start new one
-                    close_active_label(app);
+                    close_active_label(app, window);
                     let entry = serde_json::json!({
                         "type": "annotation",
                         "label": label,
                         "url": "",
+                        "window": window,
                         "timestamp": chrono::Utc::now().to_rfc3339(),
                     });
                     append_capture(app_name, &entry, &session_ts);
-                    *state.active_label.lock().unwrap() = Some(label.to_string());
+                    state
+                        .active_labels
+                        .lock()
+                        .unwrap()
+                        .insert(window.to_string(), label.to_string());
                     serde_json::json!({"ok": true, "app": app_name}).to_string()
                 }
-                None => {
-                    r#"{"error":"no active app — navigate to an app first"}"#.to_string()
-                }
+                None => CommandError::UnknownApp("no active app — navigate to an app first".to_string())
+                    .reply()
+                    .to_string(),
             }
         }
 
         "close_label" => {
             let revised = cmd.get("label").and_then(|v| v.as_str()).unwrap_or("");
             let state = app.state::<crate::AppState>();
-            let prev = state.active_label.lock().unwrap().clone();
+            let prev = state.active_labels.lock().unwrap().get(window).cloned();
             match prev {
                 Some(_) => {
                     if !revised.is_empty() {
                         // Override the active label before closing
-                        *state.active_label.lock().unwrap() = Some(revised.to_string());
+                        state
+                            .active_labels
+                            .lock()
+                            .unwrap()
+                            .insert(window.to_string(), revised.to_string());
                     }
-                    close_active_label(app);
+                    close_active_label(app, window);
                     serde_json::json!({"ok": true}).to_string()
                 }
                 None => {
@@ -449,36 +1624,45 @@ fn handle_command(app: &tauri::AppHandle, body: &str) -> String {
         "generate_endpoints" => {
             let state = app.state::<AppState>();
             let ts = state.session_ts.clone();
-            generate_all_endpoints(&ts);
+            crate::generation::generate_all(&ts);
             r#"{"ok":true}"#.to_string()
         }
 
         "end_session" => {
-            close_active_label(app);
+            close_all_labels(app);
             let state = app.state::<AppState>();
             let ts = state.session_ts.clone();
-            generate_all_endpoints(&ts);
+            crate::generation::generate_all(&ts);
             r#"{"ok":true,"note":"session finalized"}"#.to_string()
         }
 
-        _ => {
-            serde_json::json!({"error": format!("unknown action: {}", action)}).to_string()
+        "quit" => {
+            // Let this reply be written first; exiting flushes captures and marks the run clean
+            let handle = app.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                handle.exit(0);
+            });
+            r#"{"ok":true,"note":"quitting"}"#.to_string()
         }
+
+        _ => CommandError::UnknownAction(action.to_string()).reply().to_string(),
     }
 }
 
-/// Close the active label by writing a "[done]" annotation to captures.
-fn close_active_label(app: &tauri::AppHandle) {
+/// Close a window's active label by writing a "[done]" annotation to captures.
+fn close_active_label(app: &tauri::AppHandle, window: &str) {
     let state = app.state::<AppState>();
-    let label = state.active_label.lock().unwrap().take();
+    let label = state.active_labels.lock().unwrap().remove(window);
     if let Some(label) = label {
-        let current_app = state.current_app.lock().unwrap().clone();
+        let current_app = state.current_app(window);
         let session_ts = state.session_ts.clone();
         if let Some(ref app_name) = current_app {
             let entry = serde_json::json!({
                 "type": "annotation",
                 "label": format!("[done] {}", label),
                 "url": "",
+                "window": window,
                 "timestamp": chrono::Utc::now().to_rfc3339(),
             });
             append_capture(app_name, &entry, &session_ts);
@@ -486,31 +1670,166 @@ fn close_active_label(app: &tauri::AppHandle) {
     }
 }
 
-fn exec_js_with_result(app: &tauri::AppHandle, js: &str) -> String {
-    match crate::eval_js_with_result(app, js) {
+/// Close the active labels of every window (end of session).
+pub fn close_all_labels(app: &tauri::AppHandle) {
+    let windows: Vec<String> = {
+        let state = app.state::<AppState>();
+        let labels = state.active_labels.lock().unwrap();
+        labels.keys().cloned().collect()
+    };
+    for window in windows {
+        close_active_label(app, &window);
+    }
+}
+
+fn exec_js_with_result(app: &tauri::AppHandle, target: &EvalTarget, js: &str) -> String {
+    match crate::eval_js_with_timeout(app, &target.window, js, target.timeout) {
+        // intercept.js and the DevTools bridge report exceptions as "error: ..."
+        Ok(result) if result.starts_with("error: ") => {
+            CommandError::EvalFailed(result["error: ".len()..].to_string())
+                .reply()
+                .to_string()
+        }
         Ok(result) => serde_json::json!({"ok": true, "result": result}).to_string(),
-        Err(e) => serde_json::json!({"error": e}).to_string(),
+        Err(e) => e.reply().to_string(),
     }
 }
 
-fn get_browser_cookies(app: &tauri::AppHandle, _url: &str) -> String {
-    exec_js_with_result(app, "document.cookie")
+/// {"url", "method", "headers", "body", "max_body_chars"}: make a request from the page
+/// with fetch(), so it carries the page's cookies and goes through any fetch wrapper the
+/// app installed — where per-request signatures are usually added. `body` may be a
+/// string or JSON. Returns {"ok", "status", "headers", "body"}, the body cut to
+/// `max_body_chars` (default 20000).
+fn fetch_in_page(app: &tauri::AppHandle, target: &EvalTarget, cmd: &serde_json::Value) -> String {
+    let Some(url) = cmd.get("url").and_then(|v| v.as_str()) else {
+        return CommandError::InvalidParams("fetch_in_page needs a url".to_string())
+            .reply()
+            .to_string();
+    };
+    let method = cmd
+        .get("method")
+        .and_then(|v| v.as_str())
+        .unwrap_or("GET")
+        .to_uppercase();
+    let body = match cmd.get("body") {
+        None | Some(serde_json::Value::Null) => serde_json::Value::Null,
+        Some(serde_json::Value::String(s)) => serde_json::Value::String(s.clone()),
+        Some(other) => serde_json::Value::String(other.to_string()),
+    };
+    let init = serde_json::json!({
+        "method": method,
+        "headers": cmd.get("headers").cloned().unwrap_or_else(|| serde_json::json!({})),
+        "body": body,
+        "credentials": "include",
+    });
+    let max_chars = cmd
+        .get("max_body_chars")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(20_000);
+    let js = format!(
+        "fetch({}, {init}).then(async r => JSON.stringify({{status: r.status, headers: Object.fromEntries(r.headers), body: (await r.text()).slice(0, {max_chars})}}))",
+        serde_json::Value::from(url)
+    );
+    match crate::eval_js_with_timeout(app, &target.window, &js, target.timeout) {
+        Ok(result) if result.starts_with("error: ") => {
+            CommandError::EvalFailed(result["error: ".len()..].to_string())
+                .reply()
+                .to_string()
+        }
+        Ok(result) => match serde_json::from_str::<serde_json::Value>(&result) {
+            Ok(mut reply) if reply.is_object() => {
+                reply["ok"] = serde_json::Value::Bool(true);
+                reply.to_string()
+            }
+            _ => CommandError::EvalFailed(format!("unexpected result: {result}"))
+                .reply()
+                .to_string(),
+        },
+        Err(e) => e.reply().to_string(),
+    }
 }
 
-fn generate_all_endpoints(session_ts: &str) {
-    for app_name in config::list_apps() {
-        endpoints::generate_for_app(&app_name);
-        crate::cleanup::trim_captures_for_app(&app_name, session_ts);
-        crate::cleanup::clean_app_domains(&app_name);
-        crate::digest::generate_for_app(&app_name);
+/// Write cookies into the window's native cookie store (including httpOnly ones),
+/// from {"name","value"} or a whole {"cookies": {name: value}} map, sharing
+/// "domain", "path" (default "/"), "expires" (unix seconds), "secure", "http_only".
+fn set_browser_cookies(
+    app: &tauri::AppHandle,
+    window: &str,
+    cmd: &serde_json::Value,
+) -> Result<usize, CommandError> {
+    use tauri::webview::cookie::{time::OffsetDateTime, Cookie};
+
+    let wv = app
+        .get_webview_window(window)
+        .ok_or_else(|| CommandError::BrowserNotOpen(window.to_string()))?;
+    let domain = cmd
+        .get("domain")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| CommandError::InvalidParams("missing domain".to_string()))?;
+    let path = cmd.get("path").and_then(|v| v.as_str()).unwrap_or("/");
+    let secure = cmd.get("secure").and_then(|v| v.as_bool()).unwrap_or(true);
+    let http_only = cmd
+        .get("http_only")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let expires = match cmd.get("expires").and_then(|v| v.as_i64()) {
+        Some(ts) => Some(
+            OffsetDateTime::from_unix_timestamp(ts)
+                .map_err(|e| CommandError::InvalidParams(format!("bad expires: {e}")))?,
+        ),
+        None => None,
+    };
+
+    let mut pairs: Vec<(String, String)> = cmd
+        .get("cookies")
+        .and_then(|v| v.as_object())
+        .map(|m| {
+            m.iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(name) = cmd.get("name").and_then(|v| v.as_str()) {
+        let value = cmd.get("value").and_then(|v| v.as_str()).unwrap_or("");
+        pairs.push((name.to_string(), value.to_string()));
     }
+    if pairs.is_empty() {
+        return Err(CommandError::InvalidParams(
+            "missing name or cookies".to_string(),
+        ));
+    }
+
+    for (name, value) in &pairs {
+        let mut builder = Cookie::build((name.clone(), value.clone()))
+            .domain(domain.to_string())
+            .path(path.to_string())
+            .secure(secure)
+            .http_only(http_only);
+        if let Some(at) = expires {
+            builder = builder.expires(at);
+        }
+        wv.set_cookie(builder.build())
+            .map_err(|e| CommandError::Failed(e.to_string()))?;
+    }
+    Ok(pairs.len())
+}
+
+fn get_browser_cookies(app: &tauri::AppHandle, _url: &str, target: &EvalTarget) -> String {
+    exec_js_with_result(app, target, "document.cookie")
 }
 
 /// Log a UI interaction to the active app's capture JSONL.
 /// This correlates UI actions with the API calls they trigger.
-fn log_ui_action(app: &tauri::AppHandle, action: &str, ref_id: u64, value: Option<&str>, raw_result: &str) {
+fn log_ui_action(
+    app: &tauri::AppHandle,
+    window: &str,
+    action: &str,
+    ref_id: u64,
+    value: Option<&str>,
+    raw_result: &str,
+) {
     let state = app.state::<AppState>();
-    let current_app = state.current_app.lock().unwrap().clone();
+    let current_app = state.current_app(window);
     let app_name = match current_app {
         Some(name) => name,
         None => return,
@@ -534,6 +1853,7 @@ fn log_ui_action(app: &tauri::AppHandle, action: &str, ref_id: u64, value: Optio
         "role": js_info.get("role").and_then(|v| v.as_str()).unwrap_or(""),
         "label": js_info.get("label").and_then(|v| v.as_str()).unwrap_or(""),
         "url": js_info.get("url").and_then(|v| v.as_str()).unwrap_or(""),
+        "window": window,
         "timestamp": chrono::Utc::now().to_rfc3339(),
     });
 
@@ -551,7 +1871,10 @@ fn log_ui_action(app: &tauri::AppHandle, action: &str, ref_id: u64, value: Optio
 /// Any Cookie header counts as auth — the browser only sends cookies for domains that set them,
 /// which means the user has a session. This avoids the bootstrapping problem where session_cookie_names
 /// is empty on fresh starts and legitimate requests get dropped.
-fn has_auth(data: &serde_json::Value, _session_cookies: &std::collections::HashSet<String>) -> bool {
+pub(crate) fn has_auth(
+    data: &serde_json::Value,
+    _session_cookies: &std::collections::HashSet<String>,
+) -> bool {
     let headers = match data.get("requestHeaders").and_then(|v| v.as_object()) {
         Some(h) => h,
         None => return false,
@@ -576,25 +1899,54 @@ fn has_auth(data: &serde_json::Value, _session_cookies: &std::collections::HashS
 
 // --- Capture saving ---
 
+/// `capture_noise` of the app a capture belongs to: the app mapped to its domain,
+/// else the app open in the window it came from
+fn capture_noise_for(
+    app: &tauri::AppHandle,
+    data: &serde_json::Value,
+    url_str: &str,
+) -> Vec<String> {
+    let state = app.state::<AppState>();
+    let by_domain = url::Url::parse(url_str)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .and_then(|h| state.domain_map.lock().unwrap().get(&h).cloned());
+    let window = data
+        .get("window")
+        .and_then(|v| v.as_str())
+        .unwrap_or(crate::DEFAULT_WINDOW);
+    match by_domain.or_else(|| state.current_app(window)) {
+        Some(name) => config::capture_noise(&name),
+        None => Vec::new(),
+    }
+}
+
 fn save_capture(app: &tauri::AppHandle, data: &serde_json::Value, session_ts: &str) {
     // Skip xhr-start entries — always followed by the full xhr completion entry
     let entry_type = data.get("type").and_then(|v| v.as_str()).unwrap_or("");
     if entry_type == "xhr-start" {
+        crate::droplog::record(data, "xhr-start");
         return;
     }
 
     let url_str = match data.get("url").and_then(|v| v.as_str()) {
         Some(u) => u,
-        None => return,
+        None => {
+            crate::droplog::record(data, "no_url");
+            return;
+        }
     };
 
-    // Meta entries (ui-action, navigation, cookies, annotation) always pass through — no auth check needed
-    let is_meta = entry_type == "ui-action" || entry_type == "navigation" || entry_type == "cookies" || entry_type == "annotation";
+    let is_meta = is_meta(entry_type);
 
     if !is_meta {
         // API call — apply filters
-        // Static blocklist catches known noise even if authed (e.g. google analytics sharing SID cookies)
-        if should_skip_capture(url_str) {
+        // Static blocklist catches known noise even if authed (e.g. google analytics sharing SID cookies),
+        // unless the app this belongs to has opted back in to that pattern
+        if (entry_type == "beacon" || should_skip_capture(url_str))
+            && is_noise(entry_type, url_str, &capture_noise_for(app, data, url_str))
+        {
+            crate::droplog::record(data, noise_reason(url_str).unwrap_or("beacon"));
             return;
         }
 
@@ -602,58 +1954,99 @@ fn save_capture(app: &tauri::AppHandle, data: &serde_json::Value, session_ts: &s
         let state = app.state::<AppState>();
         let session_cookies = state.session_cookie_names.lock().unwrap();
         if !has_auth(data, &session_cookies) {
+            crate::droplog::record(data, "no_auth");
             return;
         }
     }
 
-    let domain = match url::Url::parse(url_str) {
-        Ok(u) => match u.host_str() {
-            Some(h) => h.to_string(),
-            None => return,
-        },
-        Err(_) => return,
+    let Some(domain) = url::Url::parse(url_str)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+    else {
+        crate::droplog::record(data, "bad_url");
+        return;
     };
 
+    // Which browser window this came from (tagged in save_capture_data)
+    let window = data
+        .get("window")
+        .and_then(|v| v.as_str())
+        .unwrap_or(crate::DEFAULT_WINDOW);
+
     let state = app.state::<AppState>();
     let app_name = {
         let map = state.domain_map.lock().unwrap();
         map.get(&domain).cloned()
-    };
+    }
+    .or_else(|| crate::domain_groups::resolve(app, &domain));
+
+    // Archived apps don't grow silently — hold their traffic and ask
+    let archived_owner = app_name
+        .clone()
+        .or_else(|| state.current_app(window))
+        .filter(|name| state.archived_apps.lock().unwrap().contains(name));
+    if let Some(name) = archived_owner {
+        hold_for_archived(app, &name, &domain, data);
+        return;
+    }
 
     match app_name {
         Some(name) => {
-            // Auto-set current_app if not already set — this handles the case where
+            // Auto-set the window's current app if not already set — this handles the case where
             // navigate went to an unmapped domain (e.g. mail.google.com) but the API
             // calls go to mapped domains (e.g. clients6.google.com → gmail).
             {
                 let state = app.state::<AppState>();
-                let mut current = state.current_app.lock().unwrap();
-                if current.is_none() {
-                    *current = Some(name.clone());
-                }
+                let mut current = state.current_apps.lock().unwrap();
+                current
+                    .entry(window.to_string())
+                    .or_insert_with(|| name.clone());
             }
             config::ensure_app_dirs(&name);
-            append_capture(&name, data, session_ts);
             update_session(app, &name, &domain, data);
+            append_capture(&name, data, session_ts);
+            emit_capture_events(&name, data, is_meta);
+            if entry_type == "navigation" {
+                crate::history::record_navigation(app, window, &name, url_str);
+            }
         }
         None => {
             // Domain not in map. If browser is open for a known app, handle it.
-            let current = app.state::<AppState>().current_app.lock().unwrap().clone();
+            let current = app.state::<AppState>().current_app(window);
 
             if let Some(ref name) = current {
                 if is_meta {
                     // Meta entries (navigation, cookies) — save to current app without auto-adding domain
                     config::ensure_app_dirs(name);
                     append_capture(name, data, session_ts);
+                    emit_capture_events(name, data, is_meta);
+                    if entry_type == "navigation" {
+                        crate::history::record_navigation(app, window, name, url_str);
+                    }
                 } else {
                     // API call — only auto-add domain if authed
                     let state_ref = app.state::<AppState>();
                     let session_cookies = state_ref.session_cookie_names.lock().unwrap();
                     if !has_auth(data, &session_cookies) {
+                        crate::droplog::record(data, "no_auth");
                         return;
                     }
                     drop(session_cookies);
 
+                    // Owned by another app on disk (e.g. created from the CLI) — hold the
+                    // capture until reassign_domain settles it rather than stealing the domain
+                    if let Some(owner) = crate::conflicting_owner(app, &domain, name) {
+                        let state = app.state::<AppState>();
+                        let mut buf = state.unmapped_captures.lock().unwrap();
+                        buf.entry(domain.clone()).or_default().push(data.clone());
+                        // Asked once per domain and app: every capture after is just held
+                        let key = (domain.clone(), name.clone());
+                        if CONFLICTS_RAISED.lock().unwrap().insert(key) {
+                            crate::emit_domain_conflict(app, &domain, &owner, name);
+                        }
+                        return;
+                    }
+
                     // Auto-add domain to the current app
                     config::add_domain_to_app(name, &domain);
                     {
@@ -661,21 +2054,87 @@ fn save_capture(app: &tauri::AppHandle, data: &serde_json::Value, session_ts: &s
                         state.domain_map.lock().unwrap().insert(domain.clone(), name.clone());
                     }
                     config::ensure_app_dirs(name);
-                    append_capture(name, data, session_ts);
                     update_session(app, name, &domain, data);
+                    append_capture(name, data, session_ts);
+                    emit_capture_events(name, data, is_meta);
+                    crate::domain_groups::suggest(app, &domain);
                 }
             } else {
-                // No active app — buffer and ask the user
+                // No active app — buffer and ask the user (or suggest grouping it
+                // with a sibling host another app already has)
+                crate::droplog::record(data, "unknown_domain");
+                crate::domain_groups::suggest(app, &domain);
                 let state = app.state::<AppState>();
                 let mut buf = state.unmapped_captures.lock().unwrap();
                 let entries = buf.entry(domain.clone()).or_insert_with(Vec::new);
                 entries.push(data.clone());
-                let _ = app.emit("unknown-domain", &domain);
+                // Asked when the domain's first capture comes in, not again for each one
+                // held after it (until it's mapped or dropped)
+                if entries.len() == 1 {
+                    let _ = app.emit("unknown-domain", &domain);
+                    events::emit(
+                        "unknown-domain",
+                        serde_json::json!({"domain": domain, "url": url_str}),
+                    );
+                }
             }
         }
     }
 }
 
+/// Buffer a capture for an archived app (under its domain, flushed if the app is
+/// unarchived) and prompt the user the first time its traffic shows up.
+fn hold_for_archived(
+    app: &tauri::AppHandle,
+    app_name: &str,
+    domain: &str,
+    data: &serde_json::Value,
+) {
+    let state = app.state::<AppState>();
+    let first = {
+        let mut buf = state.unmapped_captures.lock().unwrap();
+        let held = buf.entry(domain.to_string()).or_default();
+        held.push(data.clone());
+        held.len() == 1
+    };
+    if first {
+        let fields = serde_json::json!({"app": app_name, "domain": domain});
+        let _ = app.emit("archived-app-traffic", &fields);
+        events::emit("archived-app-traffic", fields);
+    }
+}
+
+/// Record a saved capture in the event stream, plus a session-expired event
+/// when an authenticated API call comes back 401/403 — once, until a call succeeds again.
+fn emit_capture_events(app_name: &str, data: &serde_json::Value, is_meta: bool) {
+    let url = data.get("url").and_then(|v| v.as_str()).unwrap_or("");
+    let method = data.get("method").and_then(|v| v.as_str()).unwrap_or("");
+    let entry_type = data.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let status = data.get("status").and_then(|v| v.as_u64()).unwrap_or(0);
+
+    events::emit(
+        "capture-saved",
+        serde_json::json!({"app": app_name, "type": entry_type, "method": method, "url": url, "status": status}),
+    );
+
+    if is_meta {
+        return;
+    }
+    if (200..300).contains(&status) {
+        SESSIONS_EXPIRED.lock().unwrap().remove(app_name);
+    } else if (status == 401 || status == 403)
+        && SESSIONS_EXPIRED
+            .lock()
+            .unwrap()
+            .insert(app_name.to_string())
+    {
+        events::emit(
+            "session-expired",
+            serde_json::json!({"app": app_name, "method": method, "url": url, "status": status}),
+        );
+    }
+}
+
 /// Flush buffered captures for a domain that was just mapped to an app
 pub fn flush_unmapped(app: &tauri::AppHandle, domain: &str, app_name: &str, session_ts: &str) {
     let state = app.state::<AppState>();
@@ -690,8 +2149,8 @@ pub fn flush_unmapped(app: &tauri::AppHandle, domain: &str, app_name: &str, sess
 
     config::ensure_app_dirs(app_name);
     for data in &entries {
-        append_capture(app_name, data, session_ts);
         update_session(app, app_name, domain, data);
+        append_capture(app_name, data, session_ts);
     }
 }
 
@@ -705,41 +2164,287 @@ fn append_capture(app_name: &str, data: &serde_json::Value, session_ts: &str) {
     // Skip noise URLs, but always let ui-action and annotation entries through
     if entry_type != "ui-action" && entry_type != "annotation" {
         if let Some(url_str) = data.get("url").and_then(|v| v.as_str()) {
-            if should_skip_capture(url_str) {
+            if (entry_type == "beacon" || should_skip_capture(url_str))
+                && is_noise(entry_type, url_str, &config::capture_noise(app_name))
+            {
+                crate::droplog::record(data, noise_reason(url_str).unwrap_or("beacon"));
                 return;
             }
         }
     }
 
-    let captures_dir = config::data_dir()
-        .join("apps")
-        .join(app_name)
-        .join("captures");
-    let file_path = captures_dir.join(format!("{session_ts}.jsonl"));
+    // High-frequency endpoints past the app's sampling threshold keep only a share;
+    // kept ones carry the weight of the ones dropped
+    let weighted;
+    let data = match crate::sampling::check(app_name, data) {
+        crate::sampling::Verdict::Keep => data,
+        crate::sampling::Verdict::Sampled(weight) => {
+            let mut entry = data.clone();
+            if let Some(obj) = entry.as_object_mut() {
+                obj.insert("sample_weight".to_string(), serde_json::Value::from(weight));
+            }
+            weighted = entry;
+            &weighted
+        }
+        crate::sampling::Verdict::Drop => {
+            crate::droplog::record(data, "sampled");
+            return;
+        }
+    };
+
+    if collapse_repeat(app_name, data, session_ts) {
+        return;
+    }
+    write_capture(app_name, data, session_ts);
+}
+
+/// Hold back a capture identical (method, URL, request body, status, response body)
+/// to the previous one for its endpoint within `REPEAT_WINDOW`; the run is later
+/// written as one line with `repeat_count`. Returns true if `data` was held back.
+/// Runs ended by this capture (or gone quiet) are written out first.
+fn collapse_repeat(app_name: &str, data: &serde_json::Value, session_ts: &str) -> bool {
+    let field = |k: &str| data.get(k).and_then(|v| v.as_str()).unwrap_or("");
+    let entry_type = field("type");
+    if !matches!(entry_type, "fetch" | "xhr" | "beacon") || field("url").is_empty() {
+        return false;
+    }
+    let key = (
+        app_name.to_string(),
+        format!("{} {}", field("method"), field("url")),
+    );
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let status = data
+        .get("status")
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    let request_body = data
+        .get("requestBody")
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    let response_body = data
+        .get("responseBody")
+        .map(|v| v.to_string())
+        .unwrap_or_default();
+    for part in [key.1.as_str(), &request_body, &status, &response_body] {
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    let now = std::time::Instant::now();
+    let mut ended: Vec<(String, RepeatRun)> = Vec::new();
+    {
+        let mut runs = REPEATS.lock().unwrap();
+        if let Some(run) = runs.get_mut(&key) {
+            if run.hash == hash
+                && run.session_ts == session_ts
+                && now.duration_since(run.last_at) < REPEAT_WINDOW
+                && now.duration_since(run.started) < REPEAT_MAX_RUN
+            {
+                if run.count == 0 {
+                    run.since = field("timestamp").to_string();
+                    REPEAT_TIMER.call_once(|| {
+                        std::thread::spawn(|| loop {
+                            std::thread::sleep(REPEAT_CHECK_EVERY);
+                            flush_finished_repeats();
+                        });
+                    });
+                }
+                run.count += 1;
+                run.last = data.clone();
+                run.last_at = now;
+                return true;
+            }
+        }
+        // This capture ends its endpoint's run; this app's quiet runs end too
+        let stale: Vec<(String, String)> = runs
+            .iter()
+            .filter(|(k, run)| {
+                *k == &key || (k.0 == app_name && now.duration_since(run.last_at) >= REPEAT_WINDOW)
+            })
+            .map(|(k, _)| k.clone())
+            .collect();
+        for k in stale {
+            if let Some(run) = runs.remove(&k) {
+                ended.push((k.0, run));
+            }
+        }
+        runs.insert(
+            key,
+            RepeatRun {
+                hash,
+                count: 0,
+                since: String::new(),
+                started: now,
+                last_at: now,
+                last: serde_json::Value::Null,
+                session_ts: session_ts.to_string(),
+            },
+        );
+    }
+    for (app_name, run) in ended {
+        write_repeat_run(&app_name, run);
+    }
+    false
+}
+
+/// Write out the runs no repeat has joined for `REPEAT_WINDOW`, or that have run for
+/// `REPEAT_MAX_RUN` — on a timer, so they don't wait for the next capture of their app
+fn flush_finished_repeats() {
+    let now = std::time::Instant::now();
+    let runs: Vec<((String, String), RepeatRun)> = {
+        let mut runs = REPEATS.lock().unwrap();
+        let finished: Vec<(String, String)> = runs
+            .iter()
+            .filter(|(_, run)| {
+                now.duration_since(run.last_at) >= REPEAT_WINDOW
+                    || now.duration_since(run.started) >= REPEAT_MAX_RUN
+            })
+            .map(|(k, _)| k.clone())
+            .collect();
+        finished
+            .into_iter()
+            .filter_map(|k| runs.remove(&k).map(|run| (k, run)))
+            .collect()
+    };
+    for ((app_name, _), run) in runs {
+        write_repeat_run(&app_name, run);
+    }
+}
+
+/// Write out every open repeat run (before generation reads the capture files, and
+/// before capture queries)
+pub fn flush_repeats() {
+    let runs: Vec<((String, String), RepeatRun)> = REPEATS.lock().unwrap().drain().collect();
+    for ((app_name, _), run) in runs {
+        write_repeat_run(&app_name, run);
+    }
+}
+
+/// The last held-back repeat, standing for all `count` of them
+fn write_repeat_run(app_name: &str, mut run: RepeatRun) {
+    if run.count == 0 {
+        return;
+    }
+    if let Some(obj) = run.last.as_object_mut() {
+        obj.insert(
+            "repeat_count".to_string(),
+            serde_json::Value::from(run.count),
+        );
+        obj.insert(
+            "repeat_since".to_string(),
+            serde_json::Value::String(run.since),
+        );
+    }
+    write_capture(app_name, &run.last, &run.session_ts);
+}
 
-    let line = match serde_json::to_string(data) {
+fn write_capture(app_name: &str, data: &serde_json::Value, session_ts: &str) {
+    let blobbed = crate::blobs::save(app_name, data);
+    let data = blobbed.as_ref().unwrap_or(data);
+    let mut line = match serde_json::to_string(data) {
         Ok(l) => l,
         Err(_) => return,
     };
 
-    if let Ok(mut file) = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&file_path)
+    // Link requests to the credential set they were sent with (spliced in to avoid
+    // cloning large bodies)
+    if data.get("requestHeaders").is_some()
+        && data.get("session_id").is_none()
+        && line.ends_with('}')
     {
-        let _ = writeln!(file, "{line}");
+        if let Some(id) = current_session_id(app_name) {
+            line.pop();
+            line.push_str(&format!(
+                ",\"session_id\":{}}}",
+                serde_json::Value::String(id)
+            ));
+        }
     }
+
+    if config::config().capture_line_crc {
+        crate::capture_io::seal(&mut line);
+    }
+    crate::capture_writer::write(app_name, session_ts, line, data.clone());
 }
 
-fn update_session(
-    app: &tauri::AppHandle,
+/// Id of an app's current session (credential set), cached per app
+fn current_session_id(app_name: &str) -> Option<String> {
+    let mut ids = SESSION_IDS.lock().unwrap();
+    if let Some(id) = ids.get(app_name) {
+        return Some(id.clone());
+    }
+    let session_path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("sessions")
+        .join("latest.json");
+    let id = fs::read_to_string(session_path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<config::SessionData>(&s).ok())
+        .map(|s| s.session_id)
+        .filter(|id| !id.is_empty())?;
+    ids.insert(app_name.to_string(), id.clone());
+    Some(id)
+}
+
+/// Stable short hash of a session's credentials: auth headers, CSRF tokens and
+/// auth-looking cookies. FNV-1a, so ids stay the same across runs. None when there
+/// are no credentials — analytics and preference cookies change too often to tell
+/// one login from another.
+fn session_id(session: &config::SessionData) -> Option<String> {
+    let auth_cookies = session.cookies.iter().filter(|(name, _)| {
+        let lower = name.to_lowercase();
+        endpoints::AUTH_COOKIE_PATTERNS
+            .iter()
+            .any(|p| lower.contains(p))
+    });
+    let mut parts: Vec<String> = auth_cookies
+        .chain(session.auth_headers.iter())
+        .chain(session.csrf_tokens.iter())
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect();
+    if parts.is_empty() {
+        return None;
+    }
+    parts.sort();
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in parts.join("\n").bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Some(format!("{hash:016x}"))
+}
+
+fn update_session(app: &tauri::AppHandle, app_name: &str, domain: &str, data: &serde_json::Value) {
+    let state = app.state::<AppState>();
+    let cookie_names = record_session(app_name, domain, data, &state.curl_ua);
+    // Track cookie names for auth-based capture filtering
+    state
+        .session_cookie_names
+        .lock()
+        .unwrap()
+        .extend(cookie_names);
+}
+
+/// Serializes read-modify-write of sessions/latest.json
+static SESSION_FILE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Merge a capture's cookies, auth headers and CSRF tokens into the app's
+/// sessions/latest.json (snapshotting new credential sets). Returns the cookie names seen.
+pub(crate) fn record_session(
     app_name: &str,
     domain: &str,
     data: &serde_json::Value,
-) {
+    curl_ua: &str,
+) -> Vec<String> {
+    let mut cookie_names = Vec::new();
     let req_headers = match data.get("requestHeaders").and_then(|v| v.as_object()) {
         Some(h) => h,
-        None => return,
+        None => return cookie_names,
     };
 
     let has_auth = req_headers.keys().any(|k| {
@@ -748,11 +2453,10 @@ fn update_session(
     });
 
     if !has_auth {
-        return;
+        return cookie_names;
     }
 
-    let state = app.state::<AppState>();
-    let _lock = state.session_file_lock.lock().unwrap();
+    let _lock = SESSION_FILE_LOCK.lock().unwrap();
 
     let session_path = config::data_dir()
         .join("apps")
@@ -764,10 +2468,16 @@ fn update_session(
         .ok()
         .and_then(|s| serde_json::from_str(&s).ok())
         .unwrap_or_default();
+    // The credentials as they were, so an unchanged session isn't announced again
+    let before = (
+        session.cookies.clone(),
+        session.auth_headers.clone(),
+        session.csrf_tokens.clone(),
+    );
 
     session.domain = domain.to_string();
     session.captured_at = chrono::Utc::now().to_rfc3339();
-    session.user_agent = state.curl_ua.clone();
+    session.user_agent = curl_ua.to_string();
 
     for (k, v) in req_headers {
         let lower = k.to_lowercase();
@@ -779,8 +2489,7 @@ fn update_session(
                         let name = trimmed[..eq].trim().to_string();
                         let value = trimmed[eq + 1..].trim().to_string();
                         session.cookies.insert(name.clone(), value);
-                        // Track cookie name for auth-based capture filtering
-                        state.session_cookie_names.lock().unwrap().insert(name);
+                        cookie_names.push(name);
                     }
                 }
             }
@@ -807,9 +2516,118 @@ fn update_session(
         }
     }
 
+    // New credential set (login, token rotation): snapshot it as sessions/{id}.json so
+    // captures tagged with that id can be matched to the credentials they used
+    let previous_id = std::mem::take(&mut session.session_id);
+    session.session_id = session_id(&session).unwrap_or_default();
+    if !session.session_id.is_empty() && session.session_id != previous_id {
+        let sessions_dir = session_path.parent().unwrap().to_path_buf();
+        if let Ok(json) = serde_json::to_string_pretty(&session) {
+            let _ = fs::write(
+                sessions_dir.join(format!("{}.json", session.session_id)),
+                json,
+            );
+        }
+        if !previous_id.is_empty() {
+            events::emit(
+                "session-rotated",
+                serde_json::json!({"app": app_name, "from": previous_id, "to": session.session_id}),
+            );
+        }
+    }
+    if session.session_id.is_empty() {
+        SESSION_IDS.lock().unwrap().remove(app_name);
+    } else {
+        SESSION_IDS
+            .lock()
+            .unwrap()
+            .insert(app_name.to_string(), session.session_id.clone());
+    }
+
+    let changed = before.0 != session.cookies
+        || before.1 != session.auth_headers
+        || before.2 != session.csrf_tokens;
     if let Ok(json) = serde_json::to_string_pretty(&session) {
-        let _ = fs::write(&session_path, json);
+        if fs::write(&session_path, json).is_ok() && changed {
+            events::emit(
+                "session-updated",
+                serde_json::json!({
+                    "app": app_name,
+                    "domain": domain,
+                    "cookies": session.cookies.len(),
+                    "auth_headers": session.auth_headers.len(),
+                }),
+            );
+        }
+    }
+    cookie_names
+}
+
+/// Entries that always pass through, no auth check needed: ui-action, navigation,
+/// cookies, annotation, ws handshakes, first and subscribed ws frames
+fn is_meta(entry_type: &str) -> bool {
+    matches!(
+        entry_type,
+        "ui-action" | "navigation" | "cookies" | "annotation" | "ws-open" | "ws-first-out"
+    ) || entry_type.starts_with("ws-sub-")
+}
+
+/// Headless counterpart of `save_capture`, for `engine::ingest`: the same filters,
+/// but the app is `app_name` or whichever app owns the domain on disk — with no
+/// window to ask, unknown domains are dropped rather than held. Returns the app
+/// the capture was saved to, or the droplog reason it wasn't.
+pub(crate) fn ingest(
+    data: &serde_json::Value,
+    app_name: Option<&str>,
+    session_ts: &str,
+) -> Result<String, &'static str> {
+    let entry_type = data.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let drop = |reason: &'static str| {
+        crate::droplog::record(data, reason);
+        Err(reason)
+    };
+    if entry_type == "xhr-start" {
+        return drop("xhr-start");
+    }
+    let Some(url_str) = data.get("url").and_then(|v| v.as_str()) else {
+        return drop("no_url");
+    };
+    let Some(domain) = url::Url::parse(url_str)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+    else {
+        return drop("bad_url");
+    };
+    let Some(name) = app_name
+        .map(|n| n.to_string())
+        .or_else(|| config::find_app_for_domain(&domain))
+    else {
+        return drop("unknown_domain");
+    };
+    if config::is_archived(&name) {
+        return drop("archived");
+    }
+
+    let is_meta = is_meta(entry_type);
+    if !is_meta {
+        if (entry_type == "beacon" || should_skip_capture(url_str))
+            && is_noise(entry_type, url_str, &config::capture_noise(&name))
+        {
+            return drop(noise_reason(url_str).unwrap_or("beacon"));
+        }
+        if !has_auth(data, &std::collections::HashSet::new()) {
+            return drop("no_auth");
+        }
+    }
+
+    config::ensure_app_dirs(&name);
+    // An imported recording's credentials are older than the live session's
+    if data.get("imported_from").is_none() {
+        record_session(&name, &domain, data, &config::get_curl_ua());
     }
+    append_capture(&name, data, session_ts);
+    emit_capture_events(&name, data, is_meta);
+    Ok(name)
 }
 
 /// Public wrapper so lib.rs can call append_capture for annotations.
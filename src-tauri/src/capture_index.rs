@@ -0,0 +1,290 @@
+use crate::capture_io;
+use crate::endpoints;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// Where one capture line lives, one line per capture in `apps/<name>/capture-index.jsonl`,
+/// so queries can seek straight to matching captures instead of scanning every file.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Location {
+    /// File name in captures/, e.g. "2026-02-21T14-30.jsonl"
+    pub file: String,
+    /// Byte offset and length of the line (without its newline)
+    pub offset: u64,
+    pub len: u64,
+    /// "METHOD /normalized/path"; empty for entries without a URL (ui-actions, annotations)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u64>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub timestamp: String,
+    #[serde(rename = "type", default, skip_serializing_if = "String::is_empty")]
+    pub entry_type: String,
+}
+
+/// Serializes capture appends with index updates, and index rewrites
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// An app's index as this process last read or wrote it
+struct Cached {
+    /// Length and mtime of the index file then: anything else changing it means a re-read
+    stamp: Option<(u64, SystemTime)>,
+    locs: Arc<Vec<Location>>,
+    /// (file, offset) -> position in `locs`
+    positions: HashMap<(String, u64), usize>,
+}
+
+impl Cached {
+    fn new(stamp: Option<(u64, SystemTime)>, locs: Vec<Location>) -> Self {
+        let positions = locs
+            .iter()
+            .enumerate()
+            .map(|(i, loc)| ((loc.file.clone(), loc.offset), i))
+            .collect();
+        Cached {
+            stamp,
+            locs: Arc::new(locs),
+            positions,
+        }
+    }
+}
+
+/// Indexes kept in memory by app dir, so queries don't re-read capture-index.jsonl.
+/// Taken after INDEX_LOCK when both are held.
+static CACHE: LazyLock<Mutex<HashMap<PathBuf, Cached>>> = LazyLock::new(Default::default);
+
+const INDEX_FILE: &str = "capture-index.jsonl";
+
+/// "METHOD /normalized/path" of a capture entry, if it has a parseable URL
+pub fn entry_pattern(data: &serde_json::Value) -> Option<String> {
+    let url_str = data.get("url").and_then(|v| v.as_str())?;
+    let parsed = url::Url::parse(url_str).ok()?;
+    let method = data.get("method").and_then(|v| v.as_str()).unwrap_or("GET");
+    Some(format!(
+        "{} {}",
+        method,
+        endpoints::normalize_path(parsed.path())
+    ))
+}
+
+fn location(file: &str, offset: u64, line: &str, data: &serde_json::Value) -> Location {
+    let field = |k: &str| {
+        data.get(k)
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    Location {
+        file: file.to_string(),
+        offset,
+        len: line.len() as u64,
+        pattern: entry_pattern(data).unwrap_or_default(),
+        status: data.get("status").and_then(|v| v.as_u64()),
+        timestamp: field("timestamp"),
+        entry_type: field("type"),
+    }
+}
+
+/// Append capture lines to `captures/{file_name}` in one go and, if the app has an
+/// index, record where they landed.
+pub fn append_batch(
+    app_dir: &Path,
+    file_name: &str,
+    lines: &[(&str, &serde_json::Value)],
+) -> std::io::Result<()> {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let index_path = app_dir.join(INDEX_FILE);
+    let before = stamp(&index_path);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(app_dir.join("captures").join(file_name))?;
+    let mut offset = file.seek(SeekFrom::End(0))?;
+    let mut buf = String::new();
+    let mut locs = Vec::with_capacity(lines.len());
+    for (line, data) in lines {
+        locs.push(location(file_name, offset, line, data));
+        buf.push_str(line);
+        buf.push('\n');
+        offset += line.len() as u64 + 1;
+    }
+    file.write_all(buf.as_bytes())?;
+
+    // No index yet: the first query builds one from scratch, these lines included
+    if index_path.exists() {
+        let mut entries = String::new();
+        for loc in &locs {
+            if let Ok(json) = serde_json::to_string(loc) {
+                entries.push_str(&json);
+                entries.push('\n');
+            }
+        }
+        let mut index = fs::OpenOptions::new().append(true).open(&index_path)?;
+        index.write_all(entries.as_bytes())?;
+
+        // Keep the cached copy up to date, unless the file changed under it too
+        let mut cache = CACHE.lock().unwrap();
+        if let Some(cached) = cache.get_mut(app_dir) {
+            if cached.stamp.is_some() && cached.stamp == before {
+                let all = Arc::make_mut(&mut cached.locs);
+                for loc in locs {
+                    cached
+                        .positions
+                        .insert((loc.file.clone(), loc.offset), all.len());
+                    all.push(loc);
+                }
+                cached.stamp = stamp(&index_path);
+            } else {
+                cache.remove(app_dir);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Length and mtime of a file, None if it's not there
+fn stamp(path: &Path) -> Option<(u64, SystemTime)> {
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+/// Every indexed capture of an app, oldest session first, from memory; builds the index
+/// if missing and (re-)reads it when this process hasn't seen its latest contents
+pub fn snapshot(app_dir: &Path) -> Arc<Vec<Location>> {
+    with_cached(app_dir, |cached| cached.locs.clone())
+}
+
+/// The index entry of the capture at `offset` in `file`, if it's still there
+pub fn find(app_dir: &Path, file: &str, offset: u64) -> Option<Location> {
+    with_cached(app_dir, |cached| {
+        let i = *cached.positions.get(&(file.to_string(), offset))?;
+        Some(cached.locs[i].clone())
+    })
+}
+
+fn with_cached<T>(app_dir: &Path, f: impl FnOnce(&Cached) -> T) -> T {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let index_path = app_dir.join(INDEX_FILE);
+    if !index_path.exists() {
+        rebuild(app_dir);
+    }
+    let current = stamp(&index_path);
+    let mut cache = CACHE.lock().unwrap();
+    let fresh = cache
+        .get(app_dir)
+        .is_some_and(|cached| cached.stamp.is_some() && cached.stamp == current);
+    if !fresh {
+        let locs = capture_io::lines(&index_path)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .collect();
+        cache.insert(app_dir.to_path_buf(), Cached::new(current, locs));
+    }
+    f(&cache[app_dir])
+}
+
+/// Every indexed capture of an app, oldest session first; builds the index if missing
+pub fn locations(app_dir: &Path) -> impl Iterator<Item = Location> {
+    let locs = snapshot(app_dir);
+    (0..locs.len()).map(move |i| locs[i].clone())
+}
+
+/// Read the capture at `loc` (None if the file has since been rewritten or archived, or
+/// the line fails its CRC)
+pub fn read(app_dir: &Path, loc: &Location) -> Option<serde_json::Value> {
+    let mut file = fs::File::open(app_dir.join("captures").join(&loc.file)).ok()?;
+    file.seek(SeekFrom::Start(loc.offset)).ok()?;
+    let mut buf = vec![0u8; loc.len as usize];
+    file.read_exact(&mut buf).ok()?;
+    let line = std::str::from_utf8(&buf).ok()?;
+    if !capture_io::crc_matches(line) {
+        return None;
+    }
+    serde_json::from_str(line).ok()
+}
+
+/// Locations of every line in one capture file, but those failing their CRC
+fn scan_file(path: &Path) -> Vec<Location> {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    capture_io::lines_with_offsets(path)
+        .filter(|(_, line)| capture_io::crc_matches(line))
+        .filter_map(|(offset, line)| {
+            let data = serde_json::from_str::<serde_json::Value>(&line).ok()?;
+            Some(location(&name, offset, &line, &data))
+        })
+        .collect()
+}
+
+/// Rewrite the whole index from the capture files. Caller holds INDEX_LOCK.
+fn rebuild(app_dir: &Path) {
+    write_index(
+        app_dir,
+        Vec::new(),
+        capture_io::capture_files(app_dir)
+            .iter()
+            .flat_map(|p| scan_file(p)),
+    );
+}
+
+/// Re-index one capture file after it was rewritten in place (e.g. trimmed)
+pub fn reindex_file(app_dir: &Path, file_name: &str) {
+    let _lock = INDEX_LOCK.lock().unwrap();
+    let index_path = app_dir.join(INDEX_FILE);
+    if !index_path.exists() {
+        return;
+    }
+    // An index that can't be read through is rebuilt whole rather than cut short
+    let Ok(lines) = capture_io::try_lines(&index_path).collect::<std::io::Result<Vec<_>>>() else {
+        rebuild(app_dir);
+        return;
+    };
+    let others: Vec<Location> = lines
+        .iter()
+        .filter_map(|line| serde_json::from_str::<Location>(line).ok())
+        .filter(|loc| loc.file != file_name)
+        .collect();
+    write_index(
+        app_dir,
+        others,
+        scan_file(&app_dir.join("captures").join(file_name)).into_iter(),
+    );
+}
+
+fn write_index(app_dir: &Path, keep: Vec<Location>, fresh: impl Iterator<Item = Location>) {
+    let index_path = app_dir.join(INDEX_FILE);
+    if !app_dir.join("captures").is_dir() {
+        return;
+    }
+    let Ok(mut out) = capture_io::Rewriter::new(&index_path) else {
+        return;
+    };
+    let mut locs: Vec<Location> = keep.into_iter().chain(fresh).collect();
+    // Keep file order (oldest session first), line order within a file
+    locs.sort_by(|a, b| a.file.cmp(&b.file).then(a.offset.cmp(&b.offset)));
+    for loc in &locs {
+        let Ok(json) = serde_json::to_string(loc) else {
+            continue;
+        };
+        if out.write_line(&json).is_err() {
+            return;
+        }
+    }
+    let mut cache = CACHE.lock().unwrap();
+    match out.commit() {
+        Ok(_) => {
+            cache.insert(app_dir.to_path_buf(), Cached::new(stamp(&index_path), locs));
+        }
+        Err(_) => {
+            cache.remove(app_dir);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// How a sealed line ends: `,"_crc":"1a2b3c4d"}`
+const CRC_FIELD: &str = ",\"_crc\":\"";
+const CRC_SUFFIX_LEN: usize = CRC_FIELD.len() + 8 + 2;
+
+/// Most trailing lines `repair_tail` cuts from one file
+const MAX_TAIL_REPAIRS: usize = 16;
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32 (IEEE, as in zip and gzip)
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut c = !0u32;
+    for &b in bytes {
+        c = CRC_TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+/// Add a CRC-32 of a JSON object line as its last field, `"_crc":"1a2b3c4d"`, so a
+/// line damaged after it was written can be told from a good one
+pub fn seal(line: &mut String) {
+    if line.len() <= 2 || !line.ends_with('}') {
+        return;
+    }
+    let crc = crc32(line.as_bytes());
+    line.pop();
+    line.push_str(&format!("{CRC_FIELD}{crc:08x}\"}}"));
+}
+
+/// False for a sealed line whose CRC doesn't match; lines without one pass
+pub fn crc_matches(line: &str) -> bool {
+    let Some(split) = line.len().checked_sub(CRC_SUFFIX_LEN) else {
+        return true;
+    };
+    let (Some(body), Some(suffix)) = (line.get(..split), line.get(split..)) else {
+        return true;
+    };
+    let Some(hex) = suffix
+        .strip_prefix(CRC_FIELD)
+        .and_then(|s| s.strip_suffix("\"}"))
+    else {
+        return true;
+    };
+    let Ok(expected) = u32::from_str_radix(hex, 16) else {
+        return true;
+    };
+    crc32(format!("{body}}}").as_bytes()) == expected
+}
+
+/// A JSONL line that's whole: valid JSON, matching its CRC if it has one
+pub fn intact(line: &str) -> bool {
+    crc_matches(line) && serde_json::from_str::<serde_json::Value>(line).is_ok()
+}
+
+/// Cut a JSONL file back to its last whole line. A crash mid-append leaves the last
+/// line cut short (or not ended by a newline); what's cut is kept in `{file}.damaged`.
+/// Returns the number of bytes cut.
+pub fn repair_tail(path: &Path) -> std::io::Result<u64> {
+    let mut file = fs::OpenOptions::new().read(true).write(true).open(path)?;
+    let mut cut = 0;
+    for _ in 0..MAX_TAIL_REPAIRS {
+        let len = file.metadata()?.len();
+        if len == 0 {
+            break;
+        }
+        // Find where the last line starts, reading further back for long lines
+        let mut window: u64 = 64 * 1024;
+        let (start, tail) = loop {
+            let from = len.saturating_sub(window);
+            file.seek(SeekFrom::Start(from))?;
+            let mut buf = Vec::new();
+            file.read_to_end(&mut buf)?;
+            let body = buf.strip_suffix(b"\n").unwrap_or(&buf);
+            match body.iter().rposition(|&b| b == b'\n') {
+                Some(i) => break (from + i as u64 + 1, buf[i + 1..].to_vec()),
+                None if from == 0 => break (0, buf),
+                None => window *= 4,
+            }
+        };
+        let whole = tail
+            .strip_suffix(b"\n")
+            .and_then(|line| std::str::from_utf8(line).ok())
+            .is_some_and(intact);
+        if whole {
+            break;
+        }
+        let mut damaged = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.with_extension("jsonl.damaged"))?;
+        damaged.write_all(&tail)?;
+        if !tail.ends_with(b"\n") {
+            damaged.write_all(b"\n")?;
+        }
+        file.set_len(start)?;
+        cut += tail.len() as u64;
+    }
+    if cut > 0 {
+        file.sync_all()?;
+    }
+    Ok(cut)
+}
+
+/// An app's `captures/*.jsonl`, sorted by name (i.e. oldest session first)
+pub fn capture_files(app_dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(app_dir.join("captures"))
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("jsonl"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// A JSONL file's lines, read through a buffer rather than all at once.
+/// A missing file has no lines; a read error (or a line that isn't UTF-8) ends the
+/// iteration — anything writing the lines back out wants `try_lines` instead.
+pub fn lines(path: &Path) -> impl Iterator<Item = String> {
+    try_lines(path).map_while(Result::ok)
+}
+
+/// `lines`, with a read error (or a line that isn't UTF-8) handed to the caller, after
+/// which there are no more lines. A missing file has none.
+pub fn try_lines(path: &Path) -> impl Iterator<Item = std::io::Result<String>> {
+    fs::File::open(path)
+        .ok()
+        .map(|f| BufReader::new(f).lines())
+        .into_iter()
+        .flatten()
+}
+
+/// A JSONL file's lines with the byte offset each starts at
+pub fn lines_with_offsets(path: &Path) -> impl Iterator<Item = (u64, String)> {
+    try_lines_with_offsets(path).map_while(Result::ok)
+}
+
+/// `lines_with_offsets`, with read errors as in `try_lines`
+pub fn try_lines_with_offsets(path: &Path) -> impl Iterator<Item = std::io::Result<(u64, String)>> {
+    try_lines(path).scan(0u64, |offset, line| {
+        Some(line.map(|line| {
+            let at = *offset;
+            *offset += line.len() as u64 + 1;
+            (at, line)
+        }))
+    })
+}
+
+/// A JSONL file's entries, skipping lines that aren't valid JSON or fail their CRC
+pub fn entries(path: &Path) -> impl Iterator<Item = serde_json::Value> {
+    lines(path)
+        .filter(|line| crc_matches(line))
+        .filter_map(|line| serde_json::from_str(&line).ok())
+}
+
+/// Rewrites a JSONL file line by line through `{file}.tmp`, replacing the original
+/// only on `commit` — dropped without committing, the original is left untouched.
+pub struct Rewriter {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    out: Option<BufWriter<fs::File>>,
+}
+
+impl Rewriter {
+    pub fn new(path: &Path) -> std::io::Result<Self> {
+        let tmp_path = path.with_extension("jsonl.tmp");
+        let out = BufWriter::new(fs::File::create(&tmp_path)?);
+        Ok(Rewriter {
+            path: path.to_path_buf(),
+            tmp_path,
+            out: Some(out),
+        })
+    }
+
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        match self.out.as_mut() {
+            Some(out) => writeln!(out, "{line}"),
+            None => Ok(()),
+        }
+    }
+
+    /// Flush and move the rewritten file into place
+    pub fn commit(mut self) -> std::io::Result<()> {
+        if let Some(out) = self.out.take() {
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        }
+        fs::rename(&self.tmp_path, &self.path)
+    }
+}
+
+impl Drop for Rewriter {
+    fn drop(&mut self) {
+        // Not committed (or the rename failed): throw the partial copy away
+        let _ = fs::remove_file(&self.tmp_path);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::capture_index;
+use crate::config;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::mpsc;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Captures an app's writer can fall behind by before `write` waits for it
+const QUEUE_DEPTH: usize = 1024;
+
+/// Most captures written in one go
+const MAX_BATCH: usize = 256;
+
+/// Written capture files are fsynced at most this often, and this long after the last write
+const SYNC_EVERY: Duration = Duration::from_secs(2);
+
+enum Job {
+    Capture {
+        session_ts: String,
+        line: String,
+        data: serde_json::Value,
+    },
+    /// Answered once everything queued before it is written and synced
+    Flush(mpsc::Sender<()>),
+}
+
+/// Each app's writer thread, started on its first capture
+static WRITERS: LazyLock<Mutex<HashMap<String, mpsc::SyncSender<Job>>>> =
+    LazyLock::new(Default::default);
+
+/// Queue a capture line for `app_name`'s writer thread, which appends it to
+/// `captures/{session_ts}.jsonl` and the capture index along with whatever else is
+/// queued, so the capture path (the IPC thread, for the browser) does no file IO.
+/// Waits only if the writer is `QUEUE_DEPTH` captures behind.
+pub fn write(app_name: &str, session_ts: &str, line: String, data: serde_json::Value) {
+    let job = Job::Capture {
+        session_ts: session_ts.to_string(),
+        line,
+        data,
+    };
+    // Cloned out so a full queue doesn't hold up other apps' captures
+    let tx = WRITERS
+        .lock()
+        .unwrap()
+        .entry(app_name.to_string())
+        .or_insert_with(|| spawn_writer(app_name))
+        .clone();
+    if let Err(mpsc::SendError(Job::Capture {
+        session_ts,
+        line,
+        data,
+    })) = tx.send(job)
+    {
+        // The writer died: write this one here, and start a new one next time
+        WRITERS.lock().unwrap().remove(app_name);
+        let app_dir = config::data_dir().join("apps").join(app_name);
+        write_batch(
+            app_name,
+            &app_dir,
+            vec![(session_ts, line, data)],
+            &mut HashSet::new(),
+        );
+    }
+}
+
+/// Wait until every capture queued so far is on disk (before generation reads the
+/// capture files, and before exiting)
+pub fn flush() {
+    let writers: Vec<mpsc::SyncSender<Job>> = WRITERS.lock().unwrap().values().cloned().collect();
+    let acks: Vec<mpsc::Receiver<()>> = writers
+        .iter()
+        .filter_map(|tx| {
+            let (ack_tx, ack_rx) = mpsc::channel();
+            tx.send(Job::Flush(ack_tx)).ok().map(|_| ack_rx)
+        })
+        .collect();
+    for ack in acks {
+        let _ = ack.recv();
+    }
+}
+
+fn spawn_writer(app_name: &str) -> mpsc::SyncSender<Job> {
+    let (tx, rx) = mpsc::sync_channel(QUEUE_DEPTH);
+    let app_name = app_name.to_string();
+    std::thread::spawn(move || run(&app_name, rx));
+    tx
+}
+
+fn run(app_name: &str, rx: mpsc::Receiver<Job>) {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    // Capture files written to since the last fsync
+    let mut unsynced: HashSet<String> = HashSet::new();
+    let mut last_sync = Instant::now();
+    loop {
+        let first = if unsynced.is_empty() {
+            match rx.recv() {
+                Ok(job) => job,
+                Err(_) => return,
+            }
+        } else {
+            match rx.recv_timeout(SYNC_EVERY.saturating_sub(last_sync.elapsed())) {
+                Ok(job) => job,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    sync(&app_dir, &mut unsynced);
+                    last_sync = Instant::now();
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    sync(&app_dir, &mut unsynced);
+                    return;
+                }
+            }
+        };
+
+        let mut captures = Vec::new();
+        let mut flushes = Vec::new();
+        let mut next = Some(first);
+        while let Some(job) = next {
+            match job {
+                Job::Capture {
+                    session_ts,
+                    line,
+                    data,
+                } => captures.push((session_ts, line, data)),
+                Job::Flush(ack) => flushes.push(ack),
+            }
+            if captures.len() >= MAX_BATCH {
+                break;
+            }
+            next = rx.try_recv().ok();
+        }
+
+        write_batch(app_name, &app_dir, captures, &mut unsynced);
+        if !flushes.is_empty() || last_sync.elapsed() >= SYNC_EVERY {
+            sync(&app_dir, &mut unsynced);
+            last_sync = Instant::now();
+        }
+        for ack in flushes {
+            let _ = ack.send(());
+        }
+    }
+}
+
+/// Append captures to their session files (one write per file), then do what
+/// follows a saved capture: stats, the background generator, capture plugins
+fn write_batch(
+    app_name: &str,
+    app_dir: &std::path::Path,
+    captures: Vec<(String, String, serde_json::Value)>,
+    unsynced: &mut HashSet<String>,
+) {
+    // Nearly always one session; kept in order within each
+    let mut sessions: Vec<&str> = Vec::new();
+    for (session_ts, _, _) in &captures {
+        if !sessions.contains(&session_ts.as_str()) {
+            sessions.push(session_ts);
+        }
+    }
+    for session_ts in sessions {
+        let lines: Vec<(&str, &serde_json::Value)> = captures
+            .iter()
+            .filter(|(ts, _, _)| ts == session_ts)
+            .map(|(_, line, data)| (line.as_str(), data))
+            .collect();
+        let file_name = format!("{session_ts}.jsonl");
+        if capture_index::append_batch(app_dir, &file_name, &lines).is_err() {
+            continue;
+        }
+        unsynced.insert(file_name);
+        for (line, data) in lines {
+            crate::stats::record_capture(app_name, line.len() + 1, data);
+            crate::plugins::capture(app_name, data);
+        }
+        crate::generation::mark_dirty(app_name, session_ts);
+    }
+}
+
+fn sync(app_dir: &std::path::Path, unsynced: &mut HashSet<String>) {
+    for file_name in unsynced.drain() {
+        let path = app_dir.join("captures").join(file_name);
+        if let Ok(file) = fs::OpenOptions::new().append(true).open(path) {
+            let _ = file.sync_data();
+        }
+    }
+}
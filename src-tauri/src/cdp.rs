@@ -0,0 +1,672 @@
+use crate::errors::CommandError;
+use std::collections::{HashMap, HashSet};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::Message;
+
+/// Window label commands use to drive the attached Chrome tab (the default while attached)
+pub const WINDOW: &str = "chrome";
+
+/// How long a socket read waits before queued calls get their turn
+const POLL: Duration = Duration::from_millis(100);
+
+type Socket = tungstenite::WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// A DevTools method to call, and where its result goes
+struct Call {
+    method: String,
+    params: serde_json::Value,
+    reply: mpsc::Sender<Result<serde_json::Value, String>>,
+}
+
+/// Calls for the connection thread (set once attached)
+static CALLS: OnceLock<Mutex<mpsc::Sender<Call>>> = OnceLock::new();
+
+/// Whether `harharhar attach` connected to a Chrome tab
+pub fn attached() -> bool {
+    CALLS.get().is_some()
+}
+
+/// Attach to a Chrome started with `--remote-debugging-port` (`addr` like
+/// "localhost:9222"): the fetch/XHR traffic and page loads of every tab, open now or
+/// later, and the requests of their service workers are captured like the harharhar browser's, under the "chrome" window, until
+/// Chrome goes away. Commands drive the first open tab (another one once it's closed).
+pub fn start(app: tauri::AppHandle, addr: &str) -> Result<(), String> {
+    let (ws_url, first_tab) = browser_socket_url(addr)?;
+    let (socket, _) = tungstenite::connect(ws_url.as_str())
+        .map_err(|e| format!("connecting to {ws_url}: {e}"))?;
+    let (tx, rx) = mpsc::channel();
+    CALLS
+        .set(Mutex::new(tx))
+        .map_err(|_| "already attached".to_string())?;
+    std::thread::spawn(move || {
+        if let Err(e) = run(&app, socket, rx, &first_tab) {
+            eprintln!("[harharhar] chrome detached: {e}");
+            crate::events::emit("cdp-detached", serde_json::json!({"error": e}));
+        }
+    });
+    Ok(())
+}
+
+/// The browser's DevTools socket (http://{addr}/json/version), and the target id of the
+/// first ordinary page listed at /json/list
+fn browser_socket_url(addr: &str) -> Result<(String, String), String> {
+    let get = |path: &str| -> Result<serde_json::Value, String> {
+        let body = ureq::get(&format!("http://{addr}/json/{path}"))
+            .call()
+            .map_err(|e| {
+                format!(
+                    "no Chrome DevTools at {addr} (start Chrome with --remote-debugging-port): {e}"
+                )
+            })?
+            .into_string()
+            .map_err(|e| e.to_string())?;
+        serde_json::from_str(&body).map_err(|e| e.to_string())
+    };
+    let ws_url = get("version")?
+        .get("webSocketDebuggerUrl")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| format!("no browser DevTools socket at {addr}"))?;
+    let list = get("list")?;
+    let first_tab = list
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|t| is_tab(t))
+        .find_map(|t| t.get("id").and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+        .ok_or_else(|| format!("no open tab at {addr}"))?;
+    Ok((ws_url, first_tab))
+}
+
+/// An ordinary page (not DevTools itself, a worker or an extension), from /json/list
+/// or a Target event's targetInfo
+fn is_tab(target: &serde_json::Value) -> bool {
+    target.get("type").and_then(|v| v.as_str()) == Some("page")
+        && !target
+            .get("url")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .starts_with("devtools://")
+}
+
+/// A service worker's requests (sync, prefetch, push handlers, its own fetches) never
+/// pass through a tab, so they're captured from the worker itself
+fn is_service_worker(target: &serde_json::Value) -> bool {
+    target.get("type").and_then(|v| v.as_str()) == Some("service_worker")
+}
+
+/// Call a DevTools method on the attached tab and wait for its result
+pub fn call(
+    method: &str,
+    params: serde_json::Value,
+    timeout: Duration,
+) -> Result<serde_json::Value, CommandError> {
+    let (reply, rx) = mpsc::channel();
+    let call = Call {
+        method: method.to_string(),
+        params,
+        reply,
+    };
+    CALLS
+        .get()
+        .ok_or_else(|| CommandError::BrowserNotOpen(WINDOW.to_string()))?
+        .lock()
+        .unwrap()
+        .send(call)
+        .map_err(|_| CommandError::BrowserNotOpen(WINDOW.to_string()))?;
+    rx.recv_timeout(timeout)
+        .map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => CommandError::EvalTimeout,
+            mpsc::RecvTimeoutError::Disconnected => {
+                CommandError::BrowserNotOpen(WINDOW.to_string())
+            }
+        })?
+        .map_err(CommandError::Failed)
+}
+
+/// `eval_js_with_timeout` for the attached tab: same result strings (JSON for
+/// non-strings, "error: ..." for exceptions), promises awaited
+pub fn evaluate(js: &str, timeout: Duration) -> Result<String, CommandError> {
+    let params = serde_json::json!({"expression": js, "awaitPromise": true, "returnByValue": true});
+    let result = call("Runtime.evaluate", params, timeout)?;
+    if let Some(details) = result.get("exceptionDetails") {
+        let message = details
+            .pointer("/exception/description")
+            .or_else(|| details.get("text"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("exception");
+        return Ok(format!("error: {message}"));
+    }
+    Ok(match result.pointer("/result/value") {
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+        None => "null".to_string(),
+    })
+}
+
+pub fn navigate(url: &url::Url) -> Result<(), CommandError> {
+    let result = call(
+        "Page.navigate",
+        serde_json::json!({"url": url.as_str()}),
+        Duration::from_secs(30),
+    )?;
+    match result.get("errorText").and_then(|v| v.as_str()) {
+        Some(e) => Err(CommandError::NavigationFailed(e.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// What a DevTools message id was for
+enum Reply {
+    Caller(mpsc::Sender<Result<serde_json::Value, String>>),
+    /// Network.getResponseBody for a finished request: (tab session, request id)
+    Body(String, String),
+}
+
+/// The connection thread: attaches to tabs as they open, forwards calls to the command
+/// tab, matches replies, turns each tab's Network events into captures
+fn run(
+    app: &tauri::AppHandle,
+    mut socket: Socket,
+    calls: mpsc::Receiver<Call>,
+    first_tab: &str,
+) -> Result<(), String> {
+    if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+        let _ = stream.set_read_timeout(Some(POLL));
+    }
+    // Read once, as a harharhar window gets them once when it opens
+    let settings = crate::config::config().capture.clone();
+    let mut next_id = 0u64;
+    let mut replies: HashMap<u64, Reply> = HashMap::new();
+    // DevTools session of each attached tab -> its requests in flight
+    let mut tabs: HashMap<String, Network> = HashMap::new();
+    // Targets attached to (or being attached to), so discovery doesn't attach twice
+    let mut targets: HashSet<String> = HashSet::new();
+    // The tab commands go to: the first one, then any still open after it closes
+    let mut command_tab: Option<String> = None;
+
+    // The first tab's attach goes out first, so it's the first to answer
+    let attach = serde_json::json!({"targetId": first_tab, "flatten": true});
+    send(
+        &mut socket,
+        &mut next_id,
+        None,
+        "Target.attachToTarget",
+        attach,
+    )?;
+    targets.insert(first_tab.to_string());
+    let discover = serde_json::json!({"discover": true});
+    send(
+        &mut socket,
+        &mut next_id,
+        None,
+        "Target.setDiscoverTargets",
+        discover,
+    )?;
+
+    loop {
+        while let Ok(call) = calls.try_recv() {
+            match &command_tab {
+                Some(session) => {
+                    let id = send(
+                        &mut socket,
+                        &mut next_id,
+                        Some(session),
+                        &call.method,
+                        call.params,
+                    )?;
+                    replies.insert(id, Reply::Caller(call.reply));
+                }
+                None => {
+                    let _ = call.reply.send(Err("no open tab".to_string()));
+                }
+            }
+        }
+
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => return Err("connection closed".to_string()),
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        let Ok(msg) = serde_json::from_str::<serde_json::Value>(&text) else {
+            continue;
+        };
+        let session = msg
+            .get("sessionId")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+
+        if let Some(id) = msg.get("id").and_then(|v| v.as_u64()) {
+            let result = match msg.get("error") {
+                Some(err) => Err(err
+                    .get("message")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("error")
+                    .to_string()),
+                None => Ok(msg
+                    .get("result")
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null)),
+            };
+            match replies.remove(&id) {
+                Some(Reply::Caller(tx)) => {
+                    let _ = tx.send(result);
+                }
+                Some(Reply::Body(tab, request_id)) => {
+                    let finished = tabs
+                        .get_mut(&tab)
+                        .and_then(|network| network.with_body(&request_id, result.ok()));
+                    if let Some(entry) = finished {
+                        save(app, &settings, entry);
+                    }
+                }
+                None => {}
+            }
+            continue;
+        }
+
+        let method = msg.get("method").and_then(|v| v.as_str()).unwrap_or("");
+        let params = msg
+            .get("params")
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        match method {
+            // Tabs and service workers started after attaching, and (once discovery
+            // starts) those already running
+            "Target.targetCreated" => {
+                let Some(info) = params
+                    .get("targetInfo")
+                    .filter(|t| is_tab(t) || is_service_worker(t))
+                else {
+                    continue;
+                };
+                let Some(target) = info.get("targetId").and_then(|v| v.as_str()) else {
+                    continue;
+                };
+                if targets.insert(target.to_string()) {
+                    let attach = serde_json::json!({"targetId": target, "flatten": true});
+                    send(
+                        &mut socket,
+                        &mut next_id,
+                        None,
+                        "Target.attachToTarget",
+                        attach,
+                    )?;
+                }
+            }
+            "Target.attachedToTarget" => {
+                let Some(tab) = params.get("sessionId").and_then(|v| v.as_str()) else {
+                    continue;
+                };
+                let service_worker = params.get("targetInfo").is_some_and(is_service_worker);
+                let methods: &[&str] = if service_worker {
+                    &["Network.enable"]
+                } else {
+                    &["Network.enable", "Page.enable", "Runtime.enable"]
+                };
+                for method in methods {
+                    send(
+                        &mut socket,
+                        &mut next_id,
+                        Some(tab),
+                        method,
+                        serde_json::json!({}),
+                    )?;
+                }
+                tabs.insert(
+                    tab.to_string(),
+                    Network {
+                        max_body: settings.max_body_bytes as usize,
+                        service_worker,
+                        ..Default::default()
+                    },
+                );
+                if !service_worker {
+                    command_tab.get_or_insert_with(|| tab.to_string());
+                }
+            }
+            "Target.detachedFromTarget" => {
+                let tab = params
+                    .get("sessionId")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                tabs.remove(tab);
+                if let Some(target) = params.get("targetId").and_then(|v| v.as_str()) {
+                    targets.remove(target);
+                }
+                if command_tab.as_deref() == Some(tab) {
+                    command_tab = tabs
+                        .iter()
+                        .find(|(_, network)| !network.service_worker)
+                        .map(|(session, _)| session.clone());
+                }
+            }
+            _ => {
+                let Some(tab) = session else {
+                    continue;
+                };
+                let Some(network) = tabs.get_mut(&tab) else {
+                    continue;
+                };
+                match network.event(method, &params) {
+                    Some(Done::Save(entry)) => save(app, &settings, entry),
+                    Some(Done::FetchBody(request_id)) => {
+                        let id = send(
+                            &mut socket,
+                            &mut next_id,
+                            Some(&tab),
+                            "Network.getResponseBody",
+                            serde_json::json!({"requestId": request_id}),
+                        )?;
+                        replies.insert(id, Reply::Body(tab, request_id));
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+/// Send a DevTools call, to a tab's session or (`None`) the browser itself
+fn send(
+    socket: &mut Socket,
+    next_id: &mut u64,
+    session: Option<&str>,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<u64, String> {
+    *next_id += 1;
+    let mut msg = serde_json::json!({"id": *next_id, "method": method, "params": params});
+    if let Some(session) = session {
+        msg["sessionId"] = serde_json::Value::from(session);
+    }
+    socket
+        .send(Message::Text(msg.to_string()))
+        .map_err(|e| e.to_string())?;
+    Ok(*next_id)
+}
+
+/// Apply the capture settings intercept.js applies in the page — nothing from a
+/// disabled domain, no bodies where they're turned off — then take the harharhar
+/// browser's save path
+fn save(
+    app: &tauri::AppHandle,
+    settings: &crate::config::CaptureSettings,
+    mut data: serde_json::Value,
+) {
+    let host = data
+        .get("url")
+        .and_then(|v| v.as_str())
+        .and_then(|u| url::Url::parse(u).ok())
+        .and_then(|u| u.host_str().map(String::from))
+        .unwrap_or_default();
+    let host = settings.for_host(&host);
+    if !host.enabled {
+        return;
+    }
+    if !host.request_bodies {
+        data["requestBody"] = serde_json::Value::Null;
+    }
+    if !host.response_bodies {
+        data["responseBody"] = serde_json::Value::Null;
+        if let Some(obj) = data.as_object_mut() {
+            obj.remove("responseEncoding");
+            obj.remove("responseSize");
+        }
+    }
+    crate::capture::accept(app, data);
+}
+
+/// One request in flight, assembled from its Network events
+#[derive(Default)]
+struct Request {
+    entry_type: &'static str,
+    method: String,
+    url: String,
+    request_headers: serde_json::Map<String, serde_json::Value>,
+    request_body: Option<String>,
+    /// Wall clock (unix seconds) and monotonic start, for timestamp and duration
+    wall_time: f64,
+    started: f64,
+    status: u64,
+    status_text: String,
+    response_headers: serde_json::Map<String, serde_json::Value>,
+    duration: u64,
+    /// Transport: "h2"/"h3"/"http/1.1", "ip:port", and {"version", "cipher", "group"}
+    protocol: String,
+    remote_address: String,
+    tls: Option<serde_json::Value>,
+    /// Sent by a service worker rather than a tab
+    service_worker: bool,
+}
+
+/// What a Network event finished
+enum Done {
+    Save(serde_json::Value),
+    /// The request is done; its entry is saved once the body is fetched
+    FetchBody(String),
+}
+
+#[derive(Default)]
+struct Network {
+    requests: HashMap<String, Request>,
+    /// capture.max_body_bytes
+    max_body: usize,
+    /// A service worker's session rather than a tab's
+    service_worker: bool,
+}
+
+impl Network {
+    fn event(&mut self, method: &str, params: &serde_json::Value) -> Option<Done> {
+        let request_id = params
+            .get("requestId")
+            .and_then(|v| v.as_str())?
+            .to_string();
+        let num = |k: &str| params.get(k).and_then(|v| v.as_f64()).unwrap_or(0.0);
+        match method {
+            "Network.requestWillBeSent" => {
+                // intercept.js captures fetch/XHR, beacons and page loads; so do we
+                let entry_type = match params.get("type").and_then(|v| v.as_str()).unwrap_or("") {
+                    "Fetch" | "EventSource" => "fetch",
+                    "XHR" => "xhr",
+                    "Ping" => "beacon",
+                    "Document" => "navigation",
+                    _ => {
+                        self.requests.remove(&request_id);
+                        return None;
+                    }
+                };
+                let req = params.get("request")?;
+                let r = self.requests.entry(request_id).or_default();
+                r.entry_type = entry_type;
+                r.service_worker = self.service_worker;
+                r.method = req
+                    .get("method")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("GET")
+                    .to_string();
+                r.url = req
+                    .get("url")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                r.request_body = req
+                    .get("postData")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string());
+                r.wall_time = num("wallTime");
+                r.started = num("timestamp");
+                // ExtraInfo headers (with cookies) win over these
+                merge_headers(&mut r.request_headers, req.get("headers"), false);
+                None
+            }
+            "Network.requestWillBeSentExtraInfo" => {
+                let r = self.requests.entry(request_id).or_default();
+                merge_headers(&mut r.request_headers, params.get("headers"), true);
+                None
+            }
+            "Network.responseReceived" => {
+                let res = params.get("response")?;
+                let r = self.requests.get_mut(&request_id)?;
+                r.status = res.get("status").and_then(|v| v.as_u64()).unwrap_or(0);
+                r.status_text = res
+                    .get("statusText")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                merge_headers(&mut r.response_headers, res.get("headers"), false);
+                let text = |k: &str| res.get(k).and_then(|v| v.as_str()).unwrap_or("");
+                r.protocol = text("protocol").to_string();
+                if !text("remoteIPAddress").is_empty() {
+                    let port = res.get("remotePort").and_then(|v| v.as_u64()).unwrap_or(0);
+                    r.remote_address = match text("remoteIPAddress") {
+                        ip if ip.contains(':') => format!("[{ip}]:{port}"),
+                        ip => format!("{ip}:{port}"),
+                    };
+                }
+                r.tls = res.get("securityDetails").map(|sec| {
+                    serde_json::json!({
+                        "version": sec.get("protocol"),
+                        "cipher": sec.get("cipher"),
+                        "group": sec.get("keyExchangeGroup"),
+                    })
+                });
+                None
+            }
+            "Network.responseReceivedExtraInfo" => {
+                let r = self.requests.get_mut(&request_id)?;
+                merge_headers(&mut r.response_headers, params.get("headers"), true);
+                None
+            }
+            "Network.loadingFinished" => {
+                let r = self.requests.get_mut(&request_id)?;
+                if r.entry_type.is_empty() {
+                    self.requests.remove(&request_id);
+                    return None;
+                }
+                r.duration = ((num("timestamp") - r.started) * 1000.0).max(0.0) as u64;
+                if r.entry_type == "navigation" || r.entry_type == "beacon" {
+                    let r = self.requests.remove(&request_id)?;
+                    return Some(Done::Save(r.entry(None, self.max_body)));
+                }
+                Some(Done::FetchBody(request_id))
+            }
+            "Network.loadingFailed" => {
+                let mut r = self.requests.remove(&request_id)?;
+                if r.entry_type.is_empty() {
+                    return None;
+                }
+                r.status = 0;
+                r.status_text = params
+                    .get("errorText")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                r.duration = ((num("timestamp") - r.started) * 1000.0).max(0.0) as u64;
+                Some(Done::Save(r.entry(None, self.max_body)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The finished entry once Network.getResponseBody answered (None if it failed)
+    fn with_body(
+        &mut self,
+        request_id: &str,
+        body: Option<serde_json::Value>,
+    ) -> Option<serde_json::Value> {
+        let r = self.requests.remove(request_id)?;
+        Some(r.entry(body.as_ref(), self.max_body))
+    }
+}
+
+/// Copy CDP headers in, lowercased like the ones intercept.js records
+fn merge_headers(
+    into: &mut serde_json::Map<String, serde_json::Value>,
+    headers: Option<&serde_json::Value>,
+    overwrite: bool,
+) {
+    for (k, v) in headers.and_then(|h| h.as_object()).into_iter().flatten() {
+        let k = k.to_lowercase();
+        if overwrite || !into.contains_key(&k) {
+            into.insert(k, v.clone());
+        }
+    }
+}
+
+impl Request {
+    /// A capture entry in intercept.js's format, from the "chrome" window
+    fn entry(self, body: Option<&serde_json::Value>, max_body: usize) -> serde_json::Value {
+        let base64_encoded = body
+            .and_then(|b| b.get("base64Encoded"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        // Bytes Chrome couldn't hand over as text go the same "base64:" route as intercept.js's
+        let response_body = body
+            .and_then(|b| b.get("body"))
+            .and_then(|v| v.as_str())
+            .map(|text| {
+                if base64_encoded {
+                    format!(
+                        "base64:{}",
+                        crate::capture::truncate_body(text, max_body / 3 * 4)
+                    )
+                } else {
+                    crate::capture::truncate_body(text, max_body).to_string()
+                }
+            });
+        let timestamp = chrono::DateTime::from_timestamp_millis((self.wall_time * 1000.0) as i64)
+            .unwrap_or_else(chrono::Utc::now)
+            .to_rfc3339();
+        let mut entry = serde_json::json!({
+            "type": self.entry_type,
+            "method": self.method,
+            "url": self.url,
+            "requestHeaders": self.request_headers,
+            "requestBody": self.request_body.as_deref().map(|b| crate::capture::truncate_body(b, max_body)),
+            "status": self.status,
+            "statusText": self.status_text,
+            "responseHeaders": self.response_headers,
+            "responseBody": response_body,
+            "duration": self.duration,
+            "timestamp": timestamp,
+            "window": WINDOW,
+        });
+        if base64_encoded {
+            entry["responseEncoding"] = serde_json::Value::from("base64");
+            // Chrome hands over the whole body; the size is of all of it, not what's kept
+            if let Some(text) = body.and_then(|b| b.get("body")).and_then(|v| v.as_str()) {
+                let padding = text.bytes().rev().take_while(|&b| b == b'=').count();
+                entry["responseSize"] =
+                    serde_json::Value::from((text.len() / 4 * 3).saturating_sub(padding));
+            }
+        }
+        if !self.protocol.is_empty() {
+            entry["protocol"] = serde_json::Value::from(self.protocol);
+        }
+        if !self.remote_address.is_empty() {
+            entry["remoteAddress"] = serde_json::Value::from(self.remote_address);
+        }
+        if let Some(tls) = self.tls {
+            entry["tls"] = tls;
+        }
+        if self.service_worker {
+            entry["serviceWorker"] = serde_json::Value::Bool(true);
+        }
+        entry
+    }
+}
@@ -0,0 +1,123 @@
+use crate::AppState;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+/// Spots pages a script can't get past on its own: CAPTCHA widgets/interstitials
+/// and one-time-code (2FA) prompts. Returns {"kind": "captcha"|"otp"|null, "detail"}.
+const DETECT_JS: &str = r#"(() => {
+  const vis = el => { const r = el.getBoundingClientRect(); return r.width > 0 && r.height > 0; };
+  const frames = Array.from(document.querySelectorAll('iframe'))
+    .filter(f => /recaptcha|hcaptcha|challenges\.cloudflare\.com|turnstile|arkoselabs|funcaptcha/i.test(f.src || ''))
+    .filter(vis);
+  if (frames.length) return JSON.stringify({kind: 'captcha', detail: frames[0].src.split('?')[0]});
+  const widget = document.querySelector('.g-recaptcha, .h-captcha, .cf-turnstile, #cf-challenge-running, #challenge-form');
+  if (widget) return JSON.stringify({kind: 'captcha', detail: widget.id || widget.className});
+  if (/^just a moment/i.test(document.title)) return JSON.stringify({kind: 'captcha', detail: document.title});
+
+  const OTP = /otp|totp|2fa|mfa|one.?time|verification.?code|passcode|security.?code|auth.?code/i;
+  const inputs = Array.from(document.querySelectorAll('input')).filter(vis);
+  const otp = inputs.find(i => i.autocomplete === 'one-time-code'
+    || OTP.test((i.name || '') + ' ' + (i.id || '') + ' ' + (i.placeholder || '') + ' ' + (i.getAttribute('aria-label') || '')));
+  if (otp) return JSON.stringify({kind: 'otp', detail: otp.name || otp.id || otp.autocomplete});
+  const digits = inputs.filter(i => i.maxLength === 1 && /numeric|tel|number/.test(i.inputMode + ' ' + i.type));
+  if (digits.length >= 4) return JSON.stringify({kind: 'otp', detail: digits.length + ' single-digit inputs'});
+  return JSON.stringify({kind: null});
+})()"#;
+
+/// Challenges often render a moment after the page (or the click) that caused them.
+const CHECK_DELAYS_MS: [u64; 2] = [2000, 5000];
+
+/// Look for a challenge in a window. A newly found one is recorded in
+/// AppState.human_needed and announced with a `human-needed` event.
+pub fn check(app: &tauri::AppHandle, window: &str) -> Option<serde_json::Value> {
+    let raw = crate::eval_js_with_timeout(app, window, DETECT_JS, Duration::from_secs(5)).ok()?;
+    let found: serde_json::Value = serde_json::from_str(&raw).ok()?;
+    let kind = found.get("kind").and_then(|v| v.as_str())?.to_string();
+
+    if let Some(existing) = pending(app, window) {
+        return Some(existing);
+    }
+    let url = crate::eval_js_with_timeout(app, window, "location.href", Duration::from_secs(2))
+        .unwrap_or_default();
+    let state = app.state::<AppState>();
+    let challenge = serde_json::json!({
+        "kind": kind,
+        "detail": found.get("detail"),
+        "url": url,
+        "app": state.current_app(window),
+        "since": chrono::Utc::now().to_rfc3339(),
+    });
+    state
+        .human_needed
+        .lock()
+        .unwrap()
+        .insert(window.to_string(), challenge.clone());
+
+    let mut event = challenge.clone();
+    event["window"] = serde_json::json!(window);
+    crate::events::emit("human-needed", event);
+    Some(challenge)
+}
+
+/// Check a window in the background shortly after a navigation or action.
+pub fn schedule_check(app: &tauri::AppHandle, window: &str) {
+    let app = app.clone();
+    let window = window.to_string();
+    std::thread::spawn(move || {
+        let mut waited = 0;
+        for delay in CHECK_DELAYS_MS {
+            std::thread::sleep(Duration::from_millis(delay - waited));
+            waited = delay;
+            if check(&app, &window).is_some() {
+                return;
+            }
+        }
+    });
+}
+
+/// The unresolved challenge in a window, if any.
+pub fn pending(app: &tauri::AppHandle, window: &str) -> Option<serde_json::Value> {
+    app.state::<AppState>()
+        .human_needed
+        .lock()
+        .unwrap()
+        .get(window)
+        .cloned()
+}
+
+/// Block until the human has cleared the challenge (it's no longer on the page),
+/// or the timeout runs out.
+pub fn wait_for_human(
+    app: &tauri::AppHandle,
+    window: &str,
+    timeout: Duration,
+) -> serde_json::Value {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let raw = crate::eval_js_with_timeout(app, window, DETECT_JS, Duration::from_secs(5));
+        let still_there = raw
+            .ok()
+            .and_then(|r| serde_json::from_str::<serde_json::Value>(&r).ok())
+            .is_some_and(|v| v.get("kind").is_some_and(|k| !k.is_null()));
+        if !still_there {
+            let cleared = app
+                .state::<AppState>()
+                .human_needed
+                .lock()
+                .unwrap()
+                .remove(window);
+            if cleared.is_some() {
+                crate::events::emit("human-resolved", serde_json::json!({"window": window}));
+            }
+            return serde_json::json!({"ok": true, "window": window});
+        }
+        if Instant::now() >= deadline {
+            return serde_json::json!({
+                "status": "human_needed",
+                "window": window,
+                "challenge": pending(app, window),
+            });
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
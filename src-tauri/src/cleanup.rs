@@ -1,228 +1,456 @@
+use crate::capture_index;
+use crate::capture_io;
 use crate::config;
 use crate::endpoints;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Write;
 
 /// Auth header names used for domain cleanup (same set as capture filtering).
 const AUTH_HEADER_NAMES: &[&str] = &["authorization", "x-csrf-token", "x-xsrf-token"];
 
-/// Trim response/request bodies from captures where the endpoint
-/// pattern has been seen more than 3 times in endpoints.json.
-/// Replaces bodies with "[trimmed: {byte_count} bytes]" to preserve metadata.
-/// Only trims in JSONL files that are NOT the current session.
+/// Trim bodies from captures where the endpoint pattern has been seen more than
+/// the app's `trim.after` times (3 by default) in endpoints.json.
+/// Replaces each of `trim.fields` with "[trimmed: {byte_count} bytes; endpoint: {pattern}]"
+/// to preserve metadata and say which endpoint to look up for a full sample; with
+/// `trim.keep_originals` the value is first appended to trimmed/{file}.gz.
+/// Only trims in JSONL files that are NOT the current session, and always leaves
+/// the best untrimmed sample of each endpoint pattern + status untouched.
 pub fn trim_captures_for_app(app_name: &str, current_session_ts: &str) {
+    trim_app(app_name, current_session_ts, false);
+}
+
+/// What trimming did, or in a dry run would do, to one capture file
+#[derive(Debug)]
+pub struct TrimmedFile {
+    /// File name in captures/
+    pub file: String,
+    /// Captures with at least one field trimmed
+    pub captures: usize,
+    /// Bytes of field values replaced by markers
+    pub bytes: usize,
+}
+
+/// `trim_captures_for_app` as a reporting pass: what would be trimmed, per file,
+/// without touching disk
+pub fn trim_dry_run(app_name: &str, current_session_ts: &str) -> Vec<TrimmedFile> {
+    trim_app(app_name, current_session_ts, true)
+}
+
+fn trim_app(app_name: &str, current_session_ts: &str, dry_run: bool) -> Vec<TrimmedFile> {
     let app_dir = config::data_dir().join("apps").join(app_name);
     let endpoints_path = app_dir.join("endpoints.json");
+    let policy = config::trim_policy(app_name);
+    let mut report = Vec::new();
+    if policy.fields.is_empty() {
+        return report;
+    }
 
-    // Load endpoints.json to find well-sampled patterns (>3 times_seen)
+    // Load endpoints.json to find well-sampled patterns
     let catalog: endpoints::EndpointCatalog = match fs::read_to_string(&endpoints_path)
         .ok()
         .and_then(|s| serde_json::from_str(&s).ok())
     {
         Some(c) => c,
-        None => return,
+        None => return report,
     };
 
     let well_sampled: HashSet<String> = catalog
         .endpoints
         .iter()
-        .filter(|ep| ep.times_seen > 3)
+        .filter(|ep| ep.times_seen as u64 > policy.after)
         .map(|ep| ep.pattern.clone())
         .collect();
 
     if well_sampled.is_empty() {
-        return;
+        return report;
     }
 
-    let captures_dir = app_dir.join("captures");
-    let entries = match fs::read_dir(&captures_dir) {
-        Ok(e) => e,
-        Err(_) => return,
-    };
-
+    let files = capture_io::capture_files(&app_dir);
+    let keep = best_samples(&app_dir, &well_sampled, &policy.fields);
     let current_file = format!("{current_session_ts}.jsonl");
 
-    for entry in entries.flatten() {
-        let path = entry.path();
+    for path in &files {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
 
-        // Only process .jsonl files
-        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+        // Skip the current session file
+        if name == current_file {
             continue;
         }
 
-        // Skip the current session file
-        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-            if name == current_file {
-                continue;
+        let originals = policy
+            .keep_originals
+            .then(|| app_dir.join("trimmed").join(format!("{name}.gz")));
+        let no_lines = HashSet::new();
+        let keep_lines = keep.get(&name).unwrap_or(&no_lines);
+        let trim = TrimRules {
+            well_sampled: &well_sampled,
+            fields: &policy.fields,
+            keep: keep_lines,
+            originals: originals.as_deref(),
+        };
+        if dry_run {
+            if let Some((captures, bytes)) = trim.count(path) {
+                report.push(TrimmedFile {
+                    file: name,
+                    captures,
+                    bytes,
+                });
             }
+        } else if let Some((captures, bytes)) = trim.apply(path) {
+            capture_index::reindex_file(&app_dir, &name);
+            report.push(TrimmedFile {
+                file: name,
+                captures,
+                bytes,
+            });
         }
-
-        trim_single_file(&path, &well_sampled);
     }
+    report
 }
 
-/// Trim bodies in a single JSONL file for well-sampled endpoint patterns.
-fn trim_single_file(path: &std::path::Path, well_sampled: &HashSet<String>) {
-    let contents = match fs::read_to_string(path) {
-        Ok(c) => c,
-        Err(_) => return,
-    };
-
-    let mut modified = false;
-    let mut output_lines: Vec<String> = Vec::new();
-
-    for line in contents.lines() {
-        let mut data: serde_json::Value = match serde_json::from_str(line) {
-            Ok(d) => d,
-            Err(_) => {
-                output_lines.push(line.to_string());
-                continue;
-            }
+/// Untrimmed fields, response body length, timestamp — higher is a better sample
+type SampleScore = (usize, usize, String);
+
+/// Pick, for each well-sampled endpoint pattern and status, the one capture that
+/// must stay untrimmed: the one with the most untrimmed `fields` (ideally both
+/// request and response body), then the largest response body, then the newest.
+/// Only those endpoints' captures are read, found through the capture index.
+/// Returns the chosen line offsets per capture file name.
+fn best_samples(
+    app_dir: &std::path::Path,
+    well_sampled: &HashSet<String>,
+    fields: &[String],
+) -> HashMap<String, HashSet<u64>> {
+    // (pattern, status) -> (score, file, offset)
+    let mut best: HashMap<(String, u64), (SampleScore, String, u64)> = HashMap::new();
+
+    for loc in capture_index::locations(app_dir) {
+        // WebSocket frames share their socket's URL but aren't request/response samples
+        if !well_sampled.contains(&loc.pattern) || loc.entry_type.starts_with("ws-") {
+            continue;
+        }
+        let Some(data) = capture_index::read(app_dir, &loc) else {
+            continue;
         };
-
-        let url_str = match data.get("url").and_then(|v| v.as_str()) {
-            Some(u) => u.to_string(),
-            None => {
-                output_lines.push(line.to_string());
-                continue;
-            }
+        let untrimmed = |field: &String| match data.get(field) {
+            None | Some(serde_json::Value::Null) => false,
+            Some(serde_json::Value::String(s)) => !s.is_empty() && !s.starts_with("[trimmed"),
+            Some(_) => true,
         };
-
-        // Parse URL to get path, then normalize
-        let parsed = match url::Url::parse(&url_str) {
-            Ok(u) => u,
-            Err(_) => {
-                output_lines.push(line.to_string());
-                continue;
-            }
+        let full_fields = fields.iter().filter(|f| untrimmed(f)).count();
+        if full_fields == 0 {
+            continue;
+        }
+        let response_len = match data.get("responseBody") {
+            Some(serde_json::Value::String(s)) if !s.starts_with("[trimmed") => s.len(),
+            _ => 0,
         };
+        let score = (full_fields, response_len, loc.timestamp.clone());
+        let key = (loc.pattern.clone(), loc.status.unwrap_or(0));
+        match best.get(&key) {
+            Some((current, _, _)) if *current >= score => {}
+            _ => {
+                best.insert(key, (score, loc.file, loc.offset));
+            }
+        }
+    }
 
-        let method = data
-            .get("method")
-            .and_then(|v| v.as_str())
-            .unwrap_or("GET")
-            .to_string();
-        let path_str = parsed.path().to_string();
-        let pattern = format!("{} {}", method, endpoints::normalize_path(&path_str));
+    let mut keep: HashMap<String, HashSet<u64>> = HashMap::new();
+    for (_, file, offset) in best.into_values() {
+        keep.entry(file).or_default().insert(offset);
+    }
+    keep
+}
 
-        // Only trim if the pattern is well-sampled
-        if !well_sampled.contains(&pattern) {
-            output_lines.push(line.to_string());
-            continue;
+/// Trim `fields` in a capture file for well-sampled endpoint patterns, except on
+/// the `keep` lines, appending the trimmed values to `originals` (a gzip file) when given
+struct TrimRules<'a> {
+    well_sampled: &'a HashSet<String>,
+    fields: &'a [String],
+    keep: &'a HashSet<u64>,
+    originals: Option<&'a std::path::Path>,
+}
+
+impl TrimRules<'_> {
+    /// A line with its fields trimmed, the original values as trimmed/*.gz lines, and
+    /// their total size — or None if the line is left alone
+    fn trim_line(
+        &self,
+        offset: u64,
+        line: &str,
+    ) -> Option<(serde_json::Value, Vec<String>, usize)> {
+        let (well_sampled, fields, originals) = (self.well_sampled, self.fields, self.originals);
+        if self.keep.contains(&offset) {
+            return None;
         }
+        let mut data: serde_json::Value = serde_json::from_str(line).ok()?;
 
-        let obj = match data.as_object_mut() {
-            Some(o) => o,
-            None => {
-                output_lines.push(line.to_string());
-                continue;
+        // Only trim if the pattern is well-sampled
+        let pattern = capture_index::entry_pattern(&data).filter(|p| well_sampled.contains(p))?;
+        let url_str = data
+            .get("url")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let timestamp = data
+            .get("timestamp")
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        let obj = data.as_object_mut()?;
+
+        // One line per trimmed value: {"timestamp", "url", "field", "value"}
+        let mut trimmed: Vec<String> = Vec::new();
+        let mut bytes = 0;
+        for field in fields {
+            let byte_count = match obj.get(field) {
+                None | Some(serde_json::Value::Null) => continue,
+                // Already trimmed
+                Some(serde_json::Value::String(s)) if s.starts_with("[trimmed") => continue,
+                Some(serde_json::Value::String(s)) => s.len(),
+                Some(other) => other.to_string().len(),
+            };
+            bytes += byte_count;
+            let marker = match originals {
+                Some(file) => format!(
+                    "[trimmed: {byte_count} bytes; endpoint: {pattern}; original: trimmed/{}]",
+                    file.file_name().unwrap_or_default().to_string_lossy()
+                ),
+                None => format!("[trimmed: {byte_count} bytes; endpoint: {pattern}]"),
+            };
+            if let Some(original) = obj.insert(field.clone(), serde_json::Value::String(marker)) {
+                trimmed.push(
+                    serde_json::json!({
+                        "timestamp": timestamp,
+                        "url": url_str,
+                        "field": field,
+                        "value": original,
+                    })
+                    .to_string(),
+                );
             }
-        };
+        }
+        if trimmed.is_empty() {
+            return None;
+        }
+        Some((data, trimmed, bytes))
+    }
 
-        // Trim responseBody if present and not already trimmed
-        if let Some(body_val) = obj.get("responseBody") {
-            if let Some(body_str) = body_val.as_str() {
-                if !body_str.starts_with("[trimmed") {
-                    let byte_count = body_str.len();
-                    obj.insert(
-                        "responseBody".to_string(),
-                        serde_json::Value::String(format!("[trimmed: {byte_count} bytes]")),
-                    );
-                    modified = true;
-                }
-            }
+    /// Captures and bytes that `apply` would trim in a file, without writing anything
+    fn count(&self, path: &std::path::Path) -> Option<(usize, usize)> {
+        let (captures, bytes) = capture_io::lines_with_offsets(path)
+            .filter_map(|(offset, line)| self.trim_line(offset, &line))
+            .fold((0, 0), |(n, total), (_, _, bytes)| (n + 1, total + bytes));
+        (captures > 0).then_some((captures, bytes))
+    }
+
+    /// Trim a file in place. Streams the file; untouched files aren't rewritten.
+    /// Returns the captures and bytes trimmed if the file was rewritten.
+    fn apply(&self, path: &std::path::Path) -> Option<(usize, usize)> {
+        // First pass only looks, so files with nothing to trim are never rewritten
+        let needs_trim = capture_io::lines_with_offsets(path)
+            .any(|(offset, line)| self.trim_line(offset, &line).is_some());
+        if !needs_trim {
+            return None;
         }
 
-        // Trim requestBody if present and not already trimmed
-        if let Some(body_val) = obj.get("requestBody") {
-            if let Some(body_str) = body_val.as_str() {
-                if !body_str.starts_with("[trimmed") {
-                    let byte_count = body_str.len();
-                    obj.insert(
-                        "requestBody".to_string(),
-                        serde_json::Value::String(format!("[trimmed: {byte_count} bytes]")),
-                    );
-                    modified = true;
+        let mut out = capture_io::Rewriter::new(path).ok()?;
+        // Originals go to disk before their bodies are removed from the capture file
+        let mut kept = self.originals.map(open_gzip_append).transpose().ok()?;
+
+        let (mut captures, mut bytes) = (0, 0);
+        // A line that can't be read ends it without committing: the rest of the file
+        // would be missing from the rewrite
+        for read in capture_io::try_lines_with_offsets(path) {
+            let (offset, line) = read.ok()?;
+            let written = match self.trim_line(offset, &line) {
+                Some((data, trimmed, size)) => {
+                    if let Some(kept) = kept.as_mut() {
+                        for t in &trimmed {
+                            writeln!(kept, "{t}").ok()?;
+                        }
+                    }
+                    captures += 1;
+                    bytes += size;
+                    match serde_json::to_string(&data) {
+                        Ok(l) => out.write_line(&l),
+                        Err(_) => out.write_line(&line),
+                    }
                 }
-            }
+                None => out.write_line(&line),
+            };
+            written.ok()?;
         }
 
-        match serde_json::to_string(&data) {
-            Ok(l) => output_lines.push(l),
-            Err(_) => output_lines.push(line.to_string()),
+        if let Some(kept) = kept {
+            kept.finish().ok()?;
         }
+        out.commit().ok()?;
+        Some((captures, bytes))
+    }
+}
+
+/// Open a gzip file for appending a new gzip member (readers see one stream)
+fn open_gzip_append(path: &std::path::Path) -> std::io::Result<flate2::write::GzEncoder<fs::File>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
     }
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    Ok(flate2::write::GzEncoder::new(
+        file,
+        flate2::Compression::default(),
+    ))
+}
 
-    if !modified {
+/// Remove domains from an app's config that have never been seen with auth headers.
+/// Called during generate_all_endpoints to progressively clean up bloated domain lists.
+/// Always keeps at least the first domain (the one the user originally named the app for)
+/// and any domain put back with `restore_domain`. Removals are logged to removed-domains.json.
+pub fn clean_app_domains(app_name: &str) {
+    let config_path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("config.json");
+    let Some((app_cfg, authed_domains)) = authed_domains(app_name) else {
         return;
-    }
+    };
+
+    // Filter to only domains that had auth
+    let (cleaned, removed): (Vec<String>, Vec<String>) = app_cfg
+        .domains
+        .into_iter()
+        .partition(|d| authed_domains.contains(d));
 
-    // Write to a temp file, then rename to avoid corruption
-    let tmp_path = path.with_extension("jsonl.tmp");
-    let mut tmp_file = match fs::File::create(&tmp_path) {
-        Ok(f) => f,
-        Err(_) => return,
+    // Write back if we removed any domains
+    if removed.is_empty() {
+        return;
+    }
+    let updated = config::AppConfig {
+        domains: cleaned,
+        created: app_cfg.created,
+        last_session: app_cfg.last_session,
+        rules: app_cfg.rules,
+        capture_noise: app_cfg.capture_noise,
+        domain_groups: app_cfg.domain_groups,
+        archived: app_cfg.archived,
+        trim: app_cfg.trim,
+        sampling: app_cfg.sampling,
+        digests: app_cfg.digests,
+        tls_profile: app_cfg.tls_profile,
+        stale_after_sessions: app_cfg.stale_after_sessions,
     };
 
-    for line in &output_lines {
-        if writeln!(tmp_file, "{line}").is_err() {
-            let _ = fs::remove_file(&tmp_path);
-            return;
+    if let Ok(json) = serde_json::to_string_pretty(&updated) {
+        let _ = fs::write(&config_path, json);
+    }
+
+    // Record what went, so a wrongly removed API host can be put back with restore_domain
+    let removed_at = chrono::Utc::now().to_rfc3339();
+    let mut log = removed_domains(app_name);
+    log.extend(removed.into_iter().map(|domain| {
+        serde_json::json!({
+            "domain": domain,
+            "reason": "no authenticated requests seen in captures",
+            "removed_at": removed_at,
+        })
+    }));
+    write_removed_domains(app_name, &log);
+}
+
+/// apps/<name>/removed-domains.json: every domain `clean_app_domains` removed, oldest first
+fn removed_domains(app_name: &str) -> Vec<serde_json::Value> {
+    let path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("removed-domains.json");
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn write_removed_domains(app_name: &str, log: &[serde_json::Value]) {
+    let path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("removed-domains.json");
+    if let Ok(json) = serde_json::to_string_pretty(log) {
+        let _ = fs::write(path, json);
+    }
+}
+
+/// Domains put back with `restore_domain`; cleanup leaves them alone from then on
+fn restored_domains(app_name: &str) -> HashSet<String> {
+    removed_domains(app_name)
+        .iter()
+        .filter(|e| e.get("restored_at").is_some())
+        .filter_map(|e| e.get("domain").and_then(|d| d.as_str()).map(String::from))
+        .collect()
+}
+
+/// Undo `clean_app_domains` for one domain: add it back to the app's config and mark
+/// its removed-domains.json entry restored. Errors if cleanup never removed it.
+pub fn restore_domain(app_name: &str, domain: &str) -> Result<(), String> {
+    let mut log = removed_domains(app_name);
+    let mut found = false;
+    let restored_at = chrono::Utc::now().to_rfc3339();
+    for entry in log
+        .iter_mut()
+        .filter(|e| e.get("domain").and_then(|d| d.as_str()) == Some(domain))
+    {
+        found = true;
+        if entry.get("restored_at").is_none() {
+            entry["restored_at"] = serde_json::Value::from(restored_at.as_str());
         }
     }
+    if !found {
+        return Err(format!("{domain} was never removed from {app_name}"));
+    }
+    config::add_domain_to_app(app_name, domain);
+    write_removed_domains(app_name, &log);
+    Ok(())
+}
 
-    // Flush and rename
-    drop(tmp_file);
-    let _ = fs::rename(&tmp_path, path);
+/// `clean_app_domains` as a reporting pass: the domains it would remove
+pub fn clean_domains_dry_run(app_name: &str) -> Vec<String> {
+    let Some((app_cfg, authed_domains)) = authed_domains(app_name) else {
+        return Vec::new();
+    };
+    app_cfg
+        .domains
+        .into_iter()
+        .filter(|d| !authed_domains.contains(d))
+        .collect()
 }
 
-/// Remove domains from an app's config that have never been seen with auth headers.
-/// Called during generate_all_endpoints to progressively clean up bloated domain lists.
-/// Always keeps at least the first domain (the one the user originally named the app for).
-pub fn clean_app_domains(app_name: &str) {
+/// An app's config and the domains it keeps: those seen with auth headers or cookies,
+/// plus its first domain and restored ones. None if there's nothing to clean (one domain, no captures).
+fn authed_domains(app_name: &str) -> Option<(config::AppConfig, HashSet<String>)> {
     let app_dir = config::data_dir().join("apps").join(app_name);
     let config_path = app_dir.join("config.json");
     let captures_dir = app_dir.join("captures");
 
     // Read current app config
-    let app_cfg: config::AppConfig = match fs::read_to_string(&config_path)
+    let app_cfg: config::AppConfig = fs::read_to_string(&config_path)
         .ok()
-        .and_then(|s| serde_json::from_str(&s).ok())
-    {
-        Some(c) => c,
-        None => return,
-    };
+        .and_then(|s| serde_json::from_str(&s).ok())?;
 
     // Need at least 2 domains to have anything to clean
-    if app_cfg.domains.len() <= 1 {
-        return;
+    if app_cfg.domains.len() <= 1 || !captures_dir.is_dir() {
+        return None;
     }
 
     // Scan all captures to find domains that had authenticated requests
     let mut authed_domains: HashSet<String> = HashSet::new();
-
-    let entries = match fs::read_dir(&captures_dir) {
-        Ok(e) => e,
-        Err(_) => return,
-    };
-
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
-            continue;
-        }
-        let contents = match fs::read_to_string(&path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
-
-        for line in contents.lines() {
-            let data: serde_json::Value = match serde_json::from_str(line) {
-                Ok(d) => d,
-                Err(_) => continue,
-            };
-
+    for path in capture_io::capture_files(&app_dir) {
+        for data in capture_io::entries(&path) {
             let entry_type = data.get("type").and_then(|v| v.as_str()).unwrap_or("");
             // Skip pure meta entries that don't indicate auth
             if entry_type == "ui-action" || entry_type == "navigation" || entry_type == "annotation" {
@@ -270,22 +498,59 @@ pub fn clean_app_domains(app_name: &str) {
     // Always keep the first domain (the one the user originally registered)
     let first_domain = app_cfg.domains[0].clone();
     authed_domains.insert(first_domain);
+    authed_domains.extend(restored_domains(app_name));
+    Some((app_cfg, authed_domains))
+}
 
-    // Filter to only domains that had auth
-    let cleaned: Vec<String> = app_cfg
-        .domains
-        .into_iter()
-        .filter(|d| authed_domains.contains(d))
-        .collect();
+/// Archive or unarchive an app: flips the config flag and gzips its
+/// `captures/*.jsonl` (or unpacks them again so generation can read them).
+pub fn set_archived(app_name: &str, archived: bool) -> Result<(), String> {
+    config::set_archived(app_name, archived)?;
+    let captures_dir = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("captures");
+    let from_ext = if archived { "jsonl" } else { "gz" };
+    for entry in fs::read_dir(&captures_dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(from_ext) {
+            continue;
+        }
+        let target = if archived {
+            path.with_file_name(format!("{}.gz", entry.file_name().to_string_lossy()))
+        } else {
+            path.with_extension("")
+        };
+        let converted = fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                let out = if archived {
+                    gzip(&bytes)
+                } else {
+                    gunzip(&bytes)
+                }
+                .map_err(|e| e.to_string())?;
+                fs::write(&target, out).map_err(|e| e.to_string())
+            });
+        match converted {
+            Ok(()) => {
+                let _ = fs::remove_file(&path);
+            }
+            Err(e) => return Err(format!("{}: {e}", path.display())),
+        }
+    }
+    Ok(())
+}
 
-    // Write back if we removed any domains
-    let updated = config::AppConfig {
-        domains: cleaned,
-        created: app_cfg.created,
-        last_session: app_cfg.last_session,
-    };
+fn gzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
 
-    if let Ok(json) = serde_json::to_string_pretty(&updated) {
-        let _ = fs::write(&config_path, json);
-    }
+fn gunzip(bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+    let mut out = Vec::new();
+    flate2::read::GzDecoder::new(bytes).read_to_end(&mut out)?;
+    Ok(out)
 }
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// A request header whose value is new on every request, even to the same endpoint:
+/// a signature or hash the page computes per request. A replay with a value copied
+/// from a capture will be refused — the request has to be made from the page.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ComputedHeader {
+    pub header: String,
+    pub computed: bool,
+    /// Leading word of the value when it has one ("SAPISIDHASH ..."), else "opaque"
+    pub pattern: String,
+    pub requests: usize,
+    pub distinct_values: usize,
+    pub min_len: usize,
+    pub max_len: usize,
+    /// Mean Shannon entropy of a value, in bits per character: ~4 for hex, ~6 for base64
+    pub entropy_bits_per_char: f64,
+    /// Endpoints it was sent to ("METHOD /pattern")
+    pub endpoints: Vec<String>,
+}
+
+/// Headers that change per request for reasons other than auth: tracing, caching,
+/// body framing
+const ALWAYS_VARYING: &[&str] = &[
+    "content-length",
+    "content-type",
+    "cookie",
+    "referer",
+    "date",
+    "if-none-match",
+    "if-modified-since",
+    "range",
+    "traceparent",
+    "tracestate",
+    "baggage",
+    "sentry-trace",
+    "x-request-id",
+    "request-id",
+    "x-correlation-id",
+    "x-amzn-trace-id",
+    "x-cloud-trace-context",
+];
+
+/// Values shorter than this are counters or flags, not signatures
+const MIN_VALUE_LEN: usize = 8;
+
+/// Repeat requests to one endpoint within a session needed before "new every time"
+/// means anything
+const MIN_REPEATS: usize = 2;
+
+/// Values kept per header to count distinct ones
+const MAX_VALUES: usize = 1000;
+
+#[derive(Default)]
+struct HeaderStats {
+    name: String,
+    requests: usize,
+    values: HashSet<String>,
+    min_len: usize,
+    max_len: usize,
+    entropy_sum: f64,
+    pattern: String,
+    /// (session, endpoint) -> (last value sent to it, repeat requests, repeats with a
+    /// new value). Per session, so a token that only changes at login isn't counted
+    runs: BTreeMap<(usize, String), (String, usize, usize)>,
+}
+
+/// Watches every request header for values that never repeat on the same endpoint
+#[derive(Default)]
+pub(crate) struct HeaderTrace {
+    headers: HashMap<String, HeaderStats>,
+}
+
+impl HeaderTrace {
+    /// A request to `endpoint` from capture file number `session`
+    pub(crate) fn observe(&mut self, data: &serde_json::Value, endpoint: &str, session: usize) {
+        let Some(headers) = data.get("requestHeaders").and_then(|h| h.as_object()) else {
+            return;
+        };
+        for (name, value) in headers {
+            let lower = name.to_lowercase();
+            let Some(value) = value.as_str() else {
+                continue;
+            };
+            // Auth and CSRF headers are auth.json's already, with their own lifetimes
+            if ALWAYS_VARYING.contains(&lower.as_str())
+                || crate::endpoints::AUTH_HEADER_NAMES.contains(&lower.as_str())
+                || crate::csrf::is_csrf_header(&lower)
+                || lower.starts_with("x-b3-")
+                || lower.starts_with("x-datadog-")
+                || value.len() < MIN_VALUE_LEN
+            {
+                continue;
+            }
+            let stats = self.headers.entry(lower).or_default();
+            if stats.requests == 0 {
+                stats.name = name.clone();
+                stats.min_len = value.len();
+                stats.pattern = match value.split_once(' ') {
+                    Some((word, _)) => format!("{word} ..."),
+                    None => "opaque".to_string(),
+                };
+            }
+            stats.requests += 1;
+            stats.min_len = stats.min_len.min(value.len());
+            stats.max_len = stats.max_len.max(value.len());
+            stats.entropy_sum += entropy(value);
+            if stats.values.len() < MAX_VALUES {
+                stats.values.insert(value.to_string());
+            }
+            let run = (session, endpoint.to_string());
+            match stats.runs.get_mut(&run) {
+                Some((last, repeats, changed)) => {
+                    *repeats += 1;
+                    if last != value {
+                        *changed += 1;
+                        *last = value.to_string();
+                    }
+                }
+                None => {
+                    stats.runs.insert(run, (value.to_string(), 0, 0));
+                }
+            }
+        }
+    }
+
+    /// The computed headers, and for each endpoint the computed headers it was sent
+    pub(crate) fn finish(self) -> (Vec<ComputedHeader>, HashMap<String, Vec<String>>) {
+        let mut computed = Vec::new();
+        let mut by_endpoint: HashMap<String, Vec<String>> = HashMap::new();
+        for stats in self.headers.into_values() {
+            let repeats: usize = stats.runs.values().map(|(_, r, _)| r).sum();
+            let changed: usize = stats.runs.values().map(|(_, _, c)| c).sum();
+            if repeats < MIN_REPEATS || changed < repeats {
+                continue;
+            }
+            let endpoints: BTreeSet<String> = stats
+                .runs
+                .into_keys()
+                .map(|(_, endpoint)| endpoint)
+                .collect();
+            for endpoint in &endpoints {
+                by_endpoint
+                    .entry(endpoint.clone())
+                    .or_default()
+                    .push(stats.name.clone());
+            }
+            computed.push(ComputedHeader {
+                header: stats.name,
+                computed: true,
+                pattern: stats.pattern,
+                requests: stats.requests,
+                distinct_values: stats.values.len(),
+                min_len: stats.min_len,
+                max_len: stats.max_len,
+                entropy_bits_per_char: (stats.entropy_sum / stats.requests as f64 * 100.0).round()
+                    / 100.0,
+                endpoints: endpoints.into_iter().collect(),
+            });
+        }
+        computed.sort_by(|a, b| a.header.cmp(&b.header));
+        for headers in by_endpoint.values_mut() {
+            headers.sort();
+        }
+        (computed, by_endpoint)
+    }
+}
+
+/// Shannon entropy of a string's characters, in bits per character
+fn entropy(value: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut len = 0;
+    for c in value.chars() {
+        *counts.entry(c).or_default() += 1;
+        len += 1;
+    }
+    counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / len as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -2,6 +2,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
 
 // Browser UA: Safari — WKWebView IS Safari's engine, so this is truthful.
 // Google/etc. won't block sign-in since the fingerprint matches the actual engine.
@@ -11,13 +13,311 @@ const BROWSER_UA: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleW
 // Most sites expect Chrome and may serve different responses to Safari.
 const FALLBACK_CURL_UA: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/144.0.0.0 Safari/537.36";
 
+/// TLS fingerprint presets and the curl-impersonate binary that sends each one
+pub const TLS_PROFILES: &[(&str, &str)] = &[
+    ("chrome131", "curl_chrome131"),
+    ("chrome124", "curl_chrome124"),
+    ("safari18", "curl_safari18_0"),
+    ("firefox", "curl_firefox133"),
+];
+const DEFAULT_TLS_PROFILE: &str = "chrome131";
+/// An endpoint missing from this many sessions in a row is stale, unless the app says otherwise
+const DEFAULT_STALE_AFTER_SESSIONS: u32 = 3;
+const DEFAULT_GENERATE_EVERY_MINUTES: u64 = 10;
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct GlobalConfig {
     /// Chrome UA for curl replay (paste from your real Chrome)
     #[serde(default)]
     pub user_agent: Option<String>,
+    /// TLS fingerprint replays should present: chrome131 (default), chrome124, safari18, firefox
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_profile: Option<String>,
     #[serde(default)]
     pub capture_port: Option<u16>,
+    /// Webhooks notified when human attention is needed (re-login, new domain, ...)
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Try to dismiss cookie/consent dialogs after each navigation
+    #[serde(default)]
+    pub auto_dismiss_consent: bool,
+    /// Log why each dropped capture was dropped to debug.log
+    #[serde(default)]
+    pub capture_debug: bool,
+    /// Replace secrets found in captured bodies (JWTs, API keys, card numbers) with
+    /// "[masked:kind]" instead of only flagging the capture
+    #[serde(default)]
+    pub mask_secrets: bool,
+    /// End each capture line with a CRC-32 of it (`"_crc"`), so lines damaged on disk
+    /// are skipped by analysis rather than misread
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub capture_line_crc: bool,
+    /// How long commands wait for page JS when they don't pass timeout_ms
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval_timeout_ms: Option<u64>,
+    /// Minutes after an app's first new capture that the background pass regenerates it
+    /// (default 10; 0 leaves generation to end_session / generate_endpoints)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generate_every_minutes: Option<u64>,
+    /// What the browser records (body sizes, bodies on/off, per-domain toggles)
+    #[serde(default, skip_serializing_if = "CaptureSettings::is_default")]
+    pub capture: CaptureSettings,
+    /// External programs run on each saved capture and/or after each generation pass
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<PluginConfig>,
+    /// Which agent commands wait for the user to allow them in the explorer window
+    #[serde(default, skip_serializing_if = "ConfirmPolicy::is_default")]
+    pub confirm: ConfirmPolicy,
+}
+
+/// Commands that look destructive — clicks on "Delete"/"Pay" buttons, scripts that
+/// send state-changing requests or submit forms — and are held until the user allows them
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConfirmPolicy {
+    /// false: run every command without asking
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// `click`/`click_ref` on an element whose label contains one of these phrases
+    /// (whole words, any case)
+    #[serde(default = "default_confirm_click_labels")]
+    pub click_labels: Vec<String>,
+    /// `eval` scripts containing one of these, compared ignoring case, whitespace and
+    /// quote style ("method:'delete'" matches `method: "DELETE"`)
+    #[serde(default = "default_confirm_eval_patterns")]
+    pub eval_patterns: Vec<String>,
+}
+
+fn default_confirm_click_labels() -> Vec<String> {
+    [
+        "delete",
+        "remove",
+        "pay",
+        "buy",
+        "purchase",
+        "place order",
+        "checkout",
+        "transfer",
+        "send money",
+        "unsubscribe",
+        "cancel subscription",
+        "close account",
+        "deactivate",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_confirm_eval_patterns() -> Vec<String> {
+    [
+        "method:'post'",
+        "method:'put'",
+        "method:'patch'",
+        "method:'delete'",
+        ".open('post'",
+        ".open('put'",
+        ".open('patch'",
+        ".open('delete'",
+        ".submit(",
+        ".requestsubmit(",
+        ".click(",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+impl Default for ConfirmPolicy {
+    fn default() -> Self {
+        ConfirmPolicy {
+            enabled: true,
+            click_labels: default_confirm_click_labels(),
+            eval_patterns: default_confirm_eval_patterns(),
+        }
+    }
+}
+
+impl ConfirmPolicy {
+    fn is_default(&self) -> bool {
+        *self == ConfirmPolicy::default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PluginConfig {
+    /// Names its output: apps/<app>/plugins/{name}.json
+    pub name: String,
+    /// Program and arguments; "capture" or "generate" is appended as the last argument
+    pub command: Vec<String>,
+    /// Hooks to run it on: "capture", "generate". Empty = both.
+    #[serde(default)]
+    pub on: Vec<String>,
+    /// Apps to run it for. Empty = all.
+    #[serde(default)]
+    pub apps: Vec<String>,
+}
+
+/// What intercept.js records. Pushed into each browser window when it opens, so
+/// changes apply to windows opened afterwards.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CaptureSettings {
+    /// Longest request/response body kept, in bytes (binary bodies before base64)
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: u64,
+    #[serde(default = "default_true")]
+    pub request_bodies: bool,
+    #[serde(default = "default_true")]
+    pub response_bodies: bool,
+    /// Stop pages registering service workers (and remove installed ones), so
+    /// requests a worker would make itself go through the page and get captured
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub block_service_workers: bool,
+    /// Overrides by hostname, subdomains included, the most specific winning,
+    /// e.g. {"media.example.com": {"enabled": false}}
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub domains: HashMap<String, DomainCapture>,
+    /// How much of a response body is kept, by content type
+    #[serde(default, skip_serializing_if = "ResponseBodyPolicy::is_default")]
+    pub response_types: ResponseBodyPolicy,
+    /// Save binary response bodies (images, PDFs, archives) as files under
+    /// `captures/blobs/`, named by their SHA-256, instead of base64 in the capture line
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub save_blobs: bool,
+}
+
+/// Which response bodies are saved whole, cut to a preview, or replaced by a hash, by
+/// content type. Entries match anywhere in the Content-Type, any case; the first list
+/// with a match wins, in the order full, preview, hash_only. Unmatched types are kept whole.
+/// Applied when a capture is saved, whatever the page's script sent.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ResponseBodyPolicy {
+    #[serde(default)]
+    pub full: Vec<String>,
+    #[serde(default = "default_preview_types")]
+    pub preview: Vec<String>,
+    /// Characters a preview keeps
+    #[serde(default = "default_preview_chars")]
+    pub preview_chars: usize,
+    /// Only the size and a hash are kept, to tell identical files apart
+    #[serde(default = "default_hash_only_types")]
+    pub hash_only: Vec<String>,
+}
+
+fn default_preview_types() -> Vec<String> {
+    vec!["text/html".to_string()]
+}
+
+fn default_preview_chars() -> usize {
+    2000
+}
+
+fn default_hash_only_types() -> Vec<String> {
+    [
+        "image/",
+        "font/",
+        "audio/",
+        "video/",
+        "application/font",
+        "application/vnd.ms-fontobject",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+impl Default for ResponseBodyPolicy {
+    fn default() -> Self {
+        ResponseBodyPolicy {
+            full: Vec::new(),
+            preview: default_preview_types(),
+            preview_chars: default_preview_chars(),
+            hash_only: default_hash_only_types(),
+        }
+    }
+}
+
+impl ResponseBodyPolicy {
+    fn is_default(&self) -> bool {
+        *self == ResponseBodyPolicy::default()
+    }
+}
+
+/// What applies to requests to one host once per-domain overrides are in
+pub struct HostCapture {
+    pub enabled: bool,
+    pub request_bodies: bool,
+    pub response_bodies: bool,
+}
+
+impl CaptureSettings {
+    /// The settings for requests to `host`: the global ones, then each matching domain's
+    /// overrides from the least to the most specific — intercept.js's settingsFor
+    pub fn for_host(&self, host: &str) -> HostCapture {
+        let mut out = HostCapture {
+            enabled: true,
+            request_bodies: self.request_bodies,
+            response_bodies: self.response_bodies,
+        };
+        let mut matching: Vec<(&String, &DomainCapture)> = self
+            .domains
+            .iter()
+            .filter(|(d, _)| host == d.as_str() || host.ends_with(&format!(".{d}")))
+            .collect();
+        matching.sort_by_key(|(d, _)| d.len());
+        for (_, o) in matching {
+            out.enabled = o.enabled.unwrap_or(out.enabled);
+            out.request_bodies = o.request_bodies.unwrap_or(out.request_bodies);
+            out.response_bodies = o.response_bodies.unwrap_or(out.response_bodies);
+        }
+        out
+    }
+}
+
+/// Per-domain overrides of `CaptureSettings`; unset fields inherit
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct DomainCapture {
+    /// false: nothing to or from this domain is captured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_bodies: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_bodies: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_service_workers: Option<bool>,
+}
+
+fn default_max_body_bytes() -> u64 {
+    500_000
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        CaptureSettings {
+            max_body_bytes: default_max_body_bytes(),
+            request_bodies: true,
+            response_bodies: true,
+            block_service_workers: false,
+            domains: HashMap::new(),
+            response_types: ResponseBodyPolicy::default(),
+            save_blobs: false,
+        }
+    }
+}
+
+impl CaptureSettings {
+    fn is_default(&self) -> bool {
+        *self == CaptureSettings::default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Event kinds to deliver (e.g. "session-expired"). Empty = all webhook events.
+    #[serde(default)]
+    pub events: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,6 +325,154 @@ pub struct AppConfig {
     pub domains: Vec<String>,
     pub created: String,
     pub last_session: Option<String>,
+    /// Request interception rules, applied in the browser while on this app's domains
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<InterceptRule>,
+    /// URL fragments to capture for this app even though they match the noise
+    /// filters (e.g. "/collect?" when "telemetry" is really the app's event API).
+    /// "sendBeacon" captures every navigator.sendBeacon call.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub capture_noise: Vec<String>,
+    /// Registrable domains (e.g. "google.com") confirmed as wholly this app's:
+    /// any new host under them is added without asking
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub domain_groups: Vec<String>,
+    /// Fully learned: skipped by endpoint generation, captures gzipped, and new
+    /// traffic held until the user unarchives
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// How old captures' bodies are trimmed once an endpoint is well sampled
+    #[serde(default, skip_serializing_if = "TrimPolicy::is_default")]
+    pub trim: TrimPolicy,
+    /// Keep only a random share of an endpoint's captures once it's been seen this often
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling: Option<SamplingPolicy>,
+    /// Digest files to generate; none means a single digest.md with every section
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub digests: Vec<DigestTemplate>,
+    /// TLS fingerprint for this app's replays, overriding the global `tls_profile`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_profile: Option<String>,
+    /// Mark an endpoint stale once it's missing from this many sessions in a row (default 3)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_after_sessions: Option<u32>,
+}
+
+/// One generated digest: which sections, for whom, and how big
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DigestTemplate {
+    /// Markdown file written in the app folder
+    #[serde(default = "default_digest_file")]
+    pub file: String,
+    /// "agent" (terse context for AI agents) or "human" (explained, for documentation)
+    #[serde(default = "default_digest_audience")]
+    pub audience: String,
+    /// Sections in order: domains, workflows, endpoints, resources, auth, session, reference.
+    /// Empty means the audience's default set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<String>,
+    /// Rough size cap in tokens (~4 characters each); what doesn't fit is cut off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    /// How many endpoints "endpoints" lists (default 30)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_endpoints: Option<usize>,
+}
+
+fn default_digest_file() -> String {
+    "digest.md".to_string()
+}
+
+fn default_digest_audience() -> String {
+    "agent".to_string()
+}
+
+impl Default for DigestTemplate {
+    fn default() -> Self {
+        DigestTemplate {
+            file: default_digest_file(),
+            audience: default_digest_audience(),
+            sections: Vec::new(),
+            max_tokens: None,
+            max_endpoints: None,
+        }
+    }
+}
+
+/// Per-app sampling of high-frequency endpoints. Errors and responses whose shape
+/// differs from the endpoint's sample are always kept.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SamplingPolicy {
+    /// Start sampling once an endpoint's times_seen passes this
+    pub after: u64,
+    /// Share of further captures kept (0.1 = one in ten)
+    #[serde(default = "default_sample_rate")]
+    pub rate: f64,
+}
+
+fn default_sample_rate() -> f64 {
+    0.1
+}
+
+/// Per-app body trimming. `"fields": []` turns trimming off.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrimPolicy {
+    /// Trim once an endpoint's `times_seen` in endpoints.json is above this
+    #[serde(default = "default_trim_after")]
+    pub after: u64,
+    /// Capture fields replaced by a "[trimmed: ...]" marker
+    #[serde(default = "default_trim_fields")]
+    pub fields: Vec<String>,
+    /// Keep each trimmed value, gzipped, in trimmed/{capture file}.gz
+    #[serde(default)]
+    pub keep_originals: bool,
+}
+
+fn default_trim_after() -> u64 {
+    3
+}
+
+fn default_trim_fields() -> Vec<String> {
+    vec!["responseBody".to_string(), "requestBody".to_string()]
+}
+
+impl Default for TrimPolicy {
+    fn default() -> Self {
+        TrimPolicy {
+            after: default_trim_after(),
+            fields: default_trim_fields(),
+            keep_originals: false,
+        }
+    }
+}
+
+impl TrimPolicy {
+    fn is_default(&self) -> bool {
+        *self == TrimPolicy::default()
+    }
+}
+
+/// One request interception rule. Requests whose URL contains `match` are
+/// blocked, rewritten and/or get extra headers before they're sent.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InterceptRule {
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Drop the request entirely (e.g. a chatty heartbeat endpoint)
+    #[serde(default)]
+    pub block: bool,
+    /// Headers to add, overriding any the page set (e.g. "Accept": "application/json")
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub set_headers: HashMap<String, String>,
+    /// Replace `from` with `to` in the URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rewrite: Option<UrlRewrite>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UrlRewrite {
+    pub from: String,
+    pub to: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -39,10 +487,25 @@ pub struct SessionData {
     pub csrf_tokens: HashMap<String, String>,
     #[serde(default)]
     pub user_agent: String,
+    /// Hash of the credentials above (empty while there are none); captures carry it
+    /// as "session_id", and each credential set is also saved as sessions/{session_id}.json
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub session_id: String,
+}
+
+/// Where `data_dir` points instead of ~/.harharhar/, if a tool embedding the engine says so
+static DATA_DIR: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+
+/// Use another root data directory. Only the first call counts; false after that.
+pub fn set_data_dir(path: PathBuf) -> bool {
+    DATA_DIR.set(path).is_ok()
 }
 
 /// Root data directory: ~/.harharhar/
 pub fn data_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR.get() {
+        return dir.clone();
+    }
     dirs::home_dir()
         .expect("no home directory")
         .join(".harharhar")
@@ -69,7 +532,12 @@ pub fn ensure_app_dirs(app_name: &str) {
     let _ = fs::create_dir_all(app.join("sessions"));
 }
 
-/// Read the global config, or return defaults
+/// config.json as last loaded, and its modification time then. Captures and commands
+/// read this copy; it's loaded on first use and again when the file changes.
+static LOADED: Mutex<Option<(Option<SystemTime>, Arc<GlobalConfig>)>> = Mutex::new(None);
+
+/// Read the global config from disk, or return defaults — for changing and writing it
+/// back. Everything else uses `config()`.
 pub fn read_config() -> GlobalConfig {
     let path = data_dir().join("config.json");
     fs::read_to_string(&path)
@@ -78,12 +546,45 @@ pub fn read_config() -> GlobalConfig {
         .unwrap_or_default()
 }
 
+/// The global config, without touching the disk once it's loaded
+pub fn config() -> Arc<GlobalConfig> {
+    let mut loaded = LOADED.lock().unwrap();
+    match loaded.as_ref() {
+        Some((_, config)) => config.clone(),
+        None => {
+            let config = Arc::new(read_config());
+            *loaded = Some((config_modified(), config.clone()));
+            config
+        }
+    }
+}
+
+/// Load config.json again if it changed since `config()` last read it (edited by hand,
+/// or by another harharhar process). The command watcher calls this as it wakes.
+pub fn reload_if_changed() {
+    let modified = config_modified();
+    let mut loaded = LOADED.lock().unwrap();
+    if loaded.as_ref().is_some_and(|(at, _)| *at != modified) {
+        *loaded = Some((modified, Arc::new(read_config())));
+    }
+}
+
+fn config_modified() -> Option<SystemTime> {
+    fs::metadata(data_dir().join("config.json"))
+        .and_then(|m| m.modified())
+        .ok()
+}
+
 /// Write the global config
 pub fn write_config(config: &GlobalConfig) {
     let path = data_dir().join("config.json");
     if let Ok(json) = serde_json::to_string_pretty(config) {
         let _ = fs::write(path, json);
     }
+    let mut loaded = LOADED.lock().unwrap();
+    if loaded.is_some() {
+        *loaded = Some((config_modified(), Arc::new(read_config())));
+    }
 }
 
 /// Safari UA for the WKWebView browser (always Safari — it IS Safari)
@@ -93,11 +594,48 @@ pub fn get_browser_ua() -> String {
 
 /// Chrome UA for curl replay. Priority: config > fallback
 pub fn get_curl_ua() -> String {
-    read_config()
+    config()
         .user_agent
+        .clone()
         .unwrap_or_else(|| FALLBACK_CURL_UA.to_string())
 }
 
+/// An app's TLS profile: its own, else the global one, else chrome131.
+/// Unknown names fall back to the default.
+pub fn tls_profile(app_name: &str) -> &'static str {
+    let chosen = app_config(app_name)
+        .and_then(|c| c.tls_profile.clone())
+        .or_else(|| config().tls_profile.clone());
+    TLS_PROFILES
+        .iter()
+        .map(|(name, _)| *name)
+        .find(|name| chosen.as_deref() == Some(*name))
+        .unwrap_or(DEFAULT_TLS_PROFILE)
+}
+
+/// The curl-impersonate binary for a TLS profile, e.g. "curl_chrome131"
+pub fn curl_binary(profile: &str) -> &'static str {
+    TLS_PROFILES
+        .iter()
+        .find(|(name, _)| *name == profile)
+        .map(|(_, binary)| *binary)
+        .unwrap_or("curl_chrome131")
+}
+
+/// curl-impersonate binaries installed, in `TLS_PROFILES` order. Searches PATH plus
+/// Homebrew's bin folders, which a GUI app launched from Finder doesn't have on PATH.
+pub fn installed_curl_binaries() -> Vec<&'static str> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("PATH")
+        .map(|p| std::env::split_paths(&p).collect())
+        .unwrap_or_default();
+    dirs.extend(["/opt/homebrew/bin", "/usr/local/bin"].map(PathBuf::from));
+    TLS_PROFILES
+        .iter()
+        .map(|(_, binary)| *binary)
+        .filter(|binary| dirs.iter().any(|d| d.join(binary).is_file()))
+        .collect()
+}
+
 /// Find which app name a domain belongs to, if any
 pub fn find_app_for_domain(domain: &str) -> Option<String> {
     let apps_dir = data_dir().join("apps");
@@ -138,6 +676,146 @@ pub fn list_app_details() -> Vec<(String, Vec<String>)> {
         .unwrap_or_default()
 }
 
+/// Interception rules of every app that has any, grouped with the app's domains,
+/// as pushed into the browser's inject script: [{"app","domains","rules"}]
+pub fn intercept_rules() -> serde_json::Value {
+    let apps_dir = data_dir().join("apps");
+    let mut groups = Vec::new();
+    for entry in fs::read_dir(&apps_dir).into_iter().flatten().flatten() {
+        let config_path = entry.path().join("config.json");
+        let Some(app_cfg) = fs::read_to_string(&config_path)
+            .ok()
+            .and_then(|s| serde_json::from_str::<AppConfig>(&s).ok())
+        else {
+            continue;
+        };
+        if app_cfg.rules.is_empty() {
+            continue;
+        }
+        groups.push(serde_json::json!({
+            "app": entry.file_name().to_string_lossy(),
+            "domains": app_cfg.domains,
+            "rules": app_cfg.rules,
+        }));
+    }
+    serde_json::Value::Array(groups)
+}
+
+/// Each app's config.json as last parsed, with the file's modification time and size
+/// then, so the capture path doesn't parse it again until it changes
+static APP_CONFIGS: LazyLock<Mutex<HashMap<String, CachedAppConfig>>> =
+    LazyLock::new(Default::default);
+
+/// An app's parsed config, and config.json's modification time and size when read
+type CachedAppConfig = ((Option<SystemTime>, u64), Arc<AppConfig>);
+
+/// An app's config.json (None if it has none or it doesn't parse), read again only
+/// once the file has changed
+pub fn app_config(app_name: &str) -> Option<Arc<AppConfig>> {
+    let config_path = data_dir().join("apps").join(app_name).join("config.json");
+    let mut cached = APP_CONFIGS.lock().unwrap();
+    let Ok(meta) = fs::metadata(&config_path) else {
+        cached.remove(app_name);
+        return None;
+    };
+    let stamp = (meta.modified().ok(), meta.len());
+    if let Some((at, config)) = cached.get(app_name) {
+        if *at == stamp {
+            return Some(config.clone());
+        }
+    }
+    let config = fs::read_to_string(&config_path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<AppConfig>(&s).ok())
+        .map(Arc::new);
+    match &config {
+        Some(config) => {
+            cached.insert(app_name.to_string(), (stamp, config.clone()));
+        }
+        None => {
+            cached.remove(app_name);
+        }
+    }
+    config
+}
+
+/// An app's `capture_noise` opt-ins (empty if it has none)
+pub fn capture_noise(app_name: &str) -> Vec<String> {
+    app_config(app_name)
+        .map(|c| c.capture_noise.clone())
+        .unwrap_or_default()
+}
+
+/// An app's trim policy (the default if it has none)
+pub fn trim_policy(app_name: &str) -> TrimPolicy {
+    app_config(app_name)
+        .map(|c| c.trim.clone())
+        .unwrap_or_default()
+}
+
+/// An app's sampling policy, if it samples high-frequency endpoints
+pub fn sampling_policy(app_name: &str) -> Option<SamplingPolicy> {
+    app_config(app_name).and_then(|c| c.sampling.clone())
+}
+
+/// Sessions in a row an endpoint of `app_name` can be missing from before it's stale
+pub fn stale_after_sessions(app_name: &str) -> u32 {
+    app_config(app_name)
+        .and_then(|c| c.stale_after_sessions)
+        .unwrap_or(DEFAULT_STALE_AFTER_SESSIONS)
+}
+
+/// How long new captures wait for the background generation pass; None when it's off
+pub fn generate_interval() -> Option<std::time::Duration> {
+    match config()
+        .generate_every_minutes
+        .unwrap_or(DEFAULT_GENERATE_EVERY_MINUTES)
+    {
+        0 => None,
+        minutes => Some(std::time::Duration::from_secs(minutes * 60)),
+    }
+}
+
+/// An app's `userscripts/*.js` as (file name, source), and the domains they run on
+pub struct UserScripts {
+    pub domains: Vec<String>,
+    pub scripts: Vec<(String, String)>,
+}
+
+/// Every app's userscripts, sorted by file name within each app
+pub fn user_scripts() -> Vec<UserScripts> {
+    let apps_dir = data_dir().join("apps");
+    let mut result = Vec::new();
+    for entry in fs::read_dir(&apps_dir).into_iter().flatten().flatten() {
+        let Ok(scripts_dir) = fs::read_dir(entry.path().join("userscripts")) else {
+            continue;
+        };
+        let Some(app_cfg) = fs::read_to_string(entry.path().join("config.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str::<AppConfig>(&s).ok())
+        else {
+            continue;
+        };
+        let mut scripts: Vec<(String, String)> = scripts_dir
+            .flatten()
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "js"))
+            .filter_map(|e| {
+                let source = fs::read_to_string(e.path()).ok()?;
+                Some((e.file_name().to_string_lossy().to_string(), source))
+            })
+            .collect();
+        if scripts.is_empty() {
+            continue;
+        }
+        scripts.sort_by(|a, b| a.0.cmp(&b.0));
+        result.push(UserScripts {
+            domains: app_cfg.domains,
+            scripts,
+        });
+    }
+    result
+}
+
 /// List all known app names
 pub fn list_apps() -> Vec<String> {
     let apps_dir = data_dir().join("apps");
@@ -162,6 +840,15 @@ pub fn create_app(name: &str, domain: &str) -> PathBuf {
         domains: vec![domain.to_string()],
         created: chrono::Utc::now().to_rfc3339(),
         last_session: None,
+        rules: Vec::new(),
+        capture_noise: Vec::new(),
+        domain_groups: Vec::new(),
+        archived: false,
+        trim: TrimPolicy::default(),
+        sampling: None,
+        digests: Vec::new(),
+        tls_profile: None,
+        stale_after_sessions: None,
     };
 
     let config_path = app_dir.join("config.json");
@@ -172,6 +859,52 @@ pub fn create_app(name: &str, domain: &str) -> PathBuf {
     app_dir
 }
 
+/// Whether an app is archived (false if it has no readable config)
+pub fn is_archived(app_name: &str) -> bool {
+    app_config(app_name).is_some_and(|c| c.archived)
+}
+
+/// Set or clear an app's `archived` flag
+pub fn set_archived(app_name: &str, archived: bool) -> Result<(), String> {
+    let config_path = data_dir().join("apps").join(app_name).join("config.json");
+    let contents =
+        fs::read_to_string(&config_path).map_err(|_| format!("no app named \"{app_name}\""))?;
+    let mut config = serde_json::from_str::<AppConfig>(&contents).map_err(|e| e.to_string())?;
+    config.archived = archived;
+    let json = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    fs::write(config_path, json).map_err(|e| e.to_string())
+}
+
+/// Add a confirmed domain group (registrable domain) to an app
+pub fn add_domain_group_to_app(app_name: &str, group: &str) {
+    let config_path = data_dir().join("apps").join(app_name).join("config.json");
+    if let Ok(contents) = fs::read_to_string(&config_path) {
+        if let Ok(mut config) = serde_json::from_str::<AppConfig>(&contents) {
+            if !config.domain_groups.iter().any(|g| g == group) {
+                config.domain_groups.push(group.to_string());
+                if let Ok(json) = serde_json::to_string_pretty(&config) {
+                    let _ = fs::write(config_path, json);
+                }
+            }
+        }
+    }
+}
+
+/// Remove a domain from an app's config (no-op if it isn't there)
+pub fn remove_domain_from_app(app_name: &str, domain: &str) {
+    let config_path = data_dir().join("apps").join(app_name).join("config.json");
+    if let Ok(contents) = fs::read_to_string(&config_path) {
+        if let Ok(mut config) = serde_json::from_str::<AppConfig>(&contents) {
+            if config.domains.iter().any(|d| d == domain) {
+                config.domains.retain(|d| d != domain);
+                if let Ok(json) = serde_json::to_string_pretty(&config) {
+                    let _ = fs::write(config_path, json);
+                }
+            }
+        }
+    }
+}
+
 /// Add a domain to an existing app
 pub fn add_domain_to_app(app_name: &str, domain: &str) {
     let config_path = data_dir().join("apps").join(app_name).join("config.json");
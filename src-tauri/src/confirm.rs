@@ -0,0 +1,289 @@
+use crate::config;
+use crate::errors::CommandError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+/// A command held for the user: what it was (so an answer only releases that
+/// command) and their answer, once given
+struct Confirmation {
+    fingerprint: String,
+    allowed: Option<bool>,
+    asked: Instant,
+}
+
+/// A confirmation not resent within this long is forgotten
+const CONFIRMATION_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Confirmations asked for in this run, by id
+static CONFIRMATIONS: LazyLock<Mutex<HashMap<String, Confirmation>>> =
+    LazyLock::new(Default::default);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// How often a resent command checks for the user's answer
+const POLL: Duration = Duration::from_millis(250);
+
+/// Label of the element a click would hit: the ref's, or the selector's first match
+fn click_label_js(action: &str, cmd: &serde_json::Value) -> String {
+    let find = if action == "click_ref" {
+        format!(
+            "window.__hh && window.__hh.ref({})",
+            cmd.get("ref").and_then(|v| v.as_u64()).unwrap_or(0)
+        )
+    } else {
+        format!(
+            "document.querySelector({})",
+            serde_json::to_string(cmd.get("selector").and_then(|v| v.as_str()).unwrap_or(""))
+                .unwrap_or_default()
+        )
+    };
+    format!(
+        "(() => {{ const el = {find}; if (!el) return ''; \
+         return (el.getAttribute('aria-label') || el.innerText || el.value || el.title || '').substring(0, 200); }})()"
+    )
+}
+
+/// Lowercase words of `text`, so "Delete account" contains the phrase "delete"
+/// but "Display" doesn't contain "pay"
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// Script text compared ignoring case, whitespace and quote style
+fn squash(js: &str) -> String {
+    js.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '"' | '`' => '\'',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// Why this command needs the user's go-ahead under `policy`, and what it would act
+/// on (the clicked label, or the script), if it does
+fn reason(
+    app: &tauri::AppHandle,
+    window: &str,
+    action: &str,
+    cmd: &serde_json::Value,
+    timeout: Duration,
+    policy: &config::ConfirmPolicy,
+) -> Option<(String, String)> {
+    match action {
+        "click" | "click_ref" => {
+            // A label that can't be read might be "Delete": ask rather than click blind
+            let label = match crate::eval_js_with_timeout(
+                app,
+                window,
+                &click_label_js(action, cmd),
+                timeout,
+            ) {
+                Ok(label) => label,
+                Err(e) => {
+                    let target = cmd
+                        .get("selector")
+                        .or_else(|| cmd.get("ref"))
+                        .map(|v| v.to_string())
+                        .unwrap_or_default();
+                    return Some((
+                        format!(
+                            "clicks an element whose label couldn't be read ({})",
+                            e.code()
+                        ),
+                        target,
+                    ));
+                }
+            };
+            let label_words = words(&label);
+            let phrase = policy.click_labels.iter().find(|phrase| {
+                let phrase = words(phrase);
+                !phrase.is_empty() && label_words.windows(phrase.len()).any(|w| w == phrase)
+            })?;
+            Some((
+                format!("clicks an element labeled {phrase:?}"),
+                label.trim().to_string(),
+            ))
+        }
+        "eval" => {
+            let js = cmd.get("js").and_then(|v| v.as_str()).unwrap_or("");
+            let squashed = squash(js);
+            let pattern = policy
+                .eval_patterns
+                .iter()
+                .find(|p| !p.is_empty() && squashed.contains(&squash(p)))?;
+            Some((
+                format!("script contains {pattern:?}"),
+                js.chars().take(300).collect(),
+            ))
+        }
+        "fetch_in_page" => {
+            // Judged as the fetch() call it runs, by the same patterns as eval
+            let method = cmd.get("method").and_then(|v| v.as_str()).unwrap_or("GET");
+            let url = cmd.get("url").and_then(|v| v.as_str()).unwrap_or("");
+            let squashed = squash(&format!("fetch('{url}', {{method: '{method}'}})"));
+            let pattern = policy
+                .eval_patterns
+                .iter()
+                .find(|p| !p.is_empty() && squashed.contains(&squash(p)))?;
+            Some((
+                format!("request matches {pattern:?}"),
+                format!("{method} {url}"),
+            ))
+        }
+        "ws_send" => {
+            // Judged by the click phrases ({"op": "delete_item"}) and script patterns;
+            // a binary frame can't be read, so it always asks
+            if let Some(b64) = cmd.get("binary").and_then(|v| v.as_str()) {
+                return Some((
+                    "sends a binary WebSocket message".to_string(),
+                    format!("{} base64 chars", b64.len()),
+                ));
+            }
+            let message = cmd.get("message").and_then(|v| v.as_str()).unwrap_or("");
+            let message_words = words(message);
+            let squashed = squash(message);
+            let phrase = policy.click_labels.iter().find(|phrase| {
+                let phrase = words(phrase);
+                !phrase.is_empty() && message_words.windows(phrase.len()).any(|w| w == phrase)
+            });
+            let pattern = policy
+                .eval_patterns
+                .iter()
+                .find(|p| !p.is_empty() && squashed.contains(&squash(p)));
+            let matched = phrase.or(pattern)?;
+            Some((
+                format!("WebSocket message contains {matched:?}"),
+                message.chars().take(300).collect(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// The command minus what may differ between sending it and resending it
+fn fingerprint(action: &str, cmd: &serde_json::Value) -> String {
+    let mut cmd = cmd.clone();
+    if let Some(obj) = cmd.as_object_mut() {
+        for key in ["token", "confirmation", "timeout_ms", "stream"] {
+            obj.remove(key);
+        }
+    }
+    format!("{action} {cmd}")
+}
+
+/// Hold a destructive-looking command until the user allows it in the explorer window.
+/// The first send asks (a `confirm-action` prompt and a `confirmation-needed` event) and
+/// returns `confirmation_needed` with an id; resending the same command with
+/// `"confirmation": id` runs it once allowed, waiting up to `timeout` for the answer.
+/// None: go ahead.
+pub fn check(
+    app: &tauri::AppHandle,
+    window: &str,
+    action: &str,
+    cmd: &serde_json::Value,
+    timeout: Duration,
+) -> Option<serde_json::Value> {
+    let config = config::config();
+    let policy = &config.confirm;
+    if !policy.enabled {
+        return None;
+    }
+    let fingerprint = fingerprint(action, cmd);
+
+    if let Some(id) = cmd.get("confirmation").and_then(|v| v.as_str()) {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let answer = {
+                let mut pending = CONFIRMATIONS.lock().unwrap();
+                match pending.get(id) {
+                    Some(c) if c.fingerprint != fingerprint => {
+                        return Some(
+                            CommandError::InvalidParams(format!(
+                                "confirmation {id} was for a different command"
+                            ))
+                            .reply(),
+                        );
+                    }
+                    // An answer releases the command once
+                    Some(c) if c.allowed.is_some() => pending.remove(id).and_then(|c| c.allowed),
+                    Some(_) => None,
+                    None => {
+                        return Some(
+                            CommandError::NotFound(format!("no confirmation {id}")).reply(),
+                        )
+                    }
+                }
+            };
+            match answer {
+                Some(true) => return None,
+                Some(false) => {
+                    let mut reply =
+                        CommandError::Declined("the user declined this command".to_string())
+                            .reply();
+                    reply["confirmation"] = serde_json::Value::from(id);
+                    return Some(reply);
+                }
+                None if Instant::now() >= deadline => {
+                    return Some(serde_json::json!({
+                        "status": "confirmation_needed",
+                        "confirmation": id,
+                        "hint": "the user hasn't answered yet — ask them to allow it in the harharhar window, then resend",
+                    }))
+                }
+                None => std::thread::sleep(POLL),
+            }
+        }
+    }
+
+    let (reason, detail) = reason(app, window, action, cmd, timeout, policy)?;
+    let id = format!("c{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let mut pending = CONFIRMATIONS.lock().unwrap();
+    pending.retain(|_, c| c.asked.elapsed() < CONFIRMATION_TTL);
+    pending.insert(
+        id.clone(),
+        Confirmation {
+            fingerprint,
+            allowed: None,
+            asked: Instant::now(),
+        },
+    );
+    drop(pending);
+    let prompt = serde_json::json!({
+        "id": id,
+        "window": window,
+        "action": action,
+        "reason": reason,
+        "detail": detail,
+    });
+    let _ = app.emit("confirm-action", &prompt);
+    crate::events::emit("confirmation-needed", prompt);
+    Some(serde_json::json!({
+        "status": "confirmation_needed",
+        "confirmation": id,
+        "action": action,
+        "reason": reason,
+        "hint": format!("ask the user to allow it in the harharhar window, then resend the same command with \"confirmation\": \"{id}\""),
+    }))
+}
+
+/// The user's answer from the explorer window
+pub fn answer(id: &str, allowed: bool) -> Result<(), String> {
+    let mut pending = CONFIRMATIONS.lock().unwrap();
+    let confirmation = pending
+        .get_mut(id)
+        .ok_or_else(|| format!("no confirmation {id}"))?;
+    confirmation.allowed = Some(allowed);
+    drop(pending);
+    crate::events::emit(
+        "confirmation-answered",
+        serde_json::json!({"id": id, "allowed": allowed}),
+    );
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use crate::errors::CommandError;
+use std::time::Duration;
+
+/// Cookie/consent dialogs block read_ui and clicks, and their network calls are
+/// pure noise. This finds a dismiss button — known consent-platform selectors
+/// first, then a text heuristic inside anything that looks like a consent
+/// container — and clicks it. Prefers "reject"/"necessary only" over "accept".
+/// Returns {"dismissed": bool, "via": selector-or-text}.
+const DISMISS_JS: &str = r#"(() => {
+  const SELECTORS = [
+    '#onetrust-reject-all-handler',
+    '#onetrust-accept-btn-handler',
+    '#CybotCookiebotDialogBodyButtonDecline',
+    '#CybotCookiebotDialogBodyLevelButtonLevelOptinAllowAll',
+    '#didomi-notice-disagree-button',
+    '#didomi-notice-agree-button',
+    '.qc-cmp2-summary-buttons button[mode="secondary"]',
+    '.qc-cmp2-summary-buttons button[mode="primary"]',
+    '#truste-consent-required',
+    '#truste-consent-button',
+    '.osano-cm-denyAll',
+    '.osano-cm-accept-all',
+    '.cc-deny',
+    '.cc-dismiss',
+    '.cky-btn-reject',
+    '.cky-btn-accept',
+    '[data-testid="cookie-policy-manage-dialog-btn-reject-all"]',
+    '[data-cookiebanner="accept_only_essential_button"]',
+    'button[aria-label="Reject all"]',
+    'button[aria-label="Accept all"]'
+  ];
+  const visible = el => {
+    const r = el.getBoundingClientRect();
+    return r.width > 0 && r.height > 0 && getComputedStyle(el).visibility !== 'hidden';
+  };
+  for (const sel of SELECTORS) {
+    const el = document.querySelector(sel);
+    if (el && visible(el)) { el.click(); return JSON.stringify({dismissed: true, via: sel}); }
+  }
+
+  const CONTAINER = /cookie|consent|gdpr|privacy|cmp/i;
+  const TEXTS = [/^(reject|decline|refuse)( all)?$/i, /only (necessary|essential)|necessary only|essential only/i,
+    /^(accept|allow|agree)( all)?( cookies)?$/i, /^(i agree|got it|ok|okay)$/i];
+  const buttons = Array.from(document.querySelectorAll('button, [role="button"], a'))
+    .filter(el => visible(el))
+    .filter(el => {
+      for (let p = el; p && p !== document.body; p = p.parentElement) {
+        if (CONTAINER.test((p.id || '') + ' ' + (typeof p.className === 'string' ? p.className : ''))
+          || CONTAINER.test(p.getAttribute('aria-label') || '')) return true;
+      }
+      return false;
+    });
+  for (const re of TEXTS) {
+    const el = buttons.find(b => re.test((b.innerText || b.textContent || '').trim()));
+    if (el) { el.click(); return JSON.stringify({dismissed: true, via: (el.innerText || '').trim()}); }
+  }
+  return JSON.stringify({dismissed: false});
+})()"#;
+
+/// Banners often appear a moment after load, so try at these offsets after navigation.
+const ATTEMPT_DELAYS_MS: [u64; 3] = [1500, 3000, 6000];
+
+/// Try once to dismiss a consent dialog in a browser window.
+pub fn dismiss(app: &tauri::AppHandle, window: &str) -> Result<serde_json::Value, CommandError> {
+    let raw = crate::eval_js_with_timeout(app, window, DISMISS_JS, Duration::from_secs(5))?;
+    let result: serde_json::Value =
+        serde_json::from_str(&raw).unwrap_or_else(|_| serde_json::json!({"dismissed": false}));
+    if result
+        .get("dismissed")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+    {
+        crate::events::emit(
+            "consent-dismissed",
+            serde_json::json!({"window": window, "via": result.get("via")}),
+        );
+    }
+    Ok(result)
+}
+
+/// After a navigation, keep trying in the background until a dialog is dismissed
+/// or we run out of attempts. Only when `auto_dismiss_consent` is on in config.json.
+pub fn schedule_after_navigation(app: &tauri::AppHandle, window: &str) {
+    if !crate::config::config().auto_dismiss_consent {
+        return;
+    }
+    let app = app.clone();
+    let window = window.to_string();
+    std::thread::spawn(move || {
+        let mut waited = 0;
+        for delay in ATTEMPT_DELAYS_MS {
+            std::thread::sleep(Duration::from_millis(delay - waited));
+            waited = delay;
+            match dismiss(&app, &window) {
+                Ok(result) if result.get("dismissed").and_then(|v| v.as_bool()) == Some(true) => {
+                    return
+                }
+                Ok(_) => {}
+                Err(_) => return, // window closed
+            }
+        }
+    });
+}
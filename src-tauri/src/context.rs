@@ -0,0 +1,308 @@
+use crate::capture::{noise_opted_in, should_skip_capture};
+use crate::capture_index;
+use crate::config;
+use crate::endpoints::{self, EndpointCatalog};
+use std::fs;
+
+/// Parts of a bundle in order, with their share of the budget in percent. The
+/// shares add up as the bundle grows, so room a part doesn't use goes to the next.
+const PARTS: &[(&str, usize)] = &[
+    ("digest", 20),
+    ("auth", 15),
+    ("endpoints", 40),
+    ("examples", 25),
+];
+
+/// Endpoints listed before the budget is even considered
+const MAX_ENDPOINTS: usize = 25;
+
+/// Worked examples: the busiest endpoints with a successful capture
+const EXAMPLES: usize = 2;
+
+/// A part gets left out rather than squeezed below this many characters
+const MIN_PART_CHARS: usize = 200;
+
+/// A bounded-size markdown bundle about one app for pasting into an LLM prompt:
+/// digest (domains, workflows), the current session's auth, the top endpoints with
+/// their shapes, and worked request/response examples.
+///
+/// Sizes are estimated at 4 characters a token. Each part is cut to its running
+/// share of `budget_tokens` at a line boundary, with a marker saying how much was
+/// cut; bodies in examples are cut to a fixed share of theirs. The same captures
+/// always give the same cuts. Credentials are named, never included.
+pub fn bundle(app_name: &str, budget_tokens: usize) -> Result<String, String> {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    if !app_dir.is_dir() {
+        return Err(format!("no app named \"{app_name}\""));
+    }
+    let catalog: EndpointCatalog = fs::read_to_string(app_dir.join("endpoints.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let capture_noise = config::capture_noise(app_name);
+    let top: Vec<&endpoints::Endpoint> = catalog
+        .endpoints
+        .iter()
+        .filter(|ep| {
+            ep.observed_urls
+                .first()
+                .is_some_and(|u| !should_skip_capture(u) || noise_opted_in(u, &capture_noise))
+        })
+        .take(MAX_ENDPOINTS)
+        .collect();
+
+    let max_chars = budget_tokens * 4;
+    let mut out = String::new();
+    let mut allowed = 0;
+    for (part, share) in PARTS {
+        allowed += max_chars * share / 100;
+        let room = allowed.saturating_sub(out.len());
+        let text = match *part {
+            "digest" => {
+                let template = config::DigestTemplate {
+                    sections: vec!["domains".to_string(), "workflows".to_string()],
+                    ..Default::default()
+                };
+                crate::digest::render_for_app(app_name, &template)
+            }
+            "auth" => auth_part(app_name),
+            "endpoints" => endpoints_part(&top),
+            _ => examples_part(&app_dir, &top, room),
+        };
+        out.push_str(&fit(&text, room));
+    }
+    Ok(out)
+}
+
+/// `text` whole if it fits in `room` characters, else its leading lines and a marker
+fn fit(text: &str, room: usize) -> String {
+    if text.len() <= room {
+        return text.to_string();
+    }
+    if room < MIN_PART_CHARS {
+        return String::new();
+    }
+    // Keep room for the marker itself
+    let room = room - 60;
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = String::new();
+    let mut kept = 0;
+    for line in &lines {
+        if out.len() + line.len() + 1 > room {
+            break;
+        }
+        out.push_str(line);
+        out.push('\n');
+        kept += 1;
+    }
+    out.push_str(&format!(
+        "[... {} more lines cut to fit the budget]\n\n",
+        lines.len() - kept
+    ));
+    out
+}
+
+/// The current session: how fresh it is and what it authenticates with, by name
+fn auth_part(app_name: &str) -> String {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let session: config::SessionData =
+        fs::read_to_string(app_dir.join("sessions").join("latest.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+    let auth: Option<endpoints::AuthInfo> = fs::read_to_string(app_dir.join("auth.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok());
+
+    let mut md = String::from("## Auth\n");
+    if session.captured_at.is_empty() {
+        md.push_str("No session captured yet -- browse the app while logged in.\n\n");
+        return md;
+    }
+    let (age, is_stale) =
+        crate::digest::format_session_age(&session.captured_at, &chrono::Utc::now());
+    md.push_str(&format!(
+        "Session captured {} ({}) on {}. Values are in apps/{}/sessions/latest.json; send them with every request.\n",
+        session.captured_at, age, session.domain, app_name
+    ));
+    if is_stale {
+        md.push_str("- WARNING: session may be stale (>1 hour old)\n");
+    }
+    let names = |map: &std::collections::HashMap<String, String>| {
+        let mut names: Vec<&str> = map.keys().map(|k| k.as_str()).collect();
+        names.sort();
+        names.join(", ")
+    };
+    if !session.cookies.is_empty() {
+        md.push_str(&format!("- Cookies: {}\n", names(&session.cookies)));
+    }
+    if !session.auth_headers.is_empty() {
+        md.push_str(&format!("- Headers: {}\n", names(&session.auth_headers)));
+    }
+    if !session.csrf_tokens.is_empty() {
+        md.push_str(&format!("- CSRF: {}\n", names(&session.csrf_tokens)));
+    }
+    if !session.user_agent.is_empty() {
+        md.push_str(&format!("- User-Agent: {}\n", session.user_agent));
+    }
+    if let Some(auth) = auth {
+        if let Some(login_url) = auth.login_url {
+            md.push_str(&format!("- Login URL: {}\n", login_url));
+        }
+        if !auth.observed_refresh_endpoints.is_empty() {
+            md.push_str(&format!(
+                "- Token refresh: {}\n",
+                auth.observed_refresh_endpoints.join(", ")
+            ));
+        }
+    }
+    md.push('\n');
+    md
+}
+
+/// One short block per endpoint, busiest first: params and body shapes
+fn endpoints_part(top: &[&endpoints::Endpoint]) -> String {
+    if top.is_empty() {
+        return String::new();
+    }
+    let mut md = String::from(
+        "## Top endpoints\nBusiest first. Shapes show keys and types (str, num, bool).\n\n",
+    );
+    for ep in top {
+        md.push_str(&format!(
+            "### {}{} ({}x{}{}{})\n",
+            ep.name
+                .as_ref()
+                .map(|n| format!("{n}: "))
+                .unwrap_or_default(),
+            ep.pattern,
+            ep.times_seen,
+            if ep.auth_required { ", auth" } else { "" },
+            if ep.stale { ", stale" } else { "" },
+            if ep.polymorphic { ", polymorphic" } else { "" }
+        ));
+        if !ep.tags.is_empty() {
+            md.push_str(&format!("tags: {}\n", ep.tags.join(", ")));
+        }
+        if let Some(notes) = &ep.notes {
+            md.push_str(&format!("notes: {notes}\n"));
+        }
+        if let Some(url) = ep.observed_urls.first() {
+            md.push_str(&format!("e.g. {}\n", url));
+        }
+        if !ep.query_params.is_empty() {
+            md.push_str(&format!("query: {}\n", ep.query_params.join(", ")));
+        }
+        if let Some(shape) = &ep.request_shape {
+            md.push_str(&format!("request: {}\n", compact(shape, 300)));
+        }
+        if let Some(shape) = &ep.response_shape_sample {
+            let content_type = ep
+                .response_content_types
+                .first()
+                .map(|ct| ct.split(';').next().unwrap_or(ct).trim())
+                .unwrap_or("-");
+            md.push_str(&format!(
+                "response ({}): {}\n",
+                content_type,
+                compact(shape, 400)
+            ));
+        }
+        for (status, shape) in ep
+            .response_shapes
+            .iter()
+            .filter(|(status, _)| status.as_str() >= "400")
+        {
+            md.push_str(&format!("response {status}: {}\n", compact(shape, 200)));
+        }
+        for variant in &ep.shape_variants {
+            let mut diff: Vec<String> = variant.added.iter().map(|k| format!("+{k}")).collect();
+            diff.extend(variant.missing.iter().map(|k| format!("-{k}")));
+            md.push_str(&format!(
+                "variant ({}x): {}\n",
+                variant.times_seen,
+                diff.join(" ")
+            ));
+        }
+        md.push('\n');
+    }
+    md
+}
+
+/// The latest successful capture of the busiest endpoints, request and response,
+/// with bodies cut so every example gets an equal share of `room`
+fn examples_part(app_dir: &std::path::Path, top: &[&endpoints::Endpoint], room: usize) -> String {
+    let mut latest: Vec<Option<capture_index::Location>> = vec![None; top.len()];
+    for loc in capture_index::locations(app_dir) {
+        if !loc.status.is_some_and(|s| (200..300).contains(&s)) {
+            continue;
+        }
+        if let Some(i) = top.iter().position(|ep| ep.pattern == loc.pattern) {
+            latest[i] = Some(loc);
+        }
+    }
+    let examples: Vec<serde_json::Value> = latest
+        .iter()
+        .flatten()
+        .filter_map(|loc| capture_index::read(app_dir, loc))
+        .take(EXAMPLES)
+        .collect();
+    if examples.is_empty() {
+        return String::new();
+    }
+
+    // Headers, URL and markers take ~500 characters; bodies split the rest 1:2
+    let body_room = (room / examples.len()).saturating_sub(500);
+    let mut md =
+        String::from("## Worked examples\nReal captures; auth headers omitted (see Auth).\n\n");
+    for data in &examples {
+        let field = |k: &str| data.get(k).and_then(|v| v.as_str()).unwrap_or("");
+        md.push_str(&format!("### {} {}\n", field("method"), field("url")));
+        let content_type = |headers: &str| {
+            data.get(headers)
+                .and_then(|h| h.get("content-type"))
+                .and_then(|v| v.as_str())
+                .map(|ct| ct.split(';').next().unwrap_or(ct).trim().to_string())
+        };
+        if let Some(ct) = content_type("requestHeaders") {
+            md.push_str(&format!("Content-Type: {}\n", ct));
+        }
+        let request_body = field("requestBody");
+        if !request_body.is_empty() {
+            md.push_str(&format!("```\n{}\n```\n", cut(request_body, body_room / 3)));
+        }
+        md.push_str(&format!(
+            "-> {}{}\n",
+            data.get("status").and_then(|v| v.as_u64()).unwrap_or(0),
+            content_type("responseHeaders")
+                .map(|ct| format!(" ({ct})"))
+                .unwrap_or_default()
+        ));
+        let response_body = field("responseBody");
+        if !response_body.is_empty() {
+            md.push_str(&format!(
+                "```\n{}\n```\n",
+                cut(response_body, body_room - body_room / 3)
+            ));
+        }
+        md.push('\n');
+    }
+    md
+}
+
+/// Compact JSON, cut to `max` characters
+fn compact(value: &serde_json::Value, max: usize) -> String {
+    cut(&serde_json::to_string(value).unwrap_or_default(), max)
+}
+
+/// The first `max` characters of `s` (on a char boundary), marked if anything was cut
+fn cut(s: &str, max: usize) -> String {
+    if s.len() <= max {
+        return s.to_string();
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... [{} more chars]", &s[..end], s.len() - end)
+}
@@ -0,0 +1,214 @@
+use crate::errors::CommandError;
+use crate::AppState;
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+/// Link or button text that suggests a destructive or session-ending action.
+/// The crawler never follows or clicks these.
+const UNSAFE_PATTERN: &str = r"\b(logout|log out|sign ?out|signout|delete|remove|destroy|unsubscribe|deactivate|cancel|archive|pay|purchase|checkout|billing|send|submit)\b";
+
+/// Collect same-page links, then click "safe" in-page navigation (tabs, collapsed
+/// sections, <summary>) so lazy panels fire their API calls. Returns the links found.
+const EXPLORE_JS: &str = r#"(() => {
+  const UNSAFE = new RegExp(UNSAFE_PLACEHOLDER, 'i');
+  const label = el => ((el.innerText || el.textContent || '') + ' ' + (el.getAttribute('aria-label') || '')).trim();
+  const links = new Set();
+  document.querySelectorAll('a[href]').forEach(a => {
+    try {
+      const u = new URL(a.getAttribute('href'), location.href);
+      if (!/^https?:$/.test(u.protocol) || UNSAFE.test(label(a)) || UNSAFE.test(u.pathname)) return;
+      u.hash = '';
+      links.add(u.href);
+    } catch (_) {}
+  });
+  let clicked = 0;
+  if (CLICK_PLACEHOLDER) {
+    const targets = document.querySelectorAll('[role="tab"][aria-selected="false"], [aria-expanded="false"], details:not([open]) > summary');
+    for (const el of Array.from(targets).slice(0, 10)) {
+      if (el.closest('form') || el.tagName === 'A' && el.getAttribute('href') || UNSAFE.test(label(el))) continue;
+      try { el.click(); clicked++; } catch (_) {}
+    }
+  }
+  return JSON.stringify({links: Array.from(links), clicked: clicked});
+})()"#;
+
+/// Page state used to decide when a page has loaded and the network gone quiet.
+const LOAD_STATE_JS: &str = "(() => { try { performance.setResourceTimingBufferSize(10000); } catch (_) {} return JSON.stringify([document.readyState, performance.getEntriesByType('resource').length, location.href]); })()";
+
+pub struct CrawlOptions {
+    pub depth: usize,
+    pub max_pages: usize,
+    /// Quiet period (no new resource loads) that counts as network idle
+    pub idle: Duration,
+    /// Also click tabs / collapsed sections on each page
+    pub click: bool,
+}
+
+impl CrawlOptions {
+    pub fn from_cmd(cmd: &serde_json::Value) -> Self {
+        let num = |k: &str, default: u64| cmd.get(k).and_then(|v| v.as_u64()).unwrap_or(default);
+        CrawlOptions {
+            depth: num("depth", 2) as usize,
+            max_pages: num("max_pages", 20) as usize,
+            idle: Duration::from_millis(num("idle_ms", 1500)),
+            click: cmd.get("click").and_then(|v| v.as_bool()).unwrap_or(true),
+        }
+    }
+}
+
+/// Breadth-first crawl from the window's current page over the current app's
+/// domains, waiting for network idle on every page so its API calls get captured.
+/// Stops early if a CAPTCHA/2FA challenge shows up.
+pub fn run(
+    app: &tauri::AppHandle,
+    window: &str,
+    opts: &CrawlOptions,
+    progress: &dyn Fn(serde_json::Value),
+) -> serde_json::Value {
+    let start =
+        match crate::eval_js_with_timeout(app, window, "location.href", Duration::from_secs(5)) {
+            Ok(href) => href,
+            Err(e) => return e.reply(),
+        };
+    let Ok(start_url) = url::Url::parse(&start) else {
+        return CommandError::NavigationFailed(format!("can't crawl from {start}")).reply();
+    };
+
+    // Same-app = any domain mapped to the window's current app, plus the start host
+    let mut domains: HashSet<String> = start_url
+        .host_str()
+        .map(|h| h.to_string())
+        .into_iter()
+        .collect();
+    {
+        let state = app.state::<AppState>();
+        if let Some(app_name) = state.current_app(window) {
+            let map = state.domain_map.lock().unwrap();
+            domains.extend(
+                map.iter()
+                    .filter(|(_, a)| **a == app_name)
+                    .map(|(d, _)| d.clone()),
+            );
+        }
+    }
+
+    let explore_js = EXPLORE_JS
+        .replace(
+            "UNSAFE_PLACEHOLDER",
+            &serde_json::to_string(UNSAFE_PATTERN).unwrap(),
+        )
+        .replace(
+            "CLICK_PLACEHOLDER",
+            if opts.click { "true" } else { "false" },
+        );
+
+    let mut queue: VecDeque<(url::Url, usize)> = VecDeque::from([(start_url.clone(), 0)]);
+    let mut seen: HashSet<String> = HashSet::from([start_url.to_string()]);
+    let mut visited = Vec::new();
+    let mut clicked_total = 0;
+
+    while let Some((url, depth)) = queue.pop_front() {
+        if visited.len() >= opts.max_pages {
+            break;
+        }
+        if url != start_url {
+            crate::history::note_action(app, window, "crawl");
+            if let Err(e) = crate::open_browser(app, window, url.clone()) {
+                let mut reply = e.reply();
+                reply["visited"] = serde_json::json!(visited);
+                return reply;
+            }
+        }
+        wait_for_idle(app, window, opts.idle);
+
+        if let Some(challenge) = crate::challenge::check(app, window) {
+            return serde_json::json!({
+                "status": "human_needed",
+                "challenge": challenge,
+                "visited": visited,
+            });
+        }
+
+        let found: serde_json::Value =
+            crate::eval_js_with_timeout(app, window, &explore_js, Duration::from_secs(10))
+                .ok()
+                .and_then(|r| serde_json::from_str(&r).ok())
+                .unwrap_or_default();
+        let clicked = found.get("clicked").and_then(|v| v.as_u64()).unwrap_or(0);
+        if clicked > 0 {
+            clicked_total += clicked;
+            wait_for_idle(app, window, opts.idle);
+        }
+
+        let mut queued = 0;
+        if depth < opts.depth {
+            for link in found
+                .get("links")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+            {
+                let Some(parsed) = link.as_str().and_then(|l| url::Url::parse(l).ok()) else {
+                    continue;
+                };
+                let same_app = parsed.host_str().is_some_and(|h| domains.contains(h));
+                if same_app && seen.insert(parsed.to_string()) {
+                    queue.push_back((parsed, depth + 1));
+                    queued += 1;
+                }
+            }
+        }
+
+        visited.push(url.to_string());
+        progress(serde_json::json!({
+            "event": "page",
+            "url": url.as_str(),
+            "depth": depth,
+            "clicked": clicked,
+            "queued": queued,
+            "visited": visited.len(),
+        }));
+    }
+
+    serde_json::json!({
+        "ok": true,
+        "pages": visited.len(),
+        "clicked": clicked_total,
+        "remaining": queue.len(),
+        "visited": visited,
+    })
+}
+
+/// Wait until the page has loaded and no new resources have started for `idle`
+/// (capped at 15s per page so a chatty page can't stall the crawl).
+fn wait_for_idle(app: &tauri::AppHandle, window: &str, idle: Duration) {
+    let deadline = Instant::now() + Duration::from_secs(15);
+    let mut last: Option<(u64, String)> = None;
+    let mut quiet_since = Instant::now();
+    // Give the navigation a moment to replace the old document
+    std::thread::sleep(Duration::from_millis(300));
+    while Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(250));
+        let Ok(raw) =
+            crate::eval_js_with_timeout(app, window, LOAD_STATE_JS, Duration::from_secs(2))
+        else {
+            continue; // mid-navigation
+        };
+        let Ok(serde_json::Value::Array(s)) = serde_json::from_str::<serde_json::Value>(&raw)
+        else {
+            continue;
+        };
+        let ready = s.first().and_then(|v| v.as_str()) == Some("complete");
+        let current = (
+            s.get(1).and_then(|v| v.as_u64()).unwrap_or(0),
+            s.get(2).and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        );
+        if last.as_ref() != Some(&current) {
+            last = Some(current);
+            quiet_since = Instant::now();
+        } else if ready && quiet_since.elapsed() >= idle {
+            return;
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Where the value an app sends in a CSRF header came from, and how to get a fresh one
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CsrfSource {
+    /// The request header, e.g. "X-CSRF-Token"
+    pub header: String,
+    /// "cookie", "meta_tag", "hidden_input", "response_header", "response_body", or
+    /// "unknown" when no earlier capture had the value
+    pub source: String,
+    /// The cookie, meta tag, input or response header holding it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Dotted path to it in a JSON response, e.g. "data.csrfToken"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_path: Option<String>,
+    /// The endpoint whose response carried it ("GET /settings")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// A URL that endpoint was fetched at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Requests that sent the header, and how many of those values were traced to this source
+    pub requests: usize,
+    pub traced: usize,
+    /// What to do before a replay, in words
+    pub recipe: String,
+}
+
+/// Token values shorter than this are too likely to turn up by chance
+const MIN_TOKEN_LEN: usize = 8;
+
+/// Values remembered as possible token sources, first appearance each
+const MAX_INDEXED: usize = 50_000;
+
+/// Names of headers, JSON keys, meta tags and inputs that might hold a CSRF token
+pub(crate) fn looks_like_token(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    ["csrf", "xsrf", "token", "nonce", "authenticity"]
+        .iter()
+        .any(|p| lower.contains(p))
+}
+
+/// Request headers carrying a CSRF token (the same test the session merge uses)
+pub(crate) fn is_csrf_header(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    lower.contains("csrf") || lower.contains("xsrf")
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Origin {
+    source: &'static str,
+    name: Option<String>,
+    json_path: Option<String>,
+    endpoint: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Default)]
+struct HeaderUses {
+    requests: usize,
+    traced: HashMap<Origin, usize>,
+}
+
+/// Follows CSRF header values back to where they first appeared, over an app's captures
+/// in order: each request's CSRF headers are looked up among the values earlier captures
+/// set (cookies) or returned (meta tags, hidden inputs, response headers, JSON fields)
+#[derive(Default)]
+pub(crate) struct CsrfTrace {
+    index: HashMap<String, Origin>,
+    uses: BTreeMap<String, HeaderUses>,
+}
+
+impl CsrfTrace {
+    /// Look up the CSRF headers a capture sent, then remember what it could have handed
+    /// out. `endpoint` is its "METHOD /pattern", None for entries that aren't requests.
+    pub(crate) fn observe(&mut self, data: &serde_json::Value, endpoint: Option<&str>) {
+        if let Some(headers) = data.get("requestHeaders").and_then(|h| h.as_object()) {
+            // Cookies the page could read and copy into a header (double-submit), the
+            // request's own included
+            if let Some(cookies) = headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("cookie"))
+                .and_then(|(_, v)| v.as_str())
+            {
+                for (name, value) in cookies.split(';').filter_map(|p| p.trim().split_once('=')) {
+                    self.remember(value, "cookie", Some(name.trim()), None, None, None);
+                }
+            }
+            for (name, value) in headers {
+                let Some(value) = value.as_str().filter(|_| is_csrf_header(name)) else {
+                    continue;
+                };
+                let uses = self.uses.entry(name.clone()).or_default();
+                uses.requests += 1;
+                if let Some(origin) = self.index.get(value.trim()) {
+                    *uses.traced.entry(origin.clone()).or_default() += 1;
+                }
+            }
+        }
+
+        let Some(endpoint) = endpoint else {
+            return;
+        };
+        let url = data.get("url").and_then(|v| v.as_str());
+        if let Some(headers) = data.get("responseHeaders").and_then(|h| h.as_object()) {
+            for (name, value) in headers {
+                let Some(value) = value.as_str() else {
+                    continue;
+                };
+                if name.eq_ignore_ascii_case("set-cookie") {
+                    for line in value.lines() {
+                        let pair = line.split(';').next().and_then(|p| p.split_once('='));
+                        if let Some((cookie, value)) = pair {
+                            self.remember(value, "cookie", Some(cookie.trim()), None, None, None);
+                        }
+                    }
+                } else if looks_like_token(name) {
+                    self.remember(
+                        value,
+                        "response_header",
+                        Some(name),
+                        None,
+                        Some(endpoint),
+                        url,
+                    );
+                }
+            }
+        }
+        // Tags a preview-cut page held past its cut (see capture::apply_response_policy)
+        for token in data
+            .get("responseTokens")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let field = |k: &str| token.get(k).and_then(|v| v.as_str()).unwrap_or("");
+            let source = if field("tag") == "meta" {
+                "meta_tag"
+            } else {
+                "hidden_input"
+            };
+            self.remember(
+                field("value"),
+                source,
+                Some(field("name")),
+                None,
+                Some(endpoint),
+                url,
+            );
+        }
+        let Some(body) = data.get("responseBody").and_then(|v| v.as_str()) else {
+            return;
+        };
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(body) {
+            let mut leaves = Vec::new();
+            token_leaves(&json, "", &mut leaves);
+            for (path, value) in leaves {
+                self.remember(
+                    &value,
+                    "response_body",
+                    None,
+                    Some(path),
+                    Some(endpoint),
+                    url,
+                );
+            }
+        } else if body.contains("<meta") || body.contains("<input") {
+            for (tag, name, value) in crate::page_text::named_values(body) {
+                if looks_like_token(&name) {
+                    let source = if tag == "meta" {
+                        "meta_tag"
+                    } else {
+                        "hidden_input"
+                    };
+                    self.remember(&value, source, Some(&name), None, Some(endpoint), url);
+                }
+            }
+        }
+    }
+
+    fn remember(
+        &mut self,
+        value: &str,
+        source: &'static str,
+        name: Option<&str>,
+        json_path: Option<String>,
+        endpoint: Option<&str>,
+        url: Option<&str>,
+    ) {
+        let value = value.trim();
+        if value.len() < MIN_TOKEN_LEN
+            || self.index.len() >= MAX_INDEXED
+            || self.index.contains_key(value)
+        {
+            return;
+        }
+        self.index.insert(
+            value.to_string(),
+            Origin {
+                source,
+                name: name.map(String::from),
+                json_path,
+                endpoint: endpoint.map(String::from),
+                url: url.map(String::from),
+            },
+        );
+    }
+
+    /// Each CSRF header's most common source
+    pub(crate) fn finish(self) -> Vec<CsrfSource> {
+        self.uses
+            .into_iter()
+            .map(|(header, uses)| {
+                let best = uses.traced.into_iter().max_by_key(|(_, count)| *count);
+                let traced = best.as_ref().map_or(0, |(_, count)| *count);
+                let origin = best.map(|(origin, _)| origin).unwrap_or(Origin {
+                    source: "unknown",
+                    name: None,
+                    json_path: None,
+                    endpoint: None,
+                    url: None,
+                });
+                CsrfSource {
+                    recipe: recipe(&header, &origin),
+                    header,
+                    source: origin.source.to_string(),
+                    name: origin.name,
+                    json_path: origin.json_path,
+                    endpoint: origin.endpoint,
+                    url: origin.url,
+                    requests: uses.requests,
+                    traced,
+                }
+            })
+            .collect()
+    }
+}
+
+fn recipe(header: &str, origin: &Origin) -> String {
+    let name = origin.name.as_deref().unwrap_or("");
+    let fetch = match (&origin.url, &origin.endpoint) {
+        (Some(url), _) => format!("GET {url}"),
+        (None, Some(endpoint)) => endpoint.clone(),
+        (None, None) => String::new(),
+    };
+    match origin.source {
+        "cookie" => format!("send the current value of cookie {name} as {header}"),
+        "meta_tag" => format!("{fetch} and send <meta name=\"{name}\"> content as {header}"),
+        "hidden_input" => format!("{fetch} and send <input name=\"{name}\"> value as {header}"),
+        "response_header" => format!("{fetch} and send its {name} response header as {header}"),
+        "response_body" => format!(
+            "{fetch} and send {} from the JSON response as {header}",
+            origin.json_path.as_deref().unwrap_or("")
+        ),
+        _ => format!(
+            "no earlier capture had the {header} value — reuse the one in sessions/latest.json"
+        ),
+    }
+}
+
+/// String values under token-like keys, with their dotted paths (up to 4 levels deep)
+fn token_leaves(value: &serde_json::Value, prefix: &str, out: &mut Vec<(String, String)>) {
+    if prefix.matches('.').count() >= 3 {
+        return;
+    }
+    match value {
+        serde_json::Value::Object(obj) => {
+            for (k, v) in obj {
+                let path = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{prefix}.{k}")
+                };
+                match v {
+                    serde_json::Value::String(s) if looks_like_token(k) => {
+                        out.push((path, s.clone()))
+                    }
+                    _ => token_leaves(v, &path, out),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for (i, item) in items.iter().take(20).enumerate() {
+                token_leaves(item, &format!("{prefix}[{i}]"), out);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,57 @@
+use crate::errors::CommandError;
+
+/// The URL scheme harharhar registers: `harharhar://open?url=<page>&app=<name>`
+pub const SCHEME: &str = "harharhar";
+
+/// A page to open, and the app it's meant for, if the link said
+pub struct Link {
+    pub url: String,
+    pub app: Option<String>,
+}
+
+/// Parse `harharhar://open?url=...&app=...`
+pub fn parse(link: &str) -> Result<Link, CommandError> {
+    let parsed = url::Url::parse(link)
+        .map_err(|e| CommandError::InvalidParams(format!("bad link {link:?}: {e}")))?;
+    if parsed.scheme() != SCHEME || parsed.host_str() != Some("open") {
+        return Err(CommandError::InvalidParams(format!(
+            "not a {SCHEME}://open link: {link}"
+        )));
+    }
+    let param = |key: &str| {
+        parsed
+            .query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.into_owned())
+            .filter(|v| !v.is_empty())
+    };
+    let Some(url) = param("url") else {
+        return Err(CommandError::InvalidParams(format!(
+            "link has no url: {link}"
+        )));
+    };
+    Ok(Link {
+        url,
+        app: param("app"),
+    })
+}
+
+/// The link that opens `page` in harharhar, for `app`
+pub fn link(page: &str, app: &str) -> String {
+    let mut link = url::Url::parse(&format!("{SCHEME}://open")).expect("static link base");
+    link.query_pairs_mut()
+        .append_pair("url", page)
+        .append_pair("app", app);
+    link.to_string()
+}
+
+/// A link the OS handed the running app (macOS delivers them to it rather than
+/// launching a second process): open it as `harharhar <link>` would
+pub fn open(app: &tauri::AppHandle, link: &str) {
+    let result = parse(link).and_then(|l| {
+        crate::open_url(app, &l.url, l.app.as_deref()).map_err(CommandError::NavigationFailed)
+    });
+    if let Err(e) = result {
+        eprintln!("[harharhar] {e}");
+    }
+}
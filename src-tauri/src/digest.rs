@@ -1,4 +1,5 @@
-use crate::capture::should_skip_capture;
+use crate::capture::{is_noise, noise_opted_in, should_skip_capture};
+use crate::capture_io;
 use crate::config;
 use crate::endpoints::{self, EndpointCatalog};
 use std::collections::HashMap;
@@ -21,11 +22,68 @@ struct Workflow {
     timestamp: String,
 }
 
-/// Generate digest.md for a given app.
+/// Digest sections, in the order an agent digest has them
+const SECTIONS: &[&str] = &[
+    "domains",
+    "workflows",
+    "endpoints",
+    "resources",
+    "auth",
+    "session",
+    "reference",
+];
+
+/// Sections a "human" digest has unless its template lists them
+const HUMAN_SECTIONS: &[&str] = &["domains", "resources", "endpoints", "workflows", "auth"];
+
+/// Room kept at the end of a budgeted digest for the note saying what was cut
+const CUT_NOTE_CHARS: usize = 120;
+
+/// What a digest is rendered from, read once for all of an app's templates
+struct DigestData {
+    catalog: EndpointCatalog,
+    app_config: Option<config::AppConfig>,
+    session: config::SessionData,
+    auth: Option<endpoints::AuthInfo>,
+    workflows: Vec<Workflow>,
+    capture_noise: Vec<String>,
+}
+
+/// Generate the digests for a given app: digest.md, or the files its config's
+/// `digests` templates ask for.
 /// Reads endpoints.json, config.json, sessions/latest.json, and captures/*.jsonl
-/// to produce a concise markdown summary for AI agents.
+/// to produce concise markdown summaries for AI agents (or people).
 pub fn generate_for_app(app_name: &str) {
     let app_dir = config::data_dir().join("apps").join(app_name);
+    let data = load(app_name);
+    let templates = data
+        .app_config
+        .as_ref()
+        .map(|c| c.digests.clone())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| vec![config::DigestTemplate::default()]);
+
+    for template in &templates {
+        // A bare .md file name, so a template can't overwrite endpoints.json or escape the app
+        let file = template.file.as_str();
+        if !file.ends_with(".md") || std::path::Path::new(file).file_name() != Some(file.as_ref()) {
+            eprintln!("[harharhar] {app_name}: digest file must be a .md file name, not {file:?}");
+            continue;
+        }
+        let md = render(app_name, &data, template);
+        if let Ok(mut out) = fs::File::create(app_dir.join(file)) {
+            let _ = out.write_all(md.as_bytes());
+        }
+    }
+}
+
+/// One digest for `app_name` as markdown, without writing it
+pub(crate) fn render_for_app(app_name: &str, template: &config::DigestTemplate) -> String {
+    render(app_name, &load(app_name), template)
+}
+
+fn load(app_name: &str) -> DigestData {
+    let app_dir = config::data_dir().join("apps").join(app_name);
 
     // 1. Read endpoints.json
     let endpoints_path = app_dir.join("endpoints.json");
@@ -54,22 +112,50 @@ pub fn generate_for_app(app_name: &str) {
         .and_then(|s| serde_json::from_str(&s).ok());
 
     // 5. Build workflows from captures
-    let workflows = build_workflows(&app_dir);
+    let capture_noise: Vec<String> = app_config
+        .as_ref()
+        .map(|c| c.capture_noise.clone())
+        .unwrap_or_default();
+    let workflows = build_workflows(&app_dir, &capture_noise);
+
+    DigestData {
+        catalog,
+        app_config,
+        session,
+        auth,
+        workflows,
+        capture_noise,
+    }
+}
 
-    // 6. Build the markdown
+/// Build the markdown for one template. With a token budget, sections go in whole
+/// while they fit; the first that doesn't is cut at a line boundary (if its heading
+/// and a line of content fit) and every later section is left out, so the same
+/// data always gives the same digest.
+fn render(app_name: &str, data: &DigestData, template: &config::DigestTemplate) -> String {
+    let human = template.audience == "human";
     let now = chrono::Utc::now();
     let timestamp = now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
 
     let mut md = String::new();
 
     // Header
-    md.push_str(&format!("# {} -- API Digest\n", app_name));
-    md.push_str(&format!(
-        "> Auto-generated by harharhar. Last updated: {}\n",
-        timestamp
-    ));
+    if human {
+        md.push_str(&format!("# {} API\n", app_name));
+        md.push_str(&format!(
+            "> Generated by harharhar from traffic recorded in the browser. Last updated: {}\n",
+            timestamp
+        ));
+    } else {
+        md.push_str(&format!("# {} -- API Digest\n", app_name));
+        md.push_str(&format!(
+            "> Auto-generated by harharhar. Last updated: {}\n",
+            timestamp
+        ));
+    }
 
     // Session freshness line
+    let session = &data.session;
     let (age_str, is_stale) = format_session_age(&session.captured_at, &now);
     if !session.captured_at.is_empty() {
         md.push_str(&format!(
@@ -79,46 +165,145 @@ pub fn generate_for_app(app_name: &str) {
     }
     md.push('\n');
 
-    // Domains
-    if let Some(ref cfg) = app_config {
+    let default_sections = if human { HUMAN_SECTIONS } else { SECTIONS };
+    let sections: Vec<&str> = if template.sections.is_empty() {
+        default_sections.to_vec()
+    } else {
+        template.sections.iter().map(|s| s.as_str()).collect()
+    };
+    let budget = template.max_tokens.map(|t| t * 4);
+
+    let mut left_out: Vec<&str> = Vec::new();
+    let mut full = false;
+    for name in sections {
+        if full {
+            left_out.push(name);
+            continue;
+        }
+        let text = match name {
+            "domains" => domains_section(data, human),
+            "workflows" => workflows_section(data, human),
+            "endpoints" => endpoints_section(data, human, template.max_endpoints.unwrap_or(30)),
+            "resources" => resources_section(data, human),
+            "auth" => auth_section(data, human),
+            "session" => session_section(data, &age_str, is_stale),
+            "reference" => reference_section(human),
+            _ => continue,
+        };
+        let Some(max) = budget else {
+            md.push_str(&text);
+            continue;
+        };
+        let room = max.saturating_sub(md.len() + CUT_NOTE_CHARS);
+        if text.len() <= room {
+            md.push_str(&text);
+            continue;
+        }
+        match cut_section(&text, room) {
+            Some(cut) => md.push_str(&cut),
+            None => left_out.push(name),
+        }
+        // Nothing after a cut section, even if it would fit
+        full = true;
+    }
+    if !left_out.is_empty() {
+        md.push_str(&format!(
+            "\n> Left out to stay under {} tokens: {}\n",
+            template.max_tokens.unwrap_or_default(),
+            left_out.join(", ")
+        ));
+    }
+    md
+}
+
+/// The lines of a section that fit in `room` characters, with a marker for the rest.
+/// None when not even the heading and one line after it fit.
+fn cut_section(text: &str, room: usize) -> Option<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = String::new();
+    let mut kept = 0;
+    for line in &lines {
+        if out.len() + line.len() + 1 > room {
+            break;
+        }
+        out.push_str(line);
+        out.push('\n');
+        kept += 1;
+    }
+    if kept < 2 {
+        return None;
+    }
+    let rest = lines[kept..].iter().filter(|l| !l.is_empty()).count();
+    if rest > 0 {
+        out.push_str(&format!("... ({} more lines cut)\n", rest));
+    }
+    out.push('\n');
+    Some(out)
+}
+
+fn domains_section(data: &DigestData, human: bool) -> String {
+    let mut md = String::new();
+    if let Some(ref cfg) = data.app_config {
         if !cfg.domains.is_empty() {
             md.push_str("## Domains\n");
+            if human {
+                md.push_str("Hosts the app's traffic was recorded from.\n\n");
+            }
             for d in &cfg.domains {
                 md.push_str(&format!("- {}\n", d));
             }
             md.push('\n');
         }
     }
+    md
+}
 
-    // Observed Workflows
-    if !workflows.is_empty() {
-        md.push_str("## Observed Workflows\nActions observed in the browser and the API calls they triggered.\n\n");
+fn workflows_section(data: &DigestData, human: bool) -> String {
+    let mut md = String::new();
+    if !data.workflows.is_empty() {
+        if human {
+            md.push_str("## Observed Workflows\nWhat happened on the wire when someone used the app: each row is a click or \
+                         input in the browser and the API calls made within two seconds of it.\n\n");
+        } else {
+            md.push_str("## Observed Workflows\nActions observed in the browser and the API calls they triggered.\n\n");
+        }
         md.push_str("| Action | API Calls Triggered |\n");
         md.push_str("|--------|-------------------|\n");
-        for wf in &workflows {
-            let action_desc = format_action_description(&wf.action_type, &wf.role, &wf.label, &wf.value);
+        for wf in &data.workflows {
+            let action_desc =
+                format_action_description(&wf.action_type, &wf.role, &wf.label, &wf.value);
             let calls = wf.triggered_calls.join(", ");
             md.push_str(&format!("| {} | {} |\n", action_desc, calls));
         }
         md.push('\n');
     }
+    md
+}
 
-    // Key Endpoints — top 30, skip noise
-    let filtered_endpoints: Vec<&endpoints::Endpoint> = catalog
+/// Key endpoints — top `limit`, skip noise
+fn endpoints_section(data: &DigestData, human: bool, limit: usize) -> String {
+    let mut md = String::new();
+    let filtered_endpoints: Vec<&endpoints::Endpoint> = data
+        .catalog
         .endpoints
         .iter()
         .filter(|ep| {
             // Skip endpoints with no observed URLs or whose URLs are noise
             ep.observed_urls
                 .first()
-                .map(|u| !should_skip_capture(u))
+                .map(|u| !should_skip_capture(u) || noise_opted_in(u, &data.capture_noise))
                 .unwrap_or(false)
         })
-        .take(30)
+        .take(limit)
         .collect();
 
     if !filtered_endpoints.is_empty() {
-        md.push_str("## Key Endpoints\nRanked by frequency. Noise endpoints filtered out.\n\n");
+        if human {
+            md.push_str("## Key Endpoints\nThe most-used endpoints, busiest first. IDs in paths are shown as \
+                         `{id}`; \"Freq\" is how many calls were recorded. Analytics and other noise is left out.\n\n");
+        } else {
+            md.push_str("## Key Endpoints\nRanked by frequency. Noise endpoints filtered out.\n\n");
+        }
         md.push_str("| # | Endpoint | Freq | Auth | Response Type |\n");
         md.push_str("|---|----------|------|------|---------------|\n");
         for (i, ep) in filtered_endpoints.iter().enumerate() {
@@ -132,10 +317,20 @@ pub fn generate_for_app(app_name: &str) {
                 .first()
                 .map(|ct| shorten_content_type(ct))
                 .unwrap_or_else(|| "-".to_string());
+            let mut endpoint = match &ep.name {
+                Some(name) => format!("`{name}` {}", ep.pattern),
+                None => ep.pattern.clone(),
+            };
+            if ep.stale {
+                endpoint.push_str(" (stale)");
+            }
+            if ep.polymorphic {
+                endpoint.push_str(" (polymorphic)");
+            }
             md.push_str(&format!(
                 "| {} | {} | {}x | {} | {} |\n",
                 i + 1,
-                ep.pattern,
+                endpoint,
                 ep.times_seen,
                 auth_str,
                 resp_type
@@ -143,9 +338,43 @@ pub fn generate_for_app(app_name: &str) {
         }
         md.push('\n');
     }
+    md
+}
 
-    // Auth Summary
+fn resources_section(data: &DigestData, human: bool) -> String {
+    let resources = &data.catalog.resources;
+    if resources.is_empty() {
+        return String::new();
+    }
+    let mut md = String::from("## Resources\n");
+    if human {
+        md.push_str("Endpoints grouped by what they act on. Operations: list, get, create, update, replace \
+                     (PUT), delete, or an action on one item.\n\n");
+    } else {
+        md.push_str("Endpoints grouped by collection (see `resources` in endpoints.json).\n\n");
+    }
+    md.push_str("| Resource | Path | Operations |\n");
+    md.push_str("|----------|------|------------|\n");
+    for (name, resource) in resources {
+        let ops: Vec<&str> = resource.operations.keys().map(|op| op.as_str()).collect();
+        md.push_str(&format!(
+            "| {} | {} | {} |\n",
+            name,
+            resource.path,
+            ops.join(", ")
+        ));
+    }
+    md.push('\n');
+    md
+}
+
+fn auth_section(data: &DigestData, human: bool) -> String {
+    let session = &data.session;
+    let mut md = String::new();
     md.push_str("## Auth Summary\n");
+    if human {
+        md.push_str("How requests are authenticated. Values are in sessions/latest.json; only names are shown here.\n\n");
+    }
     // Cookies
     if !session.cookies.is_empty() {
         let cookie_names: Vec<&String> = session.cookies.keys().collect();
@@ -194,7 +423,7 @@ pub fn generate_for_app(app_name: &str) {
         ));
     }
     // Additional auth mechanisms from auth.json
-    if let Some(ref auth_info) = auth {
+    if let Some(ref auth_info) = data.auth {
         for mech in &auth_info.mechanisms {
             if mech.mech_type == "cookie" {
                 // Already covered above from session
@@ -211,10 +440,25 @@ pub fn generate_for_app(app_name: &str) {
                 auth_info.observed_refresh_endpoints.join(", ")
             ));
         }
+        if !auth_info.session_duration_estimate.is_empty()
+            && auth_info.session_duration_estimate != "unknown"
+        {
+            md.push_str(&format!(
+                "- **Session lasts**: {}\n",
+                auth_info.session_duration_estimate
+            ));
+        }
+        for csrf in auth_info.csrf.iter().filter(|c| c.traced > 0) {
+            md.push_str(&format!("- **Fresh {}**: {}\n", csrf.header, csrf.recipe));
+        }
     }
     md.push('\n');
+    md
+}
 
-    // Session Status
+fn session_section(data: &DigestData, age_str: &str, is_stale: bool) -> String {
+    let session = &data.session;
+    let mut md = String::new();
     md.push_str("## Session Status\n");
     if !session.domain.is_empty() {
         md.push_str(&format!("- Domain: {}\n", session.domain));
@@ -231,57 +475,50 @@ pub fn generate_for_app(app_name: &str) {
         md.push_str("- WARNING: Session may be stale (>1 hour old) -- re-browse to refresh\n");
     }
     md.push('\n');
+    md
+}
 
-    // Quick Reference
+fn reference_section(human: bool) -> String {
+    let mut md = String::new();
     md.push_str("## Quick Reference\n");
-    md.push_str("See `examples.sh` for copy-paste curl commands.\n");
+    if human {
+        md.push_str("Files next to this one in the app folder:\n");
+    }
+    md.push_str("See `examples.sh` for copy-paste curl commands (`examples-inpage.md` for endpoints curl can't replay).\n");
     md.push_str("See `endpoints.json` for full endpoint catalog with response shapes.\n");
     md.push_str("See `captures/` for raw API traffic.\n");
+    md
+}
 
-    // Write digest.md
-    let digest_path = app_dir.join("digest.md");
-    if let Ok(mut file) = fs::File::create(&digest_path) {
-        let _ = file.write_all(md.as_bytes());
+/// Labels of the UI actions (or the annotations naming them) that set off each endpoint,
+/// by "METHOD /pattern": "Send" for `POST /api/messages`
+pub(crate) fn action_labels(app_name: &str) -> HashMap<String, Vec<String>> {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+    for wf in build_workflows(&app_dir, &config::capture_noise(app_name)) {
+        let label = wf.label.trim();
+        if label.is_empty() {
+            continue;
+        }
+        for call in wf.triggered_calls {
+            let known = labels.entry(call).or_default();
+            if known.len() < 10 && !known.iter().any(|l| l == label) {
+                known.push(label.to_string());
+            }
+        }
     }
+    labels
 }
 
 /// Build workflow entries from all JSONL capture files.
 /// For each ui-action entry, collect the next API calls within 2 seconds
 /// as "triggered" calls, normalize their URLs to endpoint patterns,
 /// deduplicate, and sort by recency.
-fn build_workflows(app_dir: &std::path::Path) -> Vec<Workflow> {
-    let captures_dir = app_dir.join("captures");
-    let entries = match fs::read_dir(&captures_dir) {
-        Ok(e) => e,
-        Err(_) => return vec![],
-    };
-
-    // Collect all JSONL file paths, sorted by name (chronological)
-    let mut jsonl_files: Vec<std::path::PathBuf> = entries
-        .flatten()
-        .filter(|e| {
-            e.path()
-                .extension()
-                .and_then(|ext| ext.to_str())
-                == Some("jsonl")
-        })
-        .map(|e| e.path())
-        .collect();
-    jsonl_files.sort();
-
-    // Parse all entries from all files
-    let mut all_entries: Vec<serde_json::Value> = Vec::new();
-    for file_path in &jsonl_files {
-        let contents = match fs::read_to_string(file_path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
-        for line in contents.lines() {
-            if let Ok(data) = serde_json::from_str::<serde_json::Value>(line) {
-                all_entries.push(data);
-            }
-        }
-    }
+fn build_workflows(app_dir: &std::path::Path, capture_noise: &[String]) -> Vec<Workflow> {
+    // All entries from all files, oldest session first, streamed line by line
+    let all_entries = capture_io::capture_files(app_dir)
+        .into_iter()
+        .flat_map(|path| capture_io::entries(&path));
 
     // Walk through entries, correlating ui-actions with following API calls.
     // Track the current annotation — when present, it overrides the auto-generated
@@ -290,7 +527,7 @@ fn build_workflows(app_dir: &std::path::Path) -> Vec<Workflow> {
     let mut current_action: Option<(Workflow, f64)> = None; // (workflow, action_epoch_ms)
     let mut current_annotation: Option<String> = None;
 
-    for entry in &all_entries {
+    for entry in all_entries {
         let entry_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
 
         if entry_type == "annotation" {
@@ -377,7 +614,7 @@ fn build_workflows(app_dir: &std::path::Path) -> Vec<Workflow> {
             if diff_ms >= 0.0 && diff_ms <= 2000.0 && wf.triggered_calls.len() < 5 {
                 // Get the URL and normalize it
                 if let Some(url_str) = entry.get("url").and_then(|v| v.as_str()) {
-                    if should_skip_capture(url_str) {
+                    if is_noise(entry_type, url_str, capture_noise) {
                         continue;
                     }
                     if let Ok(parsed) = url::Url::parse(url_str) {
@@ -454,7 +691,10 @@ fn parse_timestamp_ms(ts: &str) -> f64 {
 
 /// Format the age of a session as human-readable text.
 /// Returns (age_string, is_stale).
-fn format_session_age(captured_at: &str, now: &chrono::DateTime<chrono::Utc>) -> (String, bool) {
+pub(crate) fn format_session_age(
+    captured_at: &str,
+    now: &chrono::DateTime<chrono::Utc>,
+) -> (String, bool) {
     let dt = if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(captured_at) {
         dt.with_timezone(&chrono::Utc)
     } else {
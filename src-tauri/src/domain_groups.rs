@@ -0,0 +1,112 @@
+use crate::config;
+use crate::events;
+use crate::AppState;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use tauri::{Emitter, Manager};
+
+/// Groups already suggested this run, so the user is asked once per registrable domain
+static SUGGESTED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+/// Registrable domain of a host per the public suffix list:
+/// "mail.google.com" -> "google.com", "foo.github.io" -> "foo.github.io"
+pub fn registrable_domain(host: &str) -> String {
+    psl::domain_str(host).unwrap_or(host).to_string()
+}
+
+/// App a host belongs to through a confirmed domain group. The host is added to
+/// that app's domains on the way, so it's mapped directly from then on.
+pub fn resolve(app: &tauri::AppHandle, host: &str) -> Option<String> {
+    let state = app.state::<AppState>();
+    let group = registrable_domain(host);
+    let name = state.domain_groups.lock().unwrap().get(&group).cloned()?;
+    config::add_domain_to_app(&name, host);
+    state
+        .domain_map
+        .lock()
+        .unwrap()
+        .insert(host.to_string(), name.clone());
+    events::emit(
+        "domain-grouped",
+        serde_json::json!({"domain": host, "group": group, "app": name}),
+    );
+    Some(name)
+}
+
+/// A host shares its registrable domain with hosts of exactly one app and that
+/// group isn't confirmed yet: suggest grouping it (`domain-group-suggested`).
+pub fn suggest(app: &tauri::AppHandle, host: &str) {
+    let state = app.state::<AppState>();
+    let group = registrable_domain(host);
+    if state.domain_groups.lock().unwrap().contains_key(&group) {
+        return;
+    }
+    let (owners, hosts) = {
+        let map = state.domain_map.lock().unwrap();
+        let siblings: Vec<(&String, &String)> = map
+            .iter()
+            .filter(|(d, _)| d.as_str() != host && registrable_domain(d) == group)
+            .collect();
+        let owners: HashSet<String> = siblings.iter().map(|(_, a)| a.to_string()).collect();
+        let mut hosts: Vec<String> = siblings.iter().map(|(d, _)| d.to_string()).collect();
+        hosts.push(host.to_string());
+        hosts.sort();
+        (owners, hosts)
+    };
+    if owners.len() != 1 {
+        return;
+    }
+    if !SUGGESTED
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashSet::new)
+        .insert(group.clone())
+    {
+        return;
+    }
+    let suggestion = serde_json::json!({
+        "app": owners.into_iter().next(),
+        "group": group,
+        "hosts": hosts,
+    });
+    let _ = app.emit("domain-group-suggested", &suggestion);
+    events::emit("domain-group-suggested", suggestion);
+}
+
+/// Make every host under `group` part of `app_name` — existing buffered captures
+/// from those hosts are flushed into the app. Returns the hosts added now.
+pub fn confirm(app: &tauri::AppHandle, app_name: &str, group: &str) -> Result<Vec<String>, String> {
+    let group = registrable_domain(group.trim().trim_start_matches("*."));
+    let state = app.state::<AppState>();
+    if let Some(owner) = state.domain_groups.lock().unwrap().get(&group) {
+        if owner != app_name {
+            return Err(format!("{group} is already grouped under \"{owner}\""));
+        }
+    }
+    if !config::list_apps().iter().any(|a| a == app_name) {
+        return Err(format!("no app named \"{app_name}\""));
+    }
+    config::add_domain_group_to_app(app_name, &group);
+    state
+        .domain_groups
+        .lock()
+        .unwrap()
+        .insert(group.clone(), app_name.to_string());
+
+    // Hosts under the group still waiting for a decision
+    let waiting: Vec<String> = state
+        .unmapped_captures
+        .lock()
+        .unwrap()
+        .keys()
+        .filter(|d| registrable_domain(d) == group)
+        .cloned()
+        .collect();
+    for host in &waiting {
+        if crate::check_domain_conflict(app, host, app_name).is_ok() {
+            resolve(app, host);
+            crate::capture::flush_unmapped(app, host, app_name, &state.session_ts);
+        }
+    }
+    Ok(waiting)
+}
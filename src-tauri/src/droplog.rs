@@ -0,0 +1,82 @@
+use crate::config;
+use std::fs;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// debug.log rolls over to debug.log.1 past this size
+const MAX_LOG_BYTES: u64 = 2_000_000;
+
+/// Serializes appends (and rollover) across IPC threads
+static LOG_LOCK: Mutex<()> = Mutex::new(());
+
+/// Note why a capture was dropped in `debug.log` — only when `capture_debug` is
+/// on in config.json. `reason` is one of "xhr-start", "no_url", "noise_domain",
+/// "noise_extension", "noise_path", "beacon", "no_auth", "bad_url", "unknown_domain".
+pub fn record(data: &serde_json::Value, reason: &str) {
+    if !config::config().capture_debug {
+        return;
+    }
+    let field = |k: &str| data.get(k).and_then(|v| v.as_str()).unwrap_or("");
+    let entry = serde_json::json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "reason": reason,
+        "type": field("type"),
+        "method": field("method"),
+        "url": field("url"),
+        "window": field("window"),
+    });
+
+    let _lock = LOG_LOCK.lock().unwrap();
+    let path = config::data_dir().join("debug.log");
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+        let _ = fs::rename(&path, config::data_dir().join("debug.log.1"));
+    }
+    if let Ok(mut f) = fs::OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = writeln!(f, "{}", entry);
+    }
+}
+
+/// Recent drops whose URL contains `url` (newest first), plus what the noise
+/// filters say about `url` right now if it's a full URL.
+pub fn why_dropped(url: &str, limit: usize) -> serde_json::Value {
+    let dir = config::data_dir();
+    let mut lines: Vec<serde_json::Value> = Vec::new();
+    for name in ["debug.log.1", "debug.log"] {
+        let contents = fs::read_to_string(dir.join(name)).unwrap_or_default();
+        lines.extend(
+            contents
+                .lines()
+                .filter_map(|l| serde_json::from_str(l).ok()),
+        );
+    }
+    let drops: Vec<&serde_json::Value> = lines
+        .iter()
+        .rev()
+        .filter(|e| {
+            e.get("url")
+                .and_then(|v| v.as_str())
+                .is_some_and(|u| u.contains(url))
+        })
+        .take(limit)
+        .collect();
+
+    let mut result = serde_json::json!({
+        "capture_debug": config::config().capture_debug,
+        "drops": drops,
+    });
+    if let Ok(parsed) = url::Url::parse(url) {
+        let app = config::find_app_for_domain(parsed.host_str().unwrap_or(""));
+        let opted_in = app
+            .as_deref()
+            .is_some_and(|a| crate::capture::noise_opted_in(url, &config::capture_noise(a)));
+        result["filters"] = serde_json::json!({
+            "noise": crate::capture::noise_reason(url),
+            "capture_noise_opt_in": opted_in,
+            "app": app,
+        });
+    }
+    if !result["capture_debug"].as_bool().unwrap_or(false) {
+        result["note"] = serde_json::json!("capture_debug is off — turn it on with {\"action\":\"capture_debug\",\"on\":true} and reproduce");
+    }
+    result
+}
@@ -1,31 +1,245 @@
-use crate::capture::should_skip_capture;
+use crate::capture::{noise_opted_in, should_skip_capture};
+use crate::capture_io;
 use crate::config;
+use crate::errors::CommandError;
+use crate::events;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Sessions a path without parameters must answer 404/410 in (with no success between)
+/// before it's marked stale
+const MIN_GONE_SESSIONS: usize = 2;
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct EndpointCatalog {
     pub endpoints: Vec<Endpoint>,
+    /// Sections added by WASM analyzers, keyed by analyzer name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extensions: BTreeMap<String, serde_json::Value>,
+    /// How each host was reached, where the capture recorded it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub transport: BTreeMap<String, Transport>,
+    /// Deployments of the same API on different hosts (prod, staging, ...): environment
+    /// name -> base URL. Empty unless two or more hosts serve the same paths.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environments: BTreeMap<String, String>,
+    /// REST-style resources the endpoints add up to, by name ("messages"): only those
+    /// with at least two operations
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub resources: BTreeMap<String, Resource>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Resource {
+    /// The collection's path pattern, e.g. "/api/v1/messages"
+    pub path: String,
+    /// Operation -> endpoint pattern. Operations are list, create, get, update (PATCH or
+    /// POST to an item), replace (PUT), delete, or the name of an action posted to an item
+    /// ("POST /messages/{id}/archive" is "archive").
+    pub operations: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Transport {
+    /// Captures per negotiated protocol, e.g. {"h2": 120, "h3": 4}
+    #[serde(default)]
+    pub protocols: BTreeMap<String, u32>,
+    /// Remote "ip:port"s seen (up to 5; attached Chrome only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remote_addresses: Vec<String>,
+    /// TLS versions and cipher suites seen (attached Chrome only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tls_versions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ciphers: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Endpoint {
     pub pattern: String,
+    /// Friendly name given with `name_endpoint` ("send_message"), kept across regenerations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Tags given with `name_endpoint`, kept across regenerations
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Free-form notes, kept across regenerations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Someone checked the endpoint works as described; kept across regenerations
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub verified: bool,
+    /// Fields added to endpoints.json by hand that generation doesn't write, kept as they are
+    #[serde(flatten)]
+    pub custom: BTreeMap<String, serde_json::Value>,
     pub methods: Vec<String>,
     pub observed_urls: Vec<String>,
     #[serde(default)]
     pub query_params: Vec<String>,
+    /// Keys of url-encoded form bodies (login and legacy form posts)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub form_params: Vec<String>,
     #[serde(default)]
     pub request_content_types: Vec<String>,
     #[serde(default)]
     pub response_content_types: Vec<String>,
+    /// Sample request body shape: JSON keys/types, or {"multipart": [parts]} for uploads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_shape: Option<serde_json::Value>,
+    /// Shape of the first successful response; an error body's only until one comes
     #[serde(default)]
     pub response_shape_sample: Option<serde_json::Value>,
+    /// First response shape seen per status code: {"200": shape, "401": shape}
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub response_shapes: BTreeMap<String, serde_json::Value>,
+    /// Successful JSON responses whose top-level keys differ from the first one's, one
+    /// entry per distinct key set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shape_variants: Vec<ShapeVariant>,
+    /// The response comes in more than one shape (see `shape_variants`): check which
+    /// keys are there before reading them
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub polymorphic: bool,
+    /// Response arrives as a stream (NDJSON, SSE, token-by-token)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub streaming: bool,
+    /// Request headers computed per request (signatures): a curl replay with captured
+    /// values is refused, the request has to be made from the page
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub computed_headers: Vec<String>,
+    /// Responses are files (images, PDFs, archives) rather than text: a download endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary: Option<BinaryResponses>,
+    /// Retry/idempotency behaviour, when the app was seen retrying or sending an idempotency key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry: Option<RetrySemantics>,
+    /// Validators the server sends, when it sends any: conditional requests can skip
+    /// re-downloading unchanged data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditional: Option<ConditionalSupport>,
+    /// Rate limits the server advertised or enforced (429s)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
     pub auth_required: bool,
     pub times_seen: u32,
     pub last_seen: String,
+    /// No longer in use, as far as captures tell: see `stale_reason`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+    /// "not seen in the last 3 sessions" or "answered 410 in the last 2 sessions it was called in"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RetrySemantics {
+    /// Safe to re-send: an idempotent method, an idempotency key, or the app retries it itself
+    pub safe_to_retry: bool,
+    /// Header (e.g. "Idempotency-Key") or JSON body key (e.g. "body.clientMutationId")
+    /// that carries an idempotency key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// The key changes per operation — generate a fresh one for each new call and
+    /// reuse it only when retrying that same call
+    #[serde(default)]
+    pub regenerate_key: bool,
+    /// Identical requests re-sent after a failed attempt
+    #[serde(default)]
+    pub retries_seen: u32,
+    /// Statuses the app retried after (0 = network error)
+    #[serde(default)]
+    pub retried_statuses: Vec<u64>,
+    /// Delays before the longest observed run of retries, in ms
+    #[serde(default)]
+    pub backoff_ms: Vec<u64>,
+    /// "exponential", "fixed" or "varied" (needs two retries in a row)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backoff: Option<String>,
+}
+
+/// What's known of an endpoint's binary responses, from captures' `responseBinary`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BinaryResponses {
+    pub content_types: Vec<String>,
+    pub min_bytes: u64,
+    pub max_bytes: u64,
+    /// Distinct bodies seen (by SHA-256), up to 20: 1 means the same file every time
+    pub distinct: usize,
+    #[serde(skip)]
+    hashes: BTreeSet<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShapeVariant {
+    /// Top-level keys this shape has that the first response didn't
+    #[serde(default)]
+    pub added: Vec<String>,
+    /// Top-level keys of the first response this shape lacks
+    #[serde(default)]
+    pub missing: Vec<String>,
+    pub shape: serde_json::Value,
+    pub times_seen: u32,
+    pub last_seen: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ConditionalSupport {
+    /// Responses carry an `ETag`: send it back as `If-None-Match`
+    #[serde(default)]
+    pub etag: bool,
+    /// Responses carry `Last-Modified`: send it back as `If-Modified-Since`
+    #[serde(default)]
+    pub last_modified: bool,
+    /// The app itself sent `If-None-Match` / `If-Modified-Since`
+    #[serde(default)]
+    pub revalidated_by_app: bool,
+    /// 304 Not Modified responses seen — proof the server honours the validators
+    #[serde(default)]
+    pub not_modified_seen: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct RateLimit {
+    /// Rate-limit headers seen on responses, e.g. "x-ratelimit-remaining"
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// Smallest request budget advertised (`X-RateLimit-Limit` / `RateLimit-Limit`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+    /// Lowest `X-RateLimit-Remaining` seen
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lowest_remaining: Option<u64>,
+    /// 429 Too Many Requests responses seen
+    #[serde(default)]
+    pub throttled_seen: u32,
+    /// Longest `Retry-After` seen, in seconds (HTTP dates are converted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_s: Option<u64>,
+}
+
+/// Retry evidence gathered for one endpoint while reading captures
+#[derive(Default)]
+struct RetryStats {
+    idempotency_key: Option<String>,
+    key_values: std::collections::HashSet<String>,
+    keyed_calls: u32,
+    retries: u32,
+    statuses: Vec<u64>,
+    longest_run: Vec<u64>,
+}
+
+/// The previous request to an endpoint, to recognise a retry of it
+struct Attempt {
+    /// URL + body
+    fingerprint: String,
+    at_ms: i64,
+    status: u64,
+    /// Delays of the retry run this attempt belongs to
+    run: Vec<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -35,8 +249,104 @@ pub struct AuthInfo {
     pub login_url: Option<String>,
     #[serde(default)]
     pub observed_refresh_endpoints: Vec<String>,
+    /// From `cookie_rotation`: how long an auth cookie's value stays good, e.g. "~12h
+    /// (Max-Age of sid)", or "unknown"
     #[serde(default)]
     pub session_duration_estimate: String,
+    /// How each auth cookie's value changed across captures
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cookie_rotation: Vec<CookieRotation>,
+    /// Request headers with a new value on every request: signatures computed by the page
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub computed_headers: Vec<crate::computed_headers::ComputedHeader>,
+    /// Where each CSRF header's value comes from, to fetch a fresh one before a replay
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub csrf: Vec<crate::csrf::CsrfSource>,
+    /// How each WebSocket endpoint authenticates — not visible in HTTP captures
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub websocket: Vec<WebSocketAuth>,
+    /// Changes in how the app authenticates, oldest first (the last 50)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<AuthChange>,
+}
+
+/// One auth cookie's observed lifetime, from the Cookie headers requests carried
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CookieRotation {
+    pub name: String,
+    pub first_seen: String,
+    pub last_seen: String,
+    /// Capture sessions it was sent in
+    pub sessions: usize,
+    /// Distinct values seen
+    pub values: usize,
+    /// Times the value changed from one request to the next
+    pub rotations: usize,
+    /// How long a value was sent before being replaced (values still current aren't counted)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub median_lifetime_secs: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_lifetime_secs: Option<i64>,
+    /// No longer sent: the latest requests to every host it went to came without it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub gone: bool,
+    /// From the last Set-Cookie seen for it: max_age, expires, http_only, secure, same_site
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+/// What the captures showed of one cookie so far
+#[derive(Default)]
+struct CookieTrack {
+    first_seen: String,
+    last_seen: String,
+    sessions: BTreeSet<usize>,
+    hosts: BTreeSet<String>,
+    value: String,
+    /// When the current value was first sent
+    value_since: Option<chrono::DateTime<chrono::FixedOffset>>,
+    values: std::collections::HashSet<String>,
+    rotations: usize,
+    lifetimes: Vec<i64>,
+    attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthChange {
+    pub detected_at: String,
+    /// e.g. "header X-Api-Key added", "cookie sid removed", "GET /api/me now requires auth"
+    pub changes: Vec<String>,
+}
+
+/// An endpoint keeps this many `shape_variants`; further shapes still flag it polymorphic
+const MAX_SHAPE_VARIANTS: usize = 5;
+
+/// auth.json keeps this many entries of `history`
+const AUTH_HISTORY_LEN: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebSocketAuth {
+    /// host + normalized path, e.g. "realtime.example.com/socket/{id}"
+    pub url_pattern: String,
+    pub example_url: String,
+    /// Any of "query_param", "first_message", "cookie"; empty if nothing looked like auth
+    pub methods: Vec<String>,
+    #[serde(default)]
+    pub query_params: Vec<String>,
+    #[serde(default)]
+    pub cookies: Vec<String>,
+    /// Paths of token-like keys in the first frame the page sent, e.g. "payload.token"
+    #[serde(default)]
+    pub first_message_keys: Vec<String>,
+    #[serde(default)]
+    pub first_message_shape: Option<serde_json::Value>,
+}
+
+/// What we saw of one WebSocket connection
+#[derive(Default)]
+struct SocketObservation {
+    cookie: String,
+    first_message: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,9 +356,51 @@ pub struct AuthMechanism {
     pub details: HashMap<String, serde_json::Value>,
 }
 
-const AUTH_HEADER_NAMES: &[&str] = &["authorization", "x-csrf-token", "x-xsrf-token"];
-const AUTH_COOKIE_PATTERNS: &[&str] = &[
-    "session", "sid", "token", "auth", "csrf", "xsrf", "jwt",
+pub(crate) const AUTH_HEADER_NAMES: &[&str] = &["authorization", "x-csrf-token", "x-xsrf-token"];
+pub(crate) const AUTH_COOKIE_PATTERNS: &[&str] =
+    &["session", "sid", "token", "auth", "csrf", "xsrf", "jwt"];
+/// A repeat of a failed request within this long counts as a retry
+const RETRY_WINDOW_MS: i64 = 60_000;
+/// HTTP methods that are safe to re-send by definition
+const IDEMPOTENT_METHODS: &[&str] = &["GET", "HEAD", "OPTIONS", "PUT", "DELETE"];
+/// JSON body keys used as idempotency keys (GraphQL/Relay, payment APIs)
+const IDEMPOTENCY_BODY_KEYS: &[&str] = &[
+    "idempotencykey",
+    "idempotency_key",
+    "clientmutationid",
+    "client_mutation_id",
+];
+/// Host labels that name a non-production environment, and the name to use for it
+const ENVIRONMENT_LABELS: &[(&str, &str)] = &[
+    ("staging", "staging"),
+    ("stage", "staging"),
+    ("stg", "staging"),
+    ("preprod", "preprod"),
+    ("uat", "uat"),
+    ("qa", "qa"),
+    ("test", "test"),
+    ("testing", "test"),
+    ("dev", "dev"),
+    ("develop", "dev"),
+    ("development", "dev"),
+    ("sandbox", "sandbox"),
+    ("demo", "demo"),
+    ("localhost", "local"),
+    ("127", "local"),
+];
+/// Query-param / message-key names that suggest a credential
+const WS_TOKEN_NAMES: &[&str] = &[
+    "token",
+    "auth",
+    "ticket",
+    "jwt",
+    "key",
+    "session",
+    "sig",
+    "access",
+    "bearer",
+    "password",
+    "credential",
 ];
 
 /// Process all captures for an app and generate endpoints.json + auth.json
@@ -56,33 +408,75 @@ pub fn generate_for_app(app_name: &str) {
     let app_dir = config::data_dir().join("apps").join(app_name);
     let captures_dir = app_dir.join("captures");
 
-    let entries = match fs::read_dir(&captures_dir) {
-        Ok(e) => e,
-        Err(_) => return,
-    };
+    if !captures_dir.is_dir() {
+        return;
+    }
 
     let mut endpoints: HashMap<String, Endpoint> = HashMap::new();
     let mut seen_cookies: HashMap<String, String> = HashMap::new();
+    let mut cookie_tracks: BTreeMap<String, CookieTrack> = BTreeMap::new();
+    let mut csrf_trace = crate::csrf::CsrfTrace::default();
+    let mut header_trace = crate::computed_headers::HeaderTrace::default();
+    // Host -> cookie names in the latest Cookie header sent to it
+    let mut latest_cookies: HashMap<String, BTreeSet<String>> = HashMap::new();
     let mut seen_auth_headers: HashMap<String, String> = HashMap::new();
     let mut login_urls: Vec<String> = Vec::new();
     let mut refresh_urls: Vec<String> = Vec::new();
+    let mut sockets: Vec<(String, SocketObservation)> = Vec::new();
+    let mut retry_stats: HashMap<String, RetryStats> = HashMap::new();
+    let mut transport: BTreeMap<String, Transport> = BTreeMap::new();
+    // Origin -> (captures, endpoint keys), to spot the same API on several hosts
+    let mut origins: HashMap<String, (u32, std::collections::HashSet<String>)> = HashMap::new();
 
-    // Read all JSONL capture files
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
-            continue;
-        }
-        let contents = match fs::read_to_string(&path) {
-            Ok(c) => c,
-            Err(_) => continue,
-        };
+    // Endpoint key -> (last session it was seen in, status it last answered with)
+    let mut latest: HashMap<String, (usize, u64)> = HashMap::new();
+    // Endpoint key -> sessions it answered 404/410 in since it last succeeded
+    let mut gone_in: HashMap<String, BTreeSet<usize>> = HashMap::new();
+    // Endpoint key -> top-level keys of its first successful JSON object response
+    let mut first_keys: HashMap<String, BTreeSet<String>> = HashMap::new();
+    // Endpoint keys whose response_shape_sample is from a successful response
+    let mut ok_sampled: std::collections::HashSet<String> = std::collections::HashSet::new();
 
-        for line in contents.lines() {
-            let data: serde_json::Value = match serde_json::from_str(line) {
-                Ok(d) => d,
-                Err(_) => continue,
-            };
+    // Read all JSONL capture files, one per session
+    let capture_files = capture_io::capture_files(&app_dir);
+    let sessions = capture_files.len();
+    for (session, path) in capture_files.iter().enumerate() {
+        // Last request per endpoint within this capture file
+        let mut last_attempts: HashMap<String, Attempt> = HashMap::new();
+
+        for data in capture_io::entries(path) {
+            // WebSocket handshakes and frames feed auth.json's websocket section, not endpoints
+            let entry_type = data.get("type").and_then(|v| v.as_str()).unwrap_or("");
+            if entry_type.starts_with("ws-") {
+                let url = data
+                    .get("url")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string();
+                if entry_type == "ws-open" {
+                    let cookie = data
+                        .get("requestHeaders")
+                        .and_then(|h| h.get("cookie"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("")
+                        .to_string();
+                    sockets.push((
+                        url,
+                        SocketObservation {
+                            cookie,
+                            first_message: None,
+                        },
+                    ));
+                } else if entry_type.ends_with("-out") {
+                    let body = data.get("requestBody").and_then(|v| v.as_str());
+                    if let Some((_, obs)) = sockets.iter_mut().rev().find(|(u, _)| *u == url) {
+                        if obs.first_message.is_none() {
+                            obs.first_message = body.map(|b| b.to_string());
+                        }
+                    }
+                }
+                continue;
+            }
 
             // Skip cookie-only entries
             if data.get("type").and_then(|v| v.as_str()) == Some("cookies") {
@@ -100,7 +494,15 @@ pub fn generate_for_app(app_name: &str) {
                             seen_cookies.insert(name, value);
                         }
                     }
+                    track_cookies(
+                        &data,
+                        cookie_str,
+                        session,
+                        &mut cookie_tracks,
+                        &mut latest_cookies,
+                    );
                 }
+                csrf_trace.observe(&data, None);
                 continue;
             }
 
@@ -165,10 +567,20 @@ pub fn generate_for_app(app_name: &str) {
                                     seen_cookies.insert(name, value);
                                 }
                             }
+                            track_cookies(
+                                &data,
+                                cookie_str,
+                                session,
+                                &mut cookie_tracks,
+                                &mut latest_cookies,
+                            );
                         }
                     }
                 }
             }
+            track_set_cookies(&data, &mut cookie_tracks);
+            csrf_trace.observe(&data, Some(&key));
+            header_trace.observe(&data, &key, session);
 
             // Detect login/refresh endpoints
             let path_lower = path.to_lowercase();
@@ -192,25 +604,87 @@ pub fn generate_for_app(app_name: &str) {
                 .unwrap_or("")
                 .to_string();
 
-            // Get a sample response shape (first 3 levels of keys for JSON)
-            let response_shape = data
-                .get("responseBody")
-                .and_then(|v| v.as_str())
-                .and_then(|body| serde_json::from_str::<serde_json::Value>(body).ok())
-                .map(|v| extract_shape(&v, 0));
+            // Request body shape and content type (without the multipart boundary)
+            let req_ct = data
+                .get("requestHeaders")
+                .and_then(|h| h.as_object())
+                .and_then(|h| {
+                    h.iter()
+                        .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+                })
+                .and_then(|(_, v)| v.as_str())
+                .map(|v| v.split(';').next().unwrap_or("").trim().to_string())
+                .unwrap_or_default();
+            let request_shape = request_shape(&data);
+
+            let streamed = data
+                .get("streamed")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let response_json = response_json(&data);
+            let response_shape = response_json.as_ref().map(|v| extract_shape(v, 0));
+
+            track_retry(
+                &data,
+                url_str,
+                &timestamp,
+                &key,
+                &mut retry_stats,
+                &mut last_attempts,
+            );
+            if let Some(host) = parsed.host_str() {
+                track_transport(&data, host, &mut transport);
+                let origin = match parsed.port() {
+                    Some(port) => format!("{}://{host}:{port}", parsed.scheme()),
+                    None => format!("{}://{host}", parsed.scheme()),
+                };
+                let (count, keys) = origins.entry(origin).or_default();
+                *count += 1;
+                keys.insert(key.clone());
+            }
+
+            let status = data.get("status").and_then(|v| v.as_u64()).unwrap_or(0);
+            let last = latest.entry(key.clone()).or_insert((session, status));
+            last.0 = session;
+            if status > 0 {
+                last.1 = status;
+            }
+            if status == 404 || status == 410 {
+                gone_in.entry(key.clone()).or_default().insert(session);
+            } else if (200..400).contains(&status) {
+                gone_in.remove(&key);
+            }
 
             // Upsert endpoint
-            let ep = endpoints.entry(key).or_insert_with(|| Endpoint {
+            let ep = endpoints.entry(key.clone()).or_insert_with(|| Endpoint {
                 pattern: format!("{} {}", method.clone(), pattern.clone()),
+                name: None,
+                tags: vec![],
+                notes: None,
+                verified: false,
+                custom: BTreeMap::new(),
                 methods: vec![],
                 observed_urls: vec![],
                 query_params: vec![],
+                form_params: vec![],
                 request_content_types: vec![],
                 response_content_types: vec![],
+                request_shape: None,
                 response_shape_sample: None,
+                response_shapes: BTreeMap::new(),
+                shape_variants: vec![],
+                polymorphic: false,
+                streaming: false,
+                computed_headers: vec![],
+                binary: None,
+                retry: None,
+                conditional: None,
+                rate_limit: None,
                 auth_required: false,
                 times_seen: 0,
                 last_seen: String::new(),
+                stale: false,
+                stale_reason: None,
             });
 
             if !ep.methods.contains(&method) {
@@ -227,22 +701,203 @@ pub fn generate_for_app(app_name: &str) {
             if !resp_ct.is_empty() && !ep.response_content_types.contains(&resp_ct) {
                 ep.response_content_types.push(resp_ct);
             }
-            if ep.response_shape_sample.is_none() {
+            if let Some(form) = data.get("requestForm").and_then(|v| v.as_object()) {
+                for key in form.keys() {
+                    if !ep.form_params.contains(key) {
+                        ep.form_params.push(key.clone());
+                    }
+                }
+            }
+            if !req_ct.is_empty() && !ep.request_content_types.contains(&req_ct) {
+                ep.request_content_types.push(req_ct);
+            }
+            if ep.request_shape.is_none() {
+                ep.request_shape = request_shape;
+            }
+            // Compared on successful responses only: error bodies have shapes of their own
+            if let Some(keys) = response_json
+                .as_ref()
+                .and_then(|v| v.as_object())
+                .filter(|_| (200..400).contains(&status))
+                .map(|obj| obj.keys().cloned().collect::<BTreeSet<String>>())
+            {
+                match first_keys.get(&key) {
+                    None => {
+                        // The sample variants are told apart from, even if an error came first
+                        first_keys.insert(key.clone(), keys);
+                        ep.response_shape_sample = response_shape.clone();
+                    }
+                    Some(first) if *first != keys => {
+                        let added: Vec<String> = keys.difference(first).cloned().collect();
+                        let missing: Vec<String> = first.difference(&keys).cloned().collect();
+                        let known = ep
+                            .shape_variants
+                            .iter()
+                            .position(|v| v.added == added && v.missing == missing);
+                        match known {
+                            Some(i) => {
+                                ep.shape_variants[i].times_seen += 1;
+                                ep.shape_variants[i].last_seen = timestamp.clone();
+                            }
+                            None if ep.shape_variants.len() < MAX_SHAPE_VARIANTS => {
+                                ep.shape_variants.push(ShapeVariant {
+                                    added,
+                                    missing,
+                                    shape: response_shape.clone().unwrap_or_default(),
+                                    times_seen: 1,
+                                    last_seen: timestamp.clone(),
+                                });
+                            }
+                            None => {}
+                        }
+                        ep.polymorphic = true;
+                    }
+                    Some(_) => {}
+                }
+            }
+            if let Some(shape) = response_shape.as_ref().filter(|_| status > 0) {
+                ep.response_shapes
+                    .entry(status.to_string())
+                    .or_insert_with(|| shape.clone());
+            }
+            let ok = (200..400).contains(&status);
+            if response_shape.is_some()
+                && (ep.response_shape_sample.is_none() || ok && !ok_sampled.contains(&key))
+            {
+                if ok {
+                    ok_sampled.insert(key.clone());
+                }
                 ep.response_shape_sample = response_shape;
             }
+            ep.streaming = ep.streaming || streamed;
+            if let Some(file) = data.get("responseBinary") {
+                track_binary(file, ep.binary.get_or_insert_with(Default::default));
+            }
+            track_conditional(&data, &mut ep.conditional);
+            track_rate_limit(&data, &mut ep.rate_limit);
             ep.auth_required = ep.auth_required || has_auth;
-            ep.times_seen += 1;
+            // A collapsed run of identical polls stands for `repeat_count` requests, and
+            // a sampled capture for `sample_weight` captures
+            let repeats = data
+                .get("repeat_count")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(1);
+            let weight = data
+                .get("sample_weight")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(1);
+            ep.times_seen += (repeats.max(1) * weight.max(1)) as u32;
             ep.last_seen = timestamp;
         }
     }
 
+    let stale_after = config::stale_after_sessions(app_name) as usize;
+    for (key, ep) in endpoints.iter_mut() {
+        let Some(&(session, status)) = latest.get(key) else {
+            continue;
+        };
+        let missed = sessions - 1 - session;
+        // One 404 is as likely a deleted item or a typo'd URL as a removed endpoint, and
+        // for a path with an {id} in it, a 404 says nothing about the endpoint at all
+        let gone_sessions = gone_in.get(key).map_or(0, |s| s.len());
+        ep.stale_reason = if (status == 404 || status == 410)
+            && gone_sessions >= MIN_GONE_SESSIONS
+            && !ep.pattern.contains('{')
+        {
+            Some(format!(
+                "answered {status} in the last {gone_sessions} sessions it was called in"
+            ))
+        } else if stale_after > 0 && missed >= stale_after {
+            Some(format!("not seen in the last {missed} sessions"))
+        } else {
+            None
+        };
+        ep.stale = ep.stale_reason.is_some();
+    }
+
+    // Remember what we knew before, to report newly discovered endpoints and
+    // endpoints that started requiring auth: pattern -> auth_required
+    let previous: Vec<Endpoint> = fs::read_to_string(app_dir.join("endpoints.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<EndpointCatalog>(&s).ok())
+        .map(|c| c.endpoints)
+        .unwrap_or_default();
+    let previous_patterns: HashMap<String, bool> = previous
+        .iter()
+        .map(|ep| (ep.pattern.clone(), ep.auth_required))
+        .collect();
+    // What people added can't be recreated from captures. Held until endpoints.json is
+    // written, so a name given meanwhile isn't written over.
+    let notes_lock = NOTES_LOCK.lock().unwrap();
+    let mut notes = read_notes(&app_dir);
+    let mut moved = false;
+    for old in previous {
+        // Given before endpoint-notes.json existed, or typed into endpoints.json by hand
+        let note = EndpointNote::of(&old);
+        if !note.is_empty() && !notes.contains_key(&old.pattern) {
+            notes.insert(old.pattern.clone(), note);
+            moved = true;
+        }
+        if let Some(ep) = endpoints.get_mut(&old.pattern) {
+            ep.custom = old.custom;
+        }
+    }
+    if moved {
+        let _ = write_notes(&app_dir, &notes);
+    }
+    for (pattern, note) in &notes {
+        if let Some(ep) = endpoints.get_mut(pattern) {
+            note.apply(ep);
+        }
+    }
+
+    for (key, stats) in retry_stats {
+        if let Some(ep) = endpoints.get_mut(&key) {
+            ep.retry = retry_semantics(&ep.methods, stats);
+        }
+    }
+    let (computed_headers, computed_by_endpoint) = header_trace.finish();
+    for (key, headers) in computed_by_endpoint {
+        if let Some(ep) = endpoints.get_mut(&key) {
+            ep.computed_headers = headers;
+        }
+    }
+
     // Write endpoints.json
     let mut ep_list: Vec<Endpoint> = endpoints.into_values().collect();
     ep_list.sort_by(|a, b| b.times_seen.cmp(&a.times_seen));
-    let catalog = EndpointCatalog { endpoints: ep_list };
+    let mut catalog = EndpointCatalog {
+        endpoints: ep_list,
+        extensions: BTreeMap::new(),
+        transport,
+        environments: detect_environments(&origins),
+        resources: BTreeMap::new(),
+    };
+    catalog.resources = group_resources(&catalog.endpoints);
+    catalog.extensions = crate::analyzers::run(app_name, &catalog);
     if let Ok(json) = serde_json::to_string_pretty(&catalog) {
-        let _ = fs::write(app_dir.join("endpoints.json"), json);
+        if fs::write(app_dir.join("endpoints.json"), json).is_ok() {
+            events::emit(
+                "endpoints-generated",
+                serde_json::json!({"app": app_name, "endpoints": catalog.endpoints.len()}),
+            );
+
+            // Skip the very first generation — everything would be "new"
+            let discovered: Vec<&str> = catalog
+                .endpoints
+                .iter()
+                .map(|ep| ep.pattern.as_str())
+                .filter(|p| !previous_patterns.contains_key(*p))
+                .collect();
+            if !previous_patterns.is_empty() && !discovered.is_empty() {
+                events::emit(
+                    "endpoints-discovered",
+                    serde_json::json!({"app": app_name, "patterns": discovered}),
+                );
+            }
+        }
     }
+    drop(notes_lock);
 
     // Build auth.json
     let mut mechanisms: Vec<AuthMechanism> = Vec::new();
@@ -281,17 +936,20 @@ pub fn generate_for_app(app_name: &str) {
         } else {
             "opaque".to_string()
         };
-        details.insert(
-            "pattern".to_string(),
-            serde_json::Value::String(pattern),
-        );
+        details.insert("pattern".to_string(), serde_json::Value::String(pattern));
+        if computed_headers
+            .iter()
+            .any(|h| h.header.eq_ignore_ascii_case(header_name))
+        {
+            details.insert("computed".to_string(), serde_json::Value::Bool(true));
+        }
         mechanisms.push(AuthMechanism {
             mech_type: "header".to_string(),
             details,
         });
     }
 
-    let auth = AuthInfo {
+    let mut auth = AuthInfo {
         mechanisms,
         login_url: login_urls.first().cloned(),
         observed_refresh_endpoints: refresh_urls
@@ -299,18 +957,321 @@ pub fn generate_for_app(app_name: &str) {
             .collect::<std::collections::HashSet<_>>()
             .into_iter()
             .collect(),
-        session_duration_estimate: "unknown".to_string(),
+        session_duration_estimate: String::new(),
+        cookie_rotation: cookie_rotation(cookie_tracks, &latest_cookies),
+        csrf: csrf_trace.finish(),
+        computed_headers,
+        websocket: websocket_auth(&sockets),
+        history: Vec::new(),
     };
+    auth.session_duration_estimate = session_duration_estimate(&auth.cookie_rotation);
+    let previous_auth: Option<AuthInfo> = fs::read_to_string(app_dir.join("auth.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok());
+    if let Some(previous) = previous_auth {
+        let mut changes = auth_changes(&previous, &auth);
+        changes.extend(
+            catalog
+                .endpoints
+                .iter()
+                .filter(|ep| ep.auth_required && previous_patterns.get(&ep.pattern) == Some(&false))
+                .map(|ep| format!("{} now requires auth", ep.pattern)),
+        );
+        auth.history = previous.history;
+        if !changes.is_empty() {
+            events::emit(
+                "auth-changed",
+                serde_json::json!({"app": app_name, "changes": changes}),
+            );
+            auth.history.push(AuthChange {
+                detected_at: chrono::Utc::now().to_rfc3339(),
+                changes,
+            });
+            let excess = auth.history.len().saturating_sub(AUTH_HISTORY_LEN);
+            auth.history.drain(..excess);
+        }
+    }
     if let Ok(json) = serde_json::to_string_pretty(&auth) {
         let _ = fs::write(app_dir.join("auth.json"), json);
     }
 
     // Generate examples.sh with curl commands for the top endpoints
-    generate_examples_sh(&app_dir, &catalog);
+    // The profile's binary if installed, else any impersonation binary, else plain curl
+    let wanted = config::curl_binary(config::tls_profile(app_name));
+    let installed = config::installed_curl_binaries();
+    let curl = if installed.contains(&wanted) {
+        Some(wanted)
+    } else {
+        installed.first().copied()
+    };
+    generate_examples_sh(&app_dir, &catalog, &config::capture_noise(app_name), curl);
+    generate_examples_inpage(&app_dir, &catalog);
+}
+
+/// Group endpoints into resources by their collection path. Endpoints come busiest first,
+/// so when two map to the same operation the busier one is kept.
+fn group_resources(endpoints: &[Endpoint]) -> BTreeMap<String, Resource> {
+    let is_id = |seg: &str| seg.starts_with('{');
+    // collection path segments -> operations
+    let mut collections: BTreeMap<Vec<String>, BTreeMap<String, String>> = BTreeMap::new();
+    // Paths that answer GET are collections, so a POST there creates rather than acts
+    let listed: std::collections::HashSet<&str> = endpoints
+        .iter()
+        .filter_map(|ep| ep.pattern.strip_prefix("GET "))
+        .collect();
+    for ep in endpoints.iter().filter(|ep| !ep.stale) {
+        let Some((method, path)) = ep.pattern.split_once(' ') else {
+            continue;
+        };
+        let segs: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let n = segs.len();
+        let (collection, op) = match (segs.last(), method) {
+            (None, _) => continue,
+            // /messages/{id}
+            (Some(last), _) if is_id(last) => {
+                if n < 2 || is_id(segs[n - 2]) {
+                    continue;
+                }
+                let op = match method {
+                    "GET" => "get",
+                    "PATCH" | "POST" => "update",
+                    "PUT" => "replace",
+                    "DELETE" => "delete",
+                    _ => continue,
+                };
+                (&segs[..n - 1], op.to_string())
+            }
+            // POST /messages/{id}/archive, unless it's a nested collection (GET works there too)
+            (Some(action), m)
+                if m != "GET"
+                    && !listed.contains(path)
+                    && n >= 3
+                    && is_id(segs[n - 2])
+                    && !is_id(segs[n - 3]) =>
+            {
+                (&segs[..n - 2], action.to_lowercase().replace('-', "_"))
+            }
+            // /messages
+            (Some(_), _) => {
+                let op = match method {
+                    "GET" => "list",
+                    "POST" => "create",
+                    _ => continue,
+                };
+                (&segs[..], op.to_string())
+            }
+        };
+        let key: Vec<String> = collection.iter().map(|s| s.to_string()).collect();
+        collections
+            .entry(key)
+            .or_default()
+            .entry(op)
+            .or_insert_with(|| ep.pattern.clone());
+    }
+
+    // Named after the collection. Where two share a name the shallower one keeps it and
+    // the other gets its parent's in front ("users_messages"), or its whole path.
+    let mut collections: Vec<(Vec<String>, BTreeMap<String, String>)> = collections
+        .into_iter()
+        .filter(|(_, ops)| ops.len() >= 2)
+        .collect();
+    collections.sort_by_key(|(segs, _)| segs.len());
+    let mut resources = BTreeMap::new();
+    for (segs, operations) in collections {
+        let named: Vec<String> = segs
+            .iter()
+            .filter(|s| !is_id(s))
+            .map(|s| s.to_lowercase().replace('-', "_"))
+            .collect();
+        let n = named.len();
+        let name = [n.saturating_sub(1), n.saturating_sub(2), 0]
+            .into_iter()
+            .map(|from| named[from..].join("_"))
+            .find(|name| !resources.contains_key(name));
+        let Some(name) = name else {
+            continue;
+        };
+        resources.insert(
+            name,
+            Resource {
+                path: format!("/{}", segs.join("/")),
+                operations,
+            },
+        );
+    }
+    resources
+}
+
+/// Carry over to a regenerated endpoint what was set on the old one by hand: its name,
+/// tags, notes and verified flag, and any fields generation doesn't write at all
+/// What `name_endpoint` gave one endpoint, kept by pattern in
+/// `apps/<app>/endpoint-notes.json` — apart from endpoints.json, so it outlives the
+/// endpoint dropping out of captures and comes back with it
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+struct EndpointNote {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    verified: bool,
+}
+
+impl EndpointNote {
+    fn of(ep: &Endpoint) -> Self {
+        EndpointNote {
+            name: ep.name.clone(),
+            tags: ep.tags.clone(),
+            notes: ep.notes.clone(),
+            verified: ep.verified,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.name.is_none() && self.tags.is_empty() && self.notes.is_none() && !self.verified
+    }
+
+    fn apply(&self, ep: &mut Endpoint) {
+        ep.name = self.name.clone();
+        ep.tags = self.tags.clone();
+        ep.notes = self.notes.clone();
+        ep.verified = self.verified;
+    }
+}
+
+const NOTES_FILE: &str = "endpoint-notes.json";
+
+/// Serializes endpoint-notes.json updates with the endpoints.json writes that merge them
+static NOTES_LOCK: Mutex<()> = Mutex::new(());
+
+fn read_notes(app_dir: &Path) -> BTreeMap<String, EndpointNote> {
+    fs::read_to_string(app_dir.join(NOTES_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn write_notes(app_dir: &Path, notes: &BTreeMap<String, EndpointNote>) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(notes).map_err(std::io::Error::other)?;
+    fs::write(app_dir.join(NOTES_FILE), json)
+}
+
+/// {"pattern": "POST /gp/api/x23", "name": "send_message", "tags": ["messaging"],
+/// "notes": "...", "verified": true}: give an endpoint of `app_name` a name to refer to it
+/// by in docs and code, tags, notes, or mark it checked. An empty name or notes clears it;
+/// `tags` replaces the old ones. Stored in endpoint-notes.json and merged into
+/// endpoints.json on every generation.
+pub fn name_endpoint(app_name: &str, cmd: &serde_json::Value) -> serde_json::Value {
+    let pattern = cmd.get("pattern").and_then(|v| v.as_str()).unwrap_or("");
+    let Some((method, path)) = pattern.trim().split_once(' ') else {
+        return CommandError::InvalidParams("pattern must be \"METHOD /path\"".to_string()).reply();
+    };
+    let pattern = format!("{} {}", method.to_uppercase(), normalize_path(path.trim()));
+    let name = cmd.get("name").map(|v| v.as_str().unwrap_or("").trim());
+    if name.is_some_and(|n| !n.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')) {
+        return CommandError::InvalidParams(
+            "name must be letters, digits and underscores, like send_message".to_string(),
+        )
+        .reply();
+    }
+    let tags: Option<Vec<String>> = cmd.get("tags").and_then(|v| v.as_array()).map(|tags| {
+        tags.iter()
+            .filter_map(|t| t.as_str())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect()
+    });
+    let notes_text = cmd.get("notes").map(|v| v.as_str().unwrap_or("").trim());
+    let verified = cmd.get("verified").and_then(|v| v.as_bool());
+    if name.is_none() && tags.is_none() && notes_text.is_none() && verified.is_none() {
+        return CommandError::InvalidParams("missing name, tags, notes or verified".to_string())
+            .reply();
+    }
+
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let path = app_dir.join("endpoints.json");
+    let _lock = NOTES_LOCK.lock().unwrap();
+    let mut notes = read_notes(&app_dir);
+    let Some(mut catalog) = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str::<EndpointCatalog>(&s).ok())
+    else {
+        return CommandError::NotFound(format!("no endpoints.json for \"{app_name}\" yet")).reply();
+    };
+    // Names are unique among endpoints gone from captures too, as they may come back
+    if let Some(taken) = name.filter(|n| !n.is_empty()).and_then(|n| {
+        let named =
+            |p: &String, other: Option<&String>| *p != pattern && other.is_some_and(|o| o == n);
+        catalog
+            .endpoints
+            .iter()
+            .find(|ep| named(&ep.pattern, ep.name.as_ref()))
+            .map(|ep| ep.pattern.clone())
+            .or_else(|| {
+                notes
+                    .iter()
+                    .find(|(p, note)| named(p, note.name.as_ref()))
+                    .map(|(p, _)| p.clone())
+            })
+    }) {
+        return CommandError::InvalidParams(format!("{taken} is already named that")).reply();
+    }
+    let Some(ep) = catalog
+        .endpoints
+        .iter_mut()
+        .find(|ep| ep.pattern == pattern)
+    else {
+        return CommandError::NotFound(format!("no endpoint {pattern} in {app_name}")).reply();
+    };
+    let note = notes
+        .entry(pattern.clone())
+        .or_insert_with(|| EndpointNote::of(ep));
+    if let Some(name) = name {
+        note.name = Some(name.to_string()).filter(|n| !n.is_empty());
+    }
+    if let Some(tags) = tags {
+        note.tags = tags;
+    }
+    if let Some(text) = notes_text {
+        note.notes = Some(text.to_string()).filter(|n| !n.is_empty());
+    }
+    if let Some(verified) = verified {
+        note.verified = verified;
+    }
+    note.apply(ep);
+    if note.is_empty() {
+        notes.remove(&pattern);
+    }
+    if write_notes(&app_dir, &notes).is_err() {
+        let notes_path = app_dir.join(NOTES_FILE);
+        return CommandError::Failed(format!("can't write {}", notes_path.display())).reply();
+    }
+    let result = serde_json::json!({
+        "ok": true,
+        "app": app_name,
+        "pattern": ep.pattern,
+        "name": ep.name,
+        "tags": ep.tags,
+        "notes": ep.notes,
+        "verified": ep.verified,
+    });
+    match serde_json::to_string_pretty(&catalog) {
+        Ok(json) if fs::write(&path, &json).is_ok() => result,
+        _ => CommandError::Failed(format!("can't write {}", path.display())).reply(),
+    }
 }
 
 /// Generate examples.sh with working curl commands for the top endpoints.
-fn generate_examples_sh(app_dir: &std::path::Path, catalog: &EndpointCatalog) {
+/// `curl` is the curl-impersonate binary to use; without one, plain curl is written with
+/// a warning that the TLS fingerprint won't match a browser.
+fn generate_examples_sh(
+    app_dir: &std::path::Path,
+    catalog: &EndpointCatalog,
+    capture_noise: &[String],
+    curl: Option<&str>,
+) {
     let session_path = app_dir.join("sessions").join("latest.json");
     let session: config::SessionData = fs::read_to_string(&session_path)
         .ok()
@@ -335,6 +1296,61 @@ fn generate_examples_sh(app_dir: &std::path::Path, catalog: &EndpointCatalog) {
     let _ = writeln!(file, "# Auto-generated curl examples from harharhar captures");
     let _ = writeln!(file, "# Generated: {}", chrono::Utc::now().to_rfc3339());
     let _ = writeln!(file);
+    // Defaults to the environment the most-seen endpoint was called on
+    let environment_of = |url: &str| {
+        catalog
+            .environments
+            .values()
+            .find(|origin| url.starts_with(&format!("{origin}/")))
+    };
+    let base_url = catalog
+        .endpoints
+        .iter()
+        .filter_map(|ep| ep.observed_urls.first())
+        .find_map(|u| environment_of(u));
+    if let Some(base_url) = base_url {
+        let _ = writeln!(
+            file,
+            "# Environments — run with BASE_URL=<one of these> to switch:"
+        );
+        for (name, origin) in &catalog.environments {
+            let _ = writeln!(file, "#   {name}: {origin}");
+        }
+        let _ = writeln!(file, "BASE_URL=\"${{BASE_URL:-{base_url}}}\"");
+        let _ = writeln!(file);
+    }
+    if curl.is_none() {
+        let _ = writeln!(
+            file,
+            "# ############################################################"
+        );
+        let _ = writeln!(
+            file,
+            "# WARNING: curl-impersonate is not installed — these use plain curl."
+        );
+        let _ = writeln!(
+            file,
+            "# Plain curl's TLS fingerprint doesn't look like a browser; sites"
+        );
+        let _ = writeln!(
+            file,
+            "# behind bot protection may block or challenge it. --http2 at least"
+        );
+        let _ = writeln!(
+            file,
+            "# matches the browser's protocol. Install it for faithful replays:"
+        );
+        let _ = writeln!(
+            file,
+            "#   brew tap shakacode/brew && brew install curl-impersonate"
+        );
+        let _ = writeln!(
+            file,
+            "# ############################################################"
+        );
+        let _ = writeln!(file);
+    }
+    let curl = curl.unwrap_or("curl --http2");
 
     let mut count = 0;
     for ep in &catalog.endpoints {
@@ -348,12 +1364,19 @@ fn generate_examples_sh(app_dir: &std::path::Path, catalog: &EndpointCatalog) {
             None => continue,
         };
 
-        if should_skip_capture(observed_url) {
+        if should_skip_capture(observed_url) && !noise_opted_in(observed_url, capture_noise) {
             continue;
         }
 
         // Derive a human-readable comment from the pattern
-        let _ = writeln!(file, "# {}", ep.pattern);
+        match &ep.name {
+            Some(name) => {
+                let _ = writeln!(file, "# {name}: {}", ep.pattern);
+            }
+            None => {
+                let _ = writeln!(file, "# {}", ep.pattern);
+            }
+        }
         let _ = writeln!(
             file,
             "# Seen: {} times, last: {}",
@@ -362,14 +1385,43 @@ fn generate_examples_sh(app_dir: &std::path::Path, catalog: &EndpointCatalog) {
 
         // Determine method — use the first one
         let method = ep.methods.first().map(|s| s.as_str()).unwrap_or("GET");
+        if let Some(c) = ep.conditional.as_ref().filter(|_| method == "GET") {
+            if c.etag {
+                let _ = writeln!(file, "# Re-fetching? Add -H 'If-None-Match: <ETag of the last response>'; 304 = unchanged");
+            } else if c.last_modified {
+                let _ = writeln!(file, "# Re-fetching? Add -H 'If-Modified-Since: <its Last-Modified>'; 304 = unchanged");
+            }
+        }
+        if !ep.computed_headers.is_empty() {
+            let _ = writeln!(
+                file,
+                "# {} computed per request — a replay with a captured value is refused; see examples-inpage.md",
+                ep.computed_headers.join(", ")
+            );
+        }
         let is_post = method == "POST" || method == "PUT" || method == "PATCH";
 
         // Start building the curl command
-        let _ = write!(file, "curl");
+        let _ = write!(file, "{curl}");
         if is_post {
             let _ = write!(file, " -X {method}");
         }
-        let _ = write!(file, " '{observed_url}'");
+        match environment_of(observed_url) {
+            Some(origin) => {
+                // Escape what double quotes would still expand
+                let rest: String = observed_url[origin.len()..]
+                    .chars()
+                    .flat_map(|c| match c {
+                        '$' | '`' | '"' | '\\' => vec!['\\', c],
+                        c => vec![c],
+                    })
+                    .collect();
+                let _ = write!(file, " \"$BASE_URL{rest}\"");
+            }
+            None => {
+                let _ = write!(file, " '{observed_url}'");
+            }
+        }
 
         // Add Cookie header if we have cookies
         if !cookie_header.is_empty() {
@@ -405,6 +1457,429 @@ fn generate_examples_sh(app_dir: &std::path::Path, catalog: &EndpointCatalog) {
     }
 }
 
+/// examples-inpage.md: for endpoints sent with computed headers (signatures a captured
+/// value won't pass), the request made from the page instead, where the app's own code
+/// adds them. Removed when no endpoint has any.
+fn generate_examples_inpage(app_dir: &std::path::Path, catalog: &EndpointCatalog) {
+    let path = app_dir.join("examples-inpage.md");
+    let computed: Vec<&Endpoint> = catalog
+        .endpoints
+        .iter()
+        .filter(|ep| !ep.computed_headers.is_empty() && !ep.stale)
+        .collect();
+    if computed.is_empty() {
+        let _ = fs::remove_file(path);
+        return;
+    }
+
+    let mut md = String::from("# In-page replay examples\n\n");
+    md.push_str(
+        "These endpoints are sent with headers the page computes per request, so the curl in \
+         examples.sh with a captured value will be refused. Open a page of the app in a window, \
+         then make the request from it with `fetch_in_page`: it goes out with the page's cookies \
+         and through the app's own fetch wrapper, if it signs requests there. If the app signs in \
+         its own API client instead, call that client with `eval`.\n\n",
+    );
+    md.push_str(&format!(
+        "Generated: {}\n\n",
+        chrono::Utc::now().to_rfc3339()
+    ));
+    for ep in computed {
+        let Some(url) = ep.observed_urls.first() else {
+            continue;
+        };
+        let method = ep.methods.first().map(|s| s.as_str()).unwrap_or("GET");
+        match &ep.name {
+            Some(name) => md.push_str(&format!("## {name}: {}\n", ep.pattern)),
+            None => md.push_str(&format!("## {}\n", ep.pattern)),
+        }
+        md.push_str(&format!(
+            "Seen {} times; computed: {}\n\n",
+            ep.times_seen,
+            ep.computed_headers.join(", ")
+        ));
+
+        let mut cmd = serde_json::json!({"action": "fetch_in_page", "url": url, "method": method});
+        let json_body = ep
+            .request_content_types
+            .iter()
+            .any(|ct| ct.contains("json"));
+        if json_body && method != "GET" && method != "HEAD" {
+            cmd["headers"] = serde_json::json!({"Content-Type": "application/json"});
+            cmd["body"] = serde_json::json!({});
+        }
+        md.push_str(&format!("```json\n{cmd}\n```\n"));
+
+        let init = match cmd.get("body") {
+            Some(_) => format!(
+                "{{method: '{method}', credentials: 'include', headers: {{'Content-Type': 'application/json'}}, body: '{{}}'}}"
+            ),
+            None => format!("{{method: '{method}', credentials: 'include'}}"),
+        };
+        let js = format!(
+            "fetch({}, {init}).then(r => r.text())",
+            serde_json::Value::from(url.as_str())
+        );
+        let eval = serde_json::json!({"action": "eval", "js": js});
+        md.push_str(&format!("Or with eval:\n```json\n{eval}\n```\n\n"));
+    }
+    let _ = fs::write(path, md);
+}
+
+/// Note the auth cookies in a request's Cookie header: which value each had, and when
+/// it changed
+fn track_cookies(
+    data: &serde_json::Value,
+    cookie_str: &str,
+    session: usize,
+    tracks: &mut BTreeMap<String, CookieTrack>,
+    latest: &mut HashMap<String, BTreeSet<String>>,
+) {
+    let timestamp = data.get("timestamp").and_then(|v| v.as_str()).unwrap_or("");
+    let at = chrono::DateTime::parse_from_rfc3339(timestamp).ok();
+    let host = data
+        .get("url")
+        .and_then(|v| v.as_str())
+        .and_then(|u| url::Url::parse(u).ok())
+        .and_then(|u| u.host_str().map(String::from))
+        .unwrap_or_default();
+    let mut names = BTreeSet::new();
+    for (name, value) in cookie_str
+        .split(';')
+        .filter_map(|part| part.trim().split_once('='))
+    {
+        let name = name.trim();
+        let lower = name.to_lowercase();
+        if !AUTH_COOKIE_PATTERNS.iter().any(|p| lower.contains(p)) {
+            continue;
+        }
+        names.insert(name.to_string());
+        let track = tracks.entry(name.to_string()).or_default();
+        if track.first_seen.is_empty() {
+            track.first_seen = timestamp.to_string();
+            track.value_since = at;
+        } else if track.value != value.trim() {
+            track.rotations += 1;
+            if let (Some(since), Some(at)) = (track.value_since, at) {
+                track.lifetimes.push((at - since).num_seconds().max(0));
+            }
+            track.value_since = at;
+        }
+        track.value = value.trim().to_string();
+        if track.values.len() < 1000 {
+            track.values.insert(track.value.clone());
+        }
+        track.last_seen = timestamp.to_string();
+        track.sessions.insert(session);
+        track.hosts.insert(host.clone());
+    }
+    latest.insert(host, names);
+}
+
+/// Keep the attributes of cookies a response set (captures from the "chrome" window and
+/// HAR imports carry Set-Cookie; the page's own hooks never see it)
+fn track_set_cookies(data: &serde_json::Value, tracks: &mut BTreeMap<String, CookieTrack>) {
+    let Some(headers) = data.get("responseHeaders").and_then(|h| h.as_object()) else {
+        return;
+    };
+    let set_cookies = headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("set-cookie"))
+        .filter_map(|(_, v)| v.as_str());
+    for line in set_cookies.flat_map(|v| v.lines()) {
+        let mut parts = line.split(';');
+        let Some((name, _)) = parts.next().and_then(|p| p.split_once('=')) else {
+            continue;
+        };
+        let Some(track) = tracks.get_mut(name.trim()) else {
+            continue;
+        };
+        let mut attributes = serde_json::Map::new();
+        for part in parts {
+            let (key, value) = part.split_once('=').unwrap_or((part, ""));
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "max-age" => {
+                    if let Ok(secs) = value.parse::<i64>() {
+                        attributes.insert("max_age".to_string(), secs.into());
+                    }
+                }
+                "expires" => {
+                    attributes.insert("expires".to_string(), value.into());
+                }
+                "httponly" => {
+                    attributes.insert("http_only".to_string(), true.into());
+                }
+                "secure" => {
+                    attributes.insert("secure".to_string(), true.into());
+                }
+                "samesite" => {
+                    attributes.insert("same_site".to_string(), value.into());
+                }
+                _ => {}
+            }
+        }
+        track.attributes = attributes;
+    }
+}
+
+fn cookie_rotation(
+    tracks: BTreeMap<String, CookieTrack>,
+    latest: &HashMap<String, BTreeSet<String>>,
+) -> Vec<CookieRotation> {
+    tracks
+        .into_iter()
+        .map(|(name, mut track)| {
+            track.lifetimes.sort_unstable();
+            let gone = track
+                .hosts
+                .iter()
+                .all(|host| latest.get(host).is_some_and(|names| !names.contains(&name)));
+            CookieRotation {
+                first_seen: track.first_seen,
+                last_seen: track.last_seen,
+                sessions: track.sessions.len(),
+                values: track.values.len(),
+                rotations: track.rotations,
+                median_lifetime_secs: track.lifetimes.get(track.lifetimes.len() / 2).copied(),
+                max_lifetime_secs: track.lifetimes.last().copied(),
+                gone,
+                attributes: track.attributes,
+                name,
+            }
+        })
+        .collect()
+}
+
+/// How long a session lasts, going by its auth cookies: a Max-Age the server set, else
+/// how long values lasted before being rotated, else at least as long as one value has
+/// been seen unchanged
+fn session_duration_estimate(rotation: &[CookieRotation]) -> String {
+    if let Some((name, secs, max_age)) = session_lifetime(rotation) {
+        let secs = human_duration(secs);
+        return if max_age {
+            format!("~{secs} (Max-Age of {name})")
+        } else {
+            format!("~{secs} ({name} is replaced about that often)")
+        };
+    }
+    let current = || rotation.iter().filter(|c| !c.gone);
+    let unchanged = current()
+        .filter(|c| c.rotations == 0)
+        .filter_map(|c| {
+            let first = chrono::DateTime::parse_from_rfc3339(&c.first_seen).ok()?;
+            let last = chrono::DateTime::parse_from_rfc3339(&c.last_seen).ok()?;
+            Some((&c.name, (last - first).num_seconds()))
+        })
+        .max_by_key(|(_, secs)| *secs);
+    match unchanged {
+        Some((name, secs)) if secs > 0 => format!(
+            "at least {} ({name} unchanged since first seen)",
+            human_duration(secs)
+        ),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// 90 -> "2m", 7200 -> "2h", 259200 -> "3d"
+/// How long a session lasts, where the auth cookies say: (cookie, seconds, whether it's
+/// the Max-Age the server set rather than how often the value was replaced)
+pub(crate) fn session_lifetime(rotation: &[CookieRotation]) -> Option<(&str, i64, bool)> {
+    let current = || rotation.iter().filter(|c| !c.gone);
+    current()
+        .filter_map(|c| {
+            Some((
+                c.name.as_str(),
+                c.attributes.get("max_age")?.as_i64()?,
+                true,
+            ))
+        })
+        .max_by_key(|(_, secs, _)| *secs)
+        .or_else(|| {
+            current()
+                .filter_map(|c| Some((c.name.as_str(), c.median_lifetime_secs?, false)))
+                .max_by_key(|(_, secs, _)| *secs)
+        })
+}
+
+pub(crate) fn human_duration(secs: i64) -> String {
+    match secs {
+        s if s < 60 => format!("{s}s"),
+        s if s < 3600 => format!("{}m", (s + 30) / 60),
+        s if s < 86_400 => format!("{}h", (s + 1800) / 3600),
+        s => format!("{}d", (s + 43_200) / 86_400),
+    }
+}
+
+/// Auth headers and cookies that appeared, disappeared or changed form since the last
+/// generation — usually why replays that worked yesterday fail today
+fn auth_changes(previous: &AuthInfo, current: &AuthInfo) -> Vec<String> {
+    // "header Authorization" -> pattern ("Bearer ..."); "cookie sid" -> ""
+    let described = |auth: &AuthInfo| {
+        let mut out: BTreeMap<String, String> = BTreeMap::new();
+        for m in &auth.mechanisms {
+            let text = |k: &str| {
+                m.details
+                    .get(k)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string()
+            };
+            match m.mech_type.as_str() {
+                "header" => {
+                    out.insert(format!("header {}", text("header")), text("pattern"));
+                }
+                "cookie" => {
+                    let names = m.details.get("names").and_then(|v| v.as_array());
+                    for name in names.into_iter().flatten().filter_map(|v| v.as_str()) {
+                        out.insert(format!("cookie {name}"), String::new());
+                    }
+                }
+                _ => {}
+            }
+        }
+        out
+    };
+    let before = described(previous);
+    let after = described(current);
+    let mut changes = Vec::new();
+    for (what, pattern) in &after {
+        match before.get(what) {
+            None => changes.push(format!("{what} added")),
+            Some(old) if old != pattern => {
+                changes.push(format!("{what} changed from {old:?} to {pattern:?}"))
+            }
+            Some(_) => {}
+        }
+    }
+    for what in before.keys().filter(|w| !after.contains_key(*w)) {
+        changes.push(format!("{what} removed"));
+    }
+    changes
+}
+
+/// Work out how each WebSocket endpoint authenticates, from the handshake URL,
+/// the cookies sent with it, and the first frame the page sent.
+fn websocket_auth(sockets: &[(String, SocketObservation)]) -> Vec<WebSocketAuth> {
+    let looks_like_token = |name: &str| {
+        let lower = name.to_lowercase();
+        WS_TOKEN_NAMES.iter().any(|t| lower.contains(t))
+    };
+
+    let mut by_pattern: HashMap<String, WebSocketAuth> = HashMap::new();
+    for (url_str, obs) in sockets {
+        let Ok(parsed) = url::Url::parse(url_str) else {
+            continue;
+        };
+        let url_pattern = format!(
+            "{}{}",
+            parsed.host_str().unwrap_or(""),
+            normalize_path(parsed.path())
+        );
+        let ws = by_pattern
+            .entry(url_pattern.clone())
+            .or_insert_with(|| WebSocketAuth {
+                url_pattern,
+                example_url: url_str.clone(),
+                methods: Vec::new(),
+                query_params: Vec::new(),
+                cookies: Vec::new(),
+                first_message_keys: Vec::new(),
+                first_message_shape: None,
+            });
+
+        // Token in the query string (name says so, or the value is a long opaque string)
+        for (k, v) in parsed.query_pairs() {
+            let opaque = v.len() >= 20
+                && v.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.=%".contains(c));
+            if (looks_like_token(&k) || opaque) && !ws.query_params.contains(&k.to_string()) {
+                ws.query_params.push(k.to_string());
+            }
+        }
+
+        // Auth cookies sent with the handshake
+        for part in obs.cookie.split(';') {
+            if let Some(eq) = part.find('=') {
+                let name = part[..eq].trim();
+                let lower = name.to_lowercase();
+                if AUTH_COOKIE_PATTERNS.iter().any(|p| lower.contains(p))
+                    && !ws.cookies.contains(&name.to_string())
+                {
+                    ws.cookies.push(name.to_string());
+                }
+            }
+        }
+
+        // Credentials in the first frame (e.g. {"type":"auth","token":"..."})
+        if ws.first_message_shape.is_none() {
+            if let Some(first) = &obs.first_message {
+                match serde_json::from_str::<serde_json::Value>(first) {
+                    Ok(value) => {
+                        collect_token_keys(
+                            &value,
+                            "",
+                            &looks_like_token,
+                            &mut ws.first_message_keys,
+                        );
+                        ws.first_message_shape = Some(extract_shape(&value, 0));
+                    }
+                    Err(_) if looks_like_token(first) => {
+                        ws.first_message_keys.push("(text frame)".to_string());
+                        ws.first_message_shape =
+                            Some(serde_json::Value::String(first.chars().take(200).collect()));
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+
+    let mut result: Vec<WebSocketAuth> = by_pattern
+        .into_values()
+        .map(|mut ws| {
+            if !ws.query_params.is_empty() {
+                ws.methods.push("query_param".to_string());
+            }
+            if !ws.first_message_keys.is_empty() {
+                ws.methods.push("first_message".to_string());
+            }
+            if !ws.cookies.is_empty() {
+                ws.methods.push("cookie".to_string());
+            }
+            ws
+        })
+        .collect();
+    result.sort_by(|a, b| a.url_pattern.cmp(&b.url_pattern));
+    result
+}
+
+/// Dotted paths of token-like keys in a JSON value (up to 3 levels deep)
+fn collect_token_keys(
+    value: &serde_json::Value,
+    prefix: &str,
+    looks_like_token: &dyn Fn(&str) -> bool,
+    out: &mut Vec<String>,
+) {
+    let Some(obj) = value.as_object() else {
+        return;
+    };
+    if prefix.matches('.').count() >= 2 {
+        return;
+    }
+    for (k, v) in obj {
+        let path = if prefix.is_empty() {
+            k.clone()
+        } else {
+            format!("{prefix}.{k}")
+        };
+        if looks_like_token(k) && (v.is_string() || v.is_number()) {
+            out.push(path.clone());
+        }
+        collect_token_keys(v, &path, looks_like_token, out);
+    }
+}
+
 /// Normalize a URL path: replace numeric segments and UUIDs with {id}
 pub fn normalize_path(path: &str) -> String {
     path.split('/')
@@ -434,6 +1909,358 @@ pub fn normalize_path(path: &str) -> String {
         .join("/")
 }
 
+/// Note the validators a response carries and any revalidation the app did
+fn track_conditional(data: &serde_json::Value, conditional: &mut Option<ConditionalSupport>) {
+    use crate::capture::header_value;
+    let response = data.get("responseHeaders");
+    let request = data.get("requestHeaders");
+    let etag = header_value(response, "etag").is_some();
+    let last_modified = header_value(response, "last-modified").is_some();
+    let revalidated = header_value(request, "if-none-match").is_some()
+        || header_value(request, "if-modified-since").is_some();
+    let not_modified = data.get("status").and_then(|v| v.as_u64()) == Some(304);
+    if !(etag || last_modified || revalidated || not_modified) {
+        return;
+    }
+    let c = conditional.get_or_insert_with(Default::default);
+    c.etag |= etag;
+    c.last_modified |= last_modified;
+    c.revalidated_by_app |= revalidated;
+    if not_modified {
+        c.not_modified_seen += 1;
+    }
+}
+
+/// Hosts serving the same API: the busiest origin plus every origin sharing at least
+/// 3 endpoints and half of the smaller one's, named by the environment label in their
+/// host ("staging.example.com" -> "staging") or "prod" without one.
+fn detect_environments(
+    origins: &HashMap<String, (u32, std::collections::HashSet<String>)>,
+) -> BTreeMap<String, String> {
+    let mut environments = BTreeMap::new();
+    let Some((primary, (_, primary_keys))) = origins
+        .iter()
+        .filter(|(_, (_, keys))| keys.len() >= 3)
+        .max_by(|a, b| a.1 .0.cmp(&b.1 .0).then(b.0.cmp(a.0)))
+    else {
+        return environments;
+    };
+    let mut group: Vec<&String> = origins
+        .iter()
+        .filter(|(origin, (_, keys))| {
+            let shared = keys.intersection(primary_keys).count();
+            *origin != primary && shared >= 3 && shared * 2 >= keys.len().min(primary_keys.len())
+        })
+        .map(|(origin, _)| origin)
+        .collect();
+    if group.is_empty() {
+        return environments;
+    }
+    group.sort();
+    group.insert(0, primary);
+    for origin in group {
+        let host = origin.split("://").nth(1).unwrap_or(origin);
+        let name = host
+            .split(['.', '-', ':'])
+            .find_map(|label| {
+                ENVIRONMENT_LABELS
+                    .iter()
+                    .find(|(l, _)| label.eq_ignore_ascii_case(l))
+                    .map(|(_, name)| *name)
+            })
+            .unwrap_or("prod");
+        // Two hosts with the same label (or none) are told apart by host
+        let name = if environments.contains_key(name) {
+            host
+        } else {
+            name
+        };
+        environments.insert(name.to_string(), origin.clone());
+    }
+    environments
+}
+
+/// Add a capture's protocol, remote address and TLS details to its host's summary
+/// Add a capture's `responseBinary` to its endpoint's binary response summary
+fn track_binary(file: &serde_json::Value, binary: &mut BinaryResponses) {
+    let bytes = file.get("bytes").and_then(|v| v.as_u64()).unwrap_or(0);
+    if binary.hashes.is_empty() || bytes < binary.min_bytes {
+        binary.min_bytes = bytes;
+    }
+    binary.max_bytes = binary.max_bytes.max(bytes);
+    let content_type = file
+        .get("content_type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    if !content_type.is_empty() && !binary.content_types.iter().any(|t| t == content_type) {
+        binary.content_types.push(content_type.to_string());
+    }
+    if let Some(sha) = file.get("sha256").and_then(|v| v.as_str()) {
+        if binary.hashes.len() < 20 {
+            binary.hashes.insert(sha.to_string());
+        }
+    }
+    binary.distinct = binary.hashes.len();
+}
+
+fn track_transport(
+    data: &serde_json::Value,
+    host: &str,
+    transport: &mut BTreeMap<String, Transport>,
+) {
+    fn text(v: Option<&serde_json::Value>) -> Option<&str> {
+        v.and_then(|v| v.as_str()).filter(|s| !s.is_empty())
+    }
+    let protocol = text(data.get("protocol"));
+    let address = text(data.get("remoteAddress"));
+    let tls = data.get("tls");
+    if protocol.is_none() && address.is_none() && tls.is_none() {
+        return;
+    }
+    let t = transport.entry(host.to_string()).or_default();
+    if let Some(protocol) = protocol {
+        *t.protocols.entry(protocol.to_string()).or_default() += 1;
+    }
+    let add = |list: &mut Vec<String>, value: Option<&str>, max: usize| {
+        if let Some(value) = value {
+            if list.len() < max && !list.iter().any(|v| v == value) {
+                list.push(value.to_string());
+            }
+        }
+    };
+    add(&mut t.remote_addresses, address, 5);
+    add(
+        &mut t.tls_versions,
+        text(tls.and_then(|t| t.get("version"))),
+        10,
+    );
+    add(&mut t.ciphers, text(tls.and_then(|t| t.get("cipher"))), 10);
+}
+
+/// Note rate-limit headers, 429s and `Retry-After` on a response
+fn track_rate_limit(data: &serde_json::Value, rate_limit: &mut Option<RateLimit>) {
+    let Some(headers) = data.get("responseHeaders").and_then(|h| h.as_object()) else {
+        return;
+    };
+    let throttled = data.get("status").and_then(|v| v.as_u64()) == Some(429);
+    let seen: Vec<(String, &str)> = headers
+        .iter()
+        .map(|(k, v)| (k.to_lowercase(), v.as_str().unwrap_or("")))
+        .filter(|(k, _)| k.contains("ratelimit") || k.contains("rate-limit") || k == "retry-after")
+        .collect();
+    if seen.is_empty() && !throttled {
+        return;
+    }
+    let r = rate_limit.get_or_insert_with(Default::default);
+    if throttled {
+        r.throttled_seen += 1;
+    }
+    // "100" or "100, 100;w=60": the budget is the first number
+    let number = |v: &str| {
+        v.split([',', ';'])
+            .next()
+            .and_then(|n| n.trim().parse::<u64>().ok())
+    };
+    for (name, value) in seen {
+        if name.ends_with("limit-limit") {
+            if let Some(n) = number(value) {
+                r.limit = Some(r.limit.map_or(n, |l| l.min(n)));
+            }
+        } else if name.ends_with("limit-remaining") {
+            if let Some(n) = number(value) {
+                r.lowest_remaining = Some(r.lowest_remaining.map_or(n, |l| l.min(n)));
+            }
+        } else if name == "retry-after" {
+            let secs = value.trim().parse::<u64>().ok().or_else(|| {
+                let at = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+                let date = chrono::DateTime::parse_from_rfc3339(
+                    data.get("timestamp").and_then(|v| v.as_str())?,
+                )
+                .ok()?;
+                u64::try_from((at - date).num_seconds()).ok()
+            });
+            if let Some(secs) = secs {
+                r.retry_after_s = Some(r.retry_after_s.map_or(secs, |s| s.max(secs)));
+            }
+        }
+        if !r.headers.contains(&name) {
+            r.headers.push(name);
+        }
+    }
+}
+
+/// Record a request's idempotency key, and whether it repeats the previous
+/// (failed) request to the same endpoint — i.e. the app retried it.
+fn track_retry(
+    data: &serde_json::Value,
+    url_str: &str,
+    timestamp: &str,
+    key: &str,
+    retry_stats: &mut HashMap<String, RetryStats>,
+    last_attempts: &mut HashMap<String, Attempt>,
+) {
+    let body = data
+        .get("requestBody")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let status = data.get("status").and_then(|v| v.as_u64()).unwrap_or(0);
+    let Ok(at_ms) = chrono::DateTime::parse_from_rfc3339(timestamp).map(|t| t.timestamp_millis())
+    else {
+        return;
+    };
+
+    // Idempotency-Key style header, else a well-known key in a JSON body
+    let header_key = data
+        .get("requestHeaders")
+        .and_then(|h| h.as_object())
+        .and_then(|h| {
+            h.iter()
+                .find(|(k, _)| k.to_lowercase().contains("idempotency"))
+        })
+        .and_then(|(k, v)| Some((k.clone(), v.as_str()?.to_string())));
+    let idempotency = header_key.or_else(|| {
+        let json = serde_json::from_str::<serde_json::Value>(body).ok()?;
+        let (k, v) = json
+            .as_object()?
+            .iter()
+            .find(|(k, _)| IDEMPOTENCY_BODY_KEYS.contains(&k.to_lowercase().as_str()))?;
+        Some((format!("body.{k}"), v.to_string()))
+    });
+
+    // The key itself differs between attempts only if the app regenerates it, so leave it out
+    let fingerprint = match &idempotency {
+        Some((name, value)) if name.starts_with("body.") => {
+            format!("{url_str} {}", body.replace(value.trim_matches('"'), ""))
+        }
+        _ => format!("{url_str} {body}"),
+    };
+
+    let stats = retry_stats.entry(key.to_string()).or_default();
+    if let Some((name, value)) = idempotency {
+        stats.idempotency_key.get_or_insert(name);
+        stats.key_values.insert(value);
+        stats.keyed_calls += 1;
+    }
+
+    let mut run = Vec::new();
+    if let Some(prev) = last_attempts.get(key) {
+        let gap = at_ms - prev.at_ms;
+        let failed =
+            prev.status == 0 || prev.status == 408 || prev.status == 429 || prev.status >= 500;
+        if failed && prev.fingerprint == fingerprint && (0..=RETRY_WINDOW_MS).contains(&gap) {
+            stats.retries += 1;
+            if !stats.statuses.contains(&prev.status) {
+                stats.statuses.push(prev.status);
+            }
+            run = prev.run.clone();
+            run.push(gap as u64);
+            if run.len() > stats.longest_run.len() {
+                stats.longest_run = run.clone();
+            }
+        }
+    }
+    last_attempts.insert(
+        key.to_string(),
+        Attempt {
+            fingerprint,
+            at_ms,
+            status,
+            run,
+        },
+    );
+}
+
+/// Turn an endpoint's retry evidence into `retry` annotations (None if there was none)
+fn retry_semantics(methods: &[String], stats: RetryStats) -> Option<RetrySemantics> {
+    if stats.retries == 0 && stats.idempotency_key.is_none() {
+        return None;
+    }
+    let idempotent_method = methods
+        .iter()
+        .all(|m| IDEMPOTENT_METHODS.contains(&m.as_str()));
+    // Consecutive delays growing by >= 1.5x is exponential; within 25% of each other is fixed
+    let run = &stats.longest_run;
+    let backoff = (run.len() >= 2).then(|| {
+        let pairs: Vec<(f64, f64)> = run.windows(2).map(|w| (w[0] as f64, w[1] as f64)).collect();
+        if pairs.iter().all(|(a, b)| *b >= a * 1.5) {
+            "exponential".to_string()
+        } else if pairs.iter().all(|(a, b)| (b - a).abs() <= a * 0.25) {
+            "fixed".to_string()
+        } else {
+            "varied".to_string()
+        }
+    });
+    Some(RetrySemantics {
+        safe_to_retry: idempotent_method || stats.idempotency_key.is_some() || stats.retries > 0,
+        regenerate_key: stats.idempotency_key.is_some()
+            && !(stats.key_values.len() == 1 && stats.keyed_calls > 1 + stats.retries),
+        idempotency_key: stats.idempotency_key,
+        retries_seen: stats.retries,
+        retried_statuses: stats.statuses,
+        backoff_ms: stats.longest_run,
+        backoff,
+    })
+}
+
+/// Shape of a captured request body: multipart parts (name, text/file, content
+/// type — never values or file contents), or the key/type shape of a JSON body.
+fn request_shape(data: &serde_json::Value) -> Option<serde_json::Value> {
+    if let Some(parts) = data.get("requestParts").and_then(|v| v.as_array()) {
+        let described: Vec<serde_json::Value> = parts
+            .iter()
+            .map(|p| {
+                let mut part = serde_json::json!({
+                    "name": p.get("name"),
+                    "type": p.get("type"),
+                });
+                if let Some(ct) = p
+                    .get("contentType")
+                    .and_then(|v| v.as_str())
+                    .filter(|s| !s.is_empty())
+                {
+                    part["content_type"] = serde_json::json!(ct);
+                }
+                part
+            })
+            .collect();
+        return Some(serde_json::json!({"multipart": described}));
+    }
+    data.get("requestBody")
+        .and_then(|v| v.as_str())
+        .and_then(|body| serde_json::from_str::<serde_json::Value>(body).ok())
+        .filter(|v| v.is_object() || v.is_array())
+        .map(|v| extract_shape(&v, 0))
+}
+
+/// Shape of a capture's JSON response body (see `extract_shape`).
+pub(crate) fn response_shape(data: &serde_json::Value) -> Option<serde_json::Value> {
+    response_json(data).map(|v| extract_shape(&v, 0))
+}
+
+/// A capture's response body as JSON. Streamed bodies (NDJSON/SSE) aren't one JSON
+/// document — their first record is used.
+fn response_json(data: &serde_json::Value) -> Option<serde_json::Value> {
+    let streamed = data
+        .get("streamed")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    data.get("responseBody")
+        .and_then(|v| v.as_str())
+        .and_then(|body| {
+            serde_json::from_str::<serde_json::Value>(body)
+                .ok()
+                .or_else(|| {
+                    if !streamed {
+                        return None;
+                    }
+                    body.lines()
+                        .map(|l| l.trim().trim_start_matches("data:").trim())
+                        .find(|l| !l.is_empty())
+                        .and_then(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+                })
+        })
+}
+
 /// Extract a JSON shape: replace values with type indicators, limit depth
 fn extract_shape(value: &serde_json::Value, depth: u32) -> serde_json::Value {
     if depth > 2 {
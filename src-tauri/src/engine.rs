@@ -0,0 +1,115 @@
+use crate::config;
+use crate::endpoints::EndpointCatalog;
+use std::fs;
+use std::path::PathBuf;
+
+pub use crate::config::is_archived;
+pub use crate::config::SessionData;
+pub use crate::generation::GenerateSteps;
+
+// The capture pipeline without the GUI: everything here works on plain JSON and the
+// data directory, so other tools can feed captures in and read the analysis back
+// without a Tauri app or AppHandle.
+
+/// Keep app data somewhere other than ~/.harharhar/. Call before anything else;
+/// returns false if the data directory was already set.
+pub fn set_data_dir(path: PathBuf) -> bool {
+    config::set_data_dir(path)
+}
+
+/// Save one capture (an object in intercept.js's format: type, method, url,
+/// requestHeaders, requestBody, status, responseHeaders, responseBody, timestamp, ...)
+/// the way the browser's captures are saved: bodies decoded, secrets flagged, noise
+/// and unauthenticated API calls dropped, the app's session updated.
+///
+/// It goes to `app_name`, or else the app owning its domain. `session_ts` names the
+/// capture file (e.g. "2026-02-21T14-30" — see `session_stamp`). Returns the app it
+/// was saved to, or why it was dropped (the reasons debug.log uses).
+pub fn ingest(
+    mut data: serde_json::Value,
+    app_name: Option<&str>,
+    session_ts: &str,
+) -> Result<String, &'static str> {
+    crate::capture::normalize_bodies(&mut data);
+    crate::secrets::scan(&mut data);
+    crate::capture::ingest(&data, app_name, session_ts)
+}
+
+/// A capture file stamp for the current time
+pub fn session_stamp() -> String {
+    chrono::Utc::now().format("%Y-%m-%dT%H-%M").to_string()
+}
+
+/// Write out anything `ingest` is still holding (runs of repeated identical captures,
+/// captures queued for the writer, their counts for stats.json). Call before `generate`
+/// and before exiting.
+pub fn flush() {
+    crate::capture::flush_repeats();
+    crate::capture_writer::flush();
+    crate::stats::flush();
+}
+
+/// Regenerate an app's endpoints.json, auth.json, digest and sitemap, trimming old
+/// bodies and tidying domains like the background generator does. Captures in
+/// `session_ts`'s file are left untrimmed ("" trims everything).
+pub fn generate(app_name: &str, session_ts: &str) {
+    flush();
+    crate::generation::generate_for_app(app_name, session_ts);
+    crate::identity::generate();
+}
+
+/// `generate` with a choice of the optional steps (trimming, domain cleanup). Unlike
+/// `generate` it also runs on archived apps — check `is_archived` first.
+pub fn generate_steps(app_name: &str, session_ts: &str, steps: GenerateSteps) {
+    flush();
+    crate::generation::run_steps(app_name, session_ts, steps);
+    crate::identity::generate();
+}
+
+/// An app's generated endpoint catalog (None until `generate` has run)
+pub fn endpoints(app_name: &str) -> Option<EndpointCatalog> {
+    let path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("endpoints.json");
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+}
+
+/// The inferred shape of a capture's JSON response body, as in endpoints.json's
+/// `response_shape_sample` (None for non-JSON bodies)
+pub fn response_shape(data: &serde_json::Value) -> Option<serde_json::Value> {
+    crate::endpoints::response_shape(data)
+}
+
+/// "METHOD /normalized/{id}/path" — the endpoint a capture counts toward
+pub fn endpoint_pattern(data: &serde_json::Value) -> Option<String> {
+    crate::capture_index::entry_pattern(data)
+}
+
+/// An app's current credentials (sessions/latest.json)
+pub fn session(app_name: &str) -> Option<SessionData> {
+    let path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("sessions")
+        .join("latest.json");
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+}
+
+/// Every app, archived ones included
+pub fn apps() -> Vec<String> {
+    config::list_apps()
+}
+
+/// Create an app owning `domain`, or add `domain` to the app if it already exists
+pub fn create_app(app_name: &str, domain: &str) {
+    if config::list_apps().iter().any(|a| a == app_name) {
+        config::add_domain_to_app(app_name, domain);
+    } else {
+        config::create_app(app_name, domain);
+    }
+}
@@ -0,0 +1,104 @@
+/// Why a command failed. Sent back as `{"error": {"code", "message", "retryable"}}` so
+/// agents can branch on `code` instead of matching message text.
+#[derive(Debug, Clone)]
+pub enum CommandError {
+    /// The command isn't valid JSON
+    InvalidJson(String),
+    /// Missing or wrong `token`
+    Unauthorized,
+    UnknownAction(String),
+    /// A parameter is missing or unusable
+    InvalidParams(String),
+    /// The window the command targets isn't open (or Chrome detached)
+    BrowserNotOpen(String),
+    /// The page didn't answer in time
+    EvalTimeout,
+    /// The eval was given up on: `cancel`, or its window closed
+    EvalCancelled,
+    /// The page's script threw, or the webview refused it
+    EvalFailed(String),
+    NavigationFailed(String),
+    /// A domain no app has (or that wasn't removed, for restore_domain)
+    UnknownDomain(String),
+    /// No app by that name, or none for this window
+    UnknownApp(String),
+    /// A mark, confirmation, ... that doesn't exist
+    NotFound(String),
+    /// The user said no to a held command
+    Declined(String),
+    /// Anything else
+    Failed(String),
+}
+
+impl CommandError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CommandError::InvalidJson(_) => "INVALID_JSON",
+            CommandError::Unauthorized => "UNAUTHORIZED",
+            CommandError::UnknownAction(_) => "UNKNOWN_ACTION",
+            CommandError::InvalidParams(_) => "INVALID_PARAMS",
+            CommandError::BrowserNotOpen(_) => "BROWSER_NOT_OPEN",
+            CommandError::EvalTimeout => "EVAL_TIMEOUT",
+            CommandError::EvalCancelled => "EVAL_CANCELLED",
+            CommandError::EvalFailed(_) => "EVAL_FAILED",
+            CommandError::NavigationFailed(_) => "NAVIGATION_FAILED",
+            CommandError::UnknownDomain(_) => "UNKNOWN_DOMAIN",
+            CommandError::UnknownApp(_) => "UNKNOWN_APP",
+            CommandError::NotFound(_) => "NOT_FOUND",
+            CommandError::Declined(_) => "DECLINED",
+            CommandError::Failed(_) => "FAILED",
+        }
+    }
+
+    /// Whether sending the same command again may work (a slow page, a flaky load)
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            CommandError::EvalTimeout
+                | CommandError::EvalCancelled
+                | CommandError::NavigationFailed(_)
+        )
+    }
+
+    /// `{"code", "message", "retryable"}`
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "code": self.code(),
+            "message": self.to_string(),
+            "retryable": self.retryable(),
+        })
+    }
+
+    /// `{"error": {...}}`, the reply to a failed command
+    pub fn reply(&self) -> serde_json::Value {
+        serde_json::json!({ "error": self.to_json() })
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::Unauthorized => write!(f, "missing or wrong token"),
+            CommandError::UnknownAction(action) => write!(f, "unknown action: {action}"),
+            CommandError::BrowserNotOpen(window) => write!(f, "{window} window not open"),
+            CommandError::EvalTimeout => write!(f, "eval timeout"),
+            CommandError::EvalCancelled => write!(f, "eval cancelled"),
+            CommandError::InvalidJson(m)
+            | CommandError::InvalidParams(m)
+            | CommandError::EvalFailed(m)
+            | CommandError::NavigationFailed(m)
+            | CommandError::UnknownDomain(m)
+            | CommandError::UnknownApp(m)
+            | CommandError::NotFound(m)
+            | CommandError::Declined(m)
+            | CommandError::Failed(m) => write!(f, "{m}"),
+        }
+    }
+}
+
+/// Helpers that still report plain strings (Tauri commands, generation) take these as-is
+impl From<CommandError> for String {
+    fn from(e: CommandError) -> String {
+        e.to_string()
+    }
+}
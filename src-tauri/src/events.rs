@@ -0,0 +1,43 @@
+use crate::config;
+use crate::notifications;
+use crate::webhooks;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// events.jsonl rolls over to events.jsonl.1 past this size
+const MAX_LOG_BYTES: u64 = 5_000_000;
+
+/// Serializes appends (and the rollover) across threads
+static EVENTS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Append an event to `~/.harharhar/events.jsonl`.
+/// This mirrors the interesting `app.emit` events for consumers outside the
+/// Tauri frontend — agents and scripts can `tail -f` the file and react.
+/// Each line is `{"event": kind, "timestamp": ..., ...fields}`. Events the user has to
+/// act on are also kept in `notifications.json` until acknowledged.
+pub fn emit(kind: &str, fields: serde_json::Value) {
+    let mut entry = serde_json::json!({
+        "event": kind,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    if let (Some(obj), serde_json::Value::Object(extra)) = (entry.as_object_mut(), fields) {
+        for (k, v) in extra {
+            obj.insert(k, v);
+        }
+    }
+
+    {
+        let _lock = EVENTS_LOCK.lock().unwrap();
+        let path = config::data_dir().join("events.jsonl");
+        if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+            let _ = std::fs::rename(&path, config::data_dir().join("events.jsonl.1"));
+        }
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+            let _ = writeln!(file, "{entry}");
+        }
+    }
+
+    notifications::record(kind, &entry);
+    webhooks::dispatch(kind, &entry);
+}
@@ -0,0 +1,137 @@
+use crate::capture_index;
+use crate::config;
+use crate::query::PatternMatch;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Matches index patterns against an export pattern: "POST /api/messages*" is a glob
+/// (`*` matches anything, IDs in the path are normalized); without a `*` it's an
+/// endpoint pattern or a piece of one, as in query_captures.
+enum Filter {
+    Glob(String),
+    Pattern(PatternMatch),
+}
+
+impl Filter {
+    fn new(pattern: &str) -> Self {
+        if !pattern.contains('*') {
+            return Filter::Pattern(PatternMatch::new(pattern));
+        }
+        match pattern.split_once(' ') {
+            Some((method, path)) if path.starts_with('/') => Filter::Glob(format!(
+                "{} {}",
+                method.to_uppercase(),
+                crate::endpoints::normalize_path(path)
+            )),
+            _ => Filter::Glob(pattern.to_string()),
+        }
+    }
+
+    fn matches(&self, pattern: &str) -> bool {
+        match self {
+            Filter::Glob(glob) => !pattern.is_empty() && glob_match(glob, pattern),
+            Filter::Pattern(p) => p.matches(pattern),
+        }
+    }
+}
+
+/// `*` matches any run of characters; everything else matches itself
+fn glob_match(glob: &str, text: &str) -> bool {
+    let mut parts = glob.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Trimmed originals of one capture file, by (timestamp, url, field)
+fn originals(app_dir: &Path, file: &str) -> HashMap<(String, String, String), serde_json::Value> {
+    let mut text = String::new();
+    let Ok(bytes) = std::fs::read(app_dir.join("trimmed").join(format!("{file}.gz"))) else {
+        return HashMap::new();
+    };
+    // Trimming appends one gzip member per run
+    if flate2::read::MultiGzDecoder::new(&bytes[..])
+        .read_to_string(&mut text)
+        .is_err()
+    {
+        return HashMap::new();
+    }
+    text.lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|mut v| {
+            let field = |k: &str| v.get(k).and_then(|s| s.as_str()).unwrap_or("").to_string();
+            let key = (field("timestamp"), field("url"), field("field"));
+            Some((key, v.get_mut("value")?.take()))
+        })
+        .collect()
+}
+
+/// Write every capture of `app_name` whose endpoint matches `pattern` to `out` as
+/// JSONL, oldest first. With `untrimmed`, trimmed fields are put back from
+/// trimmed/*.gz where the trim policy kept the originals. Returns how many captures
+/// were written.
+pub fn run(app_name: &str, pattern: &str, out: &Path, untrimmed: bool) -> Result<usize, String> {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    if !app_dir.is_dir() {
+        return Err(format!("no app named \"{app_name}\""));
+    }
+    let filter = Filter::new(pattern);
+    let mut locations: Vec<capture_index::Location> = capture_index::locations(&app_dir)
+        .filter(|loc| filter.matches(&loc.pattern))
+        .collect();
+    locations.sort_by(|a, b| (&a.file, a.offset).cmp(&(&b.file, b.offset)));
+
+    let mut writer = std::io::BufWriter::new(
+        std::fs::File::create(out).map_err(|e| format!("{}: {e}", out.display()))?,
+    );
+    let mut restore: Option<(String, HashMap<_, _>)> = None;
+    let mut written = 0;
+    for loc in locations {
+        let Some(mut data) = capture_index::read(&app_dir, &loc) else {
+            continue;
+        };
+        if untrimmed {
+            if restore.as_ref().is_none_or(|(file, _)| *file != loc.file) {
+                restore = Some((loc.file.clone(), originals(&app_dir, &loc.file)));
+            }
+            let kept = restore.as_ref().map(|(_, kept)| kept);
+            let str_field = |k: &str| {
+                data.get(k)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let (timestamp, url) = (str_field("timestamp"), str_field("url"));
+            let trimmed: Vec<String> = data
+                .as_object()
+                .into_iter()
+                .flatten()
+                .filter(|(_, v)| v.as_str().is_some_and(|s| s.starts_with("[trimmed: ")))
+                .map(|(k, _)| k.clone())
+                .collect();
+            for field in trimmed {
+                let key = (timestamp.clone(), url.clone(), field.clone());
+                if let Some(original) = kept.and_then(|kept| kept.get(&key)) {
+                    data[field.as_str()] = original.clone();
+                }
+            }
+        }
+        writeln!(writer, "{data}").map_err(|e| e.to_string())?;
+        written += 1;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    Ok(written)
+}
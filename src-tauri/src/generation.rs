@@ -0,0 +1,135 @@
+use crate::config;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+use std::time::Instant;
+use tokio::sync::mpsc;
+
+/// (app name, session timestamp) for every capture line written
+static CAPTURED: OnceLock<mpsc::UnboundedSender<(String, String)>> = OnceLock::new();
+
+/// One lock per app, held for a whole generation pass: the background pass,
+/// `generate_endpoints` and the CLI would otherwise trim the same capture files and
+/// write endpoints.json and auth.json at once
+static GENERATING: LazyLock<Mutex<HashMap<String, Arc<Mutex<()>>>>> =
+    LazyLock::new(Default::default);
+
+/// Start the background generator. Captures are reported to it by `mark_dirty`;
+/// `generate_every_minutes` (config.json) after the first capture since its last run,
+/// it regenerates endpoints, digest and sitemap for just the apps that got new
+/// captures, off the capture path. A quiet app is picked up as surely as a chatty one,
+/// and a chatty one isn't regenerated more often than that.
+pub fn start() {
+    let (tx, rx) = mpsc::unbounded_channel();
+    if CAPTURED.set(tx).is_ok() {
+        tauri::async_runtime::spawn(run(rx));
+    }
+}
+
+/// Note that an app has a new capture line (cheap — just a channel send)
+pub fn mark_dirty(app_name: &str, session_ts: &str) {
+    if let Some(tx) = CAPTURED.get() {
+        let _ = tx.send((app_name.to_string(), session_ts.to_string()));
+    }
+}
+
+async fn run(mut rx: mpsc::UnboundedReceiver<(String, String)>) {
+    let mut dirty: HashSet<String> = HashSet::new();
+    let mut session_ts = String::new();
+    // When the first capture not yet generated for came in
+    let mut dirty_since: Option<Instant> = None;
+    loop {
+        // Re-read each time round, so a config.json edit applies without a restart
+        let interval = config::generate_interval();
+        let received = match (dirty_since, interval) {
+            (Some(since), Some(interval)) => {
+                let left = interval.saturating_sub(since.elapsed());
+                // Checked before waiting: a busy channel never lets the timeout fire
+                if left.is_zero() {
+                    dirty_since = None;
+                    let apps: Vec<String> = dirty.drain().collect();
+                    let session_ts = session_ts.clone();
+                    // Captures arriving meanwhile queue up in the channel for the next run
+                    let _ = tauri::async_runtime::spawn_blocking(move || {
+                        for app_name in apps {
+                            generate_for_app(&app_name, &session_ts);
+                        }
+                        crate::identity::generate();
+                    })
+                    .await;
+                    continue;
+                }
+                match tokio::time::timeout(left, rx.recv()).await {
+                    Ok(received) => received,
+                    Err(_) => continue,
+                }
+            }
+            _ => rx.recv().await,
+        };
+        let Some((app_name, ts)) = received else {
+            return;
+        };
+        dirty.insert(app_name);
+        session_ts = ts;
+        dirty_since.get_or_insert_with(Instant::now);
+    }
+}
+
+/// Regenerate every (non-archived) app — for `generate_endpoints` and `end_session`
+pub fn generate_all(session_ts: &str) {
+    crate::capture::flush_repeats();
+    crate::capture_writer::flush();
+    for app_name in config::list_apps() {
+        generate_for_app(&app_name, session_ts);
+    }
+    crate::identity::generate();
+}
+
+/// The optional steps of a generation pass; both run by default
+#[derive(Debug, Clone, Copy)]
+pub struct GenerateSteps {
+    /// Trim bodies of well-sampled endpoints in old capture files
+    pub trim: bool,
+    /// Drop third-party domains from the app's config
+    pub clean_domains: bool,
+}
+
+impl Default for GenerateSteps {
+    fn default() -> Self {
+        GenerateSteps {
+            trim: true,
+            clean_domains: true,
+        }
+    }
+}
+
+/// endpoints.json + auth.json, body trimming, domain cleanup, digest, sitemap, timelines and plugins for
+/// one app. identity.json spans apps, so callers regenerate it once after their pass.
+pub(crate) fn generate_for_app(app_name: &str, session_ts: &str) {
+    if config::is_archived(app_name) {
+        return;
+    }
+    run_steps(app_name, session_ts, GenerateSteps::default());
+}
+
+/// A generation pass with a choice of steps, archived or not. Waits for a pass
+/// already running on the same app.
+pub(crate) fn run_steps(app_name: &str, session_ts: &str, steps: GenerateSteps) {
+    let lock = GENERATING
+        .lock()
+        .unwrap()
+        .entry(app_name.to_string())
+        .or_default()
+        .clone();
+    let _generating = lock.lock().unwrap();
+    crate::endpoints::generate_for_app(app_name);
+    if steps.trim {
+        crate::cleanup::trim_captures_for_app(app_name, session_ts);
+    }
+    if steps.clean_domains {
+        crate::cleanup::clean_app_domains(app_name);
+    }
+    crate::digest::generate_for_app(app_name);
+    crate::sitemap::generate_for_app(app_name);
+    crate::timeline::generate_for_app(app_name);
+    crate::plugins::generate(app_name);
+}
@@ -0,0 +1,143 @@
+use crate::config;
+use crate::AppState;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::Manager;
+
+/// An action counts as the cause of a page load if it happened this recently.
+const ACTION_WINDOW: Duration = Duration::from_secs(15);
+
+/// Serializes appends to history.jsonl across IPC threads
+static HISTORY_LOCK: Mutex<()> = Mutex::new(());
+
+/// Remember what is about to drive a window to a new page ("navigate", "click_ref",
+/// "crawl", ...) so the resulting history entry can say how it got there.
+pub fn note_action(app: &tauri::AppHandle, window: &str, action: &str) {
+    app.state::<AppState>()
+        .last_actions
+        .lock()
+        .unwrap()
+        .insert(window.to_string(), (action.to_string(), Instant::now()));
+}
+
+/// Append a page navigation to `apps/<name>/history.jsonl`. The title isn't
+/// known until the page has rendered, so this reads it from the window shortly after.
+pub fn record_navigation(app: &tauri::AppHandle, window: &str, app_name: &str, url: &str) {
+    let via = app
+        .state::<AppState>()
+        .last_actions
+        .lock()
+        .unwrap()
+        .remove(window)
+        .filter(|(_, at)| at.elapsed() < ACTION_WINDOW)
+        .map(|(action, _)| action)
+        .unwrap_or_else(|| "browser".to_string());
+
+    let app = app.clone();
+    let window = window.to_string();
+    let app_name = app_name.to_string();
+    let url = url.to_string();
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(1500));
+        let page: serde_json::Value = crate::eval_js_with_timeout(
+            &app,
+            &window,
+            "JSON.stringify({title: document.title, referrer: document.referrer})",
+            Duration::from_secs(3),
+        )
+        .ok()
+        .and_then(|r| serde_json::from_str(&r).ok())
+        .unwrap_or_default();
+
+        let entry = serde_json::json!({
+            "url": url,
+            "title": page.get("title").and_then(|v| v.as_str()).unwrap_or(""),
+            "referrer": page.get("referrer").and_then(|v| v.as_str()).unwrap_or(""),
+            "via": via,
+            "window": window,
+            "timestamp": timestamp,
+        });
+
+        let _lock = HISTORY_LOCK.lock().unwrap();
+        let path = config::data_dir()
+            .join("apps")
+            .join(&app_name)
+            .join("history.jsonl");
+        if let Ok(mut f) = fs::OpenOptions::new().create(true).append(true).open(&path) {
+            let _ = writeln!(f, "{}", entry);
+        }
+    });
+}
+
+/// An app's history: the last `limit` visits, newest first, plus each distinct
+/// URL with its visit count and latest title.
+pub fn read(app_name: &str, limit: usize) -> serde_json::Value {
+    let path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("history.jsonl");
+    let entries: Vec<serde_json::Value> = fs::read_to_string(&path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect();
+
+    let mut pages: HashMap<String, (u64, String, String)> = HashMap::new();
+    for e in &entries {
+        let field = |k: &str| e.get(k).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let page = pages
+            .entry(field("url"))
+            .or_insert((0, String::new(), String::new()));
+        page.0 += 1;
+        if !field("title").is_empty() {
+            page.1 = field("title");
+        }
+        page.2 = field("timestamp");
+    }
+    let mut pages: Vec<serde_json::Value> = pages
+        .into_iter()
+        .map(|(url, (visits, title, last_visit))| {
+            serde_json::json!({"url": url, "title": title, "visits": visits, "last_visit": last_visit})
+        })
+        .collect();
+    pages.sort_by(|a, b| b["last_visit"].as_str().cmp(&a["last_visit"].as_str()));
+
+    let recent: Vec<&serde_json::Value> = entries.iter().rev().take(limit).collect();
+    serde_json::json!({
+        "app": app_name,
+        "visits": entries.len(),
+        "pages": pages,
+        "recent": recent,
+    })
+}
+
+/// Where to pick an app back up: the last page visited, or else its primary
+/// (first) domain
+pub fn start_url(app_name: &str) -> Result<String, String> {
+    let Some((_, domains)) = config::list_app_details()
+        .into_iter()
+        .find(|(name, _)| name == app_name)
+    else {
+        return Err(format!("no app named \"{app_name}\""));
+    };
+    let path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("history.jsonl");
+    let last = fs::read_to_string(&path)
+        .unwrap_or_default()
+        .lines()
+        .rev()
+        .filter_map(|l| serde_json::from_str::<serde_json::Value>(l).ok())
+        .find_map(|e| e.get("url").and_then(|v| v.as_str()).map(String::from))
+        .filter(|url| url.starts_with("http"));
+    match (last, domains.first()) {
+        (Some(url), _) => Ok(url),
+        (None, Some(domain)) => Ok(format!("https://{domain}/")),
+        (None, None) => Err(format!("app \"{app_name}\" has no domains")),
+    }
+}
@@ -0,0 +1,133 @@
+use crate::config;
+use crate::domain_groups::registrable_domain;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+
+/// Registrable domains of single sign-on providers
+const IDENTITY_PROVIDERS: &[&str] = &[
+    "okta.com",
+    "oktapreview.com",
+    "okta-emea.com",
+    "auth0.com",
+    "onelogin.com",
+    "microsoftonline.com",
+    "pingidentity.com",
+    "duosecurity.com",
+    "jumpcloud.com",
+    "onelogin.us",
+    "keycloak.org",
+];
+/// First host labels that mark a login host on any domain ("accounts.google.com")
+const IDENTITY_LABELS: &[&str] = &[
+    "accounts", "login", "sso", "signin", "auth", "id", "idp", "oauth",
+];
+
+/// Cookie values shorter than this are too generic ("1", "en-US") to prove a shared session
+const MIN_SHARED_VALUE_LEN: usize = 16;
+
+/// Is this host where users sign in, rather than an app?
+fn is_identity_host(host: &str) -> bool {
+    let first = host.split('.').next().unwrap_or("");
+    IDENTITY_PROVIDERS.contains(&registrable_domain(host).as_str())
+        || IDENTITY_LABELS.contains(&first)
+}
+
+/// Write ~/.harharhar/identity.json: sign-in providers several apps go through (a login
+/// host in more than one app's domains or history) and sessions apps share (the same auth cookie
+/// value in their latest sessions). Logging in again there fixes all of those apps.
+pub fn generate() {
+    let apps = config::list_apps();
+    // identity host -> apps that use it
+    let mut providers: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    // (cookie name, value) -> apps holding it
+    let mut cookies: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+
+    for app_name in &apps {
+        let app_dir = config::data_dir().join("apps").join(app_name);
+        let app_cfg = fs::read_to_string(app_dir.join("config.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str::<config::AppConfig>(&s).ok());
+        // Login hosts show up in an app's domains, or in its history: visited while
+        // signing in, or as the referrer of the page the provider sent the user back to
+        let mut hosts: BTreeSet<String> = app_cfg
+            .map(|c| c.domains)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        let history = fs::read_to_string(app_dir.join("history.jsonl")).unwrap_or_default();
+        for visit in history
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        {
+            for key in ["url", "referrer"] {
+                let url = visit.get(key).and_then(|v| v.as_str()).unwrap_or("");
+                if let Some(host) = url::Url::parse(url)
+                    .ok()
+                    .and_then(|u| u.host_str().map(String::from))
+                {
+                    hosts.insert(host);
+                }
+            }
+        }
+        for host in hosts.into_iter().filter(|h| is_identity_host(h)) {
+            providers.entry(host).or_default().insert(app_name.clone());
+        }
+        let session = fs::read_to_string(app_dir.join("sessions").join("latest.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str::<config::SessionData>(&s).ok());
+        for (name, value) in session.map(|s| s.cookies).unwrap_or_default() {
+            let lower = name.to_lowercase();
+            if value.len() >= MIN_SHARED_VALUE_LEN
+                && crate::endpoints::AUTH_COOKIE_PATTERNS
+                    .iter()
+                    .any(|p| lower.contains(p))
+            {
+                cookies
+                    .entry((name, value))
+                    .or_default()
+                    .insert(app_name.clone());
+            }
+        }
+    }
+
+    // Hosts of one provider used by the same apps are listed together
+    let mut by_domain: BTreeMap<String, (BTreeSet<String>, BTreeSet<String>)> = BTreeMap::new();
+    for (host, users) in providers.into_iter().filter(|(_, users)| users.len() > 1) {
+        let (hosts, apps) = by_domain.entry(registrable_domain(&host)).or_default();
+        hosts.insert(host);
+        apps.extend(users);
+    }
+    let providers: Vec<serde_json::Value> = by_domain
+        .into_iter()
+        .map(|(domain, (hosts, apps))| {
+            serde_json::json!({"domain": domain, "hosts": hosts, "apps": apps})
+        })
+        .collect();
+
+    // Cookies shared by the same set of apps make one shared session
+    let mut sessions: BTreeMap<BTreeSet<String>, Vec<String>> = BTreeMap::new();
+    for ((name, _), users) in cookies.into_iter().filter(|(_, users)| users.len() > 1) {
+        let names = sessions.entry(users).or_default();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    let shared_sessions: Vec<serde_json::Value> = sessions
+        .into_iter()
+        .map(|(apps, cookies)| serde_json::json!({"apps": apps, "cookies": cookies}))
+        .collect();
+
+    let path = config::data_dir().join("identity.json");
+    if providers.is_empty() && shared_sessions.is_empty() {
+        let _ = fs::remove_file(path);
+        return;
+    }
+    let identity = serde_json::json!({
+        "generated_at": chrono::Utc::now().to_rfc3339(),
+        "providers": providers,
+        "shared_sessions": shared_sessions,
+    });
+    if let Ok(json) = serde_json::to_string_pretty(&identity) {
+        let _ = fs::write(path, json);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::capture;
+use crate::config;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+/// What an import did, for the CLI to report
+#[derive(Default)]
+pub struct ImportSummary {
+    /// Captures written, per app
+    pub imported: BTreeMap<String, usize>,
+    /// Entries left out: noise, no auth to replay, or a domain no app owns
+    pub skipped: usize,
+    /// Hosts seen that no app owns (pass an app name to claim them)
+    pub unknown_domains: Vec<String>,
+}
+
+/// Import a HAR file (with content) or a Playwright trace zip into app captures.
+/// Entries go to the app that owns their domain; with `app_name`, everything goes
+/// to that app (created if missing) and new domains are added to it. The same
+/// filters as live capture apply: noise is dropped, and so are API calls that
+/// carry no cookie or auth header.
+pub fn run(path: &Path, app_name: Option<&str>) -> Result<ImportSummary, String> {
+    let source = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let is_zip = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("zip"));
+    let entries = if is_zip {
+        trace_entries(path)?
+    } else {
+        let har: serde_json::Value = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {e}", path.display()))
+            .and_then(|s| {
+                serde_json::from_str(&s).map_err(|e| format!("{}: {e}", path.display()))
+            })?;
+        har.pointer("/log/entries")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| format!("{}: not a HAR file (no log.entries)", path.display()))?
+            .iter()
+            .map(|e| capture_entry(e, &|_| None))
+            .collect()
+    };
+
+    let max_body = config::config().capture.max_body_bytes as usize;
+    let mut summary = ImportSummary::default();
+    // Captures per app, written to a capture file named for the first one's time
+    let mut by_app: HashMap<String, Vec<serde_json::Value>> = HashMap::new();
+    // Hosts already added to `app_name`
+    let mut claimed: HashSet<String> = HashSet::new();
+    for mut data in entries.into_iter().flatten() {
+        let url = data
+            .get("url")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let Some(host) = url::Url::parse(&url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+        else {
+            summary.skipped += 1;
+            continue;
+        };
+        let owner = match app_name {
+            Some(name) => Some(name.to_string()),
+            None => config::find_app_for_domain(&host),
+        };
+        let Some(owner) = owner else {
+            if !summary.unknown_domains.contains(&host) {
+                summary.unknown_domains.push(host);
+            }
+            summary.skipped += 1;
+            continue;
+        };
+
+        for field in ["requestBody", "responseBody"] {
+            if let Some(body) = data.get(field).and_then(|v| v.as_str()) {
+                // Base64 is cut on a 4-char boundary (3 bytes), or it no longer decodes
+                let max = match body.strip_prefix("base64:") {
+                    Some(_) => "base64:".len() + max_body / 3 * 4,
+                    None => max_body,
+                };
+                if body.len() > max {
+                    data[field] = serde_json::Value::from(capture::truncate_body(body, max));
+                }
+            }
+        }
+        data["imported_from"] = serde_json::Value::from(source.as_str());
+
+        if app_name.is_some() && claimed.insert(host.clone()) {
+            crate::engine::create_app(&owner, &host);
+        }
+        by_app.entry(owner).or_default().push(data);
+    }
+
+    // Saved the way live captures are (filters, sampling, repeats, blobs), into a
+    // capture file named for each app's earliest imported entry
+    for (owner, captures) in by_app {
+        let stamp = session_stamp(&captures);
+        for data in captures {
+            match crate::engine::ingest(data, Some(&owner), &stamp) {
+                Ok(_) => *summary.imported.entry(owner.clone()).or_insert(0) += 1,
+                Err(_) => summary.skipped += 1,
+            }
+        }
+    }
+    // The CLI exits straight after: write out what's queued or held
+    crate::engine::flush();
+    Ok(summary)
+}
+
+/// Capture file stamp ("2026-02-21T14-30") of the earliest imported entry, so
+/// imports sort among live sessions by when they were recorded
+fn session_stamp(captures: &[serde_json::Value]) -> String {
+    captures
+        .iter()
+        .filter_map(|d| d.get("timestamp").and_then(|v| v.as_str()))
+        .filter_map(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .min()
+        .map(|t| t.with_timezone(&chrono::Utc))
+        .unwrap_or_else(chrono::Utc::now)
+        .format("%Y-%m-%dT%H-%M")
+        .to_string()
+}
+
+/// HAR entries from a Playwright trace zip: `resource-snapshot` events in its
+/// *.network files, bodies in resources/{sha1}
+fn trace_entries(path: &Path) -> Result<Vec<Option<serde_json::Value>>, String> {
+    let file = fs::File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("{}: {e}", path.display()))?;
+    let network_files: Vec<String> = zip
+        .file_names()
+        .filter(|n| n.ends_with(".network"))
+        .map(|n| n.to_string())
+        .collect();
+    if network_files.is_empty() {
+        return Err(format!("{}: no network log in this trace", path.display()));
+    }
+
+    let mut snapshots = Vec::new();
+    for name in network_files {
+        let mut text = String::new();
+        if let Ok(mut f) = zip.by_name(&name) {
+            let _ = f.read_to_string(&mut text);
+        }
+        for line in text.lines() {
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(line) else {
+                continue;
+            };
+            if event.get("type").and_then(|v| v.as_str()) == Some("resource-snapshot") {
+                if let Some(snapshot) = event.get("snapshot") {
+                    snapshots.push(snapshot.clone());
+                }
+            }
+        }
+    }
+
+    let mut resource = |sha1: &str| -> Option<Vec<u8>> {
+        let mut f = zip.by_name(&format!("resources/{sha1}")).ok()?;
+        let mut bytes = Vec::new();
+        f.read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    };
+    // Bodies are read up front: capture_entry only borrows the resolver immutably
+    let mut bodies: HashMap<String, Vec<u8>> = HashMap::new();
+    for snapshot in &snapshots {
+        for pointer in ["/request/postData/_sha1", "/response/content/_sha1"] {
+            if let Some(sha1) = snapshot.pointer(pointer).and_then(|v| v.as_str()) {
+                if let Some(bytes) = resource(sha1) {
+                    bodies.insert(sha1.to_string(), bytes);
+                }
+            }
+        }
+    }
+    Ok(snapshots
+        .iter()
+        .map(|s| capture_entry(s, &|sha1| bodies.get(sha1).cloned()))
+        .collect())
+}
+
+/// One HAR entry as a capture in intercept.js's format (None for static resources).
+/// `resource` resolves bodies stored outside the entry (Playwright's `_sha1`).
+fn capture_entry(
+    entry: &serde_json::Value,
+    resource: &dyn Fn(&str) -> Option<Vec<u8>>,
+) -> Option<serde_json::Value> {
+    let request = entry.get("request")?;
+    let response = entry.get("response");
+    let str_at = |v: Option<&serde_json::Value>, k: &str| {
+        v.and_then(|v| v.get(k))
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+
+    // Chrome's HAR export says what made the request; Playwright's doesn't, so keep
+    // whatever isn't an obvious static resource and let the noise filters decide
+    let entry_type = match entry.get("_resourceType").and_then(|v| v.as_str()) {
+        Some("xhr") => "xhr",
+        Some("fetch") | Some("eventsource") | None => "fetch",
+        Some("ping") => "beacon",
+        Some("document") => "navigation",
+        Some(_) => return None,
+    };
+
+    let body_of = |content: Option<&serde_json::Value>| -> Option<(String, bool)> {
+        let content = content?;
+        if let Some(text) = content.get("text").and_then(|v| v.as_str()) {
+            let base64 = content.get("encoding").and_then(|v| v.as_str()) == Some("base64");
+            return Some((text.to_string(), base64));
+        }
+        let bytes = resource(content.get("_sha1").and_then(|v| v.as_str())?)?;
+        match String::from_utf8(bytes) {
+            Ok(text) => Some((text, false)),
+            Err(e) => {
+                use base64::Engine;
+                Some((
+                    base64::engine::general_purpose::STANDARD.encode(e.into_bytes()),
+                    true,
+                ))
+            }
+        }
+    };
+    let request_body = body_of(request.get("postData")).map(|(text, _)| text);
+    let response_body = body_of(response.and_then(|r| r.get("content")));
+
+    let mut data = serde_json::json!({
+        "type": entry_type,
+        "method": str_at(Some(request), "method"),
+        "url": str_at(Some(request), "url"),
+        "requestHeaders": headers(request.get("headers")),
+        "requestBody": request_body,
+        "status": response.and_then(|r| r.get("status")).and_then(|v| v.as_u64()).unwrap_or(0),
+        "statusText": str_at(response, "statusText"),
+        "responseHeaders": headers(response.and_then(|r| r.get("headers"))),
+        "responseBody": response_body.as_ref().map(|(text, base64)| {
+            if *base64 { format!("base64:{text}") } else { text.clone() }
+        }),
+        "duration": entry.get("time").and_then(|v| v.as_f64()).unwrap_or(0.0).max(0.0) as u64,
+        "timestamp": str_at(Some(entry), "startedDateTime"),
+    });
+    if response_body.is_some_and(|(_, base64)| base64) {
+        data["responseEncoding"] = serde_json::Value::from("base64");
+        if let Some(size) = response
+            .and_then(|r| r.get("content"))
+            .and_then(|c| c.get("size"))
+            .and_then(|v| v.as_u64())
+        {
+            data["responseSize"] = serde_json::Value::from(size);
+        }
+    }
+    // "HTTP/2.0" → "h2", as Resource Timing and Chrome name protocols
+    let protocol = match str_at(response, "httpVersion").to_lowercase().as_str() {
+        "http/2.0" | "http/2" | "h2" => "h2".to_string(),
+        "http/3.0" | "http/3" | "h3" => "h3".to_string(),
+        other => other.to_string(),
+    };
+    if !protocol.is_empty() && protocol != "unknown" {
+        data["protocol"] = serde_json::Value::from(protocol);
+    }
+    let ip = str_at(Some(entry), "serverIPAddress");
+    if !ip.is_empty() {
+        data["remoteAddress"] = serde_json::Value::from(ip.trim_matches(['[', ']']));
+    }
+    Some(data)
+}
+
+/// HAR's [{"name","value"}] as a header object, lowercased like intercept.js's.
+/// Repeated headers are joined ("; " for cookies).
+fn headers(list: Option<&serde_json::Value>) -> serde_json::Value {
+    let mut out = serde_json::Map::new();
+    for h in list.and_then(|v| v.as_array()).into_iter().flatten() {
+        let name = h
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_lowercase();
+        let value = h.get("value").and_then(|v| v.as_str()).unwrap_or("");
+        // HTTP/2 pseudo-headers (":authority", ...) aren't replayable headers
+        if name.is_empty() || name.starts_with(':') {
+            continue;
+        }
+        let joined = match out.get(&name).and_then(serde_json::Value::as_str) {
+            Some(prev) if name == "cookie" => format!("{prev}; {value}"),
+            Some(prev) => format!("{prev}, {value}"),
+            None => value.to_string(),
+        };
+        out.insert(name, serde_json::Value::String(joined));
+    }
+    serde_json::Value::Object(out)
+}
@@ -0,0 +1,60 @@
+use crate::capture_io;
+use crate::config;
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, RcIter};
+use jaq_json::Val;
+
+/// Run a jq expression over an app's captures, oldest first, handing each result
+/// to `emit` as compact JSON. Like `jq EXPR captures/*.jsonl`: the expression runs
+/// once per capture, or with `slurp` once over an array of all of them (`jq -s`),
+/// e.g. `map(.url) | unique`. Returns how many results there were.
+pub fn run(
+    app_name: &str,
+    expr: &str,
+    slurp: bool,
+    emit: &mut dyn FnMut(String),
+) -> Result<usize, String> {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    if !app_dir.is_dir() {
+        return Err(format!("no app named \"{app_name}\""));
+    }
+
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let arena = Arena::default();
+    let modules = loader
+        .load(
+            &arena,
+            File {
+                code: expr,
+                path: (),
+            },
+        )
+        .map_err(|errs| format!("can't parse expression: {errs:?}"))?;
+    let filter = Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+        .map_err(|errs| format!("can't compile expression: {errs:?}"))?;
+
+    let mut count = 0;
+    let mut run_on = |input: serde_json::Value| -> Result<(), String> {
+        let inputs = RcIter::new(core::iter::empty());
+        for result in filter.run((Ctx::new([], &inputs), Val::from(input))) {
+            let value = result.map_err(|e| e.to_string())?;
+            emit(value.to_string());
+            count += 1;
+        }
+        Ok(())
+    };
+
+    let captures = capture_io::capture_files(&app_dir)
+        .into_iter()
+        .flat_map(|path| capture_io::entries(&path));
+    if slurp {
+        run_on(serde_json::Value::Array(captures.collect()))?;
+    } else {
+        for data in captures {
+            run_on(data)?;
+        }
+    }
+    Ok(count)
+}
@@ -1,47 +1,147 @@
+mod analyzers;
+pub mod anonymize;
+mod assertions;
+mod audit;
+mod blobs;
 mod capture;
+mod capture_index;
+mod capture_io;
+mod capture_writer;
+mod cdp;
+mod challenge;
 pub mod cleanup;
+mod computed_headers;
 mod config;
+mod confirm;
+mod consent;
+pub mod context;
+mod crawl;
+mod csrf;
+mod deep_link;
 pub mod digest;
+mod domain_groups;
+mod droplog;
 pub mod endpoints;
+pub mod engine;
+mod errors;
+mod events;
+pub mod export;
+mod generation;
+mod history;
+mod identity;
+pub mod import;
+pub mod jq;
+mod marks;
+mod notifications;
+mod page_text;
+pub mod plugins;
+mod query;
+mod sampling;
+mod secrets;
+mod shutdown;
+pub mod sitemap;
+pub mod stats;
+mod suggest;
+mod timeline;
+mod webhooks;
 
+use errors::CommandError;
 use std::sync::Mutex;
 use tauri::{Emitter, Manager};
 
 pub struct AppState {
     pub domain_map: Mutex<std::collections::HashMap<String, String>>,
+    /// Archived apps — their new traffic is held in `unmapped_captures` until unarchived
+    pub archived_apps: Mutex<std::collections::HashSet<String>>,
+    /// Confirmed domain groups: registrable domain (e.g. "google.com") -> app
+    pub domain_groups: Mutex<std::collections::HashMap<String, String>>,
     /// Safari UA — used by WKWebView browser (matches the actual engine)
     pub browser_ua: String,
     /// Chrome UA — written to sessions/latest.json for curl replay
     pub curl_ua: String,
-    pub session_file_lock: Mutex<()>,
     pub session_ts: String,
-    pub pending_url: Mutex<Option<String>>,
-    /// Which app the browser is currently browsing (for auto-adding new domains)
-    pub current_app: Mutex<Option<String>>,
+    /// Navigations to not-yet-named domains, oldest first: (domain, url, app a
+    /// harharhar:// link suggested). The head is the one the user is being asked to name.
+    pub pending_navigations: Mutex<std::collections::VecDeque<(String, String, Option<String>)>>,
+    /// Which app each browser window is currently browsing, keyed by window label
+    /// (for routing captures and auto-adding new domains)
+    pub current_apps: Mutex<std::collections::HashMap<String, String>>,
     /// Captures from unmapped domains, keyed by domain
     pub unmapped_captures: Mutex<std::collections::HashMap<String, Vec<serde_json::Value>>>,
-    /// Pending eval callbacks: id -> sender
-    pub eval_callbacks: Mutex<std::collections::HashMap<String, std::sync::mpsc::Sender<String>>>,
+    /// Pending evals: id -> (window it runs in, where its result goes)
+    pub eval_callbacks:
+        Mutex<std::collections::HashMap<String, (String, std::sync::mpsc::Sender<String>)>>,
     /// Cookie names seen in the current session — used by auth-based capture filtering
     pub session_cookie_names: Mutex<std::collections::HashSet<String>>,
-    /// Active label for the current workflow (set by annotate, closed by next annotate or close_label)
-    pub active_label: Mutex<Option<String>>,
+    /// Active label for each window's current workflow, keyed by window label
+    /// (set by annotate, closed by next annotate or close_label)
+    pub active_labels: Mutex<std::collections::HashMap<String, String>>,
+    /// Unsolved CAPTCHA/2FA challenges, keyed by window label (cleared by resume_after_human)
+    pub human_needed: Mutex<std::collections::HashMap<String, serde_json::Value>>,
+    /// Last page-driving action per window and when it happened (for history "via")
+    pub last_actions: Mutex<std::collections::HashMap<String, (String, std::time::Instant)>>,
+}
+
+/// Label of the browser window used when a caller doesn't name one.
+/// Additional windows are "browser-2", "browser-3", ...
+pub const DEFAULT_WINDOW: &str = "browser-1";
+
+impl AppState {
+    /// App the given browser window is currently browsing
+    pub fn current_app(&self, window: &str) -> Option<String> {
+        self.current_apps.lock().unwrap().get(window).cloned()
+    }
+
+    pub fn set_current_app(&self, window: &str, app_name: String) {
+        self.current_apps
+            .lock()
+            .unwrap()
+            .insert(window.to_string(), app_name);
+    }
+}
+
+/// Normalize a user-supplied window id: none -> the default window,
+/// "2" -> "browser-2", "browser-2" -> "browser-2".
+pub fn window_label(raw: Option<&str>) -> String {
+    match raw.map(|w| w.trim()).filter(|w| !w.is_empty()) {
+        // Attached to Chrome: that's where commands go unless told otherwise
+        None if cdp::attached() => cdp::WINDOW.to_string(),
+        None => DEFAULT_WINDOW.to_string(),
+        Some(w) if w == cdp::WINDOW => w.to_string(),
+        Some(w) if w.starts_with("browser-") => w.to_string(),
+        Some(w) => format!("browser-{w}"),
+    }
 }
 
 /// Called from injected JS on external pages via Tauri IPC.
 /// This is the primary capture path — no network involved.
 #[tauri::command]
-fn save_capture_data(app: tauri::AppHandle, data: serde_json::Value) -> Result<(), String> {
-    let state = app.state::<AppState>();
-    let ts = state.session_ts.clone();
-    let _ = app.emit("request-captured", &data);
-    capture::process_single(&app, &data, &ts);
+fn save_capture_data(
+    app: tauri::AppHandle,
+    webview_window: tauri::WebviewWindow,
+    mut data: serde_json::Value,
+) -> Result<(), String> {
+    // Tag with the originating window so captures route to that window's app
+    if let Some(obj) = data.as_object_mut() {
+        obj.insert(
+            "window".to_string(),
+            serde_json::Value::String(webview_window.label().to_string()),
+        );
+    }
+    capture::accept(&app, data);
     Ok(())
 }
 
 #[tauri::command]
 async fn navigate(app: tauri::AppHandle, url: String) -> Result<(), String> {
-    let mut raw = url.clone();
+    open_url(&app, &url, None)
+}
+
+/// Open `url` in the main browser window, asking first which app it belongs to if its
+/// domain is new (suggesting `app_hint`, from a harharhar:// link). Used by the explorer's
+/// address bar, URLs a second launch hands over, and deep links.
+pub fn open_url(app: &tauri::AppHandle, url: &str, app_hint: Option<&str>) -> Result<(), String> {
+    let mut raw = url.to_string();
     if !raw.starts_with("http") {
         raw = format!("https://{raw}");
     }
@@ -53,65 +153,110 @@ async fn navigate(app: tauri::AppHandle, url: String) -> Result<(), String> {
     let app_name = {
         let map = state.domain_map.lock().unwrap();
         map.get(&domain).cloned()
-    };
+    }
+    .or_else(|| domain_groups::resolve(app, &domain));
 
-    // If domain truly unknown, block browser and ask for name first
+    // If domain truly unknown, block browser and ask for name first. Only the head of the
+    // queue is prompted for; the rest are asked about in turn as resume_navigate drains it.
     if app_name.is_none() {
-        {
-            let mut pending = state.pending_url.lock().unwrap();
-            *pending = Some(raw);
+        let queued = {
+            let mut pending = state.pending_navigations.lock().unwrap();
+            pending.push_back((domain.clone(), raw, app_hint.map(String::from)));
+            pending.len()
+        };
+        if queued == 1 {
+            let _ = app.emit(
+                "name-app-before-navigate",
+                serde_json::json!({"domain": domain, "app": app_hint}),
+            );
         }
-        let _ = app.emit("name-app-before-navigate", &domain);
+        events::emit(
+            "unknown-domain",
+            serde_json::json!({"domain": domain, "url": parsed.as_str(), "queued": queued}),
+        );
         return Ok(());
     }
 
     // Track which app the browser is on (for auto-adding new domains)
-    {
-        let mut current = state.current_app.lock().unwrap();
-        *current = app_name;
+    if let Some(name) = app_name {
+        state.set_current_app(DEFAULT_WINDOW, name);
     }
 
-    open_browser(&app, parsed)?;
+    history::note_action(app, DEFAULT_WINDOW, "explorer");
+    open_browser(app, DEFAULT_WINDOW, parsed)?;
     Ok(())
 }
 
+/// Called once a pending domain has been named: open the queued navigations whose
+/// domains now belong to an app, in order, then prompt for the next unnamed one.
 #[tauri::command]
 async fn resume_navigate(app: tauri::AppHandle) -> Result<(), String> {
     let state = app.state::<AppState>();
-    let url = {
-        let mut pending = state.pending_url.lock().unwrap();
-        pending.take()
-    };
+    loop {
+        let (domain, raw, name) = {
+            let map = state.domain_map.lock().unwrap();
+            let mut pending = state.pending_navigations.lock().unwrap();
+            let Some((domain, _, hint)) = pending.front() else {
+                return Ok(());
+            };
+            match map.get(domain).cloned() {
+                Some(name) => {
+                    let (domain, raw, _) = pending.pop_front().unwrap();
+                    (domain, raw, name)
+                }
+                None => {
+                    // Still unnamed — it's the next prompt
+                    let _ = app.emit(
+                        "name-app-before-navigate",
+                        serde_json::json!({"domain": domain, "app": hint}),
+                    );
+                    return Ok(());
+                }
+            }
+        };
 
-    if let Some(raw) = url {
         let parsed: url::Url = raw.parse().map_err(|e: url::ParseError| e.to_string())?;
-        open_browser(&app, parsed)?;
+        state.set_current_app(DEFAULT_WINDOW, name.clone());
+        history::note_action(&app, DEFAULT_WINDOW, "explorer");
+        open_browser(&app, DEFAULT_WINDOW, parsed)?;
+        events::emit(
+            "navigation-resumed",
+            serde_json::json!({"domain": domain, "url": raw, "app": name}),
+        );
     }
-
-    Ok(())
 }
 
-pub fn open_browser(app: &tauri::AppHandle, url: url::Url) -> Result<(), String> {
+/// Navigate a browser window, creating it if it isn't open yet.
+pub fn open_browser(
+    app: &tauri::AppHandle,
+    window: &str,
+    url: url::Url,
+) -> Result<(), CommandError> {
     let state = app.state::<AppState>();
     let ua = state.browser_ua.clone();
 
-    if let Some(wv) = app.get_webview_window("browser") {
+    if window == cdp::WINDOW {
+        cdp::navigate(&url)?;
+    } else if let Some(wv) = app.get_webview_window(window) {
         let js = format!(
             "window.location.href={}",
             serde_json::to_string(url.as_str()).unwrap()
         );
-        wv.eval(&js).map_err(|e| e.to_string())?;
+        wv.eval(&js)
+            .map_err(|e| CommandError::NavigationFailed(e.to_string()))?;
     } else {
-        let inject = include_str!("../../inject/intercept.js");
-        let mut builder = tauri::WebviewWindowBuilder::new(
-            app,
-            "browser",
-            tauri::WebviewUrl::External(url),
-        )
-        .title("harharhar browser")
-        .inner_size(1000.0, 800.0)
-        .user_agent(&ua)
-        .initialization_script(inject);
+        let inject = browser_init_script();
+        let title = if window == DEFAULT_WINDOW {
+            "harharhar browser".to_string()
+        } else {
+            format!("harharhar {window}")
+        };
+        let mut builder =
+            tauri::WebviewWindowBuilder::new(app, window, tauri::WebviewUrl::External(url))
+                .title(title)
+                .inner_size(1000.0, 800.0)
+                .user_agent(&ua)
+                .initialization_script(&inject);
 
         // Position the browser window to the right of the explorer window
         if let Some(explorer) = app.get_webview_window("explorer") {
@@ -121,15 +266,74 @@ pub fn open_browser(app: &tauri::AppHandle, url: url::Url) -> Result<(), String>
                 explorer.scale_factor(),
             ) {
                 let gap = 16.0; // logical pixels
-                let x = (pos.x as f64 / scale) + (size.width as f64 / scale) + gap;
-                let y = pos.y as f64 / scale;
+                                // Cascade additional windows so they don't stack exactly on top of each other
+                let cascade = app
+                    .webview_windows()
+                    .keys()
+                    .filter(|l| l.starts_with("browser-"))
+                    .count() as f64
+                    * 30.0;
+                let x = (pos.x as f64 / scale) + (size.width as f64 / scale) + gap + cascade;
+                let y = pos.y as f64 / scale + cascade;
                 builder = builder.position(x, y);
             }
         }
 
-        builder.build().map_err(|e| e.to_string())?;
+        builder
+            .build()
+            .map_err(|e| CommandError::NavigationFailed(e.to_string()))?;
+    }
+
+    consent::schedule_after_navigation(app, window);
+    challenge::schedule_check(app, window);
+    Ok(())
+}
+
+/// Everything injected into a browser window before page scripts run:
+/// interception rules, capture settings, intercept.js, then each app's userscripts,
+/// guarded so they only run on that app's domains. Read when the window opens.
+fn browser_init_script() -> String {
+    let capture = serde_json::to_string(&config::config().capture).unwrap();
+    let mut script = format!(
+        "window.__harharharRules = {};\nwindow.__harharharCapture = {};\n{}",
+        config::intercept_rules(),
+        capture,
+        include_str!("../../inject/intercept.js")
+    );
+    for app_scripts in config::user_scripts() {
+        let domains = serde_json::to_string(&app_scripts.domains).unwrap();
+        for (name, source) in app_scripts.scripts {
+            let name = serde_json::to_string(&format!("[harharhar] userscript {name}:")).unwrap();
+            script.push_str(&format!(
+                "\nif ({domains}.indexOf(location.hostname) >= 0) {{\n  try {{\n{source}\n  }} catch (e) {{ console.error({name}, e); }}\n}}\n"
+            ));
+        }
+    }
+    script
+}
+
+/// Open (or reuse) an ephemeral "incognito" window: non-persistent data store
+/// and no intercept script, so nothing it does lands in captures or sessions.
+/// Handy for checking what an app looks like logged-out.
+pub fn open_incognito(app: &tauri::AppHandle, url: url::Url) -> Result<(), String> {
+    if let Some(wv) = app.get_webview_window("incognito") {
+        let js = format!(
+            "window.location.href={}",
+            serde_json::to_string(url.as_str()).unwrap()
+        );
+        wv.eval(&js).map_err(|e| e.to_string())?;
+        return Ok(());
     }
 
+    let ua = app.state::<AppState>().browser_ua.clone();
+    tauri::WebviewWindowBuilder::new(app, "incognito", tauri::WebviewUrl::External(url))
+        .title("harharhar incognito (not captured)")
+        .inner_size(1000.0, 800.0)
+        .user_agent(&ua)
+        .incognito(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+
     Ok(())
 }
 
@@ -156,13 +360,19 @@ async fn set_user_agent(app: tauri::AppHandle, ua: String) -> Result<(), String>
 
 #[tauri::command]
 async fn get_config() -> Result<serde_json::Value, String> {
-    let cfg = config::read_config();
-    serde_json::to_value(&cfg).map_err(|e| e.to_string())
+    let cfg = config::config();
+    serde_json::to_value(&*cfg).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn register_app(app: tauri::AppHandle, name: String, domain: String) -> Result<(), String> {
-    config::create_app(&name, &domain);
+    check_domain_conflict(&app, &domain, &name)?;
+    // Naming an existing app (as a harharhar:// link suggests) adds the domain to it
+    if config::list_apps().contains(&name) {
+        config::add_domain_to_app(&name, &domain);
+    } else {
+        config::create_app(&name, &domain);
+    }
 
     let ts = {
         let state = app.state::<AppState>();
@@ -175,6 +385,59 @@ async fn register_app(app: tauri::AppHandle, name: String, domain: String) -> Re
     Ok(())
 }
 
+/// A query reply as a command result: its error's message on failure
+fn reply_result(reply: serde_json::Value) -> Result<serde_json::Value, String> {
+    match reply.pointer("/error/message").and_then(|v| v.as_str()) {
+        Some(message) => Err(message.to_string()),
+        None => Ok(reply),
+    }
+}
+
+/// The explorer's capture feed: same filters and paging as the `recent_captures` action,
+/// scoped to the explorer's app unless `app` is given
+#[tauri::command]
+async fn recent_captures(
+    app: tauri::AppHandle,
+    filter: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let current_app = app.state::<AppState>().current_app(DEFAULT_WINDOW);
+    reply_result(query::recent(current_app, &filter))
+}
+
+/// The explorer's command palette: endpoints ranked for what's typed so far, as the
+/// `suggest` action, for the explorer's app unless `app` is given
+#[tauri::command]
+async fn suggest_endpoints(
+    app: tauri::AppHandle,
+    query: serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let app_name = query
+        .get("app")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .or_else(|| app.state::<AppState>().current_app(DEFAULT_WINDOW));
+    match app_name {
+        Some(name) => reply_result(suggest::suggest(&name, &query)),
+        None => Err("missing app".to_string()),
+    }
+}
+
+#[tauri::command]
+async fn capture_detail(id: String) -> Result<serde_json::Value, String> {
+    reply_result(query::detail(&serde_json::json!({"id": id})))
+}
+
+/// Unread notifications, for the explorer to show when it opens or regains focus
+#[tauri::command]
+async fn list_notifications() -> Result<serde_json::Value, String> {
+    reply_result(notifications::list(&serde_json::json!({"unread": true})))
+}
+
+#[tauri::command]
+async fn ack_notification(id: u64) -> Result<serde_json::Value, String> {
+    reply_result(notifications::ack(&serde_json::json!({"id": id})))
+}
+
 #[tauri::command]
 async fn get_apps() -> Result<Vec<String>, String> {
     Ok(config::list_apps())
@@ -202,27 +465,73 @@ fn eval_callback(app: tauri::AppHandle, id: String, result: String) -> Result<()
         let mut cbs = state.eval_callbacks.lock().unwrap();
         cbs.remove(&id)
     };
-    if let Some(tx) = tx {
+    if let Some((_, tx)) = tx {
         let _ = tx.send(result);
     }
     Ok(())
 }
 
-/// Evaluate JS in the browser webview and return the result via IPC callback.
+/// How long to wait for an eval callback when neither the caller nor
+/// `eval_timeout_ms` in config.json says otherwise.
+pub const DEFAULT_EVAL_TIMEOUT_MS: u64 = 10_000;
+
+/// The eval timeout for commands that don't pass `timeout_ms`
+pub fn default_eval_timeout_ms() -> u64 {
+    config::config()
+        .eval_timeout_ms
+        .unwrap_or(DEFAULT_EVAL_TIMEOUT_MS)
+}
+
+/// Evaluate JS in the default browser webview and return the result via IPC callback.
 /// Works by wrapping the JS in code that calls back via Tauri IPC.
-pub fn eval_js_with_result(app: &tauri::AppHandle, js: &str) -> Result<String, String> {
+pub fn eval_js_with_result(app: &tauri::AppHandle, js: &str) -> Result<String, CommandError> {
+    eval_js_with_timeout(
+        app,
+        DEFAULT_WINDOW,
+        js,
+        std::time::Duration::from_millis(default_eval_timeout_ms()),
+    )
+}
+
+/// Give up on pending evals — in one window, or everywhere with None. Their callers
+/// fail with EVAL_CANCELLED right away; a result arriving later is ignored.
+/// Returns how many were cancelled.
+pub fn cancel_evals(app: &tauri::AppHandle, window: Option<&str>) -> usize {
+    let state = app.state::<AppState>();
+    let mut cbs = state.eval_callbacks.lock().unwrap();
+    let before = cbs.len();
+    // Dropping the sender wakes the waiting recv_timeout
+    cbs.retain(|_, (w, _)| window.is_some_and(|only| only != w));
+    before - cbs.len()
+}
+
+/// Same as `eval_js_with_result`, but in a specific window with a caller-chosen timeout.
+pub fn eval_js_with_timeout(
+    app: &tauri::AppHandle,
+    window: &str,
+    js: &str,
+    timeout: std::time::Duration,
+) -> Result<String, CommandError> {
     use std::sync::atomic::{AtomicU32, Ordering};
     static COUNTER: AtomicU32 = AtomicU32::new(0);
 
+    if window == cdp::WINDOW {
+        return cdp::evaluate(js, timeout);
+    }
+
     let wv = app
-        .get_webview_window("browser")
-        .ok_or("browser window not open")?;
+        .get_webview_window(window)
+        .ok_or_else(|| CommandError::BrowserNotOpen(window.to_string()))?;
 
     let id = format!("e{}", COUNTER.fetch_add(1, Ordering::Relaxed));
     let (tx, rx) = std::sync::mpsc::channel();
     {
         let state = app.state::<AppState>();
-        state.eval_callbacks.lock().unwrap().insert(id.clone(), tx);
+        state
+            .eval_callbacks
+            .lock()
+            .unwrap()
+            .insert(id.clone(), (window.to_string(), tx));
     }
 
     let id_json = serde_json::to_string(&id).unwrap();
@@ -236,42 +545,49 @@ pub fn eval_js_with_result(app: &tauri::AppHandle, js: &str) -> Result<String, S
         // Clean up the callback on eval failure
         let state = app.state::<AppState>();
         state.eval_callbacks.lock().unwrap().remove(&id);
-        e.to_string()
+        CommandError::EvalFailed(e.to_string())
     })?;
 
-    rx.recv_timeout(std::time::Duration::from_secs(10))
-        .map_err(|_| {
-            let state = app.state::<AppState>();
-            state.eval_callbacks.lock().unwrap().remove(&id);
-            "eval timeout".to_string()
-        })
+    rx.recv_timeout(timeout).map_err(|e| {
+        let state = app.state::<AppState>();
+        state.eval_callbacks.lock().unwrap().remove(&id);
+        match e {
+            std::sync::mpsc::RecvTimeoutError::Timeout => CommandError::EvalTimeout,
+            // cancel_evals, or the window closed
+            std::sync::mpsc::RecvTimeoutError::Disconnected => CommandError::EvalCancelled,
+        }
+    })
 }
 
-const EVAL_TEMPLATE: &str = r#"(function(){try{var __r=(JS_PLACEHOLDER);if(__r&&typeof __r.then==='function'){__r.then(function(v){var __s=typeof v==='string'?v:JSON.stringify(v);window.__TAURI_INTERNALS__.invoke('eval_callback',{id:ID_PLACEHOLDER,result:__s||'null'});}).catch(function(e){window.__TAURI_INTERNALS__.invoke('eval_callback',{id:ID_PLACEHOLDER,result:'error: '+e.message});});}else{var __s=typeof __r==='string'?__r:JSON.stringify(__r);window.__TAURI_INTERNALS__.invoke('eval_callback',{id:ID_PLACEHOLDER,result:__s||'null'});}}catch(e){window.__TAURI_INTERNALS__.invoke('eval_callback',{id:ID_PLACEHOLDER,result:'error: '+e.message});}})();"#;
+/// Replies through intercept.js's sealed `window.__hh` bridge, so a page that overrides
+/// JSON.stringify or __TAURI_INTERNALS__ can't swallow results; windows without
+/// intercept.js (incognito) fall back to calling Tauri directly.
+const EVAL_TEMPLATE: &str = r#"(function(){var __hh=window.__hh;function __reply(v){if(__hh){__hh.reply(ID_PLACEHOLDER,v);return;}var __s=typeof v==='string'?v:JSON.stringify(v);window.__TAURI_INTERNALS__.invoke('eval_callback',{id:ID_PLACEHOLDER,result:__s||'null'});}try{var __r=(JS_PLACEHOLDER);if(__r&&typeof __r.then==='function'){__r.then(__reply).catch(function(e){__reply('error: '+e.message);});}else{__reply(__r);}}catch(e){__reply('error: '+e.message);}})();"#;
 
 /// Get cookies — returns document.cookie (non-httpOnly) from browser.
 /// For full cookies including httpOnly, read sessions/latest.json directly.
 #[tauri::command]
 async fn get_cookies(app: tauri::AppHandle, _url: String) -> Result<String, String> {
-    eval_js_with_result(&app, "document.cookie")
+    eval_js_with_result(&app, "document.cookie").map_err(String::from)
 }
 
 /// Evaluate JS in the browser and return the result.
 #[tauri::command]
 async fn eval_js(app: tauri::AppHandle, js: String) -> Result<String, String> {
-    eval_js_with_result(&app, &js)
+    eval_js_with_result(&app, &js).map_err(String::from)
 }
 
 #[tauri::command]
 async fn annotate_action(app: tauri::AppHandle, label: String) -> Result<(), String> {
     let state = app.state::<AppState>();
-    let current_app = state.current_app.lock().unwrap().clone();
+    let current_app = state.current_app(DEFAULT_WINDOW);
     let session_ts = state.session_ts.clone();
 
     let entry = serde_json::json!({
         "type": "annotation",
         "label": label,
         "url": "",
+        "window": DEFAULT_WINDOW,
         "timestamp": chrono::Utc::now().to_rfc3339(),
     });
 
@@ -287,34 +603,18 @@ async fn end_session(app: tauri::AppHandle) -> Result<String, String> {
     let state = app.state::<AppState>();
     let ts = state.session_ts.clone();
 
-    // Close active label
-    let label = state.active_label.lock().unwrap().take();
-    if let Some(label) = label {
-        let current_app = state.current_app.lock().unwrap().clone();
-        if let Some(ref app_name) = current_app {
-            let entry = serde_json::json!({
-                "type": "annotation",
-                "label": format!("[done] {}", label),
-                "url": "",
-                "timestamp": chrono::Utc::now().to_rfc3339(),
-            });
-            capture::append_capture_pub(app_name, &entry, &ts);
-        }
-    }
+    // Close active labels in every window
+    capture::close_all_labels(&app);
 
     // Generate endpoints, digest, cleanup for all apps
-    for app_name in config::list_apps() {
-        endpoints::generate_for_app(&app_name);
-        cleanup::trim_captures_for_app(&app_name, &ts);
-        cleanup::clean_app_domains(&app_name);
-        digest::generate_for_app(&app_name);
-    }
+    generation::generate_all(&ts);
 
     Ok("session finalized".to_string())
 }
 
 #[tauri::command]
 async fn add_domain(app: tauri::AppHandle, name: String, domain: String) -> Result<(), String> {
+    check_domain_conflict(&app, &domain, &name)?;
     config::add_domain_to_app(&name, &domain);
 
     let ts = {
@@ -328,14 +628,315 @@ async fn add_domain(app: tauri::AppHandle, name: String, domain: String) -> Resu
     Ok(())
 }
 
+/// Refuse to map a domain that another app already owns: emit `domain-conflict`
+/// with both candidates and leave the choice to `reassign_domain`.
+pub fn check_domain_conflict(
+    app: &tauri::AppHandle,
+    domain: &str,
+    wanted_by: &str,
+) -> Result<(), String> {
+    match conflicting_owner(app, domain, wanted_by) {
+        Some(owner) => {
+            emit_domain_conflict(app, domain, &owner, wanted_by);
+            Err(format!(
+                "{domain} already belongs to \"{owner}\" — use reassign_domain to move it to \"{wanted_by}\""
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+/// The app other than `wanted_by` that already owns `domain`, if there is one
+pub fn conflicting_owner(app: &tauri::AppHandle, domain: &str, wanted_by: &str) -> Option<String> {
+    app.state::<AppState>()
+        .domain_map
+        .lock()
+        .unwrap()
+        .get(domain)
+        .cloned()
+        .or_else(|| config::find_app_for_domain(domain))
+        .filter(|owner| owner != wanted_by)
+}
+
+pub fn emit_domain_conflict(app: &tauri::AppHandle, domain: &str, owner: &str, wanted_by: &str) {
+    let conflict = serde_json::json!({"domain": domain, "owner": owner, "requested_by": wanted_by});
+    let _ = app.emit("domain-conflict", &conflict);
+    events::emit("domain-conflict", conflict);
+}
+
+/// Archive an app (or unarchive it, releasing any traffic held meanwhile)
+#[tauri::command]
+async fn set_app_archived(
+    app: tauri::AppHandle,
+    name: String,
+    archived: bool,
+) -> Result<(), String> {
+    archive_app(&app, &name, archived)
+}
+
+/// Shared by the `set_app_archived` command and the `archive_app` action
+pub fn archive_app(app: &tauri::AppHandle, name: &str, archived: bool) -> Result<(), String> {
+    cleanup::set_archived(name, archived)?;
+    let state = app.state::<AppState>();
+    if archived {
+        state.archived_apps.lock().unwrap().insert(name.to_string());
+    } else {
+        state.archived_apps.lock().unwrap().remove(name);
+        let domains: Vec<String> = state
+            .domain_map
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, a)| a.as_str() == name)
+            .map(|(d, _)| d.clone())
+            .collect();
+        for domain in domains {
+            capture::flush_unmapped(app, &domain, name, &state.session_ts);
+        }
+    }
+    events::emit(
+        "app-archived",
+        serde_json::json!({"app": name, "archived": archived}),
+    );
+    Ok(())
+}
+
+/// Make every host under a registrable domain part of an app (answer to `domain-group-suggested`)
+#[tauri::command]
+async fn confirm_domain_group(
+    app: tauri::AppHandle,
+    name: String,
+    group: String,
+) -> Result<(), String> {
+    domain_groups::confirm(&app, &name, &group).map(|_| ())
+}
+
+/// The user's answer to a `confirm-action` prompt
+#[tauri::command]
+async fn confirm_action(id: String, allowed: bool) -> Result<(), String> {
+    confirm::answer(&id, allowed)
+}
+
+/// Move a domain to another app, removing it from whichever app(s) had it
+#[tauri::command]
+async fn reassign_domain(
+    app: tauri::AppHandle,
+    domain: String,
+    name: String,
+) -> Result<(), String> {
+    move_domain(&app, &domain, &name).map(|_| ())
+}
+
+/// Shared by the `reassign_domain` command and action. Returns the previous owners.
+pub fn move_domain(app: &tauri::AppHandle, domain: &str, to: &str) -> Result<Vec<String>, String> {
+    if !config::list_apps().iter().any(|a| a == to) {
+        return Err(format!("no app named \"{to}\""));
+    }
+    let previous: Vec<String> = config::list_app_details()
+        .into_iter()
+        .filter(|(name, domains)| name != to && domains.iter().any(|d| d == domain))
+        .map(|(name, _)| name)
+        .collect();
+    for owner in &previous {
+        config::remove_domain_from_app(owner, domain);
+    }
+    config::add_domain_to_app(to, domain);
+
+    let ts = {
+        let state = app.state::<AppState>();
+        state
+            .domain_map
+            .lock()
+            .unwrap()
+            .insert(domain.to_string(), to.to_string());
+        state.session_ts.clone()
+    };
+    capture::flush_unmapped(app, domain, to, &ts);
+    events::emit(
+        "domain-reassigned",
+        serde_json::json!({"domain": domain, "from": previous, "to": to}),
+    );
+    Ok(previous)
+}
+
 pub fn run() {
+    launch(None, None);
+}
+
+/// `harharhar <url>` or `harharhar harharhar://open?url=...&app=...` (how the OS hands
+/// over a clicked link): the usual app, opening that page once it's up
+pub fn run_with_url(url: &str) {
+    let link = if url.starts_with(&format!("{}:", deep_link::SCHEME)) {
+        match deep_link::parse(url) {
+            Ok(link) => link,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(3);
+            }
+        }
+    } else {
+        deep_link::Link {
+            url: url.to_string(),
+            app: None,
+        }
+    };
+    launch(None, Some(link));
+}
+
+/// `harharhar open <app>`: the usual app, back on the page `app` was left on (or its
+/// primary domain)
+pub fn run_app(app_name: &str) {
+    match history::start_url(app_name) {
+        Ok(url) => launch(
+            None,
+            Some(deep_link::Link {
+                url,
+                app: Some(app_name.to_string()),
+            }),
+        ),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `harharhar attach --cdp host:port`: the usual app, plus capture from (and commands
+/// to) a tab of the user's own Chrome over the DevTools protocol
+pub fn run_attached(cdp_addr: &str) {
+    launch(Some(cdp_addr.to_string()), None);
+}
+
+/// `~/.harharhar/harharhar.pid`: the running browser's process id, written at launch and
+/// removed on exit, so the CLI can tell whether anything will take its commands
+pub const PID_FILE: &str = "harharhar.pid";
+
+/// `~/.harharhar/harharhar.lock`: locked by the running browser for as long as it runs.
+/// The OS lets go of it when the process ends, however it ends, so a pid file left by a
+/// crash is never taken for a live browser whose pid another process has since got.
+const LOCK_FILE: &str = "harharhar.lock";
+
+/// This process's hold on LOCK_FILE, once it's the running browser
+static INSTANCE_LOCK: std::sync::OnceLock<std::fs::File> = std::sync::OnceLock::new();
+
+fn open_lock_file() -> Option<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(config::data_dir().join(LOCK_FILE))
+        .ok()
+}
+
+/// The running browser's pid, from its pid file, if a browser holds the lock file
+pub fn running_pid() -> Option<u32> {
+    let pid = std::fs::read_to_string(config::data_dir().join(PID_FILE))
+        .ok()
+        .and_then(|p| p.trim().parse::<u32>().ok());
+    match open_lock_file()?.try_lock() {
+        // Nobody holds it (ours goes when the file closes): nothing is running
+        Ok(()) => None,
+        Err(std::fs::TryLockError::WouldBlock) => pid,
+        // No locking on this filesystem: all there is to go on is the pid
+        Err(std::fs::TryLockError::Error(_)) => pid.filter(|&pid| process_alive(pid)),
+    }
+}
+
+fn process_alive(pid: u32) -> bool {
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+/// Another harharhar is already running: give it `url` (or just bring its window
+/// forward) over the command channel instead of starting a second browser that would
+/// fight it for commands and capture files.
+fn hand_off(pid: u32, open: Option<&deep_link::Link>) {
+    let commands_dir = config::data_dir().join("commands");
+    let token = std::fs::read_to_string(config::data_dir().join("cmd-token")).unwrap_or_default();
+    let mut cmd = serde_json::json!({"action": "open", "token": token.trim()});
+    if let Some(link) = open {
+        cmd["url"] = serde_json::Value::from(link.url.as_str());
+        if let Some(app_name) = &link.app {
+            cmd["app"] = serde_json::Value::from(app_name.as_str());
+        }
+    }
+    let id = format!("open-{}", std::process::id());
+    let tmp_path = commands_dir.join(format!("{id}.json.tmp"));
+    let result_path = commands_dir.join(format!("{id}.result.json"));
+    if std::fs::write(&tmp_path, cmd.to_string())
+        .and_then(|_| std::fs::rename(&tmp_path, commands_dir.join(format!("{id}.json"))))
+        .is_err()
+    {
+        eprintln!("harharhar is already running (pid {pid}), but can't be reached");
+        std::process::exit(1);
+    }
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    let mut answered = false;
+    while std::time::Instant::now() < deadline {
+        if let Ok(result) = std::fs::read_to_string(&result_path) {
+            let _ = std::fs::remove_file(&result_path);
+            let result: serde_json::Value = serde_json::from_str(&result).unwrap_or_default();
+            if let Some(message) = result.pointer("/error/message").and_then(|m| m.as_str()) {
+                eprintln!("{message}");
+                std::process::exit(1);
+            }
+            answered = true;
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    if !answered {
+        // Not left for it to find later: the user will have moved on
+        let _ = std::fs::remove_file(commands_dir.join(format!("{id}.json")));
+        eprintln!("harharhar is already running (pid {pid}), but didn't answer within 5s");
+        std::process::exit(1);
+    }
+    match open.map(|link| &link.url) {
+        Some(url) => println!("harharhar is already running (pid {pid}) — opened {url} there"),
+        None => println!("harharhar is already running (pid {pid})"),
+    }
+}
+
+fn launch(cdp_addr: Option<String>, open: Option<deep_link::Link>) {
     config::ensure_dirs();
+    let lock = open_lock_file();
+    let held_elsewhere = lock
+        .as_ref()
+        .is_some_and(|f| matches!(f.try_lock(), Err(std::fs::TryLockError::WouldBlock)));
+    // Without a lock file to hold, as before: a live process at the pid file's pid
+    let other = match &lock {
+        Some(_) => held_elsewhere.then(|| running_pid().unwrap_or(0)),
+        None => running_pid(),
+    };
+    if let Some(pid) = other.filter(|&pid| pid != std::process::id()) {
+        if cdp_addr.is_some() {
+            eprintln!(
+                "harharhar is already running (pid {pid}) — `harharhar stop` it before attaching"
+            );
+            std::process::exit(1);
+        }
+        hand_off(pid, open.as_ref());
+        return;
+    }
+    if let Some(lock) = lock {
+        let _ = INSTANCE_LOCK.set(lock);
+    }
+    let pid_path = config::data_dir().join(PID_FILE);
+    let pid = std::process::id().to_string();
+    let _ = std::fs::write(&pid_path, &pid);
 
     let browser_ua = config::get_browser_ua();
     let curl_ua = config::get_curl_ua();
     let session_ts = chrono::Utc::now().format("%Y-%m-%dT%H-%M").to_string();
+    shutdown::start(&session_ts);
 
     let mut domain_map = std::collections::HashMap::new();
+    let mut domain_groups = std::collections::HashMap::new();
+    let mut archived_apps = std::collections::HashSet::new();
     for app_name in config::list_apps() {
         let config_path = config::data_dir()
             .join("apps")
@@ -346,25 +947,35 @@ pub fn run() {
                 for d in app_cfg.domains {
                     domain_map.insert(d, app_name.clone());
                 }
+                for g in app_cfg.domain_groups {
+                    domain_groups.insert(g, app_name.clone());
+                }
+                if app_cfg.archived {
+                    archived_apps.insert(app_name.clone());
+                }
             }
         }
     }
 
     let state = AppState {
         domain_map: Mutex::new(domain_map),
+        domain_groups: Mutex::new(domain_groups),
+        archived_apps: Mutex::new(archived_apps),
         browser_ua,
         curl_ua,
-        current_app: Mutex::new(None),
-        session_file_lock: Mutex::new(()),
+        current_apps: Mutex::new(std::collections::HashMap::new()),
         session_ts,
-        pending_url: Mutex::new(None),
-        unmapped_captures: Mutex::new(std::collections::HashMap::new()),
+        pending_navigations: Mutex::new(std::collections::VecDeque::new()),
+        unmapped_captures: Mutex::new(shutdown::take_unmapped()),
         eval_callbacks: Mutex::new(std::collections::HashMap::new()),
         session_cookie_names: Mutex::new(std::collections::HashSet::new()),
-        active_label: Mutex::new(None),
+        active_labels: Mutex::new(std::collections::HashMap::new()),
+        human_needed: Mutex::new(std::collections::HashMap::new()),
+        last_actions: Mutex::new(std::collections::HashMap::new()),
     };
 
     tauri::Builder::default()
+        .plugin(tauri_plugin_deep_link::init())
         .manage(state)
         .invoke_handler(tauri::generate_handler![
             navigate,
@@ -373,8 +984,17 @@ pub fn run() {
             get_config,
             register_app,
             add_domain,
+            reassign_domain,
+            confirm_domain_group,
+            confirm_action,
+            set_app_archived,
             get_apps,
             get_app_details,
+            recent_captures,
+            suggest_endpoints,
+            capture_detail,
+            list_notifications,
+            ack_notification,
             get_cookies,
             eval_js,
             eval_callback,
@@ -382,11 +1002,57 @@ pub fn run() {
             annotate_action,
             end_session,
         ])
-        .setup(|app| {
+        .on_window_event(|window, event| {
+            // A closed window will never answer its pending evals
+            if let tauri::WindowEvent::Destroyed = event {
+                cancel_evals(window.app_handle(), Some(window.label()));
+            }
+        })
+        .setup(move |app| {
             let handle = app.handle().clone();
-            tauri::async_runtime::spawn(capture::start_command_watcher(handle));
+            if let Some(addr) = &cdp_addr {
+                if let Err(e) = cdp::start(handle.clone(), addr) {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+                println!("Attached to Chrome at {addr}");
+            }
+            tauri::async_runtime::spawn(capture::start_command_watcher(handle.clone()));
+            if let Some(link) = &open {
+                if let Err(e) = open_url(&handle, &link.url, link.app.as_deref()) {
+                    eprintln!("{e}");
+                }
+            }
+            // Linux and Windows start a new process for a clicked harharhar:// link, which
+            // hands it over like any `harharhar <url>`; macOS delivers it here instead
+            {
+                use tauri_plugin_deep_link::DeepLinkExt;
+                #[cfg(any(target_os = "linux", windows))]
+                if let Err(e) = app.deep_link().register_all() {
+                    eprintln!(
+                        "[harharhar] can't register {}:// links: {e}",
+                        deep_link::SCHEME
+                    );
+                }
+                let links = handle.clone();
+                app.deep_link().on_open_url(move |event| {
+                    for url in event.urls() {
+                        deep_link::open(&links, url.as_str());
+                    }
+                });
+            }
+            generation::start();
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error running tauri application")
+        .run(move |app, event| {
+            if let tauri::RunEvent::Exit = event {
+                shutdown::finish(app);
+                // Only our own, in case it was replaced after a crash left it stale
+                if std::fs::read_to_string(&pid_path).is_ok_and(|p| p == pid) {
+                    let _ = std::fs::remove_file(&pid_path);
+                }
+            }
+        });
 }
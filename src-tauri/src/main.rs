@@ -2,6 +2,42 @@
 
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Process exit codes besides 0, so scripts and CI can tell failures apart
+const EXIT_FAILED: i32 = 1;
+const EXIT_TIMEOUT: i32 = 2;
+/// Bad arguments or an invalid command
+const EXIT_INVALID: i32 = 3;
+/// No harharhar browser is running to take the command
+const EXIT_NOT_RUNNING: i32 = 4;
+
+/// `--json` anywhere on the command line: results and errors are printed as JSON
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Report an error (on stderr, or as `{"error": {"code", "message"}}` on stdout with
+/// --json) and exit with `exit_code`
+fn fail(exit_code: i32, message: &str) -> ! {
+    if json_output() {
+        let code = match exit_code {
+            EXIT_TIMEOUT => "TIMEOUT",
+            EXIT_INVALID => "INVALID",
+            EXIT_NOT_RUNNING => "NOT_RUNNING",
+            _ => "FAILED",
+        };
+        println!(
+            "{}",
+            serde_json::json!({"error": {"code": code, "message": message}})
+        );
+    } else {
+        eprintln!("{message}");
+    }
+    std::process::exit(exit_code);
+}
 
 const AGENT_MD: &str = r##"# harharhar — Instructions for AI Agents
 
@@ -101,6 +137,15 @@ harharhar cmd '{"action":"eval","js":"document.title"}'
 # Get page HTML
 harharhar cmd '{"action":"read_page"}'
 
+# Big page? Stream it in chunks and allow more time
+harharhar cmd '{"action":"read_page","stream":true,"timeout_ms":60000}'
+
+# Second window, captured separately (any command takes "window")
+harharhar cmd '{"action":"navigate","url":"https://...","window":"2"}'
+
+# Bootstrap coverage: visit same-app links and open tabs/sections, 2 levels deep, 20 pages max
+harharhar crawl 2 20
+
 # Trigger endpoint/auth analysis
 harharhar cmd '{"action":"generate_endpoints"}'
 ```
@@ -121,24 +166,40 @@ fn run_init() {
     let _ = fs::create_dir_all(root.join("incoming"));
 
     // Write AGENT.md
+    let mut created = Vec::new();
     let agent_path = root.join("AGENT.md");
-    fs::write(&agent_path, AGENT_MD).expect("failed to write AGENT.md");
-    println!("Created {}", agent_path.display());
+    if let Err(e) = fs::write(&agent_path, AGENT_MD) {
+        fail(EXIT_FAILED, &format!("failed to write AGENT.md: {e}"));
+    }
+    created.push(agent_path);
 
     // Write config.json if it doesn't exist
     let config_path = root.join("config.json");
     if !config_path.exists() {
-        fs::write(
+        let written = fs::write(
             &config_path,
             r#"{
   "user_agent": null,
   "capture_port": null
 }"#,
-        )
-        .expect("failed to write config.json");
-        println!("Created {}", config_path.display());
+        );
+        if let Err(e) = written {
+            fail(EXIT_FAILED, &format!("failed to write config.json: {e}"));
+        }
+        created.push(config_path);
     }
 
+    if json_output() {
+        let created: Vec<String> = created.iter().map(|p| p.display().to_string()).collect();
+        println!(
+            "{}",
+            serde_json::json!({"ok": true, "data_dir": root.display().to_string(), "created": created})
+        );
+        return;
+    }
+    for path in &created {
+        println!("Created {}", path.display());
+    }
     println!("\nharharhar initialized at {}", root.display());
     println!("\nNext steps:");
     println!("  1. Add this to your project's CLAUDE.md:");
@@ -148,37 +209,395 @@ fn run_init() {
     println!("  4. Install curl-impersonate: brew tap shakacode/brew && brew install curl-impersonate");
 }
 
-fn run_cmd(body: &str) {
-    let root = data_dir();
-    let cmd_path = root.join("cmd.json");
-    let result_path = root.join("cmd-result.json");
+/// harharhar generate [app] [--no-trim] [--no-clean-domains] [--force] [--dry-run]
+fn run_generate(args: &[String]) {
+    let app = args.iter().find(|a| !a.starts_with("--"));
+    let flag = |name: &str| args.iter().any(|a| a == name);
+    let steps = harharhar_lib::engine::GenerateSteps {
+        trim: !flag("--no-trim"),
+        clean_domains: !flag("--no-clean-domains"),
+    };
+    let force = flag("--force");
+    let dry_run = flag("--dry-run");
+
+    let root = data_dir().join("apps");
+    let mut apps: Vec<String> = match app {
+        Some(name) if root.join(name).is_dir() => vec![name.clone()],
+        Some(name) => fail(EXIT_INVALID, &format!("No app named \"{name}\"")),
+        None => fs::read_dir(&root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+            .collect(),
+    };
+    apps.sort();
+
+    let mut results = Vec::new();
+    for name in apps {
+        let result = if harharhar_lib::engine::is_archived(&name) && !force {
+            serde_json::json!({"app": name, "skipped": "archived"})
+        } else if dry_run {
+            dry_run_report(&name, steps)
+        } else {
+            generate_app(&root, &name, steps)
+        };
+        if !json_output() {
+            print_generate_result(&result);
+        }
+        results.push(result);
+    }
+    if json_output() {
+        println!("{}", serde_json::json!({"apps": results}));
+    } else {
+        println!("Done.");
+    }
+}
+
+/// Run one app's generation pass: how long it took, its endpoint count and the
+/// top-level files it wrote
+fn generate_app(
+    root: &std::path::Path,
+    name: &str,
+    steps: harharhar_lib::engine::GenerateSteps,
+) -> serde_json::Value {
+    let started = std::time::Instant::now();
+    let since = std::time::SystemTime::now();
+    // No active session, so bodies in every capture file may be trimmed
+    harharhar_lib::engine::generate_steps(name, "", steps);
+
+    // What this pass wrote: the app's top-level files touched since it started
+    let mut produced: Vec<String> = fs::read_dir(root.join(name))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.path().is_file())
+        .filter(|e| {
+            e.metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| t >= since)
+        })
+        .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+        .collect();
+    produced.sort();
+    serde_json::json!({
+        "app": name,
+        "seconds": started.elapsed().as_secs_f64(),
+        "endpoints": harharhar_lib::engine::endpoints(name).map(|c| c.endpoints.len()),
+        "written": produced,
+    })
+}
+
+/// What `generate` would trim and which domains it would drop, without touching disk.
+/// Trimming is judged against the current endpoints.json, not a fresh one.
+fn dry_run_report(name: &str, steps: harharhar_lib::engine::GenerateSteps) -> serde_json::Value {
+    let mut report = serde_json::json!({"app": name, "dry_run": true});
+    if steps.trim {
+        let files: Vec<serde_json::Value> = harharhar_lib::cleanup::trim_dry_run(name, "")
+            .iter()
+            .map(|f| serde_json::json!({"file": f.file, "captures": f.captures, "bytes": f.bytes}))
+            .collect();
+        report["trim"] = serde_json::json!(files);
+    }
+    if steps.clean_domains {
+        report["remove_domains"] =
+            serde_json::json!(harharhar_lib::cleanup::clean_domains_dry_run(name));
+    }
+    report
+}
+
+/// One app's line(s) of `generate` output, from `generate_app` or `dry_run_report`
+fn print_generate_result(result: &serde_json::Value) {
+    let name = result.get("app").and_then(|v| v.as_str()).unwrap_or("");
+    let num = |v: &serde_json::Value, k: &str| v.get(k).and_then(|n| n.as_u64()).unwrap_or(0);
+    if result.get("skipped").is_some() {
+        println!("{name}: skipped (archived; --force to generate anyway)");
+        return;
+    }
+    if result.get("dry_run").is_none() {
+        let endpoints = result
+            .get("endpoints")
+            .and_then(|n| n.as_u64())
+            .map(|n| format!(", {n} endpoints"))
+            .unwrap_or_default();
+        let written: Vec<&str> = result
+            .get("written")
+            .and_then(|w| w.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|f| f.as_str())
+            .collect();
+        println!(
+            "{name}: {:.1}s{endpoints} — {}",
+            result
+                .get("seconds")
+                .and_then(|n| n.as_f64())
+                .unwrap_or(0.0),
+            if written.is_empty() {
+                "nothing written".to_string()
+            } else {
+                written.join(", ")
+            }
+        );
+        return;
+    }
+
+    println!("{name}: dry run, nothing written");
+    if let Some(files) = result.get("trim").and_then(|t| t.as_array()) {
+        let captures: u64 = files.iter().map(|f| num(f, "captures")).sum();
+        let bytes: u64 = files.iter().map(|f| num(f, "bytes")).sum();
+        println!(
+            "  would trim {captures} captures ({bytes} bytes) in {} files",
+            files.len()
+        );
+        for f in files {
+            let file = f.get("file").and_then(|v| v.as_str()).unwrap_or("");
+            println!(
+                "    captures/{file}: {} captures, {} bytes",
+                num(f, "captures"),
+                num(f, "bytes")
+            );
+        }
+    }
+    if let Some(domains) = result.get("remove_domains").and_then(|d| d.as_array()) {
+        let domains: Vec<&str> = domains.iter().filter_map(|d| d.as_str()).collect();
+        if domains.is_empty() {
+            println!("  would remove no domains");
+        } else {
+            println!(
+                "  would remove {} domains from config.json: {}",
+                domains.len(),
+                domains.join(", ")
+            );
+        }
+    }
+}
 
-    // Clean up stale result
-    let _ = fs::remove_file(&result_path);
+/// harharhar start [--background] [--cdp host:port]
+fn run_start(args: &[String]) {
+    let cdp = args
+        .iter()
+        .position(|a| a == "--cdp")
+        .and_then(|i| args.get(i + 1));
+    if let Some(pid) = harharhar_lib::running_pid() {
+        if json_output() {
+            println!(
+                "{}",
+                serde_json::json!({"ok": true, "pid": pid, "already_running": true})
+            );
+        } else {
+            println!("harharhar is already running (pid {pid})");
+        }
+        return;
+    }
+    if !args.iter().any(|a| a == "--background") {
+        match cdp {
+            Some(addr) => harharhar_lib::run_attached(addr),
+            None => harharhar_lib::run(),
+        }
+        return;
+    }
 
-    // Write command
-    fs::write(&cmd_path, body).expect("failed to write command");
+    let exe = std::env::current_exe().unwrap_or_else(|e| fail(EXIT_FAILED, &e.to_string()));
+    let mut command = std::process::Command::new(exe);
+    if let Some(addr) = cdp {
+        command.args(["attach", "--cdp", addr]);
+    }
+    command
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    // Its own process group, so Ctrl-C in this terminal doesn't take it down
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    // Not waited on: it outlives this process, and init reaps it
+    let pid = command
+        .spawn()
+        .map(|child| child.id())
+        .unwrap_or_else(|e| fail(EXIT_FAILED, &format!("can't start harharhar: {e}")));
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(15);
+    while std::time::Instant::now() < deadline {
+        if harharhar_lib::running_pid() == Some(pid) {
+            if json_output() {
+                println!("{}", serde_json::json!({"ok": true, "pid": pid}));
+            } else {
+                println!("harharhar started (pid {pid})");
+            }
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    fail(EXIT_TIMEOUT, "harharhar didn't come up within 15s");
+}
+
+/// harharhar stop: end the running browser, waiting until it's gone
+fn run_stop() -> bool {
+    let Some(pid) = harharhar_lib::running_pid() else {
+        return false;
+    };
+    // Asked to quit, it flushes queued captures and marks the run clean on the way out;
+    // a plain kill is the fallback if it doesn't answer. Gone means it let go of its
+    // lock file — not that no process has its pid, which another may have got since.
+    let still_running = || harharhar_lib::running_pid() == Some(pid);
+    let wait_for_exit = |secs: u64| {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(secs);
+        while still_running() {
+            if std::time::Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(200));
+        }
+        true
+    };
+    let quit = send_quit() && wait_for_exit(10);
+    // Its answer, if it got to write one: nobody reads it
+    let _ = fs::remove_file(
+        data_dir()
+            .join("commands")
+            .join(format!("quit-{}.result.json", std::process::id())),
+    );
+    // Only signalled while it still holds the lock under that pid: it's harharhar
+    if !quit && still_running() {
+        let _ = std::process::Command::new("kill")
+            .arg(pid.to_string())
+            .status();
+        if !wait_for_exit(10) {
+            fail(
+                EXIT_TIMEOUT,
+                &format!("harharhar (pid {pid}) is still running — `kill -9 {pid}` to force it"),
+            );
+        }
+    }
+    let _ = fs::remove_file(data_dir().join(harharhar_lib::PID_FILE));
+    if json_output() {
+        println!("{}", serde_json::json!({"ok": true, "stopped": pid}));
+    } else {
+        println!("Stopped harharhar (pid {pid})");
+    }
+    true
+}
+
+/// Drop a `quit` command for the running browser; false if it can't be written
+fn send_quit() -> bool {
+    let commands_dir = data_dir().join("commands");
+    let token = fs::read_to_string(data_dir().join("cmd-token")).unwrap_or_default();
+    let cmd = serde_json::json!({"action": "quit", "token": token.trim()});
+    let id = format!("quit-{}", std::process::id());
+    let tmp_path = commands_dir.join(format!("{id}.json.tmp"));
+    fs::write(&tmp_path, cmd.to_string())
+        .and_then(|_| fs::rename(&tmp_path, commands_dir.join(format!("{id}.json"))))
+        .is_ok()
+}
 
-    // Wait for result (up to 10 seconds)
-    for _ in 0..100 {
+fn run_cmd(body: &str) {
+    if harharhar_lib::running_pid().is_none() {
+        fail(
+            EXIT_NOT_RUNNING,
+            "harharhar isn't running — start it with `harharhar start --background`",
+        );
+    }
+    let commands_dir = data_dir().join("commands");
+    let _ = fs::create_dir_all(&commands_dir);
+
+    // Unique per invocation so concurrent `harharhar cmd` calls don't collide
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let id = format!("{}-{}", std::process::id(), nanos);
+
+    let cmd_path = commands_dir.join(format!("{id}.json"));
+    let tmp_path = commands_dir.join(format!("{id}.json.tmp"));
+    let result_path = commands_dir.join(format!("{id}.result.json"));
+    let progress_path = commands_dir.join(format!("{id}.progress.jsonl"));
+
+    let mut cmd = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(cmd) if cmd.is_object() => cmd,
+        Ok(_) => fail(EXIT_INVALID, "command must be a JSON object"),
+        Err(e) => fail(EXIT_INVALID, &format!("invalid command JSON: {e}")),
+    };
+
+    // Wait a little longer than the browser-side eval timeout so we get its error, not ours
+    let timeout_ms = cmd
+        .get("timeout_ms")
+        .and_then(|t| t.as_u64())
+        .unwrap_or_else(harharhar_lib::default_eval_timeout_ms);
+    let wait = std::time::Duration::from_millis(timeout_ms + 2000);
+
+    // The running browser only accepts commands carrying its per-run secret
+    if let Ok(token) = fs::read_to_string(data_dir().join("cmd-token")) {
+        cmd["token"] = serde_json::Value::from(token.trim());
+    }
+    let body = cmd.to_string();
+
+    // Write command atomically so the watcher never sees a partial file
+    fs::write(&tmp_path, body)
+        .unwrap_or_else(|e| fail(EXIT_FAILED, &format!("can't write command: {e}")));
+    fs::rename(&tmp_path, &cmd_path)
+        .unwrap_or_else(|e| fail(EXIT_FAILED, &format!("can't write command: {e}")));
+
+    // Wait for result. Streamed progress events are printed as they arrive,
+    // and each one pushes the deadline out — long-running commands stay alive
+    // as long as they keep reporting.
+    let mut deadline = std::time::Instant::now() + wait;
+    let mut progress_offset = 0;
+    while std::time::Instant::now() < deadline {
         std::thread::sleep(std::time::Duration::from_millis(100));
-        if result_path.exists() {
-            match fs::read_to_string(&result_path) {
-                Ok(result) => {
-                    println!("{result}");
-                    let _ = fs::remove_file(&result_path);
-                    return;
+
+        if let Ok(progress) = fs::read_to_string(&progress_path) {
+            // Only print complete lines; a partial trailing line is picked up next round
+            if let Some(end) = progress.rfind('\n').map(|i| i + 1) {
+                if end > progress_offset {
+                    print!("{}", &progress[progress_offset..end]);
+                    progress_offset = end;
+                    deadline = std::time::Instant::now() + wait;
+                }
+            }
+        }
+
+        if let Ok(result) = fs::read_to_string(&result_path) {
+            if let Ok(progress) = fs::read_to_string(&progress_path) {
+                if progress.len() > progress_offset {
+                    print!("{}", &progress[progress_offset..]);
+                }
+            }
+            println!("{result}");
+            let _ = fs::remove_file(&result_path);
+            let _ = fs::remove_file(&progress_path);
+            let code = serde_json::from_str::<serde_json::Value>(&result)
+                .ok()
+                .and_then(|r| {
+                    r.pointer("/error/code")
+                        .and_then(|c| c.as_str())
+                        .map(String::from)
+                });
+            match code.as_deref() {
+                None => return,
+                Some("EVAL_TIMEOUT") => std::process::exit(EXIT_TIMEOUT),
+                Some("INVALID_JSON" | "INVALID_PARAMS" | "UNKNOWN_ACTION") => {
+                    std::process::exit(EXIT_INVALID)
                 }
-                Err(_) => continue,
+                Some(_) => std::process::exit(EXIT_FAILED),
             }
         }
     }
-    eprintln!("Timeout waiting for response. Is harharhar running?");
-    std::process::exit(1);
+
+    // Don't leave the command behind to run after we've given up. If it's still
+    // there, nothing ever picked it up.
+    let picked_up = fs::remove_file(&cmd_path).is_err();
+    let _ = fs::remove_file(&progress_path);
+    if picked_up {
+        fail(EXIT_TIMEOUT, "Timeout waiting for the result");
+    }
+    fail(EXIT_NOT_RUNNING, "No response. Is harharhar running?");
 }
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+    JSON_OUTPUT.store(args.iter().any(|a| a == "--json"), Ordering::Relaxed);
+    args.retain(|a| a != "--json");
 
     if args.len() > 1 {
         match args[1].as_str() {
@@ -192,41 +611,298 @@ fn main() {
                 return;
             }
             "generate" => {
+                run_generate(&args[2..]);
+                return;
+            }
+            "open" => {
+                // harharhar open <app> — in the running browser if there is one
+                let Some(app) = args.get(2) else {
+                    fail(EXIT_INVALID, "Usage: harharhar open <app>");
+                };
+                harharhar_lib::run_app(app);
+                return;
+            }
+            "start" => {
+                run_start(&args[2..]);
+                return;
+            }
+            "stop" => {
+                if !run_stop() {
+                    fail(EXIT_NOT_RUNNING, "harharhar isn't running");
+                }
+                return;
+            }
+            "restart" => {
+                // harharhar restart [--cdp host:port] — always comes back in the background
+                run_stop();
+                let mut start_args = args[2..].to_vec();
+                start_args.push("--background".to_string());
+                run_start(&start_args);
+                return;
+            }
+            "crawl" => {
+                // harharhar crawl [depth] [max_pages] — explore from the browser's current page
+                let num = |i: usize, default: u64| {
+                    args.get(i)
+                        .and_then(|s| s.parse::<u64>().ok())
+                        .unwrap_or(default)
+                };
+                let body = serde_json::json!({
+                    "action": "crawl",
+                    "depth": num(2, 2),
+                    "max_pages": num(3, 20),
+                    "stream": true,
+                    "timeout_ms": 30000,
+                });
+                run_cmd(&body.to_string());
+                return;
+            }
+            "stats" => {
                 let root = data_dir().join("apps");
-                if let Ok(entries) = fs::read_dir(&root) {
-                    for entry in entries.flatten() {
-                        if entry.path().is_dir() {
-                            if let Some(name) = entry.file_name().to_str() {
-                                println!("Generating endpoints for {}...", name);
-                                harharhar_lib::endpoints::generate_for_app(name);
-                                // Trim bodies in old captures (no active session, so trim all)
-                                harharhar_lib::cleanup::trim_captures_for_app(name, "");
-                                harharhar_lib::digest::generate_for_app(name);
-                            }
-                        }
+                let mut names: Vec<String> = fs::read_dir(&root)
+                    .map(|entries| {
+                        entries
+                            .flatten()
+                            .filter(|e| e.path().is_dir())
+                            .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                names.sort();
+                // Optional app name narrows the report to one app
+                if let Some(only) = args.get(2) {
+                    names.retain(|n| n == only);
+                }
+                if json_output() {
+                    let apps: Vec<serde_json::Value> = names
+                        .iter()
+                        .map(|n| harharhar_lib::stats::summary(n))
+                        .collect();
+                    println!("{}", serde_json::json!({"apps": apps}));
+                    return;
+                }
+                if names.is_empty() {
+                    println!("No apps found.");
+                }
+                for name in names {
+                    println!("{}", harharhar_lib::stats::report(&name));
+                }
+                return;
+            }
+            "attach" => {
+                // harharhar attach [--cdp host:port]
+                let addr = args
+                    .iter()
+                    .position(|a| a == "--cdp")
+                    .and_then(|i| args.get(i + 1))
+                    .map(|s| s.as_str())
+                    .unwrap_or("localhost:9222");
+                harharhar_lib::run_attached(addr);
+                return;
+            }
+            "import" => {
+                // harharhar import <file.har|trace.zip> [--app <name>]
+                let file = args.get(2).filter(|a| !a.starts_with('-'));
+                let app = args
+                    .iter()
+                    .position(|a| a == "--app")
+                    .and_then(|i| args.get(i + 1))
+                    .map(|s| s.as_str());
+                let Some(file) = file else {
+                    fail(
+                        EXIT_INVALID,
+                        "Usage: harharhar import <file.har|trace.zip> [--app <name>]",
+                    );
+                };
+                let summary = match harharhar_lib::import::run(std::path::Path::new(file), app) {
+                    Ok(summary) => summary,
+                    Err(e) => fail(EXIT_FAILED, &e),
+                };
+                for name in summary.imported.keys() {
+                    harharhar_lib::engine::generate(name, "");
+                }
+                if json_output() {
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "imported": summary.imported,
+                            "skipped": summary.skipped,
+                            "unknown_domains": summary.unknown_domains,
+                        })
+                    );
+                    return;
+                }
+                for (name, count) in &summary.imported {
+                    println!("Imported {count} captures into {name}");
+                }
+                println!(
+                    "Skipped {} entries (noise, no auth, or unknown domain)",
+                    summary.skipped
+                );
+                if !summary.unknown_domains.is_empty() {
+                    println!(
+                        "No app owns: {} — pass --app <name> to import them",
+                        summary.unknown_domains.join(", ")
+                    );
+                }
+                return;
+            }
+            "context" => {
+                // harharhar context <app> [--budget 8000]
+                let app = args.get(2).filter(|a| !a.starts_with('-'));
+                let budget = args
+                    .iter()
+                    .position(|a| a == "--budget")
+                    .and_then(|i| args.get(i + 1))
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(8000);
+                let Some(app) = app else {
+                    fail(
+                        EXIT_INVALID,
+                        "Usage: harharhar context <app> [--budget <tokens>]",
+                    );
+                };
+                match harharhar_lib::context::bundle(app, budget) {
+                    Ok(text) if json_output() => {
+                        println!(
+                            "{}",
+                            serde_json::json!({"app": app, "budget": budget, "text": text})
+                        )
                     }
+                    Ok(text) => print!("{text}"),
+                    Err(e) => fail(EXIT_FAILED, &e),
+                }
+                return;
+            }
+            "jq" => {
+                // harharhar jq <app> '<expr>' [--slurp]
+                let positional: Vec<&String> = args
+                    .iter()
+                    .skip(2)
+                    .filter(|a| !a.starts_with("--"))
+                    .collect();
+                let slurp = args.iter().any(|a| a == "--slurp" || a == "-s");
+                let (Some(app), Some(expr)) = (positional.first(), positional.get(1)) else {
+                    fail(EXIT_INVALID, "Usage: harharhar jq <app> '<expr>' [--slurp]");
+                };
+                // Results are JSON already, one per line, with or without --json
+                let mut emit = |line: String| println!("{line}");
+                if let Err(e) = harharhar_lib::jq::run(app, expr, slurp, &mut emit) {
+                    fail(EXIT_FAILED, &e);
+                }
+                return;
+            }
+            "export-captures" => {
+                // harharhar export-captures <app> --pattern '<pattern>' -o <file> [--untrimmed]
+                let app = args.get(2).filter(|a| !a.starts_with('-'));
+                let flag = |names: &[&str]| {
+                    args.iter()
+                        .position(|a| names.contains(&a.as_str()))
+                        .and_then(|i| args.get(i + 1))
+                };
+                let untrimmed = args.iter().any(|a| a == "--untrimmed");
+                let (Some(app), Some(pattern), Some(out)) =
+                    (app, flag(&["--pattern", "-p"]), flag(&["-o", "--out"]))
+                else {
+                    fail(
+                        EXIT_INVALID,
+                        "Usage: harharhar export-captures <app> --pattern '<pattern>' -o <file> [--untrimmed]",
+                    );
+                };
+                let out_path = std::path::Path::new(out);
+                match harharhar_lib::export::run(app, pattern, out_path, untrimmed) {
+                    Ok(n) if json_output() => {
+                        println!(
+                            "{}",
+                            serde_json::json!({"ok": true, "app": app, "captures": n, "out": out})
+                        )
+                    }
+                    Ok(n) => println!("Wrote {n} captures to {out}"),
+                    Err(e) => fail(EXIT_FAILED, &e),
+                }
+                return;
+            }
+            "anonymize" => {
+                // harharhar anonymize <app> -o <dir>
+                let app = args.get(2).filter(|a| !a.starts_with('-'));
+                let out = args
+                    .iter()
+                    .position(|a| a == "-o" || a == "--out")
+                    .and_then(|i| args.get(i + 1));
+                let (Some(app), Some(out)) = (app, out) else {
+                    fail(EXIT_INVALID, "Usage: harharhar anonymize <app> -o <dir>");
+                };
+                match harharhar_lib::anonymize::run(app, std::path::Path::new(out)) {
+                    Ok(n) if json_output() => {
+                        println!(
+                            "{}",
+                            serde_json::json!({"ok": true, "app": app, "files": n, "out": out})
+                        )
+                    }
+                    Ok(n) => println!("Wrote {n} anonymized files to {out}"),
+                    Err(e) => fail(EXIT_FAILED, &e),
                 }
-                println!("Done.");
                 return;
             }
             "--help" | "-h" | "help" => {
                 println!("harharhar - API exploration browser\n");
                 println!("Usage:");
-                println!("  harharhar                Launch browser GUI");
+                println!("  harharhar [url]          Launch browser GUI (or pass url to the one already running)");
+                println!("  harharhar 'harharhar://open?url=<url>&app=<app>'");
+                println!("                           Same, for a page of an app (what clicking such a link runs)");
                 println!("  harharhar init           Create ~/.harharhar/ and AGENT.md");
                 println!("  harharhar cmd '<json>'   Send command to running browser");
-                println!("  harharhar generate       Generate endpoints.json + auth.json for all apps");
+                println!("  harharhar open <app>     Launch (or switch) the browser to where <app> was left off");
+                println!("  harharhar start [--background] [--cdp localhost:9222]");
+                println!("                           Launch the browser (--background: detached, returns once it's up)");
+                println!("  harharhar stop           Quit the running browser");
+                println!("  harharhar restart [--cdp localhost:9222]");
+                println!("                           Stop, then start in the background");
+                println!("  harharhar generate [app] [--no-trim] [--no-clean-domains] [--force] [--dry-run]");
+                println!("                           Regenerate endpoints, auth, digest and sitemap (all apps by default;");
+                println!("                           --force includes archived apps; --dry-run only reports");
+                println!(
+                    "                           what trimming and domain cleanup would change)"
+                );
+                println!("  harharhar stats [app]    Show capture counts, endpoints, session age, disk usage");
+                println!("  harharhar crawl [depth] [max_pages]");
+                println!("                           Auto-explore the app from the browser's current page");
+                println!("  harharhar attach [--cdp localhost:9222]");
+                println!("                           Also capture from (and drive) a tab of your own Chrome");
+                println!("  harharhar import <file.har|trace.zip> [--app <name>]");
+                println!(
+                    "                           Import a HAR or Playwright trace into app captures"
+                );
+                println!("  harharhar context <app> [--budget 8000]");
+                println!("                           Print a size-bounded summary of an app to paste into an LLM prompt");
+                println!("  harharhar jq <app> '<expr>' [--slurp]");
+                println!("                           Run a jq expression over an app's captures (--slurp: over all at once)");
+                println!("  harharhar export-captures <app> --pattern '<pattern>' -o <file> [--untrimmed]");
+                println!("                           Write the captures of matching endpoints to one JSONL file");
+                println!("  harharhar anonymize <app> -o <dir>");
+                println!("                           Copy an app with cookies, tokens, emails and IDs faked, for sharing");
                 println!("  harharhar help           Show this help");
+                println!("\nAdd --json to any command for JSON output (errors too: {{\"error\": {{\"code\", \"message\"}}}}).");
+                println!("Exit codes: 0 ok, 1 failed, 2 timeout, 3 invalid arguments or command, 4 harharhar not running");
                 println!("\nExamples:");
                 println!("  harharhar cmd '{{\"action\":\"status\"}}'");
-                println!("  harharhar cmd '{{\"action\":\"navigate\",\"url\":\"https://gmail.com\"}}'");
+                println!(
+                    "  harharhar cmd '{{\"action\":\"navigate\",\"url\":\"https://gmail.com\"}}'"
+                );
+                println!("  harharhar cmd '{{\"action\":\"read_page\",\"stream\":true,\"timeout_ms\":60000}}'");
                 return;
             }
-            other => {
-                eprintln!("Unknown command: {other}");
-                eprintln!("Run `harharhar help` for usage.");
-                std::process::exit(1);
+            url if url.starts_with("http://")
+                || url.starts_with("https://")
+                || url.starts_with("harharhar://") =>
+            {
+                harharhar_lib::run_with_url(url);
+                return;
             }
+            other => fail(
+                EXIT_INVALID,
+                &format!("Unknown command: {other}\nRun `harharhar help` for usage."),
+            ),
         }
     }
 
@@ -0,0 +1,81 @@
+use crate::capture_index;
+use crate::config;
+use crate::errors::CommandError;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+/// Named points in the capture stream: when each mark was set. Kept for the life of
+/// the browser process; setting a name again moves it.
+static MARKS: LazyLock<Mutex<HashMap<String, chrono::DateTime<chrono::Utc>>>> =
+    LazyLock::new(Default::default);
+
+/// Mark name when none is given
+pub const DEFAULT_MARK: &str = "last";
+
+/// Set mark `name` to now, returning the time it was set
+pub fn set(name: &str) -> chrono::DateTime<chrono::Utc> {
+    let now = chrono::Utc::now();
+    MARKS.lock().unwrap().insert(name.to_string(), now);
+    now
+}
+
+/// When mark `name` was set
+pub fn get(name: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    MARKS.lock().unwrap().get(name).copied()
+}
+
+/// {"mark": "before-submit", "app": "x", "limit": 100, "max_body_chars": 2000}: every
+/// capture recorded after the mark, oldest first, from `app` (default `current_app`,
+/// or every app). Bodies longer than `max_body_chars` are cut.
+pub fn captures_since(current_app: Option<String>, cmd: &serde_json::Value) -> serde_json::Value {
+    let name = cmd
+        .get("mark")
+        .and_then(|v| v.as_str())
+        .unwrap_or(DEFAULT_MARK);
+    let Some(since) = get(name) else {
+        return CommandError::NotFound(format!(
+            "no mark named {name:?} — send {{\"action\": \"mark\"}} first"
+        ))
+        .reply();
+    };
+    let limit = cmd.get("limit").and_then(|v| v.as_u64()).unwrap_or(100) as usize;
+    let max_body = cmd
+        .get("max_body_chars")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(2000) as usize;
+    let apps = crate::query::target_apps(current_app, cmd);
+
+    crate::query::flush_pending();
+    let mut found: Vec<(String, String, capture_index::Location)> = Vec::new();
+    for app_name in &apps {
+        let app_dir = config::data_dir().join("apps").join(app_name);
+        for loc in capture_index::locations(&app_dir) {
+            let after = chrono::DateTime::parse_from_rfc3339(&loc.timestamp)
+                .is_ok_and(|t| t.with_timezone(&chrono::Utc) > since);
+            if after {
+                found.push((loc.timestamp.clone(), app_name.clone(), loc));
+            }
+        }
+    }
+    found.sort_by(|a, b| a.0.cmp(&b.0));
+    let total = found.len();
+
+    let entries: Vec<serde_json::Value> = found
+        .into_iter()
+        .take(limit)
+        .filter_map(|(_, app_name, loc)| {
+            let app_dir = config::data_dir().join("apps").join(&app_name);
+            let mut data = capture_index::read(&app_dir, &loc)?;
+            crate::query::cut_bodies(&mut data, max_body);
+            data["app"] = serde_json::Value::from(app_name);
+            Some(data)
+        })
+        .collect();
+    serde_json::json!({
+        "ok": true,
+        "mark": name,
+        "since": since.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        "total": total,
+        "entries": entries,
+    })
+}
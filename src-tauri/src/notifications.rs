@@ -0,0 +1,260 @@
+use crate::config;
+use crate::errors::CommandError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Something the user has to act on, kept (in memory, and in
+/// `~/.harharhar/notifications.json` within a couple of seconds) until acknowledged. Repeats of the same event (every 401 of an expired session, every
+/// capture from an unknown domain) bump `count` on the unread notification instead of
+/// adding new ones.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Notification {
+    pub id: u64,
+    /// The event that raised it: unknown-domain, session-expired, human-needed, domain-conflict
+    pub kind: String,
+    /// What repeats are matched on: the domain, app or window the event is about
+    pub subject: String,
+    pub message: String,
+    pub first_at: String,
+    pub last_at: String,
+    pub count: u64,
+    pub read: bool,
+    /// The latest event's fields
+    pub event: serde_json::Value,
+}
+
+/// The notifications, read from notifications.json once and written back a little after
+/// each change — not once per event, which would put a file rewrite on the capture path
+struct Store {
+    notifications: Vec<Notification>,
+    dirty: bool,
+    save_pending: bool,
+}
+
+static STORE: LazyLock<Mutex<Store>> = LazyLock::new(|| {
+    Mutex::new(Store {
+        notifications: load(),
+        dirty: false,
+        save_pending: false,
+    })
+});
+
+/// How long after a change notifications.json is written: a burst of events is one write
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+const NOTIFICATIONS_FILE: &str = "notifications.json";
+
+/// Oldest read notifications are dropped past this many
+const MAX_KEPT: usize = 200;
+
+/// Events that clear what another event raised, once the user has dealt with it:
+/// (event, kind it resolves)
+const RESOLVES: &[(&str, &str)] = &[
+    ("human-resolved", "human-needed"),
+    ("navigation-resumed", "unknown-domain"),
+    ("domain-reassigned", "domain-conflict"),
+];
+
+fn load() -> Vec<Notification> {
+    fs::read_to_string(config::data_dir().join(NOTIFICATIONS_FILE))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save(notifications: &[Notification]) {
+    if let Ok(json) = serde_json::to_string_pretty(notifications) {
+        let _ = fs::write(config::data_dir().join(NOTIFICATIONS_FILE), json);
+    }
+}
+
+/// Note a change to the notifications, to be saved within SAVE_DELAY
+fn changed(store: &mut Store) {
+    store.dirty = true;
+    if !store.save_pending {
+        store.save_pending = true;
+        std::thread::spawn(|| {
+            std::thread::sleep(SAVE_DELAY);
+            flush();
+        });
+    }
+}
+
+/// Write notifications.json now if anything changed since it was last written — on
+/// exit, so nothing is lost in the last SAVE_DELAY
+pub fn flush() {
+    let mut store = STORE.lock().unwrap();
+    store.save_pending = false;
+    if store.dirty {
+        save(&store.notifications);
+        store.dirty = false;
+    }
+}
+
+/// Subject and message of an event that needs the user, None for any other event
+fn describe(kind: &str, event: &serde_json::Value) -> Option<(String, String)> {
+    let field = |k: &str| event.get(k).and_then(|v| v.as_str()).unwrap_or("");
+    match kind {
+        "unknown-domain" => Some((
+            field("domain").to_string(),
+            format!(
+                "{} isn't part of any app yet — name it to keep its traffic",
+                field("domain")
+            ),
+        )),
+        "session-expired" => Some((
+            field("app").to_string(),
+            format!(
+                "{}: {} {} came back {} — the session may have expired",
+                field("app"),
+                field("method"),
+                field("url"),
+                event.get("status").and_then(|v| v.as_u64()).unwrap_or(0)
+            ),
+        )),
+        "human-needed" => Some((
+            field("window").to_string(),
+            format!(
+                "{} on {} — solve it in the browser",
+                field("kind"),
+                field("url")
+            ),
+        )),
+        "domain-conflict" => Some((
+            field("domain").to_string(),
+            format!(
+                "{} belongs to \"{}\" but \"{}\" wants it — use reassign_domain to move it",
+                field("domain"),
+                field("owner"),
+                field("requested_by")
+            ),
+        )),
+        _ => None,
+    }
+}
+
+/// Record an event from the event stream if it needs the user (or clears something
+/// that did). Called for every `events::emit`.
+pub fn record(kind: &str, event: &serde_json::Value) {
+    if let Some((_, resolved)) = RESOLVES.iter().find(|(k, _)| *k == kind) {
+        let subject = match *resolved {
+            "human-needed" => event.get("window"),
+            _ => event.get("domain"),
+        }
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+        let mut store = STORE.lock().unwrap();
+        let mut resolved_any = false;
+        for n in store
+            .notifications
+            .iter_mut()
+            .filter(|n| !n.read && n.kind == *resolved && n.subject == subject)
+        {
+            n.read = true;
+            resolved_any = true;
+        }
+        if resolved_any {
+            changed(&mut store);
+        }
+        return;
+    }
+
+    let Some((subject, message)) = describe(kind, event) else {
+        return;
+    };
+    let now = event
+        .get("timestamp")
+        .and_then(|v| v.as_str())
+        .map(String::from)
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+
+    let mut store = STORE.lock().unwrap();
+    let notifications = &mut store.notifications;
+    match notifications
+        .iter_mut()
+        .find(|n| !n.read && n.kind == kind && n.subject == subject)
+    {
+        Some(n) => {
+            n.count += 1;
+            n.last_at = now;
+            n.message = message;
+            n.event = event.clone();
+        }
+        None => {
+            let id = notifications.iter().map(|n| n.id).max().unwrap_or(0) + 1;
+            notifications.push(Notification {
+                id,
+                kind: kind.to_string(),
+                subject,
+                message,
+                first_at: now.clone(),
+                last_at: now,
+                count: 1,
+                read: false,
+                event: event.clone(),
+            });
+        }
+    }
+    // Over the cap: drop the oldest read ones, then the oldest
+    while notifications.len() > MAX_KEPT {
+        let oldest = notifications.iter().position(|n| n.read).unwrap_or(0);
+        notifications.remove(oldest);
+    }
+    changed(&mut store);
+}
+
+/// {"unread", "kind", "limit"}: notifications, newest first, and how many are unread.
+/// `unread` (default false) lists only those not yet acknowledged.
+pub fn list(cmd: &serde_json::Value) -> serde_json::Value {
+    let unread_only = cmd.get("unread").and_then(|v| v.as_bool()).unwrap_or(false);
+    let kind = cmd.get("kind").and_then(|v| v.as_str());
+    let limit = cmd.get("limit").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
+
+    let store = STORE.lock().unwrap();
+    let notifications = &store.notifications;
+    let unread = notifications.iter().filter(|n| !n.read).count();
+    let listed: Vec<&Notification> = notifications
+        .iter()
+        .rev()
+        .filter(|n| !unread_only || !n.read)
+        .filter(|n| kind.is_none_or(|k| n.kind == k))
+        .take(limit)
+        .collect();
+    serde_json::json!({"ok": true, "unread": unread, "notifications": listed})
+}
+
+/// {"id"} or {"all": true}: mark notifications read. A later event about the same
+/// thing raises a new one.
+pub fn ack(cmd: &serde_json::Value) -> serde_json::Value {
+    let id = cmd.get("id").and_then(|v| v.as_u64());
+    let all = cmd.get("all").and_then(|v| v.as_bool()).unwrap_or(false);
+    if id.is_none() && !all {
+        return CommandError::InvalidParams(
+            "ack_notification needs an id, or \"all\": true".to_string(),
+        )
+        .reply();
+    }
+
+    let mut store = STORE.lock().unwrap();
+    if let Some(id) = id {
+        if !store.notifications.iter().any(|n| n.id == id) {
+            return CommandError::NotFound(format!("no notification {id}")).reply();
+        }
+    }
+    let mut acked = 0;
+    for n in store
+        .notifications
+        .iter_mut()
+        .filter(|n| !n.read && (all || Some(n.id) == id))
+    {
+        n.read = true;
+        acked += 1;
+    }
+    if acked > 0 {
+        changed(&mut store);
+    }
+    let unread = store.notifications.iter().filter(|n| !n.read).count();
+    serde_json::json!({"ok": true, "acked": acked, "unread": unread})
+}
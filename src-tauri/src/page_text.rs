@@ -0,0 +1,590 @@
+/// Elements dropped with everything inside them: not content, or page chrome around it
+const SKIP: &[&str] = &[
+    "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object", "head", "nav",
+    "aside", "footer", "button", "select", "dialog",
+];
+
+/// Elements that never have a closing tag
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements whose contents are raw text, not markup
+const RAW_TEXT: &[&str] = &["script", "style", "textarea", "title"];
+
+/// Elements that start on a line of their own
+const BLOCK: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "ul",
+    "ol",
+    "li",
+    "table",
+    "tr",
+    "blockquote",
+    "pre",
+    "dl",
+    "dt",
+    "dd",
+    "figure",
+    "figcaption",
+    "details",
+    "summary",
+    "address",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+];
+
+enum Token<'a> {
+    Text(&'a str),
+    Open { name: String, attrs: &'a str },
+    Close(String),
+}
+
+/// The main content of a page as plain text or markdown.
+///
+/// Readability-style: content is taken from `<main>` (or `role="main"`), else the
+/// largest `<article>`, else `<body>`; scripts, navigation, sidebars, footers,
+/// buttons and hidden elements are dropped. Relative links are resolved against `base`.
+pub(crate) fn extract(html: &str, base: Option<&url::Url>, markdown: bool) -> String {
+    let tokens = tokenize(html);
+    let (start, end) = main_range(&tokens);
+
+    let mut out = Writer {
+        buf: String::new(),
+        markdown,
+        pre: 0,
+        lists: Vec::new(),
+        links: Vec::new(),
+    };
+    // Name of the skipped element we're inside, and how deep in it
+    let mut skipping: Option<(String, usize)> = None;
+    for token in &tokens[start..end] {
+        if let Some((name, depth)) = &mut skipping {
+            match token {
+                Token::Open { name: n, .. } if n == name => *depth += 1,
+                Token::Close(n) if n == name => {
+                    *depth -= 1;
+                    if *depth == 0 {
+                        skipping = None;
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match token {
+            Token::Text(text) => out.text(&decode_entities(text)),
+            Token::Open { name, attrs } => {
+                let hidden = attr(attrs, "hidden").is_some()
+                    || attr(attrs, "aria-hidden").as_deref() == Some("true");
+                if (SKIP.contains(&name.as_str()) || hidden) && !VOID.contains(&name.as_str()) {
+                    skipping = Some((name.clone(), 1));
+                    continue;
+                }
+                out.open(name, attrs, base);
+            }
+            Token::Close(name) => out.close(name),
+        }
+    }
+    out.finish()
+}
+
+/// At most `max_chars` characters of `content`, cut at a paragraph (or line, or word)
+/// boundary, followed by a marker saying how much was left out. Markdown cut inside
+/// a code block gets the fence closed; HTML is cut after a tag.
+pub(crate) fn truncate(content: &str, max_chars: usize, mode: &str) -> String {
+    let total = content.chars().count();
+    if total <= max_chars {
+        return content.to_string();
+    }
+    let end = content
+        .char_indices()
+        .nth(max_chars)
+        .map(|(i, _)| i)
+        .unwrap_or(content.len());
+    let head = &content[..end];
+    // Only back up to a boundary if that keeps at least half of what's allowed
+    let boundary = |pat: &str| head.rfind(pat).filter(|&i| i >= end / 2);
+    let cut = if mode == "html" {
+        boundary(">").map(|i| i + 1)
+    } else {
+        boundary("\n\n")
+            .or_else(|| boundary("\n"))
+            .or_else(|| boundary(" "))
+    }
+    .unwrap_or(end);
+
+    let mut out = content[..cut].trim_end().to_string();
+    if mode == "markdown" && out.matches("```").count() % 2 == 1 {
+        out.push_str("\n```");
+    }
+    let shown = out.chars().count();
+    out.push_str(&format!(
+        "\n\n[... truncated: {shown} of {total} chars shown; raise max_chars for more]"
+    ));
+    out
+}
+
+/// Renders content tokens as text, with markdown syntax when asked
+struct Writer {
+    buf: String,
+    markdown: bool,
+    /// Depth of <pre> elements we're in: whitespace is kept there
+    pre: usize,
+    /// Open lists: the next item number for <ol>, None for <ul>
+    lists: Vec<Option<usize>>,
+    /// Open links' targets, written out when they close
+    links: Vec<Option<String>>,
+}
+
+impl Writer {
+    fn text(&mut self, text: &str) {
+        if self.pre > 0 {
+            self.buf.push_str(text);
+            return;
+        }
+        for c in text.chars() {
+            if c.is_whitespace() {
+                if !self.buf.is_empty() && !self.buf.ends_with([' ', '\n']) {
+                    self.buf.push(' ');
+                }
+            } else {
+                self.buf.push(c);
+            }
+        }
+    }
+
+    /// Start a new line, unless already at the start of one
+    fn newline(&mut self) {
+        let trimmed = self.buf.trim_end_matches(' ').len();
+        self.buf.truncate(trimmed);
+        if !self.buf.is_empty() && !self.buf.ends_with('\n') {
+            self.buf.push('\n');
+        }
+    }
+
+    /// Leave an empty line before what comes next
+    fn paragraph(&mut self) {
+        self.newline();
+        if !self.buf.is_empty() && !self.buf.ends_with("\n\n") {
+            self.buf.push('\n');
+        }
+    }
+
+    fn open(&mut self, name: &str, attrs: &str, base: Option<&url::Url>) {
+        let md = self.markdown;
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.paragraph();
+                if md {
+                    let level = name[1..].parse().unwrap_or(1);
+                    self.buf.push_str(&"#".repeat(level));
+                    self.buf.push(' ');
+                }
+            }
+            "p" | "table" | "blockquote" | "figure" | "dl" => {
+                self.paragraph();
+                if md && name == "blockquote" {
+                    self.buf.push_str("> ");
+                }
+            }
+            "ul" | "ol" => {
+                self.newline();
+                self.lists.push((name == "ol").then_some(1));
+            }
+            "li" => {
+                self.newline();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                self.buf.push_str(&indent);
+                match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        self.buf.push_str(&format!("{n}. "));
+                        *n += 1;
+                    }
+                    _ => self.buf.push_str("- "),
+                }
+            }
+            "pre" => {
+                self.paragraph();
+                if md {
+                    self.buf.push_str("```\n");
+                }
+                self.pre += 1;
+            }
+            "br" => {
+                self.buf.truncate(self.buf.trim_end_matches(' ').len());
+                self.buf.push('\n');
+            }
+            "hr" => {
+                self.paragraph();
+                if md {
+                    self.buf.push_str("---");
+                    self.paragraph();
+                }
+            }
+            "td" | "th" if !self.buf.ends_with('\n') && !self.buf.is_empty() => {
+                self.buf.push_str(" | ");
+            }
+            "a" if md => {
+                let href = attr(attrs, "href")
+                    .filter(|h| !h.starts_with('#') && !h.starts_with("javascript:"))
+                    .map(|h| resolve(&h, base));
+                if href.is_some() {
+                    self.buf.push('[');
+                }
+                self.links.push(href);
+            }
+            "strong" | "b" if md => self.buf.push_str("**"),
+            "em" | "i" if md => self.buf.push('*'),
+            "code" if md && self.pre == 0 => self.buf.push('`'),
+            "img" => {
+                let alt = attr(attrs, "alt").unwrap_or_default();
+                if md {
+                    if let Some(src) = attr(attrs, "src").filter(|s| !s.starts_with("data:")) {
+                        self.buf
+                            .push_str(&format!("![{}]({})", alt, resolve(&src, base)));
+                    }
+                } else if !alt.is_empty() {
+                    self.text(&format!(" {alt} "));
+                }
+            }
+            _ if BLOCK.contains(&name) => self.newline(),
+            _ => {}
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        let md = self.markdown;
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "table" | "blockquote" | "figure"
+            | "dl" => self.paragraph(),
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.paragraph();
+                } else {
+                    self.newline();
+                }
+            }
+            "pre" => {
+                self.pre = self.pre.saturating_sub(1);
+                if md {
+                    self.newline();
+                    self.buf.push_str("```");
+                }
+                self.paragraph();
+            }
+            "a" if md => {
+                if let Some(Some(href)) = self.links.pop() {
+                    self.buf.truncate(self.buf.trim_end_matches(' ').len());
+                    self.buf.push_str(&format!("]({href})"));
+                }
+            }
+            "strong" | "b" if md => self.buf.push_str("**"),
+            "em" | "i" if md => self.buf.push('*'),
+            "code" if md && self.pre == 0 => self.buf.push('`'),
+            _ if BLOCK.contains(&name) => self.newline(),
+            _ => {}
+        }
+    }
+
+    /// Trailing spaces off every line, at most one empty line in a row
+    fn finish(self) -> String {
+        let mut out = String::new();
+        let mut blank = false;
+        for line in self.buf.lines().map(str::trim_end) {
+            if line.is_empty() {
+                if !blank && !out.is_empty() {
+                    out.push('\n');
+                }
+                blank = true;
+                continue;
+            }
+            blank = false;
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.trim_end().to_string()
+    }
+}
+
+/// Token range of the page's main content (the element's children)
+fn main_range(tokens: &[Token]) -> (usize, usize) {
+    let is_main = |t: &Token| match t {
+        Token::Open { name, attrs } => {
+            name == "main" || attr(attrs, "role").as_deref() == Some("main")
+        }
+        _ => false,
+    };
+    if let Some(start) = tokens.iter().position(is_main) {
+        return element_range(tokens, start);
+    }
+
+    // The article with the most text
+    let articles = tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| matches!(t, Token::Open { name, .. } if name == "article"))
+        .map(|(i, _)| element_range(tokens, i));
+    let text_len = |(start, end): (usize, usize)| -> usize {
+        tokens[start..end]
+            .iter()
+            .map(|t| match t {
+                Token::Text(s) => s.trim().len(),
+                _ => 0,
+            })
+            .sum()
+    };
+    if let Some(range) = articles.max_by_key(|r| text_len(*r)) {
+        return range;
+    }
+
+    match tokens
+        .iter()
+        .position(|t| matches!(t, Token::Open { name, .. } if name == "body"))
+    {
+        Some(start) => element_range(tokens, start),
+        None => (0, tokens.len()),
+    }
+}
+
+/// Children of the element opened at `start`: up to its matching close tag
+fn element_range(tokens: &[Token], start: usize) -> (usize, usize) {
+    let Token::Open { name, .. } = &tokens[start] else {
+        return (start, start);
+    };
+    let mut depth = 1;
+    for (i, token) in tokens.iter().enumerate().skip(start + 1) {
+        match token {
+            Token::Open { name: n, .. } if n == name => depth += 1,
+            Token::Close(n) if n == name => {
+                depth -= 1;
+                if depth == 0 {
+                    return (start + 1, i);
+                }
+            }
+            _ => {}
+        }
+    }
+    (start + 1, tokens.len())
+}
+
+/// `(tag, name, value)` of every `<meta name content>` and `<input name value>` in a page,
+/// in order: where server-rendered pages hand their scripts CSRF tokens and the like
+pub(crate) fn named_values(html: &str) -> Vec<(&'static str, String, String)> {
+    tokenize(html)
+        .into_iter()
+        .filter_map(|token| {
+            let Token::Open { name, attrs } = token else {
+                return None;
+            };
+            let (tag, value_attr) = match name.as_str() {
+                "meta" => ("meta", "content"),
+                "input" => ("input", "value"),
+                _ => return None,
+            };
+            Some((tag, attr(attrs, "name")?, attr(attrs, value_attr)?))
+        })
+        .collect()
+}
+
+/// Split HTML into text, open and close tags. Comments and doctypes are dropped;
+/// void elements only ever appear as Open. Forgiving: a stray '<' is text.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let bytes = html.as_bytes();
+    let mut i = 0;
+    while i < html.len() {
+        let Some(lt) = html[i..].find('<').map(|p| i + p) else {
+            tokens.push(Token::Text(&html[i..]));
+            break;
+        };
+        if lt > i {
+            tokens.push(Token::Text(&html[i..lt]));
+        }
+        let rest = &html[lt..];
+        if rest.starts_with("<!--") {
+            i = rest.find("-->").map(|p| lt + p + 3).unwrap_or(html.len());
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            i = rest.find('>').map(|p| lt + p + 1).unwrap_or(html.len());
+            continue;
+        }
+        let closing = rest.starts_with("</");
+        let name_start = lt + if closing { 2 } else { 1 };
+        let name_len = html[name_start..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+            .unwrap_or(html.len() - name_start);
+        if name_len == 0 {
+            tokens.push(Token::Text("<"));
+            i = lt + 1;
+            continue;
+        }
+        let name = html[name_start..name_start + name_len].to_ascii_lowercase();
+
+        // End of the tag, skipping '>' inside quoted attribute values
+        let mut j = name_start + name_len;
+        let mut quote: Option<u8> = None;
+        while j < bytes.len() {
+            match (quote, bytes[j]) {
+                (None, b'"') | (None, b'\'') => quote = Some(bytes[j]),
+                (Some(q), c) if c == q => quote = None,
+                (None, b'>') => break,
+                _ => {}
+            }
+            j += 1;
+        }
+        let attrs = &html[name_start + name_len..j.min(html.len())];
+        i = (j + 1).min(html.len());
+
+        if closing {
+            tokens.push(Token::Close(name));
+            continue;
+        }
+        let raw = RAW_TEXT.contains(&name.as_str());
+        tokens.push(Token::Open {
+            name: name.clone(),
+            attrs,
+        });
+        if raw {
+            // Everything up to the closing tag is text, '<' included
+            let close = format!("</{name}");
+            let end = find_ignore_case(&html[i..], &close)
+                .map(|p| i + p)
+                .unwrap_or(html.len());
+            if end > i {
+                tokens.push(Token::Text(&html[i..end]));
+            }
+            tokens.push(Token::Close(name));
+            i = html[end..]
+                .find('>')
+                .map(|p| end + p + 1)
+                .unwrap_or(html.len());
+        }
+    }
+    tokens
+}
+
+/// Byte offset of `needle` (ASCII) in `haystack`, ignoring ASCII case, without
+/// lowercasing a copy of the rest of the page for every raw-text element
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Value of attribute `name` in a tag's attribute text, entities decoded.
+/// A present attribute without a value gives "".
+fn attr(attrs: &str, name: &str) -> Option<String> {
+    let bytes = attrs.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        let start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"=/>".contains(&bytes[i]) {
+            i += 1;
+        }
+        let key = &attrs[start..i];
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let mut value = "";
+        if i < bytes.len() && bytes[i] == b'=' {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if i < bytes.len() && (bytes[i] == b'"' || bytes[i] == b'\'') {
+                let q = bytes[i];
+                let v_start = i + 1;
+                i = attrs[v_start..]
+                    .find(q as char)
+                    .map(|p| v_start + p)
+                    .unwrap_or(attrs.len());
+                value = &attrs[v_start..i];
+                i += 1;
+            } else {
+                let v_start = i;
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                value = &attrs[v_start..i];
+            }
+        }
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value));
+        }
+        if key.is_empty() {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// An href/src made absolute against the page URL
+fn resolve(href: &str, base: Option<&url::Url>) -> String {
+    base.and_then(|b| b.join(href).ok())
+        .map(|u| u.to_string())
+        .unwrap_or_else(|| href.to_string())
+}
+
+/// Decode the character references pages actually use: named basics and numeric
+fn decode_entities(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|&semi| semi <= 10).and_then(|semi| {
+            let entity = &rest[1..semi];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                "mdash" => Some('—'),
+                "ndash" => Some('–'),
+                "hellip" => Some('…'),
+                "copy" => Some('©'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|h| u32::from_str_radix(h, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, semi))
+        });
+        match decoded {
+            Some((c, semi)) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
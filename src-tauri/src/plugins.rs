@@ -0,0 +1,182 @@
+use crate::capture_io;
+use crate::config::{self, PluginConfig};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// A processor registered from Rust (e.g. by a tool embedding `engine`).
+/// Both hooks default to doing nothing.
+pub trait Plugin: Send + Sync {
+    /// Names its output file: apps/<app>/plugins/{name}.json
+    fn name(&self) -> &str;
+
+    /// A capture was just saved. Runs on the capture path, so keep it quick.
+    fn on_capture(&self, _app_name: &str, _data: &serde_json::Value) {}
+
+    /// The app's endpoints/digest/sitemap were just regenerated. Whatever is returned
+    /// is saved as apps/<app>/plugins/{name}.json.
+    fn on_generate(&self, _app_name: &str) -> Option<serde_json::Value> {
+        None
+    }
+}
+
+static REGISTERED: LazyLock<Mutex<Vec<Arc<dyn Plugin>>>> = LazyLock::new(Default::default);
+
+/// Capture feeds of running "capture" executables, by plugin name
+static RUNNING: LazyLock<Mutex<HashMap<String, mpsc::Sender<String>>>> =
+    LazyLock::new(Default::default);
+
+/// Add a Rust plugin; it runs alongside the executables in config.json's "plugins"
+pub fn register(plugin: Box<dyn Plugin>) {
+    REGISTERED.lock().unwrap().push(Arc::from(plugin));
+}
+
+fn registered() -> Vec<Arc<dyn Plugin>> {
+    REGISTERED.lock().unwrap().clone()
+}
+
+/// Configured executables that want `hook` for `app_name`
+fn configured(hook: &str, app_name: &str) -> Vec<PluginConfig> {
+    config::config()
+        .plugins
+        .iter()
+        .filter(|p| !p.command.is_empty())
+        .filter(|p| p.on.is_empty() || p.on.iter().any(|h| h == hook))
+        .filter(|p| p.apps.is_empty() || p.apps.iter().any(|a| a == app_name))
+        .cloned()
+        .collect()
+}
+
+/// Hand a saved capture to every plugin. Executables get it as one JSONL line
+/// ({"app", "capture"}) on the stdin of a long-running `command... capture` process,
+/// started on first use and restarted if it exits.
+pub fn capture(app_name: &str, data: &serde_json::Value) {
+    for plugin in registered() {
+        plugin.on_capture(app_name, data);
+    }
+
+    let plugins = configured("capture", app_name);
+    if plugins.is_empty() {
+        return;
+    }
+    let line = serde_json::json!({"app": app_name, "capture": data}).to_string();
+    let mut running = RUNNING.lock().unwrap();
+    for plugin in plugins {
+        let delivered = running
+            .get(&plugin.name)
+            .is_some_and(|tx| tx.send(line.clone()).is_ok());
+        if delivered {
+            continue;
+        }
+        // Not started yet, or it exited: (re)start it and retry once
+        match spawn_capture_process(&plugin) {
+            Ok(tx) => {
+                let _ = tx.send(line.clone());
+                running.insert(plugin.name.clone(), tx);
+            }
+            Err(e) => {
+                running.remove(&plugin.name);
+                eprintln!("[harharhar] plugin {}: {e}", plugin.name);
+            }
+        }
+    }
+}
+
+/// Start `command... capture` with a feeder thread writing lines to its stdin,
+/// so a slow plugin never holds up capture. The feed closes when the process exits.
+fn spawn_capture_process(plugin: &PluginConfig) -> Result<mpsc::Sender<String>, String> {
+    let mut child = Command::new(&plugin.command[0])
+        .args(&plugin.command[1..])
+        .arg("capture")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().ok_or("no stdin")?;
+    let (tx, rx) = mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        for line in rx {
+            if writeln!(stdin, "{line}").is_err() {
+                break;
+            }
+        }
+        drop(stdin);
+        let _ = child.wait();
+    });
+    Ok(tx)
+}
+
+/// Run every plugin's generation hook for an app. Executables run as
+/// `command... generate` with the app's captures as JSONL on stdin and
+/// HARHARHAR_APP / HARHARHAR_APP_DIR set; JSON they print is saved like a
+/// Rust plugin's result.
+pub fn generate(app_name: &str) {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    for plugin in registered() {
+        if let Some(result) = plugin.on_generate(app_name) {
+            save_output(app_name, plugin.name(), &result);
+        }
+    }
+    for plugin in configured("generate", app_name) {
+        match run_generate_process(&plugin, app_name, &app_dir) {
+            Ok(Some(result)) => save_output(app_name, &plugin.name, &result),
+            Ok(None) => {}
+            Err(e) => eprintln!("[harharhar] plugin {}: {e}", plugin.name),
+        }
+    }
+}
+
+fn run_generate_process(
+    plugin: &PluginConfig,
+    app_name: &str,
+    app_dir: &std::path::Path,
+) -> Result<Option<serde_json::Value>, String> {
+    let mut child = Command::new(&plugin.command[0])
+        .args(&plugin.command[1..])
+        .arg("generate")
+        .env("HARHARHAR_APP", app_name)
+        .env("HARHARHAR_APP_DIR", app_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    // Feed captures from a thread so a plugin that prints as it reads can't deadlock us
+    let mut stdin = child.stdin.take().ok_or("no stdin")?;
+    let files = capture_io::capture_files(app_dir);
+    let feeder = std::thread::spawn(move || {
+        for path in files {
+            for line in capture_io::lines(&path) {
+                if writeln!(stdin, "{line}").is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    let _ = feeder.join();
+    if !output.status.success() {
+        return Err(format!("exited with {}", output.status));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| format!("output isn't JSON: {e}"))
+}
+
+fn save_output(app_name: &str, plugin_name: &str, result: &serde_json::Value) {
+    let dir = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("plugins");
+    let _ = fs::create_dir_all(&dir);
+    if let Ok(json) = serde_json::to_string_pretty(result) {
+        let _ = fs::write(dir.join(format!("{plugin_name}.json")), json);
+    }
+}
@@ -0,0 +1,370 @@
+use crate::capture_index::{self, Location};
+use crate::config;
+use crate::endpoints;
+use crate::errors::CommandError;
+
+/// Get what's been captured so far into the capture files and index before answering
+/// from them: repeat runs still held back, and captures queued for the writer threads.
+/// Otherwise a query sent right after an action misses the requests it made.
+pub(crate) fn flush_pending() {
+    crate::capture::flush_repeats();
+    crate::capture_writer::flush();
+}
+
+/// Apps a capture command looks at: its "app", else the window's app, else all of them
+pub(crate) fn target_apps(current_app: Option<String>, cmd: &serde_json::Value) -> Vec<String> {
+    match cmd.get("app").and_then(|v| v.as_str()) {
+        Some(name) => vec![name.to_string()],
+        None => current_app
+            .map(|a| vec![a])
+            .unwrap_or_else(config::list_apps),
+    }
+}
+
+/// Matches index patterns against an endpoint pattern as given by an agent:
+/// "GET /items/123" matches "GET /items/{id}"; anything else matches as a substring.
+pub(crate) struct PatternMatch {
+    given: String,
+    normalized: String,
+}
+
+impl PatternMatch {
+    pub(crate) fn new(pattern: &str) -> Self {
+        let normalized = match pattern.split_once(' ') {
+            Some((method, path)) if path.starts_with('/') => {
+                format!(
+                    "{} {}",
+                    method.to_uppercase(),
+                    endpoints::normalize_path(path)
+                )
+            }
+            _ => pattern.to_string(),
+        };
+        PatternMatch {
+            given: pattern.to_string(),
+            normalized,
+        }
+    }
+
+    pub(crate) fn matches(&self, pattern: &str) -> bool {
+        !pattern.is_empty() && (pattern == self.normalized || pattern.contains(&self.given))
+    }
+}
+
+/// Cut request/response bodies longer than `max` bytes, saying how long they were
+pub(crate) fn cut_bodies(data: &mut serde_json::Value, max: usize) {
+    for field in ["requestBody", "responseBody"] {
+        if let Some(body) = data.get(field).and_then(|v| v.as_str()) {
+            if body.len() > max {
+                let cut = format!(
+                    "{}... [{} bytes, cut]",
+                    crate::capture::truncate_body(body, max),
+                    body.len()
+                );
+                data[field] = serde_json::Value::from(cut);
+            }
+        }
+    }
+}
+
+/// A status filter: 404, "404" or a class like "4xx"
+fn status_matches(filter: &serde_json::Value, status: Option<u64>) -> bool {
+    let Some(status) = status else {
+        return false;
+    };
+    if let Some(n) = filter.as_u64() {
+        return status == n;
+    }
+    let s = filter.as_str().unwrap_or("").to_lowercase();
+    match s.strip_suffix("xx") {
+        Some(class) => class.parse::<u64>().is_ok_and(|c| status / 100 == c),
+        None => s.parse::<u64>().is_ok_and(|n| status == n),
+    }
+}
+
+fn parse_time(
+    cmd: &serde_json::Value,
+    key: &str,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+    match cmd.get(key).and_then(|v| v.as_str()) {
+        Some(ts) => chrono::DateTime::parse_from_rfc3339(ts)
+            .map(|t| Some(t.with_timezone(&chrono::Utc)))
+            .map_err(|e| format!("bad {key}: {e}")),
+        None => Ok(None),
+    }
+}
+
+/// The filters a capture command can answer from the index alone:
+/// {"pattern", "method", "status", "type", "from", "to", "mark"}
+struct IndexFilter {
+    pattern: Option<PatternMatch>,
+    method: Option<String>,
+    entry_type: Option<String>,
+    status: Option<serde_json::Value>,
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl IndexFilter {
+    fn parse(cmd: &serde_json::Value) -> Result<Self, CommandError> {
+        let str_field = |k: &str| {
+            cmd.get(k)
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+        };
+        let (from, to) = match (parse_time(cmd, "from"), parse_time(cmd, "to")) {
+            (Ok(from), Ok(to)) => (from, to),
+            (Err(e), _) | (_, Err(e)) => return Err(CommandError::InvalidParams(e)),
+        };
+        let from = match (from, str_field("mark")) {
+            (None, Some(name)) => match crate::marks::get(name) {
+                Some(t) => Some(t),
+                None => return Err(CommandError::NotFound(format!("no mark named {name:?}"))),
+            },
+            (from, _) => from,
+        };
+        Ok(IndexFilter {
+            pattern: str_field("pattern").map(PatternMatch::new),
+            method: str_field("method").map(|m| format!("{} ", m.to_uppercase())),
+            entry_type: str_field("type").map(String::from),
+            status: cmd.get("status").filter(|v| !v.is_null()).cloned(),
+            from,
+            to,
+        })
+    }
+
+    fn matches(&self, loc: &Location) -> bool {
+        if self
+            .pattern
+            .as_ref()
+            .is_some_and(|p| !p.matches(&loc.pattern))
+            || self
+                .method
+                .as_ref()
+                .is_some_and(|m| !loc.pattern.starts_with(m.as_str()))
+            || self
+                .entry_type
+                .as_ref()
+                .is_some_and(|t| &loc.entry_type != t)
+            || self
+                .status
+                .as_ref()
+                .is_some_and(|s| !status_matches(s, loc.status))
+        {
+            return false;
+        }
+        if self.from.is_none() && self.to.is_none() {
+            return true;
+        }
+        let Ok(at) = chrono::DateTime::parse_from_rfc3339(&loc.timestamp) else {
+            return false;
+        };
+        let at = at.with_timezone(&chrono::Utc);
+        self.from.is_none_or(|from| at >= from) && self.to.is_none_or(|to| at <= to)
+    }
+
+    /// Index entries of the target apps passing the filter, newest first
+    fn candidates(
+        &self,
+        current_app: Option<String>,
+        cmd: &serde_json::Value,
+    ) -> Vec<(String, Location)> {
+        let mut candidates: Vec<(String, Location)> = Vec::new();
+        for app_name in target_apps(current_app, cmd) {
+            let app_dir = config::data_dir().join("apps").join(&app_name);
+            for loc in capture_index::snapshot(&app_dir).iter() {
+                if self.matches(loc) {
+                    candidates.push((app_name.clone(), loc.clone()));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.1.timestamp.cmp(&a.1.timestamp));
+        candidates
+    }
+}
+
+/// Id of one capture, "app:session:offset": stable until its capture file is rewritten
+/// (trimmed or archived)
+fn capture_id(app_name: &str, loc: &Location) -> String {
+    let session = loc.file.strip_suffix(".jsonl").unwrap_or(&loc.file);
+    format!("{app_name}:{session}:{}", loc.offset)
+}
+
+/// Index entry of a capture id, if its capture is still where the id says
+fn find_capture(id: &str) -> Option<(String, Location)> {
+    let mut parts = id.rsplitn(3, ':');
+    let offset = parts.next()?.parse::<u64>().ok()?;
+    let file = format!("{}.jsonl", parts.next()?);
+    let app_name = parts.next().filter(|a| !a.is_empty())?;
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    capture_index::find(&app_dir, &file, offset).map(|loc| (app_name.to_string(), loc))
+}
+
+/// An index entry as listed to callers: the entry plus its app and capture id
+fn summary(app_name: &str, loc: &Location) -> serde_json::Value {
+    let mut v = serde_json::to_value(loc).unwrap_or_default();
+    v["app"] = serde_json::Value::from(app_name);
+    v["id"] = serde_json::Value::from(capture_id(app_name, loc));
+    v
+}
+
+/// {"pattern", "method", "status", "type", "from", "to", "mark", "url", "body", "app",
+/// "limit", "offset", "max_body_chars", "index_only"}: captures matching every given
+/// filter, newest first.
+///
+/// Pattern, method, status, type and time are answered from the capture index alone;
+/// only captures passing those are read to check `url` and `body` (substrings of the
+/// URL, and of the request or response body). `index_only` returns just the index
+/// entries (file, offset, pattern, status, timestamp) without reading any capture.
+pub fn run(current_app: Option<String>, cmd: &serde_json::Value) -> serde_json::Value {
+    let str_field = |k: &str| {
+        cmd.get(k)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+    };
+    let url = str_field("url");
+    let body = str_field("body");
+    let filter = match IndexFilter::parse(cmd) {
+        Ok(filter) => filter,
+        Err(e) => return e.reply(),
+    };
+    let limit = cmd.get("limit").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
+    let offset = cmd.get("offset").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+    let max_body = cmd
+        .get("max_body_chars")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(2000) as usize;
+    let index_only = cmd
+        .get("index_only")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if index_only && (url.is_some() || body.is_some()) {
+        return CommandError::InvalidParams(
+            "url and body filters need the captures: drop index_only".to_string(),
+        )
+        .reply();
+    }
+
+    flush_pending();
+    let candidates = filter.candidates(current_app, cmd);
+
+    if index_only {
+        let total = candidates.len();
+        let entries: Vec<serde_json::Value> = candidates
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(app_name, loc)| summary(&app_name, &loc))
+            .collect();
+        return serde_json::json!({"ok": true, "total": total, "entries": entries});
+    }
+
+    let read = |app_name: &str, loc: &Location| {
+        capture_index::read(&config::data_dir().join("apps").join(app_name), loc)
+    };
+    let finish = |app_name: String, loc: &Location, mut data: serde_json::Value| {
+        cut_bodies(&mut data, max_body);
+        data["id"] = serde_json::Value::from(capture_id(&app_name, loc));
+        data["app"] = serde_json::Value::from(app_name);
+        data
+    };
+
+    // The index answered everything: read just the page asked for
+    if url.is_none() && body.is_none() {
+        let total = candidates.len();
+        let entries: Vec<serde_json::Value> = candidates
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .filter_map(|(app_name, loc)| read(&app_name, &loc).map(|d| finish(app_name, &loc, d)))
+            .collect();
+        return serde_json::json!({"ok": true, "total": total, "entries": entries});
+    }
+
+    let mut total = 0;
+    let mut entries = Vec::new();
+    for (app_name, loc) in candidates {
+        let Some(data) = read(&app_name, &loc) else {
+            continue;
+        };
+        let field = |k: &str| data.get(k).and_then(|v| v.as_str()).unwrap_or("");
+        if url.is_some_and(|u| !field("url").contains(u))
+            || body.is_some_and(|b| {
+                !field("requestBody").contains(b) && !field("responseBody").contains(b)
+            })
+        {
+            continue;
+        }
+        total += 1;
+        if total <= offset || entries.len() >= limit {
+            continue;
+        }
+        entries.push(finish(app_name, &loc, data));
+    }
+    serde_json::json!({"ok": true, "total": total, "entries": entries})
+}
+
+/// {"app", "pattern", "method", "status", "type", "from", "to", "mark", "limit", "before"}:
+/// one page of the newest captures for the explorer's feed, as index entries with ids
+/// (nothing is read from the capture files). Takes the same index filters as
+/// `query_captures`; `before` is the id of the last entry of the previous page, so new
+/// captures arriving between pages don't shift what comes next.
+pub fn recent(current_app: Option<String>, cmd: &serde_json::Value) -> serde_json::Value {
+    let filter = match IndexFilter::parse(cmd) {
+        Ok(filter) => filter,
+        Err(e) => return e.reply(),
+    };
+    let limit = cmd.get("limit").and_then(|v| v.as_u64()).unwrap_or(50) as usize;
+    flush_pending();
+    let candidates = filter.candidates(current_app, cmd);
+    let total = candidates.len();
+    let start = match cmd.get("before").and_then(|v| v.as_str()) {
+        Some(before) => match candidates
+            .iter()
+            .position(|(app_name, loc)| capture_id(app_name, loc) == before)
+        {
+            Some(i) => i + 1,
+            None => {
+                return CommandError::NotFound(format!(
+                    "no capture {before:?} (its session may have been trimmed)"
+                ))
+                .reply()
+            }
+        },
+        None => 0,
+    };
+    let entries: Vec<serde_json::Value> = candidates[start..]
+        .iter()
+        .take(limit)
+        .map(|(app_name, loc)| summary(app_name, loc))
+        .collect();
+    let more = start + entries.len() < total;
+    serde_json::json!({"ok": true, "total": total, "entries": entries, "more": more})
+}
+
+/// {"id", "max_body_chars"}: the full capture behind an id from `recent_captures` or
+/// `query_captures`
+pub fn detail(cmd: &serde_json::Value) -> serde_json::Value {
+    let Some(id) = cmd.get("id").and_then(|v| v.as_str()) else {
+        return CommandError::InvalidParams("capture_detail needs an id".to_string()).reply();
+    };
+    let max_body = cmd
+        .get("max_body_chars")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(20_000) as usize;
+    let Some((app_name, loc)) = find_capture(id) else {
+        return CommandError::NotFound(format!(
+            "no capture {id:?} (its session may have been trimmed)"
+        ))
+        .reply();
+    };
+    let Some(mut data) =
+        capture_index::read(&config::data_dir().join("apps").join(&app_name), &loc)
+    else {
+        return CommandError::NotFound(format!("capture {id:?} is no longer readable")).reply();
+    };
+    cut_bodies(&mut data, max_body);
+    data["id"] = serde_json::Value::from(id);
+    data["app"] = serde_json::Value::from(app_name);
+    serde_json::json!({"ok": true, "capture": data})
+}
@@ -0,0 +1,118 @@
+use crate::capture_index;
+use crate::config;
+use crate::endpoints::{self, EndpointCatalog};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+/// What's known of one endpoint while capturing: how often it's been seen and the
+/// response shapes seen so far, seeded from endpoints.json
+struct Seen {
+    count: u64,
+    shapes: Vec<serde_json::Value>,
+}
+
+/// Distinct response shapes remembered per endpoint
+const MAX_SHAPES: usize = 20;
+
+/// Per app, per endpoint pattern (apps are loaded the first time they're sampled)
+static SEEN: LazyLock<Mutex<HashMap<String, HashMap<String, Seen>>>> =
+    LazyLock::new(Default::default);
+
+/// Mixed into the sampling coin so captures in the same nanosecond differ
+static COIN: AtomicU64 = AtomicU64::new(0);
+
+pub enum Verdict {
+    /// Not sampled (sampling off, endpoint below the threshold, an error, a new shape)
+    Keep,
+    /// Kept by the coin flip; it stands for this many captures of its endpoint
+    Sampled(u64),
+    Drop,
+}
+
+/// Decide whether a capture is saved under the app's sampling policy
+pub fn check(app_name: &str, data: &serde_json::Value) -> Verdict {
+    let entry_type = data.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    if !matches!(entry_type, "fetch" | "xhr" | "beacon") {
+        return Verdict::Keep;
+    }
+    let Some(policy) = config::sampling_policy(app_name) else {
+        return Verdict::Keep;
+    };
+    let Some(pattern) = capture_index::entry_pattern(data) else {
+        return Verdict::Keep;
+    };
+
+    let mut seen = SEEN.lock().unwrap();
+    let app_seen = seen
+        .entry(app_name.to_string())
+        .or_insert_with(|| load(app_name));
+    let endpoint = app_seen.entry(pattern).or_insert(Seen {
+        count: 0,
+        shapes: Vec::new(),
+    });
+    endpoint.count += 1;
+    if endpoint.count <= policy.after {
+        return Verdict::Keep;
+    }
+
+    // Errors and network failures are always worth keeping
+    let status = data.get("status").and_then(|v| v.as_u64()).unwrap_or(0);
+    if status == 0 || status >= 400 {
+        return Verdict::Keep;
+    }
+    // So is a response that doesn't look like the ones already seen
+    if let Some(shape) = endpoints::response_shape(data) {
+        if !endpoint.shapes.contains(&shape) {
+            if endpoint.shapes.len() < MAX_SHAPES {
+                endpoint.shapes.push(shape);
+            }
+            return Verdict::Keep;
+        }
+    }
+
+    let rate = policy.rate.clamp(0.0, 1.0);
+    if coin() < rate {
+        Verdict::Sampled((1.0 / rate).round() as u64)
+    } else {
+        Verdict::Drop
+    }
+}
+
+/// Endpoint counts and shapes from the app's endpoints.json
+fn load(app_name: &str) -> HashMap<String, Seen> {
+    let path = config::data_dir()
+        .join("apps")
+        .join(app_name)
+        .join("endpoints.json");
+    let catalog: Option<EndpointCatalog> = fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok());
+    catalog
+        .map(|c| c.endpoints)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|ep| {
+            let seen = Seen {
+                count: ep.times_seen as u64,
+                shapes: ep.response_shape_sample.into_iter().collect(),
+            };
+            (ep.pattern, seen)
+        })
+        .collect()
+}
+
+/// A number in [0, 1) that's different every call
+fn coin() -> f64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let mut x = nanos ^ COIN.fetch_add(0x9e3779b97f4a7c15, Ordering::Relaxed);
+    // splitmix64 finalizer
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^= x >> 31;
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
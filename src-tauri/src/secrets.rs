@@ -0,0 +1,311 @@
+/// Key prefixes of well-known API credentials, with the minimum full token length
+const KEY_PREFIXES: &[(&str, usize)] = &[
+    ("sk_live_", 20),
+    ("sk_test_", 20),
+    ("rk_live_", 20),
+    ("sk-", 32),
+    ("AKIA", 20),
+    ("ASIA", 20),
+    ("AIza", 39),
+    ("ghp_", 36),
+    ("gho_", 36),
+    ("ghs_", 36),
+    ("github_pat_", 40),
+    ("glpat-", 26),
+    ("xoxb-", 30),
+    ("xoxp-", 30),
+    ("xapp-", 30),
+];
+
+/// Capture fields scanned for secrets. Headers are left alone — replay needs them.
+const BODY_FIELDS: &[&str] = &["requestBody", "responseBody"];
+
+/// Look for secrets (JWTs, API keys, card numbers) in a capture's bodies and
+/// form values. Flags the capture with `"contains_secrets": ["jwt", ...]`, and
+/// with `mask_secrets` on in config.json replaces each one with "[masked:kind]".
+/// JSON bodies are scanned value by value, so a masked one is still valid JSON.
+pub fn scan(data: &mut serde_json::Value) {
+    let mut scan = Scan {
+        mask: crate::config::config().mask_secrets,
+        kinds: Vec::new(),
+    };
+    for field in BODY_FIELDS {
+        if let Some(value) = data.get_mut(*field) {
+            scan.body(value);
+        }
+    }
+    if let Some(form) = data.get_mut("requestForm").and_then(|v| v.as_object_mut()) {
+        for (name, value) in form.iter_mut() {
+            match value {
+                serde_json::Value::Array(values) => {
+                    for value in values {
+                        scan.string(value, name);
+                    }
+                }
+                other => {
+                    scan.string(other, name);
+                }
+            }
+        }
+    }
+
+    if !scan.kinds.is_empty() {
+        data["contains_secrets"] = serde_json::json!(scan.kinds);
+        if scan.mask {
+            data["secrets_masked"] = serde_json::Value::Bool(true);
+        }
+    }
+}
+
+struct Scan {
+    mask: bool,
+    kinds: Vec<&'static str>,
+}
+
+impl Scan {
+    fn found(&mut self, kind: &'static str) {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+    }
+
+    /// A request or response body: JSON has its values scanned and is written back
+    /// only if one was masked; anything else is scanned as text
+    fn body(&mut self, value: &mut serde_json::Value) {
+        let Some(text) = value.as_str() else {
+            return;
+        };
+        match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(mut json) if json.is_object() || json.is_array() => {
+                if self.json(&mut json, "") {
+                    *value = serde_json::Value::String(json.to_string());
+                }
+            }
+            _ => {
+                self.string(value, "");
+            }
+        }
+    }
+
+    /// Scan a JSON value; `context` is the dotted path of keys it sits under. True if
+    /// anything in it was masked.
+    fn json(&mut self, value: &mut serde_json::Value, context: &str) -> bool {
+        match value {
+            serde_json::Value::String(_) => self.string(value, context),
+            serde_json::Value::Number(n) => {
+                let digits: Vec<u8> = n
+                    .to_string()
+                    .bytes()
+                    .map(|b| b.wrapping_sub(b'0'))
+                    .collect();
+                if !digits.iter().all(|d| *d <= 9)
+                    || !card_context(context)
+                    || !looks_like_card(&digits)
+                {
+                    return false;
+                }
+                self.found("card_number");
+                if self.mask {
+                    *value = serde_json::Value::from("[masked:card_number]");
+                }
+                self.mask
+            }
+            serde_json::Value::Array(items) => {
+                let mut masked = false;
+                for item in items {
+                    masked |= self.json(item, context);
+                }
+                masked
+            }
+            serde_json::Value::Object(map) => {
+                let mut masked = false;
+                for (k, v) in map.iter_mut() {
+                    masked |= self.json(v, &format!("{context}.{k}"));
+                }
+                masked
+            }
+            _ => false,
+        }
+    }
+
+    /// Scan a string value in place; `context` is its key (or key path). True if masked.
+    fn string(&mut self, value: &mut serde_json::Value, context: &str) -> bool {
+        let Some(text) = value.as_str() else {
+            return false;
+        };
+        let found = find(text, context);
+        if found.is_empty() {
+            return false;
+        }
+        for (_, _, kind) in &found {
+            self.found(kind);
+        }
+        if self.mask {
+            *value = serde_json::Value::String(masked(text, &found));
+        }
+        self.mask
+    }
+}
+
+/// Words near a number that say it's a card number: its key, or the text just before it
+fn card_context(context: &str) -> bool {
+    context
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .map(|w| w.to_ascii_lowercase())
+        .any(|w| {
+            w.contains("card")
+                || w.contains("credit")
+                || w.starts_with("ccnum")
+                || matches!(w.as_str(), "pan" | "cc" | "ccn" | "cvv")
+        })
+}
+
+/// Digit groups a card number is written in: one run, fours (the last maybe shorter),
+/// or 4-6-5 / 4-6-4 (Amex, Diners)
+fn card_grouping(groups: &[usize]) -> bool {
+    match groups {
+        [_] => true,
+        [4, 6, 5] | [4, 6, 4] => true,
+        [init @ .., last] => init.iter().all(|g| *g == 4) && (1..=4).contains(last),
+        [] => false,
+    }
+}
+
+/// How far back from a digit run to look for `card_context`
+const CARD_CONTEXT_BYTES: usize = 40;
+
+/// Secrets in `text` as non-overlapping (start, end, kind) byte ranges, in order.
+/// `context` is the key the text was under, for telling card numbers from other IDs.
+fn find(text: &str, context: &str) -> Vec<(usize, usize, &'static str)> {
+    let mut found = Vec::new();
+    let is_token_char = |c: char| c.is_ascii_alphanumeric() || "-_.".contains(c);
+
+    // Tokens: JWTs and prefixed API keys
+    let mut start = None;
+    for (i, c) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        match (start, is_token_char(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                let token = text[s..i].trim_end_matches('.');
+                if let Some(kind) = classify(token) {
+                    found.push((s, s + token.len(), kind));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    // Card numbers: 13-19 digits, in one run or grouped the way cards are printed (one
+    // kind of separator throughout), passing Luhn — and with a word saying it's a card
+    // in the key or just before it, as plenty of IDs pass Luhn too
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let boundary = i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+        if !boundary || !bytes[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        let mut digits = Vec::new();
+        let mut groups = vec![0usize];
+        let mut separator = None;
+        let mut end = i;
+        let mut j = i;
+        while j < bytes.len() {
+            if bytes[j].is_ascii_digit() {
+                digits.push(bytes[j] - b'0');
+                *groups.last_mut().unwrap() += 1;
+                end = j + 1;
+                j += 1;
+            } else if (bytes[j] == b' ' || bytes[j] == b'-')
+                && separator.is_none_or(|s| s == bytes[j])
+                && j + 1 < bytes.len()
+                && bytes[j + 1].is_ascii_digit()
+            {
+                separator = Some(bytes[j]);
+                groups.push(0);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+        let followed_by_word = end < bytes.len() && bytes[end].is_ascii_alphanumeric();
+        let mut before = i.saturating_sub(CARD_CONTEXT_BYTES);
+        while !text.is_char_boundary(before) {
+            before += 1;
+        }
+        if !followed_by_word
+            && card_grouping(&groups)
+            && looks_like_card(&digits)
+            && (card_context(context) || card_context(&text[before..i]))
+            && !found.iter().any(|(s, e, _)| i < *e && end > *s)
+        {
+            found.push((i, end, "card_number"));
+        }
+        i = end.max(i + 1);
+    }
+
+    found.sort_by_key(|(s, _, _)| *s);
+    found
+}
+
+fn classify(token: &str) -> Option<&'static str> {
+    // JWT: three base64url segments, header starting with {" ("eyJ")
+    let segments: Vec<&str> = token.split('.').collect();
+    if segments.len() == 3
+        && token.starts_with("eyJ")
+        && token.len() >= 30
+        && segments.iter().all(|s| {
+            !s.is_empty()
+                && s.bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        })
+    {
+        return Some("jwt");
+    }
+    let is_key = KEY_PREFIXES
+        .iter()
+        .any(|(prefix, min_len)| token.starts_with(prefix) && token.len() >= *min_len);
+    is_key.then_some("api_key")
+}
+
+/// Card-length digit run with a card network's leading digit and a valid Luhn checksum
+fn looks_like_card(digits: &[u8]) -> bool {
+    if !(13..=19).contains(&digits.len()) || !(2..=6).contains(&digits[0]) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            let d = d as u32;
+            if i % 2 == 1 {
+                if d * 2 > 9 {
+                    d * 2 - 9
+                } else {
+                    d * 2
+                }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum % 10 == 0
+}
+
+fn masked(text: &str, found: &[(usize, usize, &'static str)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end, kind) in found {
+        out.push_str(&text[last..*start]);
+        out.push_str(&format!("[masked:{kind}]"));
+        last = *end;
+    }
+    out.push_str(&text[last..]);
+    out
+}
@@ -0,0 +1,164 @@
+use crate::capture_io;
+use crate::config;
+use crate::events;
+use crate::AppState;
+use std::collections::HashMap;
+use std::fs;
+use tauri::Manager;
+
+/// `{"session", "pid", "clean"}`: written unclean when the browser starts, clean once
+/// it has flushed everything on the way out. Still unclean at the next start means the
+/// last run crashed or was killed.
+const MARKER_FILE: &str = "shutdown.json";
+
+/// Captures from domains no app had yet when the browser quit, by domain — held
+/// again on the next start
+const UNMAPPED_FILE: &str = "unmapped.json";
+
+/// Check how the last run ended (repairing its session's capture files if it didn't
+/// end cleanly), then mark this one as running
+pub fn start(session_ts: &str) {
+    repair_tails();
+    let marker_path = config::data_dir().join(MARKER_FILE);
+    let previous: Option<serde_json::Value> = fs::read_to_string(&marker_path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok());
+    if let Some(previous) =
+        previous.filter(|m| m.get("clean").and_then(|v| v.as_bool()) == Some(false))
+    {
+        let session = previous
+            .get("session")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        if !session.is_empty() {
+            let repaired = check_session(session);
+            eprintln!(
+                "[harharhar] the last run (session {session}) didn't shut down cleanly — checked its capture files: {repaired}"
+            );
+            events::emit(
+                "unclean-shutdown",
+                serde_json::json!({"session": session, "apps": repaired}),
+            );
+        }
+    }
+    write_marker(session_ts, false);
+}
+
+/// Everything held in memory that would be lost on exit goes to disk: repeat runs and
+/// queued captures, notifications and capture counts not yet saved, captures waiting for
+/// their domain to be named. Then the run is marked clean.
+pub fn finish(app: &tauri::AppHandle) {
+    crate::capture::flush_repeats();
+    crate::capture_writer::flush();
+    crate::notifications::flush();
+    crate::stats::flush();
+
+    let state = app.state::<AppState>();
+    let unmapped = state.unmapped_captures.lock().unwrap();
+    let path = config::data_dir().join(UNMAPPED_FILE);
+    if unmapped.is_empty() {
+        let _ = fs::remove_file(&path);
+    } else if let Ok(json) = serde_json::to_string(&*unmapped) {
+        let _ = fs::write(&path, json);
+    }
+    drop(unmapped);
+
+    write_marker(&state.session_ts, true);
+}
+
+/// Captures the last run held for unnamed domains (or archived apps), to be held again
+pub fn take_unmapped() -> HashMap<String, Vec<serde_json::Value>> {
+    let path = config::data_dir().join(UNMAPPED_FILE);
+    let held = fs::read_to_string(&path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let _ = fs::remove_file(path);
+    held
+}
+
+fn write_marker(session_ts: &str, clean: bool) {
+    let marker = serde_json::json!({
+        "session": session_ts,
+        "pid": std::process::id(),
+        "clean": clean,
+    });
+    let _ = fs::write(config::data_dir().join(MARKER_FILE), marker.to_string());
+}
+
+/// Each app's capture file for `session_ts`, with lines cut short by the crash (or
+/// otherwise not JSON, or failing their CRC) dropped and its index entries rebuilt.
+/// Returns what was done per app: `{"lines", "dropped"}`.
+fn check_session(session_ts: &str) -> serde_json::Value {
+    let file_name = format!("{session_ts}.jsonl");
+    let mut report = serde_json::Map::new();
+    for app_name in config::list_apps() {
+        let app_dir = config::data_dir().join("apps").join(&app_name);
+        let path = app_dir.join("captures").join(&file_name);
+        if !path.exists() {
+            continue;
+        }
+        let Ok(mut out) = capture_io::Rewriter::new(&path) else {
+            continue;
+        };
+        let (mut kept, mut dropped, mut written) = (0, 0, true);
+        for line in capture_io::try_lines(&path) {
+            // Unreadable past here: left as it is rather than rewritten without the rest
+            let Ok(line) = line else {
+                written = false;
+                break;
+            };
+            if !capture_io::intact(&line) {
+                dropped += 1;
+                continue;
+            }
+            if out.write_line(&line).is_err() {
+                written = false;
+                break;
+            }
+            kept += 1;
+        }
+        // Only rewritten if something was wrong; the index is rebuilt either way, as the
+        // crash may have come between a capture and its index entry
+        if dropped > 0 && !(written && out.commit().is_ok()) {
+            continue;
+        }
+        crate::capture_index::reindex_file(&app_dir, &file_name);
+        report.insert(
+            app_name,
+            serde_json::json!({"lines": kept, "dropped": dropped}),
+        );
+    }
+    serde_json::Value::Object(report)
+}
+
+/// Cut any capture file that ends in a partial line (a crash mid-append, whichever run
+/// it was) back to its last whole one, and re-index it. Cheap: only file ends are read.
+fn repair_tails() {
+    let mut report = serde_json::Map::new();
+    for app_name in config::list_apps() {
+        let app_dir = config::data_dir().join("apps").join(&app_name);
+        for path in capture_io::capture_files(&app_dir) {
+            let Ok(cut) = capture_io::repair_tail(&path) else {
+                continue;
+            };
+            if cut == 0 {
+                continue;
+            }
+            let file_name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            crate::capture_index::reindex_file(&app_dir, &file_name);
+            eprintln!("[harharhar] {app_name}/captures/{file_name}: cut {cut} bytes of damaged lines (kept in {file_name}.damaged)");
+            report.insert(
+                format!("{app_name}/{file_name}"),
+                serde_json::Value::from(cut),
+            );
+        }
+    }
+    if !report.is_empty() {
+        events::emit("captures-repaired", serde_json::json!({"files": report}));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::capture::is_noise;
+use crate::capture_io;
+use crate::config;
+use crate::endpoints::normalize_path;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+
+/// Capture entry types that aren't API calls
+const NON_API_TYPES: &[&str] = &[
+    "navigation",
+    "cookies",
+    "annotation",
+    "ui-action",
+    "xhr-start",
+];
+
+#[derive(Default)]
+struct Route {
+    host: String,
+    visits: u32,
+    title: String,
+    example_urls: BTreeSet<String>,
+    /// "METHOD /pattern" (as in endpoints.json) -> times called while on this route
+    endpoints: BTreeMap<String, u32>,
+    links_to: BTreeSet<String>,
+}
+
+/// Generate sitemap.json for an app: the UI routes visited (from navigation
+/// captures and history.jsonl), which API endpoints fire while on each route,
+/// and which routes lead to which.
+pub fn generate_for_app(app_name: &str) {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let capture_noise = config::capture_noise(app_name);
+    let mut routes: HashMap<String, Route> = HashMap::new();
+
+    // 1. Walk captures in order; API calls belong to the route their window was last on
+    for file in capture_io::capture_files(&app_dir) {
+        // Window label -> route it's on (reset per capture file, i.e. per session)
+        let mut current: HashMap<String, String> = HashMap::new();
+        for entry in capture_io::entries(&file) {
+            let entry_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
+            let window = entry
+                .get("window")
+                .and_then(|v| v.as_str())
+                .unwrap_or(crate::DEFAULT_WINDOW)
+                .to_string();
+            let Some(url) = entry
+                .get("url")
+                .and_then(|v| v.as_str())
+                .and_then(|u| url::Url::parse(u).ok())
+            else {
+                continue;
+            };
+
+            if entry_type == "navigation" {
+                let key = route_key(&url);
+                let route = routes.entry(key.clone()).or_default();
+                route.host = url.host_str().unwrap_or("").to_string();
+                route.visits += 1;
+                if route.example_urls.len() < 3 {
+                    route.example_urls.insert(url.to_string());
+                }
+                if let Some(prev) = current.insert(window, key.clone()) {
+                    if prev != key {
+                        routes.entry(prev).or_default().links_to.insert(key);
+                    }
+                }
+                continue;
+            }
+
+            if NON_API_TYPES.contains(&entry_type)
+                || is_noise(entry_type, url.as_str(), &capture_noise)
+            {
+                continue;
+            }
+            if let Some(key) = current.get(&window) {
+                let method = entry
+                    .get("method")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("GET");
+                let endpoint = format!("{} {}", method, normalize_path(url.path()));
+                *routes
+                    .get_mut(key)
+                    .unwrap()
+                    .endpoints
+                    .entry(endpoint)
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    // 2. history.jsonl adds page titles and referrer links
+    for entry in capture_io::entries(&app_dir.join("history.jsonl")) {
+        let Some(url) = entry
+            .get("url")
+            .and_then(|v| v.as_str())
+            .and_then(|u| url::Url::parse(u).ok())
+        else {
+            continue;
+        };
+        let key = route_key(&url);
+        let title = entry.get("title").and_then(|v| v.as_str()).unwrap_or("");
+        if let Some(route) = routes.get_mut(&key) {
+            if !title.is_empty() {
+                route.title = title.to_string();
+            }
+        }
+        let referrer = entry
+            .get("referrer")
+            .and_then(|v| v.as_str())
+            .and_then(|u| url::Url::parse(u).ok())
+            .map(|u| route_key(&u));
+        if let Some(from) = referrer {
+            if let Some(route) = routes.get_mut(&from) {
+                if from != key {
+                    route.links_to.insert(key);
+                }
+            }
+        }
+    }
+
+    if routes.is_empty() {
+        return;
+    }
+
+    let mut sorted: Vec<(String, Route)> = routes.into_iter().collect();
+    sorted.sort_by(|a, b| b.1.visits.cmp(&a.1.visits).then(a.0.cmp(&b.0)));
+    let routes_json: Vec<serde_json::Value> = sorted
+        .into_iter()
+        .map(|(key, r)| {
+            let mut endpoints: Vec<(String, u32)> = r.endpoints.into_iter().collect();
+            endpoints.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            // A harharhar:// link to one of its pages, for docs and other tools
+            let open = r
+                .example_urls
+                .iter()
+                .next()
+                .map(|url| crate::deep_link::link(url, app_name));
+            serde_json::json!({
+                "route": key,
+                "host": r.host,
+                "open": open,
+                "title": r.title,
+                "visits": r.visits,
+                "example_urls": r.example_urls,
+                "endpoints": endpoints
+                    .into_iter()
+                    .map(|(e, n)| serde_json::json!({"endpoint": e, "times": n}))
+                    .collect::<Vec<_>>(),
+                "links_to": r.links_to,
+            })
+        })
+        .collect();
+
+    let sitemap = serde_json::json!({
+        "generated_at": chrono::Utc::now().to_rfc3339(),
+        "routes": routes_json,
+    });
+    if let Ok(json) = serde_json::to_string_pretty(&sitemap) {
+        let _ = fs::write(app_dir.join("sitemap.json"), json);
+    }
+}
+
+/// "host/normalized/path" — IDs collapsed so /inbox/123 and /inbox/456 are one route
+fn route_key(url: &url::Url) -> String {
+    format!(
+        "{}{}",
+        url.host_str().unwrap_or(""),
+        normalize_path(url.path())
+    )
+}
@@ -0,0 +1,337 @@
+use crate::config;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+/// Per-app counters in `apps/<name>/stats.json`, bumped on every capture append
+/// so `harharhar stats` never has to scan the JSONL files.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct AppStats {
+    pub captures: u64,
+    /// Bytes appended to captures/*.jsonl (before any trimming)
+    pub bytes: u64,
+    #[serde(default)]
+    pub first_capture: Option<String>,
+    #[serde(default)]
+    pub last_capture: Option<String>,
+    /// Captures per UTC day, e.g. "2026-02-21" -> 120
+    #[serde(default)]
+    pub per_day: BTreeMap<String, u64>,
+    /// Status of the latest API call captured, and its capture's timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_api_status: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_api_at: Option<String>,
+}
+
+impl AppStats {
+    /// Fold in counts recorded after these
+    fn add(&mut self, later: &AppStats) {
+        self.captures += later.captures;
+        self.bytes += later.bytes;
+        if self.first_capture.is_none() {
+            self.first_capture = later.first_capture.clone();
+        }
+        if later.last_capture.is_some() {
+            self.last_capture = later.last_capture.clone();
+        }
+        for (day, n) in &later.per_day {
+            *self.per_day.entry(day.clone()).or_insert(0) += n;
+        }
+        if later.last_api_at.is_some() {
+            self.last_api_status = later.last_api_status;
+            self.last_api_at = later.last_api_at.clone();
+        }
+    }
+}
+
+/// Serializes read-modify-write of stats.json across threads; taken before PENDING
+static STATS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Counts recorded since stats.json was last written, by app. They're added to the file
+/// a little later rather than on every capture, and added (not written over it) so an
+/// import in another process isn't lost.
+struct Pending {
+    apps: HashMap<String, AppStats>,
+    save_pending: bool,
+}
+
+static PENDING: LazyLock<Mutex<Pending>> = LazyLock::new(|| {
+    Mutex::new(Pending {
+        apps: HashMap::new(),
+        save_pending: false,
+    })
+});
+
+/// How long after a capture its counts reach stats.json: a burst of captures is one write
+const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+/// Record one appended capture line of `bytes` length.
+pub fn record_capture(app_name: &str, bytes: usize, data: &serde_json::Value) {
+    let now = chrono::Utc::now();
+    let ts = now.to_rfc3339();
+    let mut pending = PENDING.lock().unwrap();
+    let stats = pending.apps.entry(app_name.to_string()).or_default();
+    stats.captures += 1;
+    stats.bytes += bytes as u64;
+    if stats.first_capture.is_none() {
+        stats.first_capture = Some(ts.clone());
+    }
+    stats.last_capture = Some(ts);
+    *stats
+        .per_day
+        .entry(now.format("%Y-%m-%d").to_string())
+        .or_insert(0) += 1;
+    // API calls only: page loads and UI actions say nothing about the session
+    let status = data.get("status").and_then(|v| v.as_u64()).unwrap_or(0);
+    if status > 0 && data.get("url").and_then(|v| v.as_str()).is_some() {
+        stats.last_api_status = Some(status);
+        stats.last_api_at = data
+            .get("timestamp")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+    }
+
+    if !pending.save_pending {
+        pending.save_pending = true;
+        std::thread::spawn(|| {
+            std::thread::sleep(SAVE_DELAY);
+            flush();
+        });
+    }
+}
+
+/// Add everything recorded so far to the apps' stats.json files — on exit, so nothing
+/// is lost in the last SAVE_DELAY
+pub fn flush() {
+    let _lock = STATS_LOCK.lock().unwrap();
+    let apps = {
+        let mut pending = PENDING.lock().unwrap();
+        pending.save_pending = false;
+        std::mem::take(&mut pending.apps)
+    };
+    for (app_name, recorded) in apps {
+        let path = config::data_dir()
+            .join("apps")
+            .join(&app_name)
+            .join("stats.json");
+        let mut stats = read_stats_file(&path);
+        stats.add(&recorded);
+        if let Ok(json) = serde_json::to_string_pretty(&stats) {
+            let _ = fs::write(&path, json);
+        }
+    }
+}
+
+/// An app's stats.json plus what's been recorded since, or zeroed stats if there's nothing yet
+pub fn read_stats(app_name: &str) -> AppStats {
+    let _lock = STATS_LOCK.lock().unwrap();
+    let mut stats = read_stats_file(
+        &config::data_dir()
+            .join("apps")
+            .join(app_name)
+            .join("stats.json"),
+    );
+    if let Some(recorded) = PENDING.lock().unwrap().apps.get(app_name) {
+        stats.add(recorded);
+    }
+    stats
+}
+
+fn read_stats_file(path: &std::path::Path) -> AppStats {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Sessions older than this are guessed expired, when auth.json has no estimate of how
+/// long the app's sessions last
+const EXPIRED_AFTER_HOURS: i64 = 24;
+
+/// An app's session for the `status` command: `captured_at`, `age`, `session_id` (which
+/// credential set sessions/latest.json holds) and `freshness` — "fresh" (under an hour),
+/// "stale" (older), "expired" (a guess: older than auth.json's estimate of how long
+/// sessions last, or a day without one, or the app's latest API call since was answered
+/// 401/403), or "none".
+pub(crate) fn session_status(app_name: &str) -> serde_json::Value {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let Some(session) = fs::read_to_string(app_dir.join("sessions").join("latest.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<config::SessionData>(&s).ok())
+        .filter(|s| !s.captured_at.is_empty())
+    else {
+        return serde_json::json!({"freshness": "none"});
+    };
+    let now = chrono::Utc::now();
+    let (age, is_stale) = crate::digest::format_session_age(&session.captured_at, &now);
+    let captured = chrono::DateTime::parse_from_rfc3339(&session.captured_at)
+        .map(|t| t.with_timezone(&chrono::Utc))
+        .ok();
+
+    let lifetime = fs::read_to_string(app_dir.join("auth.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<crate::endpoints::AuthInfo>(&s).ok())
+        .and_then(|auth| crate::endpoints::session_lifetime(&auth.cookie_rotation).map(|l| l.1));
+    let expires_after = lifetime.unwrap_or(EXPIRED_AFTER_HOURS * 3600);
+    // The newest API answer since the session was saved: 401/403 means it's dead
+    let stats = read_stats(app_name);
+    let refused_since = |t: chrono::DateTime<chrono::Utc>| {
+        matches!(stats.last_api_status, Some(401) | Some(403))
+            && stats.last_api_at.as_deref().is_some_and(|at| {
+                chrono::DateTime::parse_from_rfc3339(at)
+                    .is_ok_and(|at| at.with_timezone(&chrono::Utc) >= t)
+            })
+    };
+
+    let (freshness, reason) = match captured {
+        None => ("expired", Some("captured_at unreadable".to_string())),
+        Some(t) if (now - t).num_seconds() > expires_after => {
+            let over = crate::endpoints::human_duration(expires_after);
+            let reason = match lifetime {
+                Some(_) => format!("over {over} old, about how long its sessions last"),
+                None => format!("over {over} old"),
+            };
+            ("expired", Some(reason))
+        }
+        Some(t) if refused_since(t) => ("expired", Some("last API call was refused".to_string())),
+        Some(_) if is_stale => ("stale", None),
+        Some(_) => ("fresh", None),
+    };
+    let mut result = serde_json::json!({
+        "captured_at": session.captured_at,
+        "age": age,
+        "freshness": freshness,
+        "session_id": session.session_id,
+    });
+    if let Some(reason) = reason {
+        result["reason"] = serde_json::Value::from(reason);
+    }
+    result
+}
+
+/// `harharhar stats --json`: the same figures as `report`, for scripts
+pub fn summary(app_name: &str) -> serde_json::Value {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let stats = read_stats(app_name);
+    let now = chrono::Utc::now();
+    let endpoint_count = fs::read_to_string(app_dir.join("endpoints.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<crate::endpoints::EndpointCatalog>(&s).ok())
+        .map(|c| c.endpoints.len());
+    let last_7_days: BTreeMap<String, u64> = (0..7)
+        .map(|d| {
+            let key = (now - chrono::Duration::days(d))
+                .format("%Y-%m-%d")
+                .to_string();
+            let n = stats.per_day.get(&key).copied().unwrap_or(0);
+            (key, n)
+        })
+        .collect();
+    serde_json::json!({
+        "app": app_name,
+        "captures": stats.captures,
+        "captured_bytes": stats.bytes,
+        "disk_bytes": dir_size(&app_dir),
+        "endpoints": endpoint_count,
+        "session": session_status(app_name),
+        "last_7_days": last_7_days,
+    })
+}
+
+/// Human-readable summary for `harharhar stats`.
+pub fn report(app_name: &str) -> String {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let stats = read_stats(app_name);
+    let now = chrono::Utc::now();
+
+    // Distinct endpoints come from the last generation pass, not a capture scan
+    let endpoint_count = fs::read_to_string(app_dir.join("endpoints.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str::<crate::endpoints::EndpointCatalog>(&s).ok())
+        .map(|c| c.endpoints.len());
+
+    let session: config::SessionData =
+        fs::read_to_string(app_dir.join("sessions").join("latest.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+    let mut out = String::new();
+    out.push_str(&format!("{app_name}\n"));
+
+    let today = now.format("%Y-%m-%d").to_string();
+    out.push_str(&format!(
+        "  captures:   {} ({} today)\n",
+        stats.captures,
+        stats.per_day.get(&today).copied().unwrap_or(0)
+    ));
+    match endpoint_count {
+        Some(n) => out.push_str(&format!("  endpoints:  {n}\n")),
+        None => out.push_str("  endpoints:  - (run `harharhar generate`)\n"),
+    }
+    if session.captured_at.is_empty() {
+        out.push_str("  session:    none\n");
+    } else {
+        let (age, is_stale) = crate::digest::format_session_age(&session.captured_at, &now);
+        out.push_str(&format!(
+            "  session:    {age}{}\n",
+            if is_stale { " (stale)" } else { "" }
+        ));
+    }
+    out.push_str(&format!(
+        "  disk:       {} (captured {})\n",
+        format_bytes(dir_size(&app_dir)),
+        format_bytes(stats.bytes)
+    ));
+
+    // Capture rate: last 7 days, oldest first
+    let counts: Vec<(String, u64)> = (0..7)
+        .rev()
+        .map(|d| {
+            let day = now - chrono::Duration::days(d);
+            let key = day.format("%Y-%m-%d").to_string();
+            (
+                day.format("%m-%d").to_string(),
+                stats.per_day.get(&key).copied().unwrap_or(0),
+            )
+        })
+        .collect();
+    let rate: Vec<String> = counts.iter().map(|(day, n)| format!("{day}:{n}")).collect();
+    let total: u64 = counts.iter().map(|(_, n)| n).sum();
+    out.push_str(&format!(
+        "  last 7 days: {} ({:.0}/day)\n",
+        rate.join(" "),
+        total as f64 / 7.0
+    ));
+
+    out
+}
+
+/// Total size of all files under a directory
+fn dir_size(path: &std::path::Path) -> u64 {
+    let entries = match fs::read_dir(path) {
+        Ok(e) => e,
+        Err(_) => return 0,
+    };
+    entries
+        .flatten()
+        .map(|e| match e.metadata() {
+            Ok(m) if m.is_dir() => dir_size(&e.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{bytes} B")
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
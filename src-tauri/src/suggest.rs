@@ -0,0 +1,388 @@
+use crate::config;
+use crate::endpoints::{AuthInfo, Endpoint, EndpointCatalog};
+use crate::errors::CommandError;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// UI action labels by endpoint pattern
+type Labels = HashMap<String, Vec<String>>;
+
+/// What an app's suggestions are ranked from, as of the endpoints.json and auth.json
+/// modification times in `stamp`
+struct Loaded {
+    stamp: (Option<SystemTime>, Option<SystemTime>),
+    catalog: EndpointCatalog,
+    auth: Option<AuthInfo>,
+    labels: Labels,
+}
+
+/// Each app's catalog, auth and labels, kept until generation rewrites its files: the
+/// palette asks on every keystroke, and the labels take a pass over every capture file
+static LOADED: LazyLock<Mutex<HashMap<String, Arc<Loaded>>>> = LazyLock::new(Default::default);
+
+/// Query words that say nothing about which endpoint is meant
+const STOPWORDS: &[&str] = &[
+    "a", "an", "the", "to", "for", "of", "my", "me", "i", "in", "on", "at", "by", "from", "with",
+    "and", "or", "is", "it", "this", "that", "some", "how", "do", "can", "want", "please", "api",
+    "endpoint",
+];
+
+/// Path segments every endpoint of an app tends to share
+const PATH_NOISE: &[&str] = &["api", "v1", "v2", "v3", "v4", "rest", "graphql", "json"];
+
+/// Verbs -> the resource operations and methods they ask for
+const VERBS: &[(&[&str], &[&str], &[&str])] = &[
+    (
+        &[
+            "send", "create", "add", "new", "post", "make", "write", "compose", "upload", "submit",
+        ],
+        &["create"],
+        &["POST", "PUT"],
+    ),
+    (
+        &["delete", "remove", "destroy", "drop", "trash", "clear"],
+        &["delete"],
+        &["DELETE"],
+    ),
+    (
+        &[
+            "update", "edit", "change", "rename", "modify", "set", "patch", "mark", "save",
+        ],
+        &["update", "replace"],
+        &["PATCH", "PUT", "POST"],
+    ),
+    (
+        &["list", "all", "browse", "search", "find", "recent", "feed"],
+        &["list"],
+        &["GET"],
+    ),
+    (
+        &[
+            "get", "open", "view", "read", "show", "fetch", "load", "detail", "download",
+        ],
+        &["get"],
+        &["GET"],
+    ),
+];
+
+/// Weight of a query word found in each place an endpoint is described
+const NAME_WEIGHT: f64 = 3.0;
+const TAG_WEIGHT: f64 = 2.0;
+const LABEL_WEIGHT: f64 = 2.0;
+const RESOURCE_WEIGHT: f64 = 2.0;
+const PATH_WEIGHT: f64 = 1.0;
+const NOTES_WEIGHT: f64 = 1.0;
+
+/// {"query": "send a message", "app", "limit"}: the endpoints that best match a partial
+/// natural-language query, by their names, tags, the UI actions that set them off and
+/// their paths, with what each needs called first and a command that makes the request
+pub fn suggest(app_name: &str, cmd: &serde_json::Value) -> serde_json::Value {
+    let query = cmd.get("query").and_then(|v| v.as_str()).unwrap_or("");
+    let limit = cmd.get("limit").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
+    let Some(loaded) = load(app_name) else {
+        return CommandError::NotFound(format!(
+            "no endpoints.json for {app_name} yet — run generate_endpoints first"
+        ))
+        .reply();
+    };
+    let (catalog, auth, labels) = (&loaded.catalog, &loaded.auth, &loaded.labels);
+
+    // The last word may still be being typed: it counts as a prefix
+    let partial = !query.ends_with(char::is_whitespace);
+    let words: Vec<String> = words(query)
+        .into_iter()
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+        .collect();
+
+    // Endpoint pattern -> (resource, operation)
+    let mut operations: HashMap<&str, (&str, &str)> = HashMap::new();
+    for (name, resource) in &catalog.resources {
+        for (op, pattern) in &resource.operations {
+            operations.insert(pattern.as_str(), (name.as_str(), op.as_str()));
+        }
+    }
+
+    let mut scored: Vec<(f64, &Endpoint, Vec<String>)> = catalog
+        .endpoints
+        .iter()
+        .filter_map(|ep| {
+            let operation = operations.get(ep.pattern.as_str()).copied();
+            let ep_labels = labels.get(&ep.pattern).map(|l| l.as_slice()).unwrap_or(&[]);
+            let (score, matched) = score(ep, operation, ep_labels, &words, partial);
+            (score > 0.0 || words.is_empty()).then_some((score, ep, matched))
+        })
+        .collect();
+    for (score, ep, _) in &mut scored {
+        if ep.verified {
+            *score += 1.0;
+        }
+        if ep.name.is_some() {
+            *score += 0.5;
+        }
+        *score += (1.0 + ep.times_seen as f64).ln() * 0.2;
+        if ep.stale {
+            *score *= 0.5;
+        }
+    }
+
+    let needs: HashMap<&str, Vec<String>> = scored
+        .iter()
+        .map(|(_, ep, _)| {
+            (
+                ep.pattern.as_str(),
+                needs(ep, &operations, catalog, auth.as_ref()),
+            )
+        })
+        .collect();
+    // A call the better matches can't be made without ranks a little higher
+    let mut boosts: HashMap<&str, f64> = HashMap::new();
+    for prerequisites in needs.values() {
+        for p in prerequisites {
+            *boosts.entry(p.as_str()).or_default() += 0.5;
+        }
+    }
+    for (score, ep, _) in &mut scored {
+        *score += boosts.get(ep.pattern.as_str()).copied().unwrap_or(0.0);
+    }
+    scored.sort_by(|a, b| {
+        b.0.partial_cmp(&a.0)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.1.times_seen.cmp(&a.1.times_seen))
+    });
+
+    let total = scored.len();
+    let candidates: Vec<serde_json::Value> = scored
+        .into_iter()
+        .take(limit)
+        .map(|(score, ep, matched)| {
+            let mut candidate = serde_json::json!({
+                "pattern": ep.pattern,
+                "score": (score * 100.0).round() / 100.0,
+                "matched": matched,
+                "needs": needs.get(ep.pattern.as_str()).cloned().unwrap_or_default(),
+                "times_seen": ep.times_seen,
+                "replay": if ep.computed_headers.is_empty() { "curl" } else { "in_page" },
+                "example": example(ep),
+            });
+            if let Some(name) = &ep.name {
+                candidate["name"] = serde_json::Value::from(name.as_str());
+            }
+            if !ep.tags.is_empty() {
+                candidate["tags"] = serde_json::json!(ep.tags);
+            }
+            if let Some(labels) = labels.get(&ep.pattern) {
+                candidate["labels"] = serde_json::json!(labels);
+            }
+            if let Some((resource, op)) = operations.get(ep.pattern.as_str()) {
+                candidate["resource"] = serde_json::json!({"name": resource, "operation": op});
+            }
+            if ep.stale {
+                candidate["stale"] = serde_json::Value::Bool(true);
+            }
+            candidate
+        })
+        .collect();
+    serde_json::json!({
+        "ok": true,
+        "app": app_name,
+        "query": query,
+        "total": total,
+        "candidates": candidates,
+    })
+}
+
+/// How well `ep` matches the query words, and which of them it matched
+fn score(
+    ep: &Endpoint,
+    operation: Option<(&str, &str)>,
+    labels: &[String],
+    query: &[String],
+    partial: bool,
+) -> (f64, Vec<String>) {
+    let method = ep.pattern.split(' ').next().unwrap_or("");
+    let path: Vec<String> = ep
+        .pattern
+        .split(' ')
+        .nth(1)
+        .unwrap_or("")
+        .split('/')
+        .filter(|s| !s.starts_with('{') && !PATH_NOISE.contains(&s.to_lowercase().as_str()))
+        .flat_map(words)
+        .collect();
+    let fields: [(Vec<String>, f64); 6] = [
+        (
+            ep.name.as_deref().map(words).unwrap_or_default(),
+            NAME_WEIGHT,
+        ),
+        (ep.tags.iter().flat_map(|t| words(t)).collect(), TAG_WEIGHT),
+        (labels.iter().flat_map(|l| words(l)).collect(), LABEL_WEIGHT),
+        (
+            operation
+                .map(|(resource, op)| [words(resource), words(op)].concat())
+                .unwrap_or_default(),
+            RESOURCE_WEIGHT,
+        ),
+        (path, PATH_WEIGHT),
+        (
+            ep.notes.as_deref().map(words).unwrap_or_default(),
+            NOTES_WEIGHT,
+        ),
+    ];
+
+    let mut total = 0.0;
+    let mut matched = Vec::new();
+    for (i, word) in query.iter().enumerate() {
+        let prefix = partial && i + 1 == query.len() && word.len() >= 2;
+        let mut best = fields
+            .iter()
+            .map(|(tokens, weight)| {
+                if tokens.contains(word) {
+                    *weight
+                } else if prefix && tokens.iter().any(|t| t.starts_with(word.as_str())) {
+                    weight * 0.7
+                } else {
+                    0.0
+                }
+            })
+            .fold(0.0, f64::max);
+        // A verb matches the operation it asks for, or failing that the method
+        if let Some((_, ops, methods)) = VERBS
+            .iter()
+            .find(|(verbs, _, _)| verbs.contains(&word.as_str()))
+        {
+            let verb = match operation {
+                Some((_, op)) if ops.contains(&op) => 2.0,
+                _ if methods.contains(&method) => 1.0,
+                _ => 0.0,
+            };
+            best = f64::max(best, verb);
+        }
+        if best > 0.0 {
+            total += best;
+            matched.push(word.clone());
+        }
+    }
+    // Every word matched beats a strong match on a few
+    if !query.is_empty() {
+        total *= matched.len() as f64 / query.len() as f64;
+    }
+    (total, matched)
+}
+
+/// Calls to make before `ep`: the list an `{id}` in its path comes from, and the
+/// endpoint that hands out a CSRF token for a write
+fn needs(
+    ep: &Endpoint,
+    operations: &HashMap<&str, (&str, &str)>,
+    catalog: &EndpointCatalog,
+    auth: Option<&AuthInfo>,
+) -> Vec<String> {
+    let mut needs = Vec::new();
+    if ep.pattern.contains('{') {
+        let list = operations
+            .get(ep.pattern.as_str())
+            .and_then(|(resource, _)| catalog.resources.get(*resource))
+            .and_then(|r| r.operations.get("list"))
+            .filter(|list| **list != ep.pattern);
+        if let Some(list) = list {
+            needs.push(list.clone());
+        }
+    }
+    let method = ep.pattern.split(' ').next().unwrap_or("");
+    if method != "GET" && method != "HEAD" {
+        let tokens = auth.map(|a| a.csrf.as_slice()).unwrap_or(&[]);
+        for endpoint in tokens.iter().filter_map(|c| c.endpoint.as_ref()) {
+            if *endpoint != ep.pattern && !needs.contains(endpoint) {
+                needs.push(endpoint.clone());
+            }
+        }
+    }
+    needs
+}
+
+/// The `fetch_in_page` command that makes `ep`'s request from a page of the app, as in
+/// examples-inpage.md: it works whether or not the endpoint needs computed headers
+fn example(ep: &Endpoint) -> serde_json::Value {
+    let url = ep.observed_urls.first().map(|s| s.as_str()).unwrap_or("");
+    let method = ep.methods.first().map(|s| s.as_str()).unwrap_or("GET");
+    let mut cmd = serde_json::json!({"action": "fetch_in_page", "url": url, "method": method});
+    let json_body = ep
+        .request_content_types
+        .iter()
+        .any(|ct| ct.contains("json"));
+    if json_body && method != "GET" && method != "HEAD" {
+        cmd["headers"] = serde_json::json!({"Content-Type": "application/json"});
+        cmd["body"] = ep
+            .request_shape
+            .clone()
+            .unwrap_or_else(|| serde_json::json!({}));
+    }
+    cmd
+}
+
+/// Lowercase words of `text`, split at punctuation and camelCase, plurals folded
+/// ("sendMessages" -> ["send", "message"])
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+    for c in text.chars() {
+        if (!c.is_alphanumeric() || (c.is_uppercase() && prev_lower)) && !word.is_empty() {
+            words.push(singular(&word));
+            word.clear();
+        }
+        if c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+    }
+    if !word.is_empty() {
+        words.push(singular(&word));
+    }
+    words
+}
+
+fn singular(word: &str) -> String {
+    if let Some(stem) = word.strip_suffix("ies").filter(|s| s.len() >= 2) {
+        format!("{stem}y")
+    } else if word.len() > 3
+        && word.ends_with('s')
+        && !word.ends_with("ss")
+        && !word.ends_with("us")
+    {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    }
+}
+
+/// An app's catalog, auth info and action labels, from memory unless endpoints.json or
+/// auth.json changed since they were read; None without an endpoints.json
+fn load(app_name: &str) -> Option<Arc<Loaded>> {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let endpoints_path = app_dir.join("endpoints.json");
+    let auth_path = app_dir.join("auth.json");
+    let modified = |path: &std::path::Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let stamp = (modified(&endpoints_path), modified(&auth_path));
+
+    let mut cache = LOADED.lock().unwrap();
+    if let Some(loaded) = cache.get(app_name).filter(|l| l.stamp == stamp) {
+        return Some(loaded.clone());
+    }
+    let catalog = fs::read_to_string(&endpoints_path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())?;
+    let auth = fs::read_to_string(&auth_path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok());
+    let loaded = Arc::new(Loaded {
+        stamp,
+        catalog,
+        auth,
+        labels: crate::digest::action_labels(app_name),
+    });
+    cache.insert(app_name.to_string(), loaded.clone());
+    Some(loaded)
+}
@@ -0,0 +1,148 @@
+use crate::capture_io;
+use crate::config;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// API calls starting this soon after a UI action in the same window are taken to be
+/// caused by it (as for digest workflows)
+const CORRELATE_MS: i64 = 2000;
+
+/// Write `timelines/<session>.json` for each of an app's sessions whose captures changed
+/// since its timeline was last written
+pub fn generate_for_app(app_name: &str) {
+    let app_dir = config::data_dir().join("apps").join(app_name);
+    let out_dir = app_dir.join("timelines");
+    for path in capture_io::capture_files(&app_dir) {
+        let Some(session) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        let out = out_dir.join(format!("{session}.json"));
+        let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+        if modified(&out).is_some_and(|written| modified(&path).is_some_and(|m| m <= written)) {
+            continue;
+        }
+        let timeline = build(app_name, &path);
+        let _ = fs::create_dir_all(&out_dir);
+        if let Ok(json) = serde_json::to_string(&timeline) {
+            let _ = fs::write(out, json);
+        }
+    }
+}
+
+/// One session's timeline, in the Chrome trace event format that Perfetto and
+/// chrome://tracing open: one track per window, API calls as spans, UI actions and
+/// page loads as instants. An action and the calls it set off share a `correlation`
+/// id, and are joined by flow arrows.
+pub fn build(app_name: &str, capture_file: &Path) -> serde_json::Value {
+    let session = capture_file
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+    let mut events: Vec<serde_json::Value> = vec![serde_json::json!({
+        "name": "process_name", "ph": "M", "pid": 1, "args": {"name": app_name},
+    })];
+    // Window label -> track id
+    let mut tracks: HashMap<String, u32> = HashMap::new();
+    // Window -> (correlation id, action start in ms) of its latest UI action
+    let mut last_action: HashMap<String, (String, i64)> = HashMap::new();
+    let mut actions = 0;
+    let mut calls = 0;
+
+    for entry in capture_io::entries(capture_file) {
+        let field = |k: &str| entry.get(k).and_then(|v| v.as_str()).unwrap_or("");
+        let Some(at_ms) = chrono::DateTime::parse_from_rfc3339(field("timestamp"))
+            .ok()
+            .map(|t| t.timestamp_millis())
+        else {
+            continue;
+        };
+        let window = match field("window") {
+            "" => crate::DEFAULT_WINDOW,
+            w => w,
+        };
+        let next_track = tracks.len() as u32 + 1;
+        let tid = *tracks.entry(window.to_string()).or_insert_with(|| {
+            events.push(serde_json::json!({
+                "name": "thread_name", "ph": "M", "pid": 1, "tid": next_track,
+                "args": {"name": window},
+            }));
+            next_track
+        });
+
+        match field("type") {
+            "ui-action" => {
+                actions += 1;
+                let id = format!("a{actions}");
+                let label = match (field("action"), field("label")) {
+                    (action, "") => action.to_string(),
+                    (action, label) => format!("{action} {label}"),
+                };
+                events.push(serde_json::json!({
+                    "name": label, "cat": "ui-action", "ph": "i", "s": "t",
+                    "ts": at_ms * 1000, "pid": 1, "tid": tid,
+                    "args": {"correlation": id, "role": field("role"), "url": field("url")},
+                }));
+                last_action.insert(window.to_string(), (id, at_ms));
+            }
+            "navigation" | "annotation" => {
+                let name = match field("type") {
+                    "annotation" => format!("label: {}", field("label")),
+                    _ => format!("page {}", field("url")),
+                };
+                events.push(serde_json::json!({
+                    "name": name, "cat": field("type"), "ph": "i", "s": "t",
+                    "ts": at_ms * 1000, "pid": 1, "tid": tid,
+                    "args": {"url": field("url")},
+                }));
+            }
+            "cookies" | "xhr-start" => {}
+            entry_type => {
+                let Some(pattern) = crate::capture_index::entry_pattern(&entry) else {
+                    continue;
+                };
+                calls += 1;
+                let duration = entry.get("duration").and_then(|v| v.as_u64()).unwrap_or(0) as i64;
+                // The page stamps calls when they finish; attached Chrome when they start
+                let start_ms = if window == crate::cdp::WINDOW {
+                    at_ms
+                } else {
+                    at_ms - duration
+                };
+                let correlation = last_action
+                    .get(window)
+                    .filter(|(_, action_ms)| (0..=CORRELATE_MS).contains(&(start_ms - action_ms)))
+                    .map(|(id, action_ms)| (id.clone(), *action_ms));
+                let mut args = serde_json::json!({
+                    "url": field("url"),
+                    "status": entry.get("status"),
+                    "type": entry_type,
+                });
+                if let Some((id, action_ms)) = &correlation {
+                    args["correlation"] = serde_json::Value::from(id.as_str());
+                    // Flow arrow from the action to this call
+                    events.push(serde_json::json!({
+                        "name": "triggered", "cat": "correlation", "ph": "s", "id": calls,
+                        "ts": action_ms * 1000, "pid": 1, "tid": tid,
+                    }));
+                    events.push(serde_json::json!({
+                        "name": "triggered", "cat": "correlation", "ph": "f", "bp": "e",
+                        "id": calls, "ts": start_ms * 1000, "pid": 1, "tid": tid,
+                    }));
+                }
+                events.push(serde_json::json!({
+                    "name": pattern, "cat": "request", "ph": "X",
+                    "ts": start_ms * 1000, "dur": duration.max(1) * 1000,
+                    "pid": 1, "tid": tid, "args": args,
+                }));
+            }
+        }
+    }
+
+    serde_json::json!({
+        "app": app_name,
+        "session": session,
+        "displayTimeUnit": "ms",
+        "traceEvents": events,
+    })
+}
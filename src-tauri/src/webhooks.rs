@@ -0,0 +1,109 @@
+use crate::config;
+use std::time::Duration;
+
+/// Events worth pinging a human (or an orchestrating agent) about.
+/// Everything else stays in events.jsonl only.
+pub const WEBHOOK_EVENTS: &[&str] = &[
+    "session-expired",
+    "endpoints-discovered",
+    "unknown-domain",
+    "human-needed",
+    "auth-changed",
+    "confirmation-needed",
+];
+
+/// Deliver an event to every configured webhook whose filter matches.
+/// Runs on a background thread — capture processing never waits on the network.
+pub fn dispatch(kind: &str, entry: &serde_json::Value) {
+    if !WEBHOOK_EVENTS.contains(&kind) {
+        return;
+    }
+
+    let targets: Vec<String> = config::config()
+        .webhooks
+        .iter()
+        .filter(|w| w.events.is_empty() || w.events.iter().any(|e| e == kind))
+        .map(|w| w.url.clone())
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    let mut payload = entry.clone();
+    if let Some(obj) = payload.as_object_mut() {
+        // Slack-style incoming webhooks render "text"; other receivers can ignore it
+        obj.insert(
+            "text".to_string(),
+            serde_json::Value::String(describe(kind, entry)),
+        );
+    }
+    let body = payload.to_string();
+
+    std::thread::spawn(move || {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(5))
+            .build();
+        for url in targets {
+            let _ = agent
+                .post(&url)
+                .set("Content-Type", "application/json")
+                .send_string(&body);
+        }
+    });
+}
+
+/// One-line human summary of an event.
+fn describe(kind: &str, entry: &serde_json::Value) -> String {
+    let field = |k: &str| {
+        entry
+            .get(k)
+            .and_then(|v| v.as_str())
+            .unwrap_or("?")
+            .to_string()
+    };
+    match kind {
+        "session-expired" => format!(
+            "harharhar: session for {} looks expired ({} on {}) — log in again",
+            field("app"),
+            entry.get("status").and_then(|v| v.as_u64()).unwrap_or(0),
+            field("url")
+        ),
+        "endpoints-discovered" => format!(
+            "harharhar: {} new endpoint(s) discovered for {}",
+            entry
+                .get("patterns")
+                .and_then(|v| v.as_array())
+                .map(|a| a.len())
+                .unwrap_or(0),
+            field("app")
+        ),
+        "unknown-domain" => format!(
+            "harharhar: traffic from unknown domain {} — name it in the explorer",
+            field("domain")
+        ),
+        "human-needed" => format!(
+            "harharhar: {} challenge on {} — solve it in the browser window",
+            field("kind"),
+            field("url")
+        ),
+        "auth-changed" => format!(
+            "harharhar: {} changed how it authenticates ({}) — replays may start failing",
+            field("app"),
+            entry
+                .get("changes")
+                .and_then(|v| v.as_array())
+                .map(|a| a
+                    .iter()
+                    .filter_map(|c| c.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; "))
+                .unwrap_or_default()
+        ),
+        "confirmation-needed" => format!(
+            "harharhar: an agent wants to run {} ({}) — allow or deny it in the explorer",
+            field("action"),
+            field("reason")
+        ),
+        _ => format!("harharhar: {kind}"),
+    }
+}
@@ -322,12 +322,14 @@ listen('request-captured', event => {
 });
 
 // --- Pre-navigate naming: blocks browser until app is named ---
+// The backend queues navigations to unnamed domains and asks about one at a time
+// (the next one after each resume_navigate), so every event is a fresh prompt.
 listen('name-app-before-navigate', event => {
-  const domain = event.payload;
-  if (pendingDomains.has(domain)) return;
+  // app: what a harharhar:// link said the page belongs to
+  const { domain, app } = event.payload;
   pendingDomains.add(domain);
 
-  let suggested = domain
+  let suggested = app || domain
     .replace(/^(www|app|api|mail)\./, '')
     .replace(/\.(com|org|net|io|dev|co)$/, '')
     .replace(/\./g, '-');
@@ -352,6 +354,110 @@ listen('unknown-domain', event => {
   _domainBatchTimer = setTimeout(processNextDomain, 1000);
 });
 
+// --- Domain already owned by another app: offer to move it ---
+listen('domain-conflict', event => {
+  const { domain, owner, requested_by } = event.payload;
+  showAlert(
+    `<b>${esc(domain)}</b> already belongs to "${esc(owner)}". Move it to app:`,
+    'conflict-' + domain,
+    async (name) => {
+      try {
+        await invoke('reassign_domain', { domain, name });
+        showNotice(`Moved ${domain} to "${name}"`);
+        loadAppSelector();
+      } catch (err) {
+        showNotice('Error: ' + err);
+      }
+    },
+    requested_by
+  );
+});
+
+// --- Sibling hosts (mail.google.com, chat.google.com, ...): offer to group them ---
+listen('domain-group-suggested', event => {
+  const { app, group } = event.payload;
+  showAlert(
+    `Treat every <b>*.${esc(group)}</b> host as part of app:`,
+    'group-' + group,
+    async (name) => {
+      try {
+        await invoke('confirm_domain_group', { name, group });
+        showNotice(`All ${group} hosts now go to "${name}"`);
+        loadAppSelector();
+      } catch (err) {
+        showNotice('Error: ' + err);
+      }
+    },
+    app
+  );
+});
+
+// --- Traffic for an archived app: held until it's unarchived ---
+listen('archived-app-traffic', event => {
+  const { app, domain } = event.payload;
+  showAlert(
+    `"${esc(app)}" is archived — new traffic from <b>${esc(domain)}</b> is on hold. Unarchive app:`,
+    'archived-' + app,
+    async (name) => {
+      try {
+        await invoke('set_app_archived', { name, archived: false });
+        showNotice(`"${name}" unarchived — held captures saved`);
+      } catch (err) {
+        showNotice('Error: ' + err);
+      }
+    },
+    app
+  );
+});
+
+// --- Agent command that looks destructive: held until the user allows it ---
+listen('confirm-action', event => {
+  const { id, action, reason, detail } = event.payload;
+  showConfirm(
+    `An agent wants to <b>${esc(action)}</b> — ${esc(reason)}:<br><code>${esc(detail)}</code>`,
+    id,
+    async (allowed) => {
+      try {
+        await invoke('confirm_action', { id, allowed });
+        showNotice(allowed ? `Allowed ${action}` : `Denied ${action}`);
+      } catch (err) {
+        showNotice('Error: ' + err);
+      }
+    }
+  );
+});
+
+// --- Notifications: events that needed the user while the explorer wasn't looking ---
+async function loadNotifications() {
+  try {
+    const { notifications } = await invoke('list_notifications');
+    // Oldest first, so the newest ends up on top
+    notifications.reverse().forEach(showNotification);
+  } catch (e) {}
+}
+
+function showNotification(n) {
+  if (document.getElementById(`alert-notification-${n.id}`)) return;
+
+  const el = document.createElement('div');
+  el.className = 'alert';
+  el.id = `alert-notification-${n.id}`;
+  el.innerHTML = `
+    <div class="alert-row">
+      <div class="alert-msg">${esc(n.message)}${n.count > 1 ? ` <i>(×${n.count})</i>` : ''}</div>
+      <button class="alert-dismiss" title="Mark as read">✕</button>
+    </div>
+  `;
+  el.querySelector('.alert-dismiss').addEventListener('click', async () => {
+    el.remove();
+    try { await invoke('ack_notification', { id: n.id }); } catch (e) {}
+  });
+  alerts.append(el);
+}
+
+loadNotifications();
+window.addEventListener('focus', loadNotifications);
+
 async function processNextDomain() {
   if (modalActive || domainQueue.length === 0) return;
   modalActive = true;
@@ -363,7 +469,8 @@ async function processNextDomain() {
   if (apps.length > 0) {
     showAddDomainModal(domains, apps, async (appName) => {
       for (const d of domains) {
-        await invoke('add_domain', { name: appName, domain: d });
+        // A domain another app owns raises a domain-conflict alert instead
+        try { await invoke('add_domain', { name: appName, domain: d }); } catch (_) {}
       }
       showNotice(`Added ${domains.length} domain(s) to "${appName}"`);
       loadAppSelector();
@@ -534,6 +641,26 @@ function showAlert(message, id, onSubmit, defaultValue) {
   input.select();
 }
 
+function showConfirm(message, id, onAnswer) {
+  if (document.getElementById(`alert-confirm-${id}`)) return;
+
+  const el = document.createElement('div');
+  el.className = 'alert';
+  el.id = `alert-confirm-${id}`;
+  el.innerHTML = `
+    <div class="alert-msg">${message}</div>
+    <div class="alert-row">
+      <button class="alert-btn alert-allow">Allow</button>
+      <button class="alert-btn alert-deny">Deny</button>
+    </div>
+  `;
+
+  el.querySelector('.alert-allow').addEventListener('click', () => { onAnswer(true); el.remove(); });
+  el.querySelector('.alert-deny').addEventListener('click', () => { onAnswer(false); el.remove(); });
+
+  alerts.prepend(el);
+}
+
 function showNotice(text) {
   const el = document.createElement('div');
   el.className = 'notice';